@@ -0,0 +1,171 @@
+//! Benchmarks of building and serializing bundles from synthetic datasets, sized to resemble those in production ISPyB
+
+// The bundler is a binary crate, so the modules exercised by the benchmarks are compiled into them directly
+#![allow(dead_code)]
+// Without the test harness, the unit tests of those modules are discarded, leaving their imports unused
+#![cfg_attr(test, allow(unused_imports))]
+
+#[path = "../src/built_info.rs"]
+mod built_info;
+#[path = "../src/bundle.rs"]
+mod bundle;
+#[path = "../src/database.rs"]
+mod database;
+#[path = "../src/disabled_datasets.rs"]
+mod disabled_datasets;
+#[path = "../src/fetch_status.rs"]
+mod fetch_status;
+#[path = "../src/permissionables/mod.rs"]
+mod permissionables;
+#[path = "../src/policy_source.rs"]
+mod policy_source;
+#[path = "../src/polling.rs"]
+mod polling;
+#[path = "../src/redaction.rs"]
+mod redaction;
+#[path = "../src/timestamp.rs"]
+mod timestamp;
+#[path = "../src/transformation.rs"]
+mod transformation;
+#[path = "../src/validation.rs"]
+mod validation;
+#[path = "../src/volume.rs"]
+mod volume;
+
+use bundle::{gzip, Bundle, NoMetadata};
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use permissionables::{
+    beamlines::Beamlines, instrument_scientists::InstrumentScientists, lab_contacts::LabContacts,
+    proposals::Proposals, roles::Roles, session_participants::SessionParticipants,
+    sessions::Sessions, subjects::Subjects,
+};
+use serde_json::{json, Map, Value};
+
+/// The number of subjects in the synthetic datasets
+const SUBJECTS: u32 = 20_000;
+/// The number of proposals in the synthetic datasets
+const PROPOSALS: u32 = 10_000;
+/// The number of sessions of each proposal in the synthetic datasets
+const SESSIONS_PER_PROPOSAL: u32 = 10;
+/// The number of beamlines in the synthetic datasets
+const BEAMLINES: u32 = 50;
+/// The number of proposals each subject is a member of
+const PROPOSALS_PER_SUBJECT: u32 = 5;
+
+/// Synthetic datasets from which a [`Bundle`] can be built
+struct Datasets {
+    /// The synthetic subjects
+    subjects: Subjects,
+    /// The synthetic sessions
+    sessions: Sessions,
+    /// The synthetic proposals
+    proposals: Proposals,
+    /// The synthetic beamlines
+    beamlines: Beamlines,
+}
+
+impl Datasets {
+    /// Generates synthetic datasets, in which each subject is a member of several proposals and all of their sessions
+    fn generate() -> Self {
+        let session_id = |proposal: u32, visit: u32| proposal * SESSIONS_PER_PROPOSAL + visit;
+        let beamline = |session: u32| format!("i{:02}", session % BEAMLINES);
+
+        let mut sessions = Map::new();
+        let mut proposals = Map::new();
+        let mut beamlines = Map::<String, Value>::new();
+        for proposal in 0..PROPOSALS {
+            let mut visits = Map::new();
+            for visit in 0..SESSIONS_PER_PROPOSAL {
+                let session = session_id(proposal, visit);
+                sessions.insert(
+                    session.to_string(),
+                    json!({"proposal_number": proposal, "visit_number": visit, "beamline": beamline(session)}),
+                );
+                visits.insert(visit.to_string(), json!(session));
+                beamlines
+                    .entry(beamline(session))
+                    .or_insert_with(|| json!({"sessions": []}))["sessions"]
+                    .as_array_mut()
+                    .unwrap()
+                    .push(json!(session));
+            }
+            proposals.insert(proposal.to_string(), json!({"sessions": visits}));
+        }
+
+        let mut subjects = Map::new();
+        for subject in 0..SUBJECTS {
+            let subject_proposals = (0..PROPOSALS_PER_SUBJECT)
+                .map(|offset| (subject * PROPOSALS_PER_SUBJECT + offset) % PROPOSALS)
+                .collect::<Vec<_>>();
+            let subject_sessions = subject_proposals
+                .iter()
+                .flat_map(|&proposal| {
+                    (0..SESSIONS_PER_PROPOSAL).map(move |visit| session_id(proposal, visit))
+                })
+                .collect::<Vec<_>>();
+            subjects.insert(
+                format!("abc{subject:05}"),
+                json!({
+                    "permissions": if subject % 100 == 0 { vec!["b07_admin", "super_admin"] } else { vec![] },
+                    "proposals": subject_proposals,
+                    "sessions": subject_sessions,
+                }),
+            );
+        }
+
+        Self {
+            subjects: serde_json::from_value(Value::Object(subjects)).unwrap(),
+            sessions: serde_json::from_value(Value::Object(sessions)).unwrap(),
+            proposals: serde_json::from_value(Value::Object(proposals)).unwrap(),
+            beamlines: serde_json::from_value(Value::Object(beamlines)).unwrap(),
+        }
+    }
+
+    /// Builds a [`Bundle`] from the datasets, hashing them to derive its revision
+    fn into_bundle(self) -> Bundle<NoMetadata> {
+        Bundle::new(
+            NoMetadata,
+            self.subjects,
+            self.sessions,
+            self.proposals,
+            self.beamlines,
+            Roles::default(),
+            None,
+            SessionParticipants::default(),
+            LabContacts::default(),
+            InstrumentScientists::default(),
+        )
+    }
+}
+
+/// Benchmarks hashing the datasets, serializing them without compression, and serializing them into a gzipped archive
+fn bundle(criterion: &mut Criterion) {
+    let mut group = criterion.benchmark_group("bundle");
+    group.sample_size(10);
+    group.bench_function("new", |bencher| {
+        bencher.iter_batched(
+            Datasets::generate,
+            Datasets::into_bundle,
+            BatchSize::LargeInput,
+        )
+    });
+    let datasets = Datasets::generate();
+    group.bench_function("serialize", |bencher| {
+        bencher.iter(|| {
+            serde_json::to_writer(std::io::sink(), &datasets.subjects).unwrap();
+            serde_json::to_writer(std::io::sink(), &datasets.sessions).unwrap();
+            serde_json::to_writer(std::io::sink(), &datasets.proposals).unwrap();
+            serde_json::to_writer(std::io::sink(), &datasets.beamlines).unwrap();
+        })
+    });
+    let bundle = datasets.into_bundle();
+    group.bench_function("to_tar", |bencher| {
+        bencher.iter(|| bundle.to_tar().unwrap())
+    });
+    let tar = bundle.to_tar().unwrap();
+    group.bench_function("gzip", |bencher| bencher.iter(|| gzip(&tar).unwrap()));
+    group.finish();
+}
+
+criterion_group!(benches, bundle);
+criterion_main!(benches);
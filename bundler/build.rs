@@ -1,3 +1,37 @@
+use std::{
+    process::Command,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
 fn main() {
     built::write_built_file().unwrap();
+    println!("cargo:rerun-if-env-changed=GIT_COMMIT");
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+    let git_commit = std::env::var("GIT_COMMIT").ok().or_else(|| {
+        Command::new("git")
+            .args(["rev-parse", "HEAD"])
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .and_then(|output| String::from_utf8(output.stdout).ok())
+    });
+    if let Some(git_commit) = git_commit.as_deref().map(str::trim) {
+        println!("cargo:rustc-env=BUNDLER_GIT_COMMIT={git_commit}");
+    }
+    let build_timestamp = std::env::var("SOURCE_DATE_EPOCH").unwrap_or_else(|_| {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+            .to_string()
+    });
+    println!("cargo:rustc-env=BUNDLER_BUILD_TIMESTAMP={build_timestamp}");
+    #[cfg(feature = "grpc")]
+    {
+        std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path().unwrap());
+        tonic_build::configure()
+            .bytes(["."])
+            .compile(&["proto/bundler.proto"], &["proto"])
+            .unwrap();
+    }
 }
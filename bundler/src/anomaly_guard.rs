@@ -0,0 +1,149 @@
+use crate::volume::DatasetVolume;
+use axum::{extract::State, http::StatusCode, response::IntoResponse, routing::post, Router};
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+use tokio::sync::Notify;
+use utoipa::OpenApi;
+
+/// An interlock refusing bundle updates which shrink any dataset by more than a threshold, until accepted manually
+#[derive(Debug, Clone)]
+pub struct AnomalyGuard {
+    /// The percentage by which the row count of a dataset may shrink before an update is refused, if any
+    max_shrink: Option<f64>,
+    /// Whether the next update should be accepted regardless of how much it shrinks any dataset
+    forced: Arc<AtomicBool>,
+}
+
+impl AnomalyGuard {
+    /// Creates an [`AnomalyGuard`] which refuses updates shrinking a dataset by more than the percentage, if any
+    pub fn new(max_shrink: Option<f64>) -> Self {
+        Self {
+            max_shrink,
+            forced: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Checks the volume of each dataset in an update against those of the served bundle, returning the reason the update should be refused, if it should be
+    ///
+    /// A forced update is accepted once, whereupon the guard is rearmed
+    pub fn check(
+        &self,
+        served: &BTreeMap<&'static str, DatasetVolume>,
+        update: &BTreeMap<&'static str, DatasetVolume>,
+    ) -> Result<(), String> {
+        if self.forced.swap(false, Ordering::AcqRel) {
+            tracing::warn!("Accepting bundle update, as forced");
+            return Ok(());
+        }
+        let Some(max_shrink) = self.max_shrink else {
+            return Ok(());
+        };
+        let shrunk = served
+            .iter()
+            .filter(|(_, served)| served.rows > 0)
+            .filter_map(|(dataset, served)| {
+                let rows = update.get(dataset).map_or(0, |update| update.rows);
+                let shrink = served.rows.saturating_sub(rows) as f64 / served.rows as f64 * 100.0;
+                (shrink > max_shrink)
+                    .then(|| format!("{dataset} shrank from {} to {rows} rows", served.rows))
+            })
+            .collect::<Vec<_>>();
+        if shrunk.is_empty() {
+            Ok(())
+        } else {
+            Err(format!(
+                "{}, exceeding the threshold of {max_shrink}%",
+                shrunk.join(", ")
+            ))
+        }
+    }
+}
+
+/// The paths served by the anomaly guard endpoints
+#[derive(OpenApi)]
+#[openapi(paths(force_update_endpoint))]
+pub struct AnomalyGuardApi;
+
+/// Shared state of the anomaly guard endpoints
+#[derive(Clone)]
+struct AnomalyGuardState {
+    /// The guard which refuses suspicious updates
+    anomaly_guard: AnomalyGuard,
+    /// A notification which wakes the update loop
+    refresh_requested: Arc<Notify>,
+}
+
+/// Creates a [`Router`] serving the endpoint which accepts an update refused by the [`AnomalyGuard`]
+pub fn router(anomaly_guard: AnomalyGuard, refresh_requested: Arc<Notify>) -> Router {
+    Router::new()
+        .route("/admin/force-update", post(force_update_endpoint))
+        .with_state(AnomalyGuardState {
+            anomaly_guard,
+            refresh_requested,
+        })
+}
+
+/// Requests an immediate refresh, accepting the fetched bundle regardless of how much it shrinks any dataset
+#[utoipa::path(
+    post,
+    path = "/admin/force-update",
+    tag = "admin",
+    responses((status = ACCEPTED, description = "A forced update was requested")),
+)]
+async fn force_update_endpoint(State(state): State<AnomalyGuardState>) -> impl IntoResponse {
+    state.anomaly_guard.forced.store(true, Ordering::Release);
+    tracing::warn!("Forced update requested");
+    state.refresh_requested.notify_one();
+    StatusCode::ACCEPTED
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AnomalyGuard;
+    use crate::volume::DatasetVolume;
+    use std::{collections::BTreeMap, sync::atomic::Ordering};
+
+    fn volumes(sessions: usize) -> BTreeMap<&'static str, DatasetVolume> {
+        BTreeMap::from([(
+            "sessions",
+            DatasetVolume {
+                rows: sessions,
+                bytes: 0,
+            },
+        )])
+    }
+
+    #[test]
+    fn shrink_refused_until_forced() {
+        let anomaly_guard = AnomalyGuard::new(Some(50.0));
+        assert!(anomaly_guard
+            .check(&volumes(40_000), &volumes(30_000))
+            .is_ok());
+        assert_eq!(
+            Err(
+                "sessions shrank from 40000 to 300 rows, exceeding the threshold of 50%"
+                    .to_string()
+            ),
+            anomaly_guard.check(&volumes(40_000), &volumes(300))
+        );
+        assert!(anomaly_guard
+            .check(&volumes(40_000), &BTreeMap::new())
+            .is_err());
+        anomaly_guard.forced.store(true, Ordering::Release);
+        assert!(anomaly_guard.check(&volumes(40_000), &volumes(300)).is_ok());
+        assert!(anomaly_guard
+            .check(&volumes(40_000), &volumes(300))
+            .is_err());
+    }
+
+    #[test]
+    fn unguarded() {
+        let anomaly_guard = AnomalyGuard::new(None);
+        assert!(anomaly_guard.check(&volumes(40_000), &volumes(0)).is_ok());
+    }
+}
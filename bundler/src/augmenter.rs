@@ -0,0 +1,81 @@
+use crate::bundle::{Bundle, NoMetadata};
+use std::{fmt::Debug, future::Future, pin::Pin, sync::Arc};
+
+/// The future produced by a [`BundleAugmenter`], resolving to the augmented [`Bundle`]
+pub type Augmentation<'a> =
+    Pin<Box<dyn Future<Output = Result<Bundle<NoMetadata>, anyhow::Error>> + Send + 'a>>;
+
+/// A stage of the bundle build pipeline, invoked with each [`Bundle`] fetched from ISPyB before it is validated, redacted and served
+///
+/// Augmenters contribute data from sources other than ISPyB, such as facility-specific datasets computed from internal APIs, typically via [`Bundle::with_augmented_dataset`]
+pub trait BundleAugmenter: Debug + Send + Sync {
+    /// The name of the augmenter, as logged should it fail
+    fn name(&self) -> &str;
+
+    /// Augments the [`Bundle`], returning the bundle to be served in its place
+    fn augment(&self, bundle: Bundle<NoMetadata>) -> Augmentation<'_>;
+}
+
+/// The registry of [`BundleAugmenter`]s composing the bundle build pipeline, which are invoked in the order registered
+///
+/// The registry is empty unless a binary composing its own pipeline registers augmenters before serving
+#[derive(Debug, Clone, Default)]
+pub struct BundleAugmenters(Vec<Arc<dyn BundleAugmenter>>);
+
+impl BundleAugmenters {
+    /// Registers the [`BundleAugmenter`], to be invoked after those already registered
+    pub fn register(mut self, augmenter: impl BundleAugmenter + 'static) -> Self {
+        self.0.push(Arc::new(augmenter));
+        self
+    }
+
+    /// Passes the [`Bundle`] through each [`BundleAugmenter`] in turn, failing if any does
+    pub async fn augment(
+        &self,
+        mut bundle: Bundle<NoMetadata>,
+    ) -> Result<Bundle<NoMetadata>, anyhow::Error> {
+        for augmenter in &self.0 {
+            bundle = augmenter.augment(bundle).await.map_err(|err| {
+                anyhow::anyhow!("Bundle augmenter {} failed: {err}", augmenter.name())
+            })?;
+        }
+        Ok(bundle)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Augmentation, BundleAugmenter, BundleAugmenters};
+    use crate::bundle::{Bundle, NoMetadata};
+    use serde_json::json;
+
+    #[derive(Debug)]
+    struct Facilities;
+
+    impl BundleAugmenter for Facilities {
+        fn name(&self) -> &str {
+            "facilities"
+        }
+
+        fn augment(&self, bundle: Bundle<NoMetadata>) -> Augmentation<'_> {
+            Box::pin(
+                async move { bundle.with_augmented_dataset("facilities", &json!({"diamond": {}})) },
+            )
+        }
+    }
+
+    #[tokio::test]
+    async fn augmenters_applied() {
+        let bundle = Bundle::<NoMetadata>::default();
+        let revision = bundle.revision().to_string();
+        let bundle = BundleAugmenters::default().augment(bundle).await.unwrap();
+        assert_eq!(revision, bundle.revision());
+        let bundle = BundleAugmenters::default()
+            .register(Facilities)
+            .augment(bundle)
+            .await
+            .unwrap();
+        assert_ne!(revision, bundle.revision());
+        assert!(bundle.dataset_to_tar("facilities").unwrap().is_some());
+    }
+}
@@ -0,0 +1,144 @@
+use ring::constant_time::verify_slices_are_equal;
+use sha2::{Digest, Sha256};
+use std::{collections::BTreeMap, str::FromStr};
+
+/// The hash of a password, against which supplied passwords are verified
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum PasswordHash {
+    /// A salted bcrypt hash, in modular crypt format
+    Bcrypt(String),
+    /// The hex encoded unsalted SHA-256 digest of the password, accepted for compatibility with existing configuration
+    Sha256(String),
+}
+
+impl PasswordHash {
+    /// Whether the password matches the hash, compared in constant time
+    fn verify(&self, password: &str) -> bool {
+        match self {
+            Self::Bcrypt(hash) => bcrypt::verify(password, hash).unwrap_or(false),
+            Self::Sha256(digest) => verify_slices_are_equal(
+                format!("{:x}", Sha256::digest(password.as_bytes())).as_bytes(),
+                digest.as_bytes(),
+            )
+            .is_ok(),
+        }
+    }
+}
+
+/// A username and the hash of its password, accepted via HTTP Basic authentication
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BasicAuthUser {
+    /// The username
+    username: String,
+    /// The hash of the password
+    password_hash: PasswordHash,
+}
+
+impl FromStr for BasicAuthUser {
+    type Err = anyhow::Error;
+
+    fn from_str(user: &str) -> Result<Self, Self::Err> {
+        let (username, password_hash) = user.split_once(':').ok_or_else(|| {
+            anyhow::anyhow!("Basic auth user is not of the form '<username>:<password hash>'")
+        })?;
+        if username.is_empty() {
+            anyhow::bail!("Basic auth user has an empty username");
+        }
+        let password_hash = if password_hash.starts_with('$') {
+            bcrypt::HashParts::from_str(password_hash).map_err(|err| {
+                anyhow::anyhow!(
+                    "Basic auth user '{username}' has a password hash which is not a bcrypt hash: {err}"
+                )
+            })?;
+            PasswordHash::Bcrypt(password_hash.to_string())
+        } else if password_hash.len() == 64
+            && password_hash.chars().all(|char| char.is_ascii_hexdigit())
+        {
+            PasswordHash::Sha256(password_hash.to_ascii_lowercase())
+        } else {
+            anyhow::bail!(
+                "Basic auth user '{username}' has a password hash which is neither a bcrypt hash nor a hex encoded SHA-256 digest"
+            );
+        };
+        Ok(Self {
+            username: username.to_string(),
+            password_hash,
+        })
+    }
+}
+
+/// The users accepted via HTTP Basic authentication, by username
+#[derive(Debug, Clone, Default)]
+pub struct BasicAuthUsers(BTreeMap<String, PasswordHash>);
+
+impl From<Vec<BasicAuthUser>> for BasicAuthUsers {
+    fn from(users: Vec<BasicAuthUser>) -> Self {
+        Self(
+            users
+                .into_iter()
+                .map(|user| (user.username, user.password_hash))
+                .collect(),
+        )
+    }
+}
+
+impl BasicAuthUsers {
+    /// Whether no users are accepted
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The accepted usernames
+    pub fn usernames(&self) -> Vec<String> {
+        self.0.keys().cloned().collect()
+    }
+
+    /// Whether the password matches the hash configured for the username
+    ///
+    /// Verifying a bcrypt hash is deliberately slow, so this should be called from the blocking thread pool
+    pub fn verify(&self, username: &str, password: &str) -> bool {
+        self.0
+            .get(username)
+            .is_some_and(|password_hash| password_hash.verify(password))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BasicAuthUser, BasicAuthUsers};
+
+    /// The SHA-256 digest of "password"
+    const PASSWORD_DIGEST: &str =
+        "5E884898DA28047151D0E56F8DC6292773603D0D6AABBDD62A11EF721D1542D8";
+
+    #[test]
+    fn verify_password() {
+        let users = BasicAuthUsers::from(vec![format!("legacy:{PASSWORD_DIGEST}")
+            .parse::<BasicAuthUser>()
+            .unwrap()]);
+        assert!(users.verify("legacy", "password"));
+        assert!(!users.verify("legacy", "wrong"));
+        assert!(!users.verify("other", "password"));
+        assert_eq!(vec!["legacy"], users.usernames());
+    }
+
+    #[test]
+    fn verify_bcrypt_password() {
+        let password_hash = bcrypt::hash("password", 4).unwrap();
+        let users = BasicAuthUsers::from(vec![format!("alice:{password_hash}")
+            .parse::<BasicAuthUser>()
+            .unwrap()]);
+        assert!(users.verify("alice", "password"));
+        assert!(!users.verify("alice", "wrong"));
+    }
+
+    #[test]
+    fn invalid_users_rejected() {
+        assert!("legacy".parse::<BasicAuthUser>().is_err());
+        assert!("legacy:password".parse::<BasicAuthUser>().is_err());
+        assert!("legacy:$2b$04$short".parse::<BasicAuthUser>().is_err());
+        assert!(format!(":{PASSWORD_DIGEST}")
+            .parse::<BasicAuthUser>()
+            .is_err());
+    }
+}
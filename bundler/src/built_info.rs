@@ -1 +1,6 @@
 include!(concat!(env!("OUT_DIR"), "/built.rs"));
+
+/// The git commit from which the crate was built, if known, as provided by the `GIT_COMMIT` environment variable or read from the repository
+pub const GIT_COMMIT: Option<&str> = option_env!("BUNDLER_GIT_COMMIT");
+/// The time at which the crate was built, in seconds since the Unix epoch, as provided by the `SOURCE_DATE_EPOCH` environment variable or read from the clock
+pub const BUILD_TIMESTAMP: &str = env!("BUNDLER_BUILD_TIMESTAMP");
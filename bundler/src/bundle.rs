@@ -1,18 +1,51 @@
-use flate2::{write::GzEncoder, Compression};
-use schemars::{schema::RootSchema, schema_for, JsonSchema};
-use serde::Serialize;
+use flate2::{read::GzDecoder, Compression, GzBuilder};
+use schemars::{
+    schema::{
+        InstanceType, RootSchema, Schema, SchemaObject, StringValidation, SubschemaValidation,
+    },
+    schema_for, JsonSchema,
+};
+use serde::{ser::Error as _, Serialize, Serializer};
+use sha2::{Digest, Sha256};
 use sqlx::MySqlPool;
 use std::{
-    collections::{hash_map::DefaultHasher, BTreeMap},
+    collections::{BTreeMap, BTreeSet},
     fmt::Debug,
-    hash::{Hash, Hasher},
+    future::Future,
+    io::{BufWriter, Read, Write},
+    path::Path,
+    time::{Duration, Instant},
 };
-use tar::Header;
+use tar::{EntryType, Header};
 use tokio::try_join;
 use tracing::instrument;
 
-use crate::permissionables::{
-    beamlines::Beamlines, proposals::Proposals, sessions::Sessions, subjects::Subjects,
+use crate::{
+    database::database_time,
+    dataset_roots::DatasetRoots,
+    disabled_datasets::DisabledDatasets,
+    fetch_status::FetchStatus,
+    layout::DatasetLayouts,
+    partial_update::PartialUpdates,
+    permissionables::{
+        beamlines::Beamlines,
+        instrument_scientists::InstrumentScientists,
+        lab_contacts::LabContacts,
+        people::People,
+        proposals::{ProposalFilters, Proposals},
+        roles::Roles,
+        session_members::SessionMembers,
+        session_participants::SessionParticipants,
+        sessions::Sessions,
+        subjects::Subjects,
+        with_timeout, FetchError,
+    },
+    policy_source::{Policies, POLICY_PREFIX},
+    polling::DatasetIntervals,
+    redaction::Redactions,
+    transformation::Transformations,
+    validation::{validate, Violation},
+    volume::DatasetVolume,
 };
 
 /// A compiled Web Assembly module
@@ -25,7 +58,7 @@ struct WasmModule {
 }
 
 /// A placeholder to be used when no metadata is required
-#[derive(Debug, Hash, Serialize)]
+#[derive(Debug, Default, Serialize)]
 pub struct NoMetadata;
 
 /// The manifest file, which contains data about the bundle and optional additonal metadata
@@ -40,22 +73,153 @@ where
     roots: Vec<String>,
     /// A set of WebAssembly modules included in the bundle
     wasm: Vec<WasmModule>,
+    /// Optional extra metadata, marking any datasets carried over from a previous fetch
+    metadata: ManifestMetadata<Metadata>,
+}
+
+/// The metadata of the manifest, comprising the optional extra metadata and, in a partial update, the datasets carried over from a previous fetch
+///
+/// The extra metadata is serialized as is unless datasets are stale, whereupon they are listed under `stale_datasets` alongside its fields
+#[derive(Debug)]
+struct ManifestMetadata<Metadata> {
     /// Optional extra metadata
-    metadata: Metadata,
+    extra: Metadata,
+    /// The datasets served from a previous fetch, as their own fetch failed
+    stale_datasets: BTreeSet<String>,
+}
+
+impl<Metadata> Serialize for ManifestMetadata<Metadata>
+where
+    Metadata: Serialize,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if self.stale_datasets.is_empty() {
+            return self.extra.serialize(serializer);
+        }
+        let mut metadata = match serde_json::to_value(&self.extra).map_err(S::Error::custom)? {
+            serde_json::Value::Object(fields) => fields,
+            serde_json::Value::Null => serde_json::Map::new(),
+            extra => serde_json::Map::from_iter([("extra".to_string(), extra)]),
+        };
+        metadata.insert(
+            "stale_datasets".to_string(),
+            serde_json::json!(self.stale_datasets),
+        );
+        metadata.serialize(serializer)
+    }
+}
+
+/// The size of a block in a tar archive, to which entries are padded
+const TAR_BLOCK_SIZE: u64 = 512;
+
+/// The permissions of each entry in a tar archive, readable by all and writable by the owner
+const TAR_ENTRY_MODE: u32 = 0o644;
+
+/// An extension trait used to implement appending JSON serialized entries to an archive
+pub trait AppendJson {
+    /// Serializes the value directly into the archive, rather than via an intermediate buffer
+    ///
+    /// Headers are normalized, with a zero modification time and owner, such that identical values produce identical entries
+    fn append_json(
+        &mut self,
+        path: impl AsRef<Path>,
+        value: &impl Serialize,
+    ) -> Result<(), anyhow::Error>;
 }
 
-/// An extension trait used to implement header creation from byte slices
-trait FromByteSlice {
-    #[allow(clippy::missing_docs_in_private_items)]
-    fn from_bytes(slice: &[u8]) -> Self;
+impl<W: Write> AppendJson for tar::Builder<W> {
+    fn append_json(
+        &mut self,
+        path: impl AsRef<Path>,
+        value: &impl Serialize,
+    ) -> Result<(), anyhow::Error> {
+        let mut size = ByteCount::default();
+        serde_json::to_writer(&mut size, value)?;
+        let header = normalized_header(path, size.0)?;
+
+        let mut writer = BufWriter::new(self.get_mut());
+        writer.write_all(header.as_bytes())?;
+        serde_json::to_writer(&mut writer, value)?;
+        let padding = (TAR_BLOCK_SIZE - size.0 % TAR_BLOCK_SIZE) % TAR_BLOCK_SIZE;
+        writer.write_all(&[0; TAR_BLOCK_SIZE as usize][..padding as usize])?;
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Creates the header of a regular file entry, with a zero modification time and owner, such that identical contents produce identical entries
+fn normalized_header(path: impl AsRef<Path>, size: u64) -> Result<Header, anyhow::Error> {
+    let mut header = Header::new_gnu();
+    header.set_path(path)?;
+    header.set_size(size);
+    header.set_entry_type(EntryType::Regular);
+    header.set_mode(TAR_ENTRY_MODE);
+    header.set_mtime(0);
+    header.set_uid(0);
+    header.set_gid(0);
+    header.set_cksum();
+    Ok(header)
+}
+
+/// Compresses a tar archive with gzip, as served to Open Policy Agent
+///
+/// The modification time of the gzip header is zeroed, such that identical archives compress identically
+pub fn gzip(archive: &[u8]) -> Result<Vec<u8>, std::io::Error> {
+    let mut encoder = GzBuilder::new()
+        .mtime(0)
+        .write(Vec::new(), Compression::best());
+    encoder.write_all(archive)?;
+    encoder.finish()
+}
+
+/// Decompresses a gzipped tar archive
+pub fn gunzip(archive: &[u8]) -> Result<Vec<u8>, std::io::Error> {
+    let mut decompressed = Vec::new();
+    GzDecoder::new(archive).read_to_end(&mut decompressed)?;
+    Ok(decompressed)
 }
 
-impl FromByteSlice for Header {
-    fn from_bytes(slice: &[u8]) -> Self {
-        let mut header = Self::new_gnu();
-        header.set_size(slice.len() as u64);
-        header.set_cksum();
-        header
+/// A hasher of the JSON serialization of values, producing a SHA-256 digest which is stable across processes and releases
+///
+/// Datasets are serialized from ordered collections, such that identical data always produces an identical digest
+#[derive(Default)]
+pub struct ContentHasher(Sha256);
+
+impl ContentHasher {
+    /// Feeds the JSON serialization of the value into the digest
+    pub fn update(&mut self, value: &impl Serialize) {
+        serde_json::to_writer(&mut *self, value).expect("Values are serializable as JSON");
+    }
+
+    /// The hex encoded digest of the values fed into the hasher
+    pub fn finish(self) -> String {
+        format!("{:x}", self.0.finalize())
+    }
+}
+
+impl Write for ContentHasher {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A [`Write`] which discards its input, counting the number of bytes written
+#[derive(Debug, Default)]
+struct ByteCount(u64);
+
+impl Write for ByteCount {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0 += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
     }
 }
 
@@ -74,14 +238,284 @@ where
     proposals: Proposals,
     /// A mapping of beamlines to their various attributes
     beamlines: Beamlines,
+    /// A mapping of roles to the permissions they grant
+    roles: Roles,
+    /// A mapping of subjects to their personal details, if personal data is included
+    people: Option<People>,
+    /// A mapping of sessions to the subjects registered on them
+    session_participants: SessionParticipants,
+    /// A mapping of proposals to their lab contacts
+    lab_contacts: LabContacts,
+    /// A mapping of beamlines to the instrument scientists assigned to them
+    instrument_scientists: InstrumentScientists,
+    /// A mapping of sessions to the subjects associated with them, if the inverted index is included
+    session_members: Option<SessionMembers>,
+    /// The redactions applied to each dataset as it is serialized
+    redactions: Redactions,
+    /// The transformations applied to each dataset as it is serialized, after any redactions
+    transformations: Transformations,
+    /// The layout in which each dataset is written, after any transformations
+    layouts: DatasetLayouts,
+    /// The Rego policies included alongside the datasets
+    policies: Policies,
+    /// The manifest root under which each dataset is placed
+    dataset_roots: DatasetRoots,
+    /// The datasets excluded from the bundle
+    disabled_datasets: DisabledDatasets,
+    /// Datasets contributed by a [`BundleAugmenter`](crate::augmenter::BundleAugmenter), keyed by the name of each dataset
+    augmented_datasets: BTreeMap<String, serde_json::Value>,
 }
 
-/// The prefix applied to data files in the bundle. Open Policy Agent does not support loading bundles with overlapping prefixes
-const BUNDLE_PREFIX: &str = "diamond/data";
+/// Datasets derived from ISPyB sessions, retained between polls so they can be updated incrementally
+#[derive(Debug, Clone)]
+pub struct SessionSnapshot {
+    /// The database time, as a unix timestamp, at which the snapshot was taken
+    taken_at: i64,
+    /// A mapping of sessions to their various attributes
+    sessions: Sessions,
+    /// A mapping of proposals to their various attributes
+    proposals: Proposals,
+    /// A mapping of beamlines to their various attributes
+    beamlines: Beamlines,
+}
+
+impl SessionSnapshot {
+    /// Fetches all session data from ISPyB
+    async fn fetch(
+        ispyb_pool: &MySqlPool,
+        query_timeout: Duration,
+        proposal_filters: &ProposalFilters,
+        disabled_datasets: &DisabledDatasets,
+        fetch_status: &FetchStatus,
+        taken_at: i64,
+    ) -> Result<Self, FetchError> {
+        let (sessions, proposals, beamlines) = try_join!(
+            fetch_unless_disabled(
+                "sessions",
+                disabled_datasets,
+                fetch_status.record(
+                    "sessions",
+                    with_timeout("sessions", query_timeout, Sessions::fetch(ispyb_pool))
+                )
+            ),
+            fetch_unless_disabled(
+                "proposals",
+                disabled_datasets,
+                fetch_status.record(
+                    "proposals",
+                    with_timeout(
+                        "proposals",
+                        query_timeout,
+                        Proposals::fetch(ispyb_pool, proposal_filters)
+                    )
+                )
+            ),
+            fetch_unless_disabled(
+                "beamlines",
+                disabled_datasets,
+                fetch_status.record(
+                    "beamlines",
+                    with_timeout("beamlines", query_timeout, Beamlines::fetch(ispyb_pool))
+                )
+            ),
+        )?;
+        Ok(Self {
+            taken_at,
+            sessions,
+            proposals,
+            beamlines,
+        })
+    }
+
+    /// Fetches session data which has changed since the snapshot was taken and merges it into a copy of the snapshot
+    async fn update(
+        &self,
+        ispyb_pool: &MySqlPool,
+        query_timeout: Duration,
+        proposal_filters: &ProposalFilters,
+        disabled_datasets: &DisabledDatasets,
+        fetch_status: &FetchStatus,
+        taken_at: i64,
+    ) -> Result<Self, FetchError> {
+        let (sessions, proposals, beamlines) = try_join!(
+            fetch_unless_disabled(
+                "sessions",
+                disabled_datasets,
+                fetch_status.record(
+                    "sessions",
+                    with_timeout(
+                        "sessions",
+                        query_timeout,
+                        Sessions::fetch_changed(ispyb_pool, self.taken_at)
+                    )
+                )
+            ),
+            fetch_unless_disabled(
+                "proposals",
+                disabled_datasets,
+                fetch_status.record(
+                    "proposals",
+                    with_timeout(
+                        "proposals",
+                        query_timeout,
+                        Proposals::fetch_changed(ispyb_pool, proposal_filters, self.taken_at)
+                    )
+                )
+            ),
+            fetch_unless_disabled(
+                "beamlines",
+                disabled_datasets,
+                fetch_status.record(
+                    "beamlines",
+                    with_timeout(
+                        "beamlines",
+                        query_timeout,
+                        Beamlines::fetch_changed(ispyb_pool, self.taken_at)
+                    )
+                )
+            ),
+        )?;
+        tracing::info!(
+            "Fetched {} sessions changed since {}",
+            sessions.len(),
+            self.taken_at
+        );
+        let mut snapshot = self.clone();
+        snapshot.taken_at = taken_at;
+        snapshot.sessions.merge(sessions);
+        snapshot.proposals.merge(proposals);
+        snapshot.beamlines.merge(beamlines);
+        Ok(snapshot)
+    }
+}
+
+/// A dataset retained between polls, with the time at which it was fetched
+#[derive(Debug, Clone)]
+struct Retained<Dataset> {
+    /// The dataset as fetched
+    dataset: Dataset,
+    /// The time at which the fetch began
+    fetched_at: Instant,
+    /// Whether the dataset was carried over from a previous fetch, as its own fetch failed
+    stale: bool,
+}
+
+/// Datasets retained between polls, such that each is only refetched once its polling interval has elapsed
+#[derive(Debug, Clone)]
+pub struct RetainedDatasets {
+    /// A mapping of subjects to their various attributes
+    subjects: Retained<Subjects>,
+    /// A mapping of sessions to their various attributes
+    sessions: Retained<Sessions>,
+    /// A mapping of proposals to their various attributes
+    proposals: Retained<Proposals>,
+    /// A mapping of beamlines to their various attributes
+    beamlines: Retained<Beamlines>,
+    /// A mapping of roles to the permissions they grant
+    roles: Retained<Roles>,
+    /// A mapping of subjects to their personal details, if personal data is included
+    people: Retained<Option<People>>,
+    /// A mapping of sessions to the subjects registered on them
+    session_participants: Retained<SessionParticipants>,
+    /// A mapping of proposals to their lab contacts
+    lab_contacts: Retained<LabContacts>,
+    /// A mapping of beamlines to the instrument scientists assigned to them
+    instrument_scientists: Retained<InstrumentScientists>,
+}
+
+/// Awaits the fetch of the named dataset if its polling interval has elapsed since it was retained, otherwise reusing the retained dataset
+///
+/// Should the fetch fail, the retained dataset is carried over as stale if [`PartialUpdates`] permit it, retaining the time of its fetch such that it is due again at the next poll
+async fn fetch_if_due<Dataset: Clone>(
+    dataset: &str,
+    intervals: &DatasetIntervals,
+    partial_updates: &PartialUpdates,
+    retained: Option<&Retained<Dataset>>,
+    fetched_at: Instant,
+    fetch: impl Future<Output = Result<Dataset, FetchError>>,
+) -> Result<Retained<Dataset>, FetchError> {
+    if let Some(retained) = retained.filter(|retained| {
+        !intervals.is_due(dataset, fetched_at.duration_since(retained.fetched_at))
+    }) {
+        tracing::debug!("Reusing {dataset}, which is not yet due to be polled");
+        return Ok(retained.clone());
+    }
+    match (fetch.await, retained) {
+        (Ok(fetched), _) => Ok(Retained {
+            dataset: fetched,
+            fetched_at,
+            stale: false,
+        }),
+        (Err(err), Some(retained)) if partial_updates.permits(dataset) => {
+            tracing::warn!(
+                monotonic_counter.bundle_stale_datasets = 1,
+                "Could not fetch {dataset}, serving the previous fetch: {err}"
+            );
+            Ok(Retained {
+                stale: true,
+                ..retained.clone()
+            })
+        }
+        (Err(err), _) => Err(err),
+    }
+}
+
+/// Awaits the fetch of the named dataset unless it is disabled by the [`DisabledDatasets`], in which case it is left empty without querying ISPyB
+async fn fetch_unless_disabled<Dataset: Default>(
+    dataset: &str,
+    disabled_datasets: &DisabledDatasets,
+    fetch: impl Future<Output = Result<Dataset, FetchError>>,
+) -> Result<Dataset, FetchError> {
+    if disabled_datasets.disables(dataset) {
+        return Ok(Dataset::default());
+    }
+    fetch.await
+}
+
+/// The prefix applied to data files in the bundle, unless their dataset is placed under another root. Open Policy Agent does not support loading bundles with overlapping prefixes
+pub const BUNDLE_PREFIX: &str = "diamond/data";
+
+/// The prefix applied to the JSON Schemas of the datasets in the bundle, such that policy authors can discover the available fields
+pub const SCHEMA_PREFIX: &str = "diamond/schemas";
+
+/// The names of the datasets which may be included in the bundle
+pub const DATASETS: [&str; 10] = [
+    "subjects",
+    "sessions",
+    "proposals",
+    "beamlines",
+    "roles",
+    "people",
+    "session_participants",
+    "lab_contacts",
+    "instrument_scientists",
+    "session_members",
+];
+
+impl<Metadata> Default for Bundle<Metadata>
+where
+    Metadata: Debug + Default + Serialize,
+{
+    /// Creates a [`Bundle`] containing no datasets
+    fn default() -> Self {
+        Self::new(
+            Metadata::default(),
+            Subjects::default(),
+            Sessions::default(),
+            Proposals::default(),
+            Beamlines::default(),
+            Roles::default(),
+            None,
+            SessionParticipants::default(),
+            LabContacts::default(),
+            InstrumentScientists::default(),
+        )
+    }
+}
 
 impl<Metadata> Bundle<Metadata>
 where
-    Metadata: Debug + Hash + Serialize,
+    Metadata: Debug + Serialize,
 {
     /// Creates a [`Bundle`] from known [`Subjects`]
     pub fn new(
@@ -90,98 +524,1435 @@ where
         sessions: Sessions,
         proposals: Proposals,
         beamlines: Beamlines,
+        roles: Roles,
+        people: Option<People>,
+        session_participants: SessionParticipants,
+        lab_contacts: LabContacts,
+        instrument_scientists: InstrumentScientists,
     ) -> Self {
-        let mut hasher = DefaultHasher::new();
-        metadata.hash(&mut hasher);
-        subjects.hash(&mut hasher);
-        sessions.hash(&mut hasher);
-        proposals.hash(&mut hasher);
-        beamlines.hash(&mut hasher);
-        let hash = hasher.finish();
-
-        Self {
+        let mut hasher = ContentHasher::default();
+        hasher.update(&metadata);
+        hasher.update(&subjects);
+        hasher.update(&sessions);
+        hasher.update(&proposals);
+        hasher.update(&beamlines);
+        hasher.update(&roles);
+        if let Some(people) = &people {
+            hasher.update(people);
+        }
+        hasher.update(&session_participants);
+        hasher.update(&lab_contacts);
+        hasher.update(&instrument_scientists);
+
+        let mut bundle = Self {
             manifest: Manifest {
-                revision: format!("{}:{}", crate::built_info::PKG_VERSION, hash),
-                roots: vec![BUNDLE_PREFIX.to_string()],
+                revision: format!("{}:{}", crate::built_info::PKG_VERSION, hasher.finish()),
+                roots: Vec::new(),
                 wasm: vec![],
-                metadata,
+                metadata: ManifestMetadata {
+                    extra: metadata,
+                    stale_datasets: BTreeSet::new(),
+                },
             },
             subjects,
             sessions,
             proposals,
             beamlines,
+            roles,
+            people,
+            session_participants,
+            lab_contacts,
+            instrument_scientists,
+            session_members: None,
+            redactions: Redactions::default(),
+            transformations: Transformations::default(),
+            layouts: DatasetLayouts::default(),
+            policies: Policies::default(),
+            dataset_roots: DatasetRoots::default(),
+            disabled_datasets: DisabledDatasets::default(),
+            augmented_datasets: BTreeMap::new(),
+        };
+        bundle.manifest.roots = bundle.manifest_roots();
+        bundle
+    }
+
+    /// Applies the [`Redactions`] to each dataset as it is serialized, deriving a new revision from the original and the redactions
+    ///
+    /// Redactions which are only reported leave both the data and the revision unchanged
+    pub fn redact(mut self, redactions: Redactions) -> Self {
+        if !redactions.is_empty() && !redactions.is_dry_run() {
+            self.rehash_revision("redactions", &redactions);
+        }
+        self.redactions = redactions;
+        self
+    }
+
+    /// Applies the [`Transformations`] to each dataset as it is serialized, deriving a new revision from the original and the transformations
+    pub fn transform(mut self, transformations: Transformations) -> Self {
+        if !transformations.is_empty() {
+            self.rehash_revision("transformations", &transformations);
+        }
+        self.transformations = transformations;
+        self
+    }
+
+    /// Writes each dataset in the layout configured in the [`DatasetLayouts`], deriving a new revision from the original and the layouts
+    pub fn with_layouts(mut self, layouts: DatasetLayouts) -> Self {
+        if !layouts.is_empty() {
+            self.rehash_revision("layouts", &layouts);
+        }
+        self.layouts = layouts;
+        self
+    }
+
+    /// Includes the [`Policies`] alongside the datasets, deriving a new revision from the original and the policies
+    pub fn with_policies(mut self, policies: Policies) -> Self {
+        if !policies.is_empty() {
+            self.rehash_revision("policies", &policies);
+        }
+        self.policies = policies;
+        self.manifest.roots = self.manifest_roots();
+        self
+    }
+
+    /// Places each dataset under its root in [`DatasetRoots`], deriving a new revision from the original and the roots
+    pub fn with_dataset_roots(mut self, dataset_roots: DatasetRoots) -> Self {
+        if !dataset_roots.is_empty() {
+            self.rehash_revision("dataset_roots", &dataset_roots);
+        }
+        self.dataset_roots = dataset_roots;
+        self.manifest.roots = self.manifest_roots();
+        self
+    }
+
+    /// Includes a [`SessionMembers`] index derived from the [`Subjects`], if enabled, deriving a new revision from the original and the index
+    pub fn with_session_members(mut self, include_session_members: bool) -> Self {
+        if include_session_members {
+            let session_members = SessionMembers::from(&self.subjects);
+            self.rehash_revision("session_members", &session_members);
+            self.session_members = Some(session_members);
+        } else {
+            self.session_members = None;
+        }
+        self.manifest.roots = self.manifest_roots();
+        self
+    }
+
+    /// Marks the named datasets as carried over from a previous fetch in the manifest metadata, deriving a new revision from the original and the marked datasets
+    pub fn with_stale_datasets(mut self, stale_datasets: BTreeSet<String>) -> Self {
+        if !stale_datasets.is_empty() {
+            self.rehash_revision("stale_datasets", &stale_datasets);
+        }
+        self.manifest.metadata.stale_datasets = stale_datasets;
+        self
+    }
+
+    /// Excludes the [`DisabledDatasets`] from the bundle, deriving a new revision from the original and the disabled datasets
+    pub fn with_disabled_datasets(mut self, disabled_datasets: DisabledDatasets) -> Self {
+        if !disabled_datasets.is_empty() {
+            self.rehash_revision("disabled_datasets", &disabled_datasets);
+        }
+        self.disabled_datasets = disabled_datasets;
+        self.manifest.roots = self.manifest_roots();
+        self
+    }
+
+    /// Includes an additional dataset, as contributed by a [`BundleAugmenter`](crate::augmenter::BundleAugmenter), deriving a new revision from the original and the dataset
+    ///
+    /// The dataset is written alongside those fetched from ISPyB, with any redactions, transformations and layout configured for its name, unless it is disabled. Fails if the name is that of a dataset fetched from ISPyB
+    pub fn with_augmented_dataset(
+        mut self,
+        dataset: impl Into<String>,
+        value: &impl Serialize,
+    ) -> Result<Self, anyhow::Error> {
+        let dataset = dataset.into();
+        if DATASETS.contains(&dataset.as_str()) {
+            anyhow::bail!(
+                "Augmented dataset '{dataset}' would replace a dataset fetched from ISPyB"
+            );
+        }
+        let value = serde_json::to_value(value)?;
+        self.rehash_revision(&dataset, &value);
+        self.augmented_datasets.insert(dataset, value);
+        self.manifest.roots = self.manifest_roots();
+        Ok(self)
+    }
+
+    /// Derives a new revision from the original and the value applied by a builder method, tagged with what the value is such that differing values with identical serializations derive differing revisions
+    fn rehash_revision(&mut self, tag: &str, value: &impl Serialize) {
+        let mut hasher = ContentHasher::default();
+        hasher.update(&self.manifest.revision);
+        hasher.update(&tag);
+        hasher.update(value);
+        self.manifest.revision = format!("{}:{}", crate::built_info::PKG_VERSION, hasher.finish());
+    }
+
+    /// Whether the named dataset is included in the bundle, with disabled datasets excluded and personal data and the session index only included if enabled
+    fn includes(&self, dataset: &str) -> bool {
+        if self.disabled_datasets.disables(dataset) {
+            return false;
         }
+        match dataset {
+            "people" => self.people.is_some(),
+            "session_members" => self.session_members.is_some(),
+            _ => true,
+        }
+    }
+
+    /// The roots of the datasets included in the bundle, followed by those of the schemas and any policies
+    fn manifest_roots(&self) -> Vec<String> {
+        let mut roots = self.dataset_roots.manifest_roots(
+            DATASETS
+                .into_iter()
+                .chain(self.augmented_datasets.keys().map(String::as_str))
+                .filter(|dataset| self.includes(dataset)),
+        );
+        roots.push(SCHEMA_PREFIX.to_string());
+        if !self.policies.is_empty() {
+            roots.push(POLICY_PREFIX.to_string());
+        }
+        roots
+    }
+
+    /// Fetches [`Subjects`] from ISPyB and constructs a [`Bundle`], cancelling any query which exceeds the timeout
+    ///
+    /// [`People`] are only fetched if personal data is to be included, while [`DisabledDatasets`] are not fetched at all. The outcome of each fetch is recorded in the [`FetchStatus`]
+    #[allow(clippy::too_many_arguments)]
+    pub async fn fetch(
+        metadata: Metadata,
+        ispyb_pool: &MySqlPool,
+        query_timeout: Duration,
+        include_personal_data: bool,
+        proposal_filters: &ProposalFilters,
+        disabled_datasets: &DisabledDatasets,
+        fetch_status: &FetchStatus,
+    ) -> Result<Self, FetchError> {
+        Self::fetch_retaining(
+            metadata,
+            ispyb_pool,
+            query_timeout,
+            include_personal_data,
+            proposal_filters,
+            disabled_datasets,
+            fetch_status,
+            &DatasetIntervals::default(),
+            &PartialUpdates::default(),
+            None,
+        )
+        .await
+        .map(|(bundle, _)| bundle)
+    }
+
+    /// Fetches [`Subjects`] from ISPyB and constructs a [`Bundle`], reusing any [`RetainedDatasets`] which are not yet due to be polled
+    ///
+    /// Datasets are fetched as by [`Bundle::fetch`], with those fetched retained for subsequent polls. Where [`PartialUpdates`] permit it, a dataset which fails to fetch is carried over from the retained datasets and marked as stale in the manifest metadata
+    #[allow(clippy::too_many_arguments)]
+    #[instrument(name = "fetch_bundle", skip(fetch_status, retained))]
+    pub async fn fetch_retaining(
+        metadata: Metadata,
+        ispyb_pool: &MySqlPool,
+        query_timeout: Duration,
+        include_personal_data: bool,
+        proposal_filters: &ProposalFilters,
+        disabled_datasets: &DisabledDatasets,
+        fetch_status: &FetchStatus,
+        intervals: &DatasetIntervals,
+        partial_updates: &PartialUpdates,
+        retained: Option<&RetainedDatasets>,
+    ) -> Result<(Self, RetainedDatasets), FetchError> {
+        let fetched_at = Instant::now();
+        let (
+            subjects,
+            sessions,
+            proposals,
+            beamlines,
+            roles,
+            people,
+            session_participants,
+            lab_contacts,
+            instrument_scientists,
+        ) = try_join!(
+            fetch_if_due(
+                "subjects",
+                intervals,
+                partial_updates,
+                retained.map(|retained| &retained.subjects),
+                fetched_at,
+                fetch_unless_disabled(
+                    "subjects",
+                    disabled_datasets,
+                    fetch_status.record("subjects", Subjects::fetch(ispyb_pool, query_timeout))
+                )
+            ),
+            fetch_if_due(
+                "sessions",
+                intervals,
+                partial_updates,
+                retained.map(|retained| &retained.sessions),
+                fetched_at,
+                fetch_unless_disabled(
+                    "sessions",
+                    disabled_datasets,
+                    fetch_status.record(
+                        "sessions",
+                        with_timeout("sessions", query_timeout, Sessions::fetch(ispyb_pool))
+                    )
+                )
+            ),
+            fetch_if_due(
+                "proposals",
+                intervals,
+                partial_updates,
+                retained.map(|retained| &retained.proposals),
+                fetched_at,
+                fetch_unless_disabled(
+                    "proposals",
+                    disabled_datasets,
+                    fetch_status.record(
+                        "proposals",
+                        with_timeout(
+                            "proposals",
+                            query_timeout,
+                            Proposals::fetch(ispyb_pool, proposal_filters)
+                        )
+                    )
+                )
+            ),
+            fetch_if_due(
+                "beamlines",
+                intervals,
+                partial_updates,
+                retained.map(|retained| &retained.beamlines),
+                fetched_at,
+                fetch_unless_disabled(
+                    "beamlines",
+                    disabled_datasets,
+                    fetch_status.record(
+                        "beamlines",
+                        with_timeout("beamlines", query_timeout, Beamlines::fetch(ispyb_pool))
+                    )
+                )
+            ),
+            fetch_if_due(
+                "roles",
+                intervals,
+                partial_updates,
+                retained.map(|retained| &retained.roles),
+                fetched_at,
+                fetch_unless_disabled(
+                    "roles",
+                    disabled_datasets,
+                    fetch_status.record(
+                        "roles",
+                        with_timeout("roles", query_timeout, Roles::fetch(ispyb_pool))
+                    )
+                )
+            ),
+            fetch_if_due(
+                "people",
+                intervals,
+                partial_updates,
+                retained.map(|retained| &retained.people),
+                fetched_at,
+                fetch_unless_disabled(
+                    "people",
+                    disabled_datasets,
+                    fetch_people(
+                        ispyb_pool,
+                        query_timeout,
+                        include_personal_data,
+                        fetch_status
+                    )
+                )
+            ),
+            fetch_if_due(
+                "session_participants",
+                intervals,
+                partial_updates,
+                retained.map(|retained| &retained.session_participants),
+                fetched_at,
+                fetch_unless_disabled(
+                    "session_participants",
+                    disabled_datasets,
+                    fetch_status.record(
+                        "session_participants",
+                        with_timeout(
+                            "session_participants",
+                            query_timeout,
+                            SessionParticipants::fetch(ispyb_pool)
+                        )
+                    )
+                )
+            ),
+            fetch_if_due(
+                "lab_contacts",
+                intervals,
+                partial_updates,
+                retained.map(|retained| &retained.lab_contacts),
+                fetched_at,
+                fetch_unless_disabled(
+                    "lab_contacts",
+                    disabled_datasets,
+                    fetch_status.record(
+                        "lab_contacts",
+                        with_timeout(
+                            "lab_contacts",
+                            query_timeout,
+                            LabContacts::fetch(ispyb_pool)
+                        )
+                    )
+                )
+            ),
+            fetch_if_due(
+                "instrument_scientists",
+                intervals,
+                partial_updates,
+                retained.map(|retained| &retained.instrument_scientists),
+                fetched_at,
+                fetch_unless_disabled(
+                    "instrument_scientists",
+                    disabled_datasets,
+                    fetch_status.record(
+                        "instrument_scientists",
+                        with_timeout(
+                            "instrument_scientists",
+                            query_timeout,
+                            InstrumentScientists::fetch(ispyb_pool)
+                        )
+                    )
+                )
+            ),
+        )?;
+        let stale_datasets = [
+            ("subjects", subjects.stale),
+            ("sessions", sessions.stale),
+            ("proposals", proposals.stale),
+            ("beamlines", beamlines.stale),
+            ("roles", roles.stale),
+            ("people", people.stale),
+            ("session_participants", session_participants.stale),
+            ("lab_contacts", lab_contacts.stale),
+            ("instrument_scientists", instrument_scientists.stale),
+        ]
+        .into_iter()
+        .filter_map(|(dataset, stale)| stale.then(|| dataset.to_string()))
+        .collect();
+        let retained = RetainedDatasets {
+            subjects,
+            sessions,
+            proposals,
+            beamlines,
+            roles,
+            people,
+            session_participants,
+            lab_contacts,
+            instrument_scientists,
+        };
+        Ok((
+            Self::new(
+                metadata,
+                retained.subjects.dataset.clone(),
+                retained.sessions.dataset.clone(),
+                retained.proposals.dataset.clone(),
+                retained.beamlines.dataset.clone(),
+                retained.roles.dataset.clone(),
+                retained.people.dataset.clone(),
+                retained.session_participants.dataset.clone(),
+                retained.lab_contacts.dataset.clone(),
+                retained.instrument_scientists.dataset.clone(),
+            )
+            .with_stale_datasets(stale_datasets)
+            .with_disabled_datasets(disabled_datasets.clone()),
+            retained,
+        ))
     }
 
-    /// Fetches [`Subjects`] from ISPyB and constructs a [`Bundle`]
-    #[instrument(name = "fetch_bundle")]
-    pub async fn fetch(metadata: Metadata, ispyb_pool: &MySqlPool) -> Result<Self, sqlx::Error> {
-        let (subjects, sessions, proposals, beamlines) = try_join!(
-            Subjects::fetch(ispyb_pool),
-            Sessions::fetch(ispyb_pool),
-            Proposals::fetch(ispyb_pool),
-            Beamlines::fetch(ispyb_pool),
+    /// Fetches [`Subjects`] from ISPyB and constructs a [`Bundle`], updating session data incrementally if a [`SessionSnapshot`] is available
+    #[allow(clippy::too_many_arguments)]
+    #[instrument(name = "fetch_bundle_incremental", skip(fetch_status, snapshot))]
+    pub async fn fetch_incremental(
+        metadata: Metadata,
+        ispyb_pool: &MySqlPool,
+        query_timeout: Duration,
+        include_personal_data: bool,
+        proposal_filters: &ProposalFilters,
+        disabled_datasets: &DisabledDatasets,
+        fetch_status: &FetchStatus,
+        snapshot: Option<&SessionSnapshot>,
+    ) -> Result<(Self, SessionSnapshot), FetchError> {
+        let taken_at =
+            with_timeout("database_time", query_timeout, database_time(ispyb_pool)).await?;
+        let (
+            subjects,
+            roles,
+            people,
+            session_participants,
+            lab_contacts,
+            instrument_scientists,
+            snapshot,
+        ) = try_join!(
+            fetch_unless_disabled(
+                "subjects",
+                disabled_datasets,
+                fetch_status.record("subjects", Subjects::fetch(ispyb_pool, query_timeout))
+            ),
+            fetch_unless_disabled(
+                "roles",
+                disabled_datasets,
+                fetch_status.record(
+                    "roles",
+                    with_timeout("roles", query_timeout, Roles::fetch(ispyb_pool))
+                )
+            ),
+            fetch_unless_disabled(
+                "people",
+                disabled_datasets,
+                fetch_people(
+                    ispyb_pool,
+                    query_timeout,
+                    include_personal_data,
+                    fetch_status
+                )
+            ),
+            fetch_unless_disabled(
+                "session_participants",
+                disabled_datasets,
+                fetch_status.record(
+                    "session_participants",
+                    with_timeout(
+                        "session_participants",
+                        query_timeout,
+                        SessionParticipants::fetch(ispyb_pool)
+                    )
+                )
+            ),
+            fetch_unless_disabled(
+                "lab_contacts",
+                disabled_datasets,
+                fetch_status.record(
+                    "lab_contacts",
+                    with_timeout(
+                        "lab_contacts",
+                        query_timeout,
+                        LabContacts::fetch(ispyb_pool)
+                    )
+                )
+            ),
+            fetch_unless_disabled(
+                "instrument_scientists",
+                disabled_datasets,
+                fetch_status.record(
+                    "instrument_scientists",
+                    with_timeout(
+                        "instrument_scientists",
+                        query_timeout,
+                        InstrumentScientists::fetch(ispyb_pool)
+                    )
+                )
+            ),
+            async {
+                match snapshot {
+                    Some(snapshot) => {
+                        snapshot
+                            .update(
+                                ispyb_pool,
+                                query_timeout,
+                                proposal_filters,
+                                disabled_datasets,
+                                fetch_status,
+                                taken_at,
+                            )
+                            .await
+                    }
+                    None => {
+                        SessionSnapshot::fetch(
+                            ispyb_pool,
+                            query_timeout,
+                            proposal_filters,
+                            disabled_datasets,
+                            fetch_status,
+                            taken_at,
+                        )
+                        .await
+                    }
+                }
+            }
         )?;
-        Ok(Self::new(
-            metadata, subjects, sessions, proposals, beamlines,
+        Ok((
+            Self::new(
+                metadata,
+                subjects,
+                snapshot.sessions.clone(),
+                snapshot.proposals.clone(),
+                snapshot.beamlines.clone(),
+                roles,
+                people,
+                session_participants,
+                lab_contacts,
+                instrument_scientists,
+            )
+            .with_disabled_datasets(disabled_datasets.clone()),
+            snapshot,
         ))
     }
 
+    /// The number of entries in each dataset and the size of its serialization, before any [`Redactions`] or [`Transformations`] are applied
+    pub fn volumes(&self) -> Result<BTreeMap<&'static str, DatasetVolume>, serde_json::Error> {
+        let mut volumes = BTreeMap::from([
+            (
+                "subjects",
+                dataset_volume(self.subjects.len(), &self.subjects)?,
+            ),
+            (
+                "sessions",
+                dataset_volume(self.sessions.len(), &self.sessions)?,
+            ),
+            (
+                "proposals",
+                dataset_volume(self.proposals.len(), &self.proposals)?,
+            ),
+            (
+                "beamlines",
+                dataset_volume(self.beamlines.len(), &self.beamlines)?,
+            ),
+            ("roles", dataset_volume(self.roles.len(), &self.roles)?),
+            (
+                "session_participants",
+                dataset_volume(self.session_participants.len(), &self.session_participants)?,
+            ),
+            (
+                "lab_contacts",
+                dataset_volume(self.lab_contacts.len(), &self.lab_contacts)?,
+            ),
+            (
+                "instrument_scientists",
+                dataset_volume(
+                    self.instrument_scientists.len(),
+                    &self.instrument_scientists,
+                )?,
+            ),
+        ]);
+        if let Some(people) = &self.people {
+            volumes.insert("people", dataset_volume(people.len(), people)?);
+        }
+        volumes.retain(|dataset, _| self.includes(dataset));
+        Ok(volumes)
+    }
+
     /// The current revision of the bundle, as recorded in the [`Manifest`]
     pub fn revision(&self) -> &str {
         &self.manifest.revision
     }
 
-    /// Serializes the [`Bundle`] as a gzipped tar archive, for import by Open Policy Agent
-    pub fn to_tar_gz(&self) -> Result<Vec<u8>, anyhow::Error> {
-        let mut bundle_builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::best()));
-
-        let manifest = serde_json::to_vec(&self.manifest)?;
-        let mut manifest_header = Header::from_bytes(&manifest);
-        bundle_builder.append_data(&mut manifest_header, ".manifest", manifest.as_slice())?;
+    /// Serializes the [`Bundle`] as a tar archive, for import by Open Policy Agent once compressed with [`gzip`]
+    ///
+    /// Entries are written in a fixed order, with normalized headers, such that identical bundles are byte-identical
+    pub fn to_tar(&self) -> Result<Vec<u8>, anyhow::Error> {
+        let mut bundle_builder = tar::Builder::new(Vec::new());
 
-        let subjects = serde_json::to_vec(&self.subjects)?;
-        let mut subjects_header = Header::from_bytes(&subjects);
-        bundle_builder.append_data(
-            &mut subjects_header,
-            format!("{BUNDLE_PREFIX}/subjects/data.json"),
-            subjects.as_slice(),
+        bundle_builder.append_json(".manifest", &self.manifest)?;
+        self.append_dataset(&mut bundle_builder, "subjects", &self.subjects)?;
+        self.append_dataset(&mut bundle_builder, "sessions", &self.sessions)?;
+        self.append_dataset(&mut bundle_builder, "proposals", &self.proposals)?;
+        self.append_dataset(&mut bundle_builder, "beamlines", &self.beamlines)?;
+        self.append_dataset(&mut bundle_builder, "roles", &self.roles)?;
+        if let Some(people) = &self.people {
+            self.append_dataset(&mut bundle_builder, "people", people)?;
+        }
+        self.append_dataset(
+            &mut bundle_builder,
+            "session_participants",
+            &self.session_participants,
         )?;
-
-        let sessions = serde_json::to_vec(&self.sessions)?;
-        let mut sessions_header = Header::from_bytes(&sessions);
-        bundle_builder.append_data(
-            &mut sessions_header,
-            format!("{BUNDLE_PREFIX}/sessions/data.json"),
-            sessions.as_slice(),
+        self.append_dataset(&mut bundle_builder, "lab_contacts", &self.lab_contacts)?;
+        self.append_dataset(
+            &mut bundle_builder,
+            "instrument_scientists",
+            &self.instrument_scientists,
         )?;
+        if let Some(session_members) = &self.session_members {
+            self.append_dataset(&mut bundle_builder, "session_members", session_members)?;
+        }
+        for (dataset, value) in &self.augmented_datasets {
+            self.append_dataset(&mut bundle_builder, dataset, value)?;
+        }
+        for (dataset, schema) in Self::dataset_schemas() {
+            if self.includes(dataset) {
+                bundle_builder
+                    .append_json(format!("{SCHEMA_PREFIX}/{dataset}/data.json"), &schema)?;
+            }
+        }
+        for (path, source) in self.policies.modules() {
+            bundle_builder.append(
+                &normalized_header(format!("{POLICY_PREFIX}/{path}"), source.len() as u64)?,
+                source.as_bytes(),
+            )?;
+        }
 
-        let proposals = serde_json::to_vec(&self.proposals)?;
-        let mut proposals_header = Header::from_bytes(&proposals);
-        bundle_builder.append_data(
-            &mut proposals_header,
-            format!("{BUNDLE_PREFIX}/proposals/data.json"),
-            proposals.as_slice(),
-        )?;
+        Ok(bundle_builder.into_inner()?)
+    }
 
-        let beamlines = serde_json::to_vec(&self.beamlines)?;
-        let mut beamlines_header = Header::from_bytes(&beamlines);
-        bundle_builder.append_data(
-            &mut beamlines_header,
-            format!("{BUNDLE_PREFIX}/beamlines/data.json"),
-            beamlines.as_slice(),
-        )?;
+    /// Serializes the named dataset and its schema alone as a tar archive, rooted at the dataset, returning the revision of the archive alongside it
+    ///
+    /// The revision is derived from the dataset rather than the whole bundle, such that it only changes with the dataset. Returns [`None`] if the dataset is not included in the bundle
+    pub fn dataset_to_tar(
+        &self,
+        dataset: &str,
+    ) -> Result<Option<(String, Vec<u8>)>, anyhow::Error> {
+        if !self.includes(dataset) {
+            return Ok(None);
+        }
+        match dataset {
+            "subjects" => self.single_dataset_tar(dataset, &self.subjects).map(Some),
+            "sessions" => self.single_dataset_tar(dataset, &self.sessions).map(Some),
+            "proposals" => self.single_dataset_tar(dataset, &self.proposals).map(Some),
+            "beamlines" => self.single_dataset_tar(dataset, &self.beamlines).map(Some),
+            "roles" => self.single_dataset_tar(dataset, &self.roles).map(Some),
+            "people" => self
+                .people
+                .as_ref()
+                .map(|people| self.single_dataset_tar(dataset, people))
+                .transpose(),
+            "session_participants" => self
+                .single_dataset_tar(dataset, &self.session_participants)
+                .map(Some),
+            "lab_contacts" => self
+                .single_dataset_tar(dataset, &self.lab_contacts)
+                .map(Some),
+            "instrument_scientists" => self
+                .single_dataset_tar(dataset, &self.instrument_scientists)
+                .map(Some),
+            "session_members" => self
+                .session_members
+                .as_ref()
+                .map(|session_members| self.single_dataset_tar(dataset, session_members))
+                .transpose(),
+            _ => self
+                .augmented_datasets
+                .get(dataset)
+                .map(|value| self.single_dataset_tar(dataset, value))
+                .transpose(),
+        }
+    }
 
-        Ok(bundle_builder.into_inner()?.finish()?)
+    /// Serializes the named dataset and its schema as a tar archive with a manifest of its own, returning the revision of the archive alongside it
+    fn single_dataset_tar(
+        &self,
+        dataset: &str,
+        value: &impl Serialize,
+    ) -> Result<(String, Vec<u8>), anyhow::Error> {
+        let root = format!("{}/{dataset}", self.dataset_roots.root(dataset));
+        let metadata = ManifestMetadata {
+            extra: &self.manifest.metadata.extra,
+            stale_datasets: self
+                .manifest
+                .metadata
+                .stale_datasets
+                .iter()
+                .filter(|stale_dataset| *stale_dataset == dataset)
+                .cloned()
+                .collect(),
+        };
+        let mut hasher = ContentHasher::default();
+        hasher.update(&metadata);
+        hasher.update(value);
+        if !self.redactions.is_dry_run() {
+            hasher.update(&self.redactions);
+        }
+        hasher.update(&self.transformations);
+        hasher.update(&self.layouts);
+        hasher.update(&root);
+        let manifest = Manifest {
+            revision: format!("{}:{}", crate::built_info::PKG_VERSION, hasher.finish()),
+            roots: vec![root, format!("{SCHEMA_PREFIX}/{dataset}")],
+            wasm: vec![],
+            metadata,
+        };
+
+        let mut bundle_builder = tar::Builder::new(Vec::new());
+        bundle_builder.append_json(".manifest", &manifest)?;
+        self.append_dataset(&mut bundle_builder, dataset, value)?;
+        if let Some(schema) = Self::dataset_schemas().get(dataset) {
+            bundle_builder.append_json(format!("{SCHEMA_PREFIX}/{dataset}/data.json"), schema)?;
+        }
+        Ok((manifest.revision, bundle_builder.into_inner()?))
+    }
+
+    /// Appends the named dataset to the archive, unless it is excluded from the bundle, applying any [`Redactions`] and then any [`Transformations`] to its serialized entries, before writing them in the configured layout
+    ///
+    /// Redactions which are only reported are logged, with the dataset appended unredacted
+    fn append_dataset(
+        &self,
+        bundle_builder: &mut tar::Builder<Vec<u8>>,
+        dataset: &str,
+        value: &impl Serialize,
+    ) -> Result<(), anyhow::Error> {
+        if !self.includes(dataset) {
+            return Ok(());
+        }
+        let path = format!("{}/{dataset}/data.json", self.dataset_roots.root(dataset));
+        if !self.redactions.applies_to(dataset)
+            && !self.transformations.applies_to(dataset)
+            && !self.layouts.applies_to(dataset)
+        {
+            return bundle_builder.append_json(path, value);
+        }
+        let mut entries = serde_json::to_value(value)?;
+        if self.redactions.is_dry_run() {
+            for (rule, count) in self.redactions.apply(dataset, &mut entries.clone()) {
+                tracing::info!("Redaction {rule} would redact {count} values from {dataset}");
+            }
+        } else {
+            for (rule, count) in self.redactions.apply(dataset, &mut entries) {
+                tracing::debug!("Redaction {rule} redacted {count} values from {dataset}");
+            }
+        }
+        self.transformations.apply(dataset, &mut entries);
+        self.layouts.apply(dataset, &mut entries);
+        bundle_builder.append_json(path, &entries)
+    }
+
+    /// Reads the revision from the [`Manifest`] of a previously serialized gzipped tar archive
+    pub fn read_revision(archive: &[u8]) -> Result<String, anyhow::Error> {
+        let mut archive = tar::Archive::new(GzDecoder::new(archive));
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            if entry.path()?.as_os_str() == ".manifest" {
+                let mut manifest = Vec::new();
+                entry.read_to_end(&mut manifest)?;
+                let manifest = serde_json::from_slice::<serde_json::Value>(&manifest)?;
+                return manifest["revision"]
+                    .as_str()
+                    .map(ToString::to_string)
+                    .ok_or(anyhow::anyhow!("Manifest did not contain a revision"));
+            }
+        }
+        Err(anyhow::anyhow!("Archive did not contain a manifest"))
     }
 
     /// Produces a set of schemas associated with the data in the bundle
     pub fn schemas() -> BTreeMap<String, RootSchema> {
         BTreeMap::from([
-            (Subjects::schema_name(), schema_for!(Subjects)),
-            (Sessions::schema_name(), schema_for!(Sessions)),
-            (Proposals::schema_name(), schema_for!(Proposals)),
-            (Beamlines::schema_name(), schema_for!(Beamlines)),
+            (Subjects::schema_name(), dataset_schema::<Subjects>()),
+            (Sessions::schema_name(), dataset_schema::<Sessions>()),
+            (Proposals::schema_name(), dataset_schema::<Proposals>()),
+            (Beamlines::schema_name(), dataset_schema::<Beamlines>()),
+            (Roles::schema_name(), dataset_schema::<Roles>()),
+            (People::schema_name(), dataset_schema::<People>()),
+            (
+                SessionParticipants::schema_name(),
+                dataset_schema::<SessionParticipants>(),
+            ),
+            (LabContacts::schema_name(), dataset_schema::<LabContacts>()),
+            (
+                InstrumentScientists::schema_name(),
+                dataset_schema::<InstrumentScientists>(),
+            ),
+            (
+                SessionMembers::schema_name(),
+                dataset_schema::<SessionMembers>(),
+            ),
         ])
     }
+
+    /// Produces the schema of each dataset, keyed by the name of the dataset
+    ///
+    /// Schemas describe datasets as fetched, before any [`Redactions`] or [`Transformations`] are applied, in the keyed layout
+    pub fn dataset_schemas() -> BTreeMap<&'static str, RootSchema> {
+        BTreeMap::from([
+            ("subjects", dataset_schema::<Subjects>()),
+            ("sessions", dataset_schema::<Sessions>()),
+            ("proposals", dataset_schema::<Proposals>()),
+            ("beamlines", dataset_schema::<Beamlines>()),
+            ("roles", dataset_schema::<Roles>()),
+            ("people", dataset_schema::<People>()),
+            (
+                "session_participants",
+                dataset_schema::<SessionParticipants>(),
+            ),
+            ("lab_contacts", dataset_schema::<LabContacts>()),
+            (
+                "instrument_scientists",
+                dataset_schema::<InstrumentScientists>(),
+            ),
+            ("session_members", dataset_schema::<SessionMembers>()),
+        ])
+    }
+
+    /// Validates each dataset against its schema, failing with a summary of the violations if any dataset does not conform
+    ///
+    /// Datasets are validated as fetched, before any [`Redactions`] or [`Transformations`] are applied
+    pub fn validate(&self) -> Result<(), anyhow::Error> {
+        let mut invalid = vec![
+            dataset_violations("subjects", &self.subjects)?,
+            dataset_violations("sessions", &self.sessions)?,
+            dataset_violations("proposals", &self.proposals)?,
+            dataset_violations("beamlines", &self.beamlines)?,
+            dataset_violations("roles", &self.roles)?,
+            dataset_violations("session_participants", &self.session_participants)?,
+            dataset_violations("lab_contacts", &self.lab_contacts)?,
+            dataset_violations("instrument_scientists", &self.instrument_scientists)?,
+        ];
+        if let Some(people) = &self.people {
+            invalid.push(dataset_violations("people", people)?);
+        }
+        let invalid = invalid.into_iter().flatten().collect::<Vec<_>>();
+        if invalid.is_empty() {
+            return Ok(());
+        }
+        anyhow::bail!(
+            "Datasets do not conform to their schemas: {}",
+            invalid
+                .iter()
+                .map(|(dataset, violations)| format!(
+                    "{dataset} has {} violations, including {}",
+                    violations.len(),
+                    violations
+                        .iter()
+                        .take(REPORTED_VIOLATIONS)
+                        .map(ToString::to_string)
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ))
+                .collect::<Vec<_>>()
+                .join("; ")
+        )
+    }
+}
+
+/// The number of violations reported for each dataset which does not conform to its schema
+const REPORTED_VIOLATIONS: usize = 3;
+
+/// Produces the schema of a dataset, which is keyed by the non-empty names of its entries
+///
+/// Keys of `null` are rejected, as are produced by string conversion of a missing upstream identifier
+fn dataset_schema<Dataset: JsonSchema>() -> RootSchema {
+    let mut schema = schema_for!(Dataset);
+    schema.schema.object().property_names = Some(Box::new(Schema::Object(SchemaObject {
+        instance_type: Some(InstanceType::String.into()),
+        string: Some(Box::new(StringValidation {
+            min_length: Some(1),
+            ..Default::default()
+        })),
+        subschemas: Some(Box::new(SubschemaValidation {
+            not: Some(Box::new(Schema::Object(SchemaObject {
+                enum_values: Some(vec![serde_json::Value::String("null".to_string())]),
+                ..Default::default()
+            }))),
+            ..Default::default()
+        })),
+        ..Default::default()
+    })));
+    schema
+}
+
+/// Validates the named dataset against its schema, returning its violations if it does not conform
+fn dataset_violations<Dataset: JsonSchema + Serialize>(
+    name: &'static str,
+    dataset: &Dataset,
+) -> Result<Option<(&'static str, Vec<Violation>)>, serde_json::Error> {
+    let violations = validate(
+        &dataset_schema::<Dataset>(),
+        &serde_json::to_value(dataset)?,
+    );
+    Ok((!violations.is_empty()).then_some((name, violations)))
+}
+
+/// Measures the volume of a dataset of the given number of entries by serializing it
+pub fn dataset_volume(
+    rows: usize,
+    dataset: &impl Serialize,
+) -> Result<DatasetVolume, serde_json::Error> {
+    let mut size = ByteCount::default();
+    serde_json::to_writer(&mut size, dataset)?;
+    Ok(DatasetVolume {
+        rows,
+        bytes: size.0,
+    })
+}
+
+/// Fetches [`People`] from ISPyB if personal data is to be included, cancelling the query if it exceeds the timeout and recording its outcome
+async fn fetch_people(
+    ispyb_pool: &MySqlPool,
+    query_timeout: Duration,
+    include_personal_data: bool,
+    fetch_status: &FetchStatus,
+) -> Result<Option<People>, FetchError> {
+    if !include_personal_data {
+        return Ok(None);
+    }
+    Ok(Some(
+        fetch_status
+            .record(
+                "people",
+                with_timeout("people", query_timeout, People::fetch(ispyb_pool)),
+            )
+            .await?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        fetch_if_due, gunzip, gzip, AppendJson, Bundle, NoMetadata, Retained, BUNDLE_PREFIX,
+        SCHEMA_PREFIX,
+    };
+    use crate::{
+        dataset_roots::{DatasetRoot, DatasetRoots},
+        disabled_datasets::DisabledDatasets,
+        fetch_status::FetchStatus,
+        partial_update::PartialUpdates,
+        permissionables::{
+            proposals::ProposalFilters,
+            roles::{Role, Roles},
+            FetchError,
+        },
+        polling::DatasetIntervals,
+        redaction::{RedactionArgs, Redactions},
+    };
+    use clap::Parser;
+    use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+    use serde_json::json;
+    use sqlx::MySqlPool;
+    use std::{
+        collections::{BTreeMap, BTreeSet},
+        io::Read,
+        str::FromStr,
+        time::{Duration, Instant},
+    };
+
+    #[test]
+    fn append_json_roundtrip() {
+        let values = [
+            json!({"a": [1, 2, 3]}),
+            json!("x".repeat(1024)),
+            json!(null),
+        ];
+        let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::best()));
+        for (idx, value) in values.iter().enumerate() {
+            builder
+                .append_json(format!("{idx}/data.json"), value)
+                .unwrap();
+        }
+        let archive = builder.into_inner().unwrap().finish().unwrap();
+
+        let mut archive = tar::Archive::new(GzDecoder::new(archive.as_slice()));
+        let entries = archive
+            .entries()
+            .unwrap()
+            .map(|entry| {
+                let mut entry = entry.unwrap();
+                let mut contents = Vec::new();
+                entry.read_to_end(&mut contents).unwrap();
+                (
+                    entry.path().unwrap().to_string_lossy().to_string(),
+                    serde_json::from_slice::<serde_json::Value>(&contents).unwrap(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            values
+                .iter()
+                .enumerate()
+                .map(|(idx, value)| (format!("{idx}/data.json"), value.clone()))
+                .collect::<Vec<_>>(),
+            entries
+        );
+    }
+
+    #[test]
+    fn read_revision_roundtrip() {
+        let bundle = Bundle::<NoMetadata>::default();
+        let archive = gzip(&bundle.to_tar().unwrap()).unwrap();
+        assert_eq!(
+            bundle.revision(),
+            Bundle::<NoMetadata>::read_revision(&archive).unwrap()
+        );
+    }
+
+    #[test]
+    fn datasets_placed_under_roots() {
+        let bundle = Bundle::<NoMetadata>::default();
+        let revision = bundle.revision().to_string();
+        let bundle = bundle.with_dataset_roots(DatasetRoots::from(vec![DatasetRoot::from_str(
+            "beamlines=diamond/instruments",
+        )
+        .unwrap()]));
+        assert_ne!(revision, bundle.revision());
+        let tar = bundle.to_tar().unwrap();
+        let entries = tar::Archive::new(tar.as_slice())
+            .entries()
+            .unwrap()
+            .map(|entry| {
+                let mut entry = entry.unwrap();
+                let mut contents = Vec::new();
+                entry.read_to_end(&mut contents).unwrap();
+                (
+                    entry.path().unwrap().to_string_lossy().to_string(),
+                    serde_json::from_slice::<serde_json::Value>(&contents).unwrap(),
+                )
+            })
+            .collect::<BTreeMap<_, _>>();
+        assert_eq!(
+            json!([
+                "diamond/data",
+                "diamond/instruments",
+                "users",
+                "diamond/schemas"
+            ]),
+            entries[".manifest"]["roots"]
+        );
+        assert!(entries.contains_key("diamond/instruments/beamlines/data.json"));
+        assert!(entries.contains_key(&format!("{BUNDLE_PREFIX}/sessions/data.json")));
+        assert!(!entries.contains_key(&format!("{BUNDLE_PREFIX}/beamlines/data.json")));
+    }
+
+    #[test]
+    fn disabled_datasets_excluded() {
+        let bundle = Bundle::<NoMetadata>::default();
+        let revision = bundle.revision().to_string();
+        let bundle = bundle.with_disabled_datasets(DisabledDatasets::from(vec![
+            "roles".to_string(),
+            "lab_contacts".to_string(),
+        ]));
+        assert_ne!(revision, bundle.revision());
+        assert!(bundle.dataset_to_tar("roles").unwrap().is_none());
+        assert!(!bundle.volumes().unwrap().contains_key("roles"));
+        let tar = bundle.to_tar().unwrap();
+        let paths = tar::Archive::new(tar.as_slice())
+            .entries()
+            .unwrap()
+            .map(|entry| entry.unwrap().path().unwrap().to_string_lossy().to_string())
+            .collect::<BTreeSet<_>>();
+        assert!(paths.contains(&format!("{BUNDLE_PREFIX}/sessions/data.json")));
+        assert!(!paths.contains(&format!("{BUNDLE_PREFIX}/roles/data.json")));
+        assert!(!paths.contains(&format!("{SCHEMA_PREFIX}/roles/data.json")));
+        assert!(!paths.contains(&format!("{BUNDLE_PREFIX}/lab_contacts/data.json")));
+    }
+
+    #[test]
+    fn augmented_dataset_included() {
+        let bundle = Bundle::<NoMetadata>::default();
+        let revision = bundle.revision().to_string();
+        let bundle = bundle
+            .with_augmented_dataset("facilities", &json!({"diamond": {"site": "Harwell"}}))
+            .unwrap();
+        assert_ne!(revision, bundle.revision());
+        assert!(bundle.dataset_to_tar("facilities").unwrap().is_some());
+        let tar = bundle.to_tar().unwrap();
+        let paths = tar::Archive::new(tar.as_slice())
+            .entries()
+            .unwrap()
+            .map(|entry| entry.unwrap().path().unwrap().to_string_lossy().to_string())
+            .collect::<BTreeSet<_>>();
+        assert!(paths.contains(&format!("{BUNDLE_PREFIX}/facilities/data.json")));
+        assert!(bundle
+            .with_augmented_dataset("sessions", &json!({}))
+            .is_err());
+    }
+
+    #[test]
+    fn dataset_tar_rooted_at_dataset() {
+        let build = |roles| {
+            Bundle::new(
+                NoMetadata,
+                Default::default(),
+                Default::default(),
+                Default::default(),
+                Default::default(),
+                roles,
+                None,
+                Default::default(),
+                Default::default(),
+                Default::default(),
+            )
+        };
+        let mut roles = Roles::default();
+        roles.insert("admin".to_string(), Role::default());
+        let (revision, tar) = build(Roles::default())
+            .dataset_to_tar("sessions")
+            .unwrap()
+            .unwrap();
+        let (unchanged_revision, _) = build(roles.clone())
+            .dataset_to_tar("sessions")
+            .unwrap()
+            .unwrap();
+        assert_eq!(revision, unchanged_revision);
+        let (changed_revision, _) = build(roles).dataset_to_tar("roles").unwrap().unwrap();
+        assert_ne!(revision, changed_revision);
+        assert!(build(Roles::default())
+            .dataset_to_tar("people")
+            .unwrap()
+            .is_none());
+
+        let paths = tar::Archive::new(tar.as_slice())
+            .entries()
+            .unwrap()
+            .map(|entry| entry.unwrap().path().unwrap().to_string_lossy().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                ".manifest".to_string(),
+                format!("{BUNDLE_PREFIX}/sessions/data.json"),
+                format!("{SCHEMA_PREFIX}/sessions/data.json"),
+            ],
+            paths
+        );
+        assert_eq!(
+            revision,
+            Bundle::<NoMetadata>::read_revision(&gzip(&tar).unwrap()).unwrap()
+        );
+    }
+
+    #[test]
+    fn gzip_roundtrip() {
+        let bundle = Bundle::<NoMetadata>::default();
+        let tar = bundle.to_tar().unwrap();
+        assert_eq!(tar, gunzip(&gzip(&tar).unwrap()).unwrap());
+    }
+
+    #[test]
+    fn redaction_changes_revision_unless_dry_run() {
+        let bundle = || Bundle::<NoMetadata>::default();
+        #[derive(Parser)]
+        struct Args {
+            #[command(flatten)]
+            redaction: RedactionArgs,
+        }
+        let redactions = |args: &[&str]| {
+            Redactions::from(
+                Args::parse_from(["bundler"].into_iter().chain(args.iter().copied())).redaction,
+            )
+        };
+        let revision = bundle().revision().to_string();
+        assert_eq!(revision, bundle().redact(redactions(&[])).revision());
+        assert_eq!(
+            revision,
+            bundle()
+                .redact(redactions(&[
+                    "--redact-emails",
+                    "drop",
+                    "--redaction-dry-run"
+                ]))
+                .revision()
+        );
+        let redacted = bundle().redact(redactions(&["--redact-emails", "drop"]));
+        assert_ne!(revision, redacted.revision());
+        assert_eq!(
+            redacted.revision(),
+            Bundle::<NoMetadata>::read_revision(&gzip(&redacted.to_tar().unwrap()).unwrap())
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn stale_datasets_marked_in_manifest() {
+        let bundle = Bundle::<NoMetadata>::default();
+        let revision = bundle.revision().to_string();
+        let bundle = bundle.with_stale_datasets(BTreeSet::from(["roles".to_string()]));
+        assert_ne!(revision, bundle.revision());
+        let tar = bundle.to_tar().unwrap();
+        let mut archive = tar::Archive::new(tar.as_slice());
+        let mut manifest = archive.entries().unwrap().next().unwrap().unwrap();
+        let mut contents = Vec::new();
+        manifest.read_to_end(&mut contents).unwrap();
+        let manifest = serde_json::from_slice::<serde_json::Value>(&contents).unwrap();
+        assert_eq!(json!({"stale_datasets": ["roles"]}), manifest["metadata"]);
+    }
+
+    #[tokio::test]
+    async fn failed_fetch_carried_over_if_permitted() {
+        let retained = Retained {
+            dataset: Roles::default(),
+            fetched_at: Instant::now(),
+            stale: false,
+        };
+        let failed_fetch = || async {
+            Err::<Roles, _>(FetchError::Timeout {
+                dataset: "roles",
+                timeout: Duration::from_secs(1),
+            })
+        };
+        let partial_updates = PartialUpdates::from(vec!["roles".to_string()]);
+        let carried_over = fetch_if_due(
+            "roles",
+            &DatasetIntervals::default(),
+            &partial_updates,
+            Some(&retained),
+            Instant::now(),
+            failed_fetch(),
+        )
+        .await
+        .unwrap();
+        assert!(carried_over.stale);
+        assert_eq!(retained.fetched_at, carried_over.fetched_at);
+        assert!(fetch_if_due(
+            "roles",
+            &DatasetIntervals::default(),
+            &PartialUpdates::default(),
+            Some(&retained),
+            Instant::now(),
+            failed_fetch(),
+        )
+        .await
+        .is_err());
+        assert!(fetch_if_due(
+            "roles",
+            &DatasetIntervals::default(),
+            &partial_updates,
+            None,
+            Instant::now(),
+            failed_fetch(),
+        )
+        .await
+        .is_err());
+    }
+
+    #[test]
+    fn revision_is_content_hash() {
+        let bundle = Bundle::<NoMetadata>::default();
+        assert_eq!(
+            format!(
+                "{}:fb053b9275ff282c5fd33e094745753fc2c3a34dc1b1dfffa1f1e0ab7b7e8b40",
+                crate::built_info::PKG_VERSION
+            ),
+            bundle.revision()
+        );
+    }
+
+    #[test]
+    fn tar_gz_reproducible() {
+        let build = || {
+            Bundle::<NoMetadata>::default()
+                .to_tar()
+                .map(|tar| gzip(&tar).unwrap())
+                .unwrap()
+        };
+        let archive = build();
+        assert_eq!(archive, build());
+
+        let mut archive = tar::Archive::new(GzDecoder::new(archive.as_slice()));
+        for entry in archive.entries().unwrap() {
+            let header = entry.unwrap().header().clone();
+            assert_eq!(tar::EntryType::Regular, header.entry_type());
+            assert_eq!(0o644, header.mode().unwrap());
+            assert_eq!(0, header.mtime().unwrap());
+            assert_eq!(0, header.uid().unwrap());
+            assert_eq!(0, header.gid().unwrap());
+        }
+    }
+
+    #[test]
+    fn validate_rejects_null_keys() {
+        let mut roles = Roles::default();
+        roles.insert("null".to_string(), Role::default());
+        let build = |roles| {
+            Bundle::new(
+                NoMetadata,
+                Default::default(),
+                Default::default(),
+                Default::default(),
+                Default::default(),
+                roles,
+                None,
+                Default::default(),
+                Default::default(),
+                Default::default(),
+            )
+        };
+        assert!(build(Roles::default()).validate().is_ok());
+        let err = build(roles).validate().unwrap_err();
+        assert_eq!(
+            "Datasets do not conform to their schemas: roles has 1 violations, including /null: property name 'null' is not permitted",
+            err.to_string()
+        );
+    }
+
+    #[sqlx::test(
+        migrations = "tests/migrations",
+        fixtures(
+            path = "../tests/fixtures",
+            scripts(
+                "beamline_sessions",
+                "group_permissions",
+                "instrument_scientists",
+                "lab_contacts",
+                "laboratories",
+                "permissions",
+                "persons",
+                "proposal_membership",
+                "proposals",
+                "session_membership",
+                "user_group_membership",
+                "user_groups"
+            )
+        )
+    )]
+    async fn bundle_contents_snapshot(ispyb_pool: MySqlPool) {
+        let bundle = Bundle::fetch(
+            NoMetadata,
+            &ispyb_pool,
+            Duration::from_secs(30),
+            false,
+            &ProposalFilters::default(),
+            &DisabledDatasets::default(),
+            &FetchStatus::default(),
+        )
+        .await
+        .unwrap();
+        let archive = gzip(&bundle.to_tar().unwrap()).unwrap();
+        let mut entries = tar::Archive::new(GzDecoder::new(archive.as_slice()))
+            .entries()
+            .unwrap()
+            .map(|entry| {
+                let mut entry = entry.unwrap();
+                let mut contents = Vec::new();
+                entry.read_to_end(&mut contents).unwrap();
+                (
+                    entry.path().unwrap().to_string_lossy().to_string(),
+                    serde_json::from_slice::<serde_json::Value>(&contents).unwrap(),
+                )
+            })
+            .collect::<BTreeMap<_, _>>();
+
+        insta::assert_json_snapshot!(
+            "manifest",
+            entries.remove(".manifest").unwrap(),
+            { ".revision" => "[revision]" }
+        );
+        for dataset in [
+            "subjects",
+            "sessions",
+            "proposals",
+            "beamlines",
+            "roles",
+            "session_participants",
+            "lab_contacts",
+        ] {
+            assert!(entries
+                .remove(&format!("{SCHEMA_PREFIX}/{dataset}/data.json"))
+                .is_some());
+            insta::assert_json_snapshot!(
+                dataset,
+                entries
+                    .remove(&format!("{BUNDLE_PREFIX}/{dataset}/data.json"))
+                    .unwrap(),
+                { ".*.*" => insta::sorted_redaction() }
+            );
+        }
+        assert!(entries
+            .remove(&format!("{SCHEMA_PREFIX}/instrument_scientists/data.json"))
+            .is_some());
+        insta::assert_json_snapshot!(
+            "instrument_scientists",
+            entries
+                .remove("users/instrument_scientists/data.json")
+                .unwrap()
+        );
+        assert!(
+            entries.is_empty(),
+            "Unexpected entries {:?}",
+            entries.keys()
+        );
+    }
 }
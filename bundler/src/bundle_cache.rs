@@ -0,0 +1,25 @@
+use crate::{
+    bundle::{Bundle, NoMetadata},
+    verification, BundleFile,
+};
+use std::{io, path::Path};
+
+/// Writes the serialized bundle to the cache path, via a temporary file such that a partially written bundle is never loaded
+pub async fn store(cache_path: &Path, file: &[u8]) -> Result<(), io::Error> {
+    let temporary_path = cache_path.with_extension("tmp");
+    tokio::fs::write(&temporary_path, file).await?;
+    tokio::fs::rename(temporary_path, cache_path).await
+}
+
+/// Loads a previously cached bundle, marking it as stale, refusing it should it fail verification
+pub async fn load(cache_path: &Path) -> Result<BundleFile, anyhow::Error> {
+    let file = tokio::fs::read(cache_path).await?;
+    let bundle_file = BundleFile::new(
+        Bundle::<NoMetadata>::read_revision(&file)?,
+        file.into(),
+        true,
+    )?;
+    verification::verify(&bundle_file)
+        .map_err(|err| anyhow::anyhow!("Cached bundle failed verification: {err}"))?;
+    Ok(bundle_file)
+}
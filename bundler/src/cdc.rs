@@ -0,0 +1,125 @@
+use clap::Args;
+use futures_util::StreamExt;
+use mysql_async::{
+    binlog::events::{EventData, RowsEventData},
+    prelude::Queryable,
+    BinlogStreamRequest, Conn, Row,
+};
+use std::{sync::Arc, time::Duration};
+use tokio::{
+    sync::Notify,
+    time::{sleep, sleep_until, Instant},
+};
+use tracing::instrument;
+use url::Url;
+
+/// The ISPyB tables from which permissionables are derived
+const WATCHED_TABLES: &[&str] = &[
+    "BLSession",
+    "LabContact",
+    "Permission",
+    "Person",
+    "Proposal",
+    "ProposalHasPerson",
+    "Session_has_Person",
+    "UserGroup",
+    "UserGroup_has_Permission",
+    "UserGroup_has_Person",
+];
+
+/// The time to wait before re-subscribing to the binlog after a failure
+const RETRY_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Options for following changes to ISPyB via the MySQL binlog
+#[derive(Debug, Clone, Args)]
+pub struct CdcArgs {
+    /// The URL of an ISPyB instance whose binlog should be followed, the user requires the REPLICATION SLAVE and REPLICATION CLIENT privileges
+    #[arg(long, env = "BUNDLER_CDC_DATABASE_URL")]
+    cdc_database_url: Option<Url>,
+    /// The server ID with which to register as a replica, which must be unique amongst the replicas of the instance
+    #[arg(long, env = "BUNDLER_CDC_SERVER_ID", default_value_t = 4242)]
+    cdc_server_id: u32,
+    /// The time to wait after a change before rebuilding the bundle, allowing changes to be batched
+    #[arg(long, env = "BUNDLER_CDC_DEBOUNCE", default_value_t = humantime::Duration::from(Duration::from_secs(2)))]
+    cdc_debounce: humantime::Duration,
+}
+
+/// Follows the ISPyB binlog if configured, notifying when rows in any watched table change
+///
+/// Failures are logged and the subscription re-established, as periodic polling remains in place as a fallback
+pub async fn follow_binlog(args: CdcArgs, refresh_requested: Arc<Notify>) {
+    let Some(database_url) = args.cdc_database_url else {
+        return std::future::pending().await;
+    };
+    loop {
+        if let Err(err) = watch_binlog(
+            &database_url,
+            args.cdc_server_id,
+            args.cdc_debounce.into(),
+            &refresh_requested,
+        )
+        .await
+        {
+            tracing::warn!(
+                monotonic_counter.cdc_failures = 1,
+                "Binlog subscription failed: {err}"
+            );
+        }
+        sleep(RETRY_INTERVAL).await;
+    }
+}
+
+/// Subscribes to the binlog from its current position, notifying after changes to watched tables have settled
+#[instrument(skip_all)]
+async fn watch_binlog(
+    database_url: &Url,
+    server_id: u32,
+    debounce: Duration,
+    refresh_requested: &Notify,
+) -> Result<(), mysql_async::Error> {
+    let mut conn = Conn::from_url(database_url.as_str()).await?;
+    let status = conn
+        .query_first::<Row, _>("SHOW MASTER STATUS")
+        .await?
+        .ok_or(mysql_async::Error::Other(
+            "Binary logging is not enabled".into(),
+        ))?;
+    let filename = status.get::<String, _>(0).unwrap_or_default();
+    let position = status.get::<u64, _>(1).unwrap_or_default();
+    tracing::info!("Following binlog from {filename}:{position}");
+    let mut stream = conn
+        .get_binlog_stream(
+            BinlogStreamRequest::new(server_id)
+                .with_filename(filename.as_bytes())
+                .with_pos(position),
+        )
+        .await?;
+
+    let mut pending_refresh = None;
+    loop {
+        tokio::select! {
+            event = stream.next() => {
+                let Some(event) = event else {
+                    return Ok(());
+                };
+                if let Some(EventData::RowsEvent(rows)) = event?.read_data()? {
+                    if let Some(table) = watched_table(&stream, &rows) {
+                        tracing::debug!("Observed change to {table}");
+                        pending_refresh.get_or_insert_with(|| Instant::now() + debounce);
+                    }
+                }
+            }
+            _ = sleep_until(pending_refresh.unwrap_or_else(Instant::now)), if pending_refresh.is_some() => {
+                tracing::info!(monotonic_counter.cdc_refreshes = 1, "Requesting refresh following upstream change");
+                pending_refresh = None;
+                refresh_requested.notify_one();
+            }
+        }
+    }
+}
+
+/// The name of the table modified by a rows event, if it is one of the [`WATCHED_TABLES`]
+fn watched_table(stream: &mysql_async::BinlogStream, rows: &RowsEventData<'_>) -> Option<String> {
+    let table = stream.get_tme(rows.table_id())?.table_name().into_owned();
+    WATCHED_TABLES.contains(&table.as_str()).then_some(table)
+}
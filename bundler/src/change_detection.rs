@@ -0,0 +1,108 @@
+use crate::bundle::ContentHasher;
+use clap::ValueEnum;
+use serde::Serialize;
+use sqlx::{query, query_scalar, MySqlPool, Row};
+use tracing::instrument;
+use utoipa::ToSchema;
+
+/// The ISPyB tables from which the datasets in the bundle are derived
+const TABLES: [&str; 11] = [
+    "BLSession",
+    "LabContact",
+    "Laboratory",
+    "Permission",
+    "Person",
+    "Proposal",
+    "ProposalHasPerson",
+    "Session_has_Person",
+    "UserGroup",
+    "UserGroup_has_Permission",
+    "UserGroup_has_Person",
+];
+
+/// The means by which changes to ISPyB are detected before fetching the bundle
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ValueEnum, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeDetection {
+    /// The row count of each table and the latest session timestamp, detecting insertions and deletions in any table but updates only to sessions
+    Counts,
+    /// The checksum of each table, detecting any change at the cost of reading each table in full
+    Checksum,
+}
+
+impl ChangeDetection {
+    /// Fetches a fingerprint of the tables from which the bundle is derived, which differs from the previous fingerprint if a change was detected
+    #[instrument(name = "fetch_change_fingerprint")]
+    pub async fn fingerprint(self, ispyb_pool: &MySqlPool) -> Result<String, sqlx::Error> {
+        match self {
+            Self::Counts => {
+                query_scalar!(
+                    "
+                    SELECT CONCAT_WS(
+                        ',',
+                        (SELECT COUNT(*) FROM BLSession),
+                        (SELECT COUNT(*) FROM LabContact),
+                        (SELECT COUNT(*) FROM Laboratory),
+                        (SELECT COUNT(*) FROM Permission),
+                        (SELECT COUNT(*) FROM Person),
+                        (SELECT COUNT(*) FROM Proposal),
+                        (SELECT COUNT(*) FROM ProposalHasPerson),
+                        (SELECT COUNT(*) FROM Session_has_Person),
+                        (SELECT COUNT(*) FROM UserGroup),
+                        (SELECT COUNT(*) FROM UserGroup_has_Permission),
+                        (SELECT COUNT(*) FROM UserGroup_has_Person),
+                        (SELECT COALESCE(UNIX_TIMESTAMP(MAX(bltimeStamp)), 0) FROM BLSession)
+                    ) AS `fingerprint!: String`
+                    "
+                )
+                .fetch_one(ispyb_pool)
+                .await
+            }
+            Self::Checksum => {
+                let checksums = query(&format!("CHECKSUM TABLE {}", TABLES.join(", ")))
+                    .fetch_all(ispyb_pool)
+                    .await?
+                    .iter()
+                    .map(|row| {
+                        Ok((
+                            row.try_get::<String, _>("Table")?,
+                            row.try_get::<Option<u64>, _>("Checksum")?,
+                        ))
+                    })
+                    .collect::<Result<Vec<_>, sqlx::Error>>()?;
+                let mut hasher = ContentHasher::default();
+                hasher.update(&checksums);
+                Ok(hasher.finish())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ChangeDetection;
+    use sqlx::{query, MySqlPool};
+
+    #[sqlx::test(migrations = "tests/migrations")]
+    async fn fingerprint_changes(ispyb_pool: MySqlPool) {
+        for change_detection in [ChangeDetection::Counts, ChangeDetection::Checksum] {
+            let before = change_detection.fingerprint(&ispyb_pool).await.unwrap();
+            assert_eq!(
+                before,
+                change_detection.fingerprint(&ispyb_pool).await.unwrap()
+            );
+            query("INSERT INTO UserGroup (name) VALUES ('change_detection')")
+                .execute(&ispyb_pool)
+                .await
+                .unwrap();
+            assert_ne!(
+                before,
+                change_detection.fingerprint(&ispyb_pool).await.unwrap()
+            );
+            query("DELETE FROM UserGroup WHERE name = 'change_detection'")
+                .execute(&ispyb_pool)
+                .await
+                .unwrap();
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use crate::{bundle_endpoint, problem::ApiError, CurrentBundle};
+use axum::{
+    extract::State,
+    http::StatusCode,
+    routing::{get, post},
+    Router,
+};
+use clap::Args;
+use std::time::Duration;
+use tokio::time::{interval_at, Instant, MissedTickBehavior};
+use utoipa::OpenApi;
+
+/// Options for serving the bundle via stable and canary channels
+#[derive(Debug, Clone, Args)]
+pub struct ChannelArgs {
+    /// The interval at which the canary bundle captured at the previous interval is promoted to stable, such that stable bundles have been on canary for at least this long. If unset, promotion is manual
+    #[arg(long, env = "BUNDLER_STABLE_PROMOTION_DELAY")]
+    stable_promotion_delay: Option<humantime::Duration>,
+}
+
+/// The paths served by the channel endpoints, excluding the bundles which share those of the current bundle
+#[derive(OpenApi)]
+#[openapi(paths(promote_endpoint))]
+pub struct ChannelsApi;
+
+/// Shared state of the channel endpoints
+#[derive(Clone)]
+struct ChannelState {
+    /// The freshest bundle, served on the canary channel
+    canary: CurrentBundle,
+    /// The promoted bundle, served on the stable channel
+    stable: CurrentBundle,
+}
+
+/// Creates a [`Router`] serving the canary and stable channels
+pub fn router(canary: CurrentBundle, stable: CurrentBundle) -> Router {
+    Router::new()
+        .route(
+            "/channels/canary/bundle.tar.gz",
+            get(bundle_endpoint).with_state(canary),
+        )
+        .route(
+            "/channels/stable/bundle.tar.gz",
+            get(bundle_endpoint).with_state(stable),
+        )
+}
+
+/// Creates a [`Router`] serving manual promotion of the canary bundle to stable, which is an administrative action
+pub fn admin_router(canary: CurrentBundle, stable: CurrentBundle) -> Router {
+    Router::new()
+        .route("/channels/stable/promote", post(promote_endpoint))
+        .with_state(ChannelState { canary, stable })
+}
+
+/// Promotes the current canary bundle to stable immediately
+#[utoipa::path(
+    post,
+    path = "/channels/stable/promote",
+    tag = "admin",
+    responses(
+        (status = OK, description = "The canary bundle was promoted to stable"),
+        (status = SERVICE_UNAVAILABLE, description = "No bundle has been fetched yet"),
+    ),
+)]
+async fn promote_endpoint(State(state): State<ChannelState>) -> Result<StatusCode, ApiError> {
+    let bundle_file = state.canary.as_ref().read().await.clone();
+    if bundle_file.is_placeholder() {
+        return Err(ApiError::Unavailable);
+    }
+    tracing::info!(
+        monotonic_counter.stable_promotions = 1,
+        "Manually promoting {} to stable",
+        bundle_file.revision
+    );
+    state.stable.replace(bundle_file).await;
+    Ok(StatusCode::OK)
+}
+
+/// Periodically promotes the canary bundle captured at the previous interval to stable, if a promotion delay is configured
+pub async fn promote_periodically(args: ChannelArgs, canary: CurrentBundle, stable: CurrentBundle) {
+    let Some(promotion_delay) = args.stable_promotion_delay.map(Into::<Duration>::into) else {
+        return std::future::pending().await;
+    };
+    let mut promotions = interval_at(Instant::now() + promotion_delay, promotion_delay);
+    promotions.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let mut candidate = canary.as_ref().read().await.clone();
+    loop {
+        promotions.tick().await;
+        let next_candidate = canary.as_ref().read().await.clone();
+        let promoted = std::mem::replace(&mut candidate, next_candidate);
+        if !promoted.is_placeholder() && promoted.revision != stable.as_ref().read().await.revision
+        {
+            tracing::info!(
+                monotonic_counter.stable_promotions = 1,
+                "Promoting {} to stable",
+                promoted.revision
+            );
+            stable.replace(promoted).await;
+        }
+    }
+}
@@ -0,0 +1,293 @@
+use crate::{problem::ApiError, CurrentBundle};
+use axum::{
+    extract::{Request, State},
+    http::{StatusCode, Uri},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    routing::put,
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+use tokio::time::Instant;
+use utoipa::{OpenApi, ToSchema};
+
+/// A failure injected into the responses of the bundle endpoints
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+enum Fault {
+    /// Requests are answered with 500 Internal Server Error
+    Error,
+    /// Requests are answered after a delay
+    Delay {
+        /// The time by which each response is delayed, in milliseconds
+        delay_ms: u64,
+    },
+    /// Requests for the current bundle are answered with a previous revision, which must be retained in the revision history
+    StaleRevision {
+        /// The revision served in place of the current bundle
+        revision: String,
+    },
+}
+
+/// A request to inject a [`Fault`] for a duration
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+struct FaultRequest {
+    /// The failure to inject
+    fault: Fault,
+    /// The number of seconds for which the failure is injected
+    duration_secs: u64,
+}
+
+/// The [`Fault`] currently injected, if any
+#[derive(Debug, Clone, Serialize, ToSchema)]
+struct ActiveFault {
+    /// The failure injected
+    fault: Fault,
+    /// The time at which the failure stops being injected
+    #[serde(serialize_with = "crate::timestamp::serialize")]
+    #[schema(value_type = String, format = DateTime)]
+    expires_at: SystemTime,
+    /// The instant at which the failure stops being injected
+    #[serde(skip)]
+    until: Instant,
+}
+
+/// The failures injected into the bundle endpoints, for chaos testing the Open Policy Agent fleet
+#[derive(Debug, Clone, Default)]
+pub struct Faults(Arc<Mutex<Option<ActiveFault>>>);
+
+impl Faults {
+    /// The [`Fault`] currently injected, clearing it if it has expired
+    fn active(&self) -> Option<ActiveFault> {
+        let mut active = self.0.lock().unwrap();
+        if active
+            .as_ref()
+            .is_some_and(|active| active.until <= Instant::now())
+        {
+            tracing::info!("Injected fault expired");
+            *active = None;
+        }
+        active.clone()
+    }
+
+    /// Injects the [`Fault`] for the duration, replacing any fault already injected
+    fn inject(&self, fault: Fault, duration: Duration) -> ActiveFault {
+        let active = ActiveFault {
+            fault,
+            expires_at: SystemTime::now() + duration,
+            until: Instant::now() + duration,
+        };
+        *self.0.lock().unwrap() = Some(active.clone());
+        active
+    }
+
+    /// Stops injecting any [`Fault`]
+    fn clear(&self) {
+        *self.0.lock().unwrap() = None;
+    }
+}
+
+/// Applies the [`Fault`] currently injected, if any, to the request
+pub async fn inject_faults(
+    State(faults): State<Faults>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let Some(active) = faults.active() else {
+        return next.run(request).await;
+    };
+    tracing::warn!(
+        monotonic_counter.injected_faults = 1,
+        "Injecting {:?} into request for {}",
+        active.fault,
+        request.uri().path()
+    );
+    match active.fault {
+        Fault::Error => ApiError::Internal("Injected failure".to_string()).into_response(),
+        Fault::Delay { delay_ms } => {
+            tokio::time::sleep(Duration::from_millis(delay_ms)).await;
+            next.run(request).await
+        }
+        Fault::StaleRevision { revision } => {
+            let query = url::form_urlencoded::Serializer::new(String::new())
+                .append_pair("revision", &revision)
+                .finish();
+            match Uri::try_from(format!("{}?{query}", request.uri().path())) {
+                Ok(uri) => *request.uri_mut() = uri,
+                Err(err) => tracing::error!("Could not request revision {revision}: {err}"),
+            }
+            next.run(request).await
+        }
+    }
+}
+
+/// The paths served by the failure injection endpoints
+#[derive(OpenApi)]
+#[openapi(
+    paths(fault_endpoint, inject_fault_endpoint, clear_fault_endpoint),
+    components(schemas(Fault, FaultRequest, ActiveFault))
+)]
+pub struct ChaosApi;
+
+/// Shared state of the failure injection endpoints
+#[derive(Clone)]
+struct ChaosState {
+    /// The failures injected into the bundle endpoints
+    faults: Faults,
+    /// The bundle currently being served, the history of which stale revisions are served from
+    current_bundle: CurrentBundle,
+}
+
+/// Creates a [`Router`] serving endpoints which report, inject and clear failures of the bundle endpoints
+pub fn router(faults: Faults, current_bundle: CurrentBundle) -> Router {
+    Router::new()
+        .route(
+            "/admin/faults",
+            put(inject_fault_endpoint)
+                .get(fault_endpoint)
+                .delete(clear_fault_endpoint),
+        )
+        .with_state(ChaosState {
+            faults,
+            current_bundle,
+        })
+}
+
+/// Returns the failure currently injected into the bundle endpoints, if any
+#[utoipa::path(
+    get,
+    path = "/admin/faults",
+    tag = "admin",
+    responses(
+        (status = OK, description = "The failure currently injected", body = ActiveFault),
+        (status = NOT_FOUND, description = "No failure is injected"),
+    ),
+)]
+async fn fault_endpoint(State(state): State<ChaosState>) -> Result<Json<ActiveFault>, ApiError> {
+    state
+        .faults
+        .active()
+        .map(Json)
+        .ok_or_else(|| ApiError::NotFound("No failure is injected".to_string()))
+}
+
+/// Injects a failure into the bundle endpoints for a duration, replacing any failure already injected
+///
+/// Failures are only injectable in builds with the 'chaos' feature, which should never be deployed outside of test environments
+#[utoipa::path(
+    put,
+    path = "/admin/faults",
+    tag = "admin",
+    request_body = FaultRequest,
+    responses(
+        (status = OK, description = "The failure was injected", body = ActiveFault),
+        (status = BAD_REQUEST, description = "The stale revision requested is not retained"),
+    ),
+)]
+async fn inject_fault_endpoint(
+    State(state): State<ChaosState>,
+    Json(request): Json<FaultRequest>,
+) -> Result<Json<ActiveFault>, ApiError> {
+    if let Fault::StaleRevision { revision } = &request.fault {
+        if state
+            .current_bundle
+            .history
+            .read()
+            .await
+            .get(revision)
+            .is_none()
+        {
+            return Err(ApiError::BadRequest(format!(
+                "Revision {revision} is not retained"
+            )));
+        }
+    }
+    let active = state
+        .faults
+        .inject(request.fault, Duration::from_secs(request.duration_secs));
+    tracing::warn!(
+        "Injecting {:?} into the bundle endpoints until {}",
+        active.fault,
+        humantime::format_rfc3339_seconds(active.expires_at)
+    );
+    Ok(Json(active))
+}
+
+/// Stops injecting failures into the bundle endpoints
+#[utoipa::path(
+    delete,
+    path = "/admin/faults",
+    tag = "admin",
+    responses((status = OK, description = "No failure is injected")),
+)]
+async fn clear_fault_endpoint(State(state): State<ChaosState>) -> impl IntoResponse {
+    state.faults.clear();
+    tracing::info!("Injected fault cleared");
+    StatusCode::OK
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{inject_faults, Fault, Faults};
+    use axum::{
+        body::Body,
+        extract::{Query, Request},
+        http::StatusCode,
+        middleware::from_fn_with_state,
+        routing::get,
+        Router,
+    };
+    use std::{collections::HashMap, time::Duration};
+    use tower::ServiceExt;
+
+    async fn respond(faults: &Faults) -> (StatusCode, String) {
+        let response = Router::new()
+            .route(
+                "/bundle.tar.gz",
+                get(|Query(query): Query<HashMap<String, String>>| async move {
+                    query.get("revision").cloned().unwrap_or_default()
+                }),
+            )
+            .layer(from_fn_with_state(faults.clone(), inject_faults))
+            .oneshot(
+                Request::builder()
+                    .uri("/bundle.tar.gz")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn faults_injected_until_cleared() {
+        let faults = Faults::default();
+        assert_eq!((StatusCode::OK, String::new()), respond(&faults).await);
+        faults.inject(Fault::Error, Duration::from_secs(60));
+        assert_eq!(StatusCode::INTERNAL_SERVER_ERROR, respond(&faults).await.0);
+        faults.inject(
+            Fault::StaleRevision {
+                revision: "0.1.0:abc".to_string(),
+            },
+            Duration::from_secs(60),
+        );
+        assert_eq!(
+            (StatusCode::OK, "0.1.0:abc".to_string()),
+            respond(&faults).await
+        );
+        faults.clear();
+        assert_eq!((StatusCode::OK, String::new()), respond(&faults).await);
+        faults.inject(Fault::Error, Duration::ZERO);
+        assert_eq!((StatusCode::OK, String::new()), respond(&faults).await);
+    }
+}
@@ -0,0 +1,275 @@
+use crate::{
+    bundle_cache,
+    database::{connect_limited, database_time, endpoint},
+    discovery, jwt,
+    permissionables::{
+        beamlines::Beamlines,
+        instrument_scientists::InstrumentScientists,
+        lab_contacts::LabContacts,
+        people::People,
+        proposals::{ProposalFilters, Proposals},
+        roles::Roles,
+        session_participants::SessionParticipants,
+        sessions::Sessions,
+        subjects::Subjects,
+        with_timeout,
+    },
+    signature,
+    transformation::Transformations,
+    ServeArgs,
+};
+use sqlx::MySqlPool;
+use std::{fmt::Display, time::Duration};
+
+/// A report of pre-flight checks, printed as each check completes
+#[derive(Debug, Default)]
+pub struct CheckReport {
+    /// The number of checks which failed
+    pub failures: usize,
+}
+
+impl CheckReport {
+    /// Prints the outcome of a check, recording it if it failed
+    pub fn record(&mut self, name: &str, outcome: Result<impl Display, impl Display>) {
+        match outcome {
+            Ok(detail) => println!("[ OK ] {name}: {detail}"),
+            Err(err) => {
+                self.failures += 1;
+                println!("[FAIL] {name}: {err}");
+            }
+        }
+    }
+}
+
+/// Validates the configuration and the queries run against each ISPyB instance, printing a report and returning whether all checks passed
+///
+/// Each query returns at most a single row, such that the checks are cheap enough to run before each deployment
+pub async fn run(args: ServeArgs) -> bool {
+    let mut report = CheckReport::default();
+    let query_timeout = args.query_timeout.into();
+
+    report.record(
+        "require token",
+        match &args.require_token {
+            Some(token) if token.trim().is_empty() => Err("Token is empty"),
+            Some(_) => Ok("configured"),
+            None => Ok("not configured"),
+        },
+    );
+    report.record(
+        "require admin token",
+        match &args.require_admin_token {
+            Some(token) if token.trim().is_empty() => Err("Token is empty"),
+            Some(token) if args.require_token.as_ref() == Some(token) => {
+                Err("Token is the same as the bundle token")
+            }
+            Some(_) => Ok("configured"),
+            None => Ok("not configured"),
+        },
+    );
+    report.record(
+        "basic auth users",
+        Ok::<_, &str>(configured(!args.basic_auth_users.is_empty())),
+    );
+    report.record(
+        "JSON Web Token decoding key",
+        jwt::JwtValidator::from_args(args.jwt.clone())
+            .map(|validator| configured(validator.is_some())),
+    );
+    #[cfg(feature = "introspection")]
+    report.record(
+        "token introspection",
+        Ok::<_, &str>(configured(args.introspection.is_configured())),
+    );
+    report.record(
+        "bundle signing key",
+        signature::BundleSigner::from_args(args.signing.clone())
+            .map(|bundle_signer| configured(bundle_signer.is_some())),
+    );
+    report.record(
+        "transformations",
+        Transformations::load(args.transformations.as_deref())
+            .map(|transformations| configured(!transformations.is_empty())),
+    );
+    report.record(
+        "discovery template",
+        discovery::render(&args.discovery).map(|bundle_file| configured(bundle_file.is_some())),
+    );
+    if let Some(bundle_cache_path) = &args.bundle_cache_path {
+        report.record(
+            "bundle cache",
+            match bundle_cache_path.parent() {
+                Some(directory) if !directory.as_os_str().is_empty() && !directory.is_dir() => {
+                    Err(format!("{} is not a directory", directory.display()))
+                }
+                _ if !bundle_cache_path.exists() => Ok("empty".to_string()),
+                _ => bundle_cache::load(bundle_cache_path)
+                    .await
+                    .map(|bundle_file| format!("contains revision {}", bundle_file.revision))
+                    .map_err(|err| err.to_string()),
+            },
+        );
+    }
+    for tls_file in args.database.tls_files() {
+        report.record(
+            &format!("TLS file {}", tls_file.display()),
+            std::fs::metadata(tls_file).map(|_| "readable"),
+        );
+    }
+
+    for database_url in args.database.database_urls() {
+        let endpoint = endpoint(database_url);
+        match connect_limited(database_url, &args.database).await {
+            Ok(ispyb_pool) => {
+                report.record(
+                    &format!("{endpoint} connection"),
+                    Ok::<_, String>("established"),
+                );
+                check_queries(
+                    &mut report,
+                    &endpoint,
+                    &ispyb_pool,
+                    query_timeout,
+                    args.include_personal_data,
+                    &args.proposal_filters,
+                )
+                .await;
+                ispyb_pool.close().await;
+            }
+            Err(err) => report.record(&format!("{endpoint} connection"), Err::<&str, _>(err)),
+        }
+    }
+
+    println!("{} checks failed", report.failures);
+    report.failures == 0
+}
+
+/// Runs each permissionable query, and those used for incremental updates, against an ISPyB instance
+///
+/// The query for personal data is only run if it is to be included
+async fn check_queries(
+    report: &mut CheckReport,
+    endpoint: &str,
+    ispyb_pool: &MySqlPool,
+    query_timeout: Duration,
+    include_personal_data: bool,
+    proposal_filters: &ProposalFilters,
+) {
+    report.record(
+        &format!("{endpoint} subjects"),
+        Subjects::fetch(ispyb_pool, query_timeout)
+            .await
+            .map(|_| "valid"),
+    );
+    report.record(
+        &format!("{endpoint} sessions"),
+        with_timeout("sessions", query_timeout, Sessions::fetch(ispyb_pool))
+            .await
+            .map(|_| "valid"),
+    );
+    report.record(
+        &format!("{endpoint} proposals"),
+        with_timeout(
+            "proposals",
+            query_timeout,
+            Proposals::fetch(ispyb_pool, proposal_filters),
+        )
+        .await
+        .map(|_| "valid"),
+    );
+    report.record(
+        &format!("{endpoint} beamlines"),
+        with_timeout("beamlines", query_timeout, Beamlines::fetch(ispyb_pool))
+            .await
+            .map(|_| "valid"),
+    );
+    report.record(
+        &format!("{endpoint} roles"),
+        with_timeout("roles", query_timeout, Roles::fetch(ispyb_pool))
+            .await
+            .map(|_| "valid"),
+    );
+    report.record(
+        &format!("{endpoint} session participants"),
+        with_timeout(
+            "session_participants",
+            query_timeout,
+            SessionParticipants::fetch(ispyb_pool),
+        )
+        .await
+        .map(|_| "valid"),
+    );
+    report.record(
+        &format!("{endpoint} lab contacts"),
+        with_timeout(
+            "lab_contacts",
+            query_timeout,
+            LabContacts::fetch(ispyb_pool),
+        )
+        .await
+        .map(|_| "valid"),
+    );
+    report.record(
+        &format!("{endpoint} instrument scientists"),
+        with_timeout(
+            "instrument_scientists",
+            query_timeout,
+            InstrumentScientists::fetch(ispyb_pool),
+        )
+        .await
+        .map(|_| "valid"),
+    );
+    if include_personal_data {
+        report.record(
+            &format!("{endpoint} people"),
+            with_timeout("people", query_timeout, People::fetch(ispyb_pool))
+                .await
+                .map(|_| "valid"),
+        );
+    }
+    let since = match database_time(ispyb_pool).await {
+        Ok(now) => now,
+        Err(err) => {
+            return report.record(&format!("{endpoint} database time"), Err::<&str, _>(err))
+        }
+    };
+    report.record(
+        &format!("{endpoint} changed sessions"),
+        with_timeout(
+            "sessions",
+            query_timeout,
+            Sessions::fetch_changed(ispyb_pool, since),
+        )
+        .await
+        .map(|_| "valid"),
+    );
+    report.record(
+        &format!("{endpoint} changed proposals"),
+        with_timeout(
+            "proposals",
+            query_timeout,
+            Proposals::fetch_changed(ispyb_pool, proposal_filters, since),
+        )
+        .await
+        .map(|_| "valid"),
+    );
+    report.record(
+        &format!("{endpoint} changed beamlines"),
+        with_timeout(
+            "beamlines",
+            query_timeout,
+            Beamlines::fetch_changed(ispyb_pool, since),
+        )
+        .await
+        .map(|_| "valid"),
+    );
+}
+
+/// Describes whether an optional component is configured
+fn configured(is_configured: bool) -> &'static str {
+    if is_configured {
+        "configured"
+    } else {
+        "not configured"
+    }
+}
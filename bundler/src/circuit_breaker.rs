@@ -0,0 +1,215 @@
+use clap::Args;
+use serde::Serialize;
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+use tokio::time::Instant;
+use utoipa::ToSchema;
+
+/// Options for a circuit breaker suspending fetches from ISPyB after repeated failures, such that replicas do not compound the load on a misbehaving database
+#[derive(Debug, Clone, Args)]
+pub struct CircuitBreakerArgs {
+    /// The number of consecutive failed fetches from ISPyB after which the circuit breaker opens, suspending fetches. The circuit breaker is disabled unless set
+    #[arg(long, env = "BUNDLER_CIRCUIT_BREAKER_FAILURE_THRESHOLD", value_parser = clap::value_parser!(u32).range(1..))]
+    circuit_breaker_failure_threshold: Option<u32>,
+    /// The time for which the circuit breaker remains open before a single probing fetch is permitted
+    #[arg(long, env = "BUNDLER_CIRCUIT_BREAKER_OPEN_DURATION", default_value_t = humantime::Duration::from(Duration::from_secs(60)))]
+    circuit_breaker_open_duration: humantime::Duration,
+}
+
+/// The state of a [`CircuitBreaker`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitState {
+    /// Fetches are permitted
+    Closed,
+    /// Fetches are suspended until the open duration has elapsed
+    Open,
+    /// A single fetch is permitted, probing whether the database has recovered
+    HalfOpen,
+}
+
+impl CircuitState {
+    /// The value of the circuit breaker state metric, being 0 whilst closed, 1 whilst half open and 2 whilst open
+    fn level(self) -> i64 {
+        match self {
+            Self::Closed => 0,
+            Self::HalfOpen => 1,
+            Self::Open => 2,
+        }
+    }
+}
+
+/// The state of a [`CircuitBreaker`], as reported in the status of the service
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct CircuitReport {
+    /// Whether fetches from ISPyB are permitted
+    state: CircuitState,
+    /// The number of consecutive failed fetches
+    consecutive_failures: u32,
+    /// The time until which fetches are suspended, if the circuit breaker is open
+    #[serde(serialize_with = "crate::timestamp::serialize_optional")]
+    #[schema(value_type = Option<String>, format = DateTime)]
+    open_until: Option<SystemTime>,
+}
+
+/// The outcomes of recent fetches, from which the state of a [`CircuitBreaker`] is determined
+#[derive(Debug)]
+struct Circuit {
+    /// Whether fetches are permitted
+    state: CircuitState,
+    /// The number of consecutive failed fetches
+    consecutive_failures: u32,
+    /// The instant until which fetches are suspended, if the circuit breaker is open
+    open_until: Option<Instant>,
+}
+
+/// A thread safe circuit breaker around fetches from ISPyB, which opens after a number of consecutive failures and permits a probing fetch once the open duration has elapsed
+#[derive(Debug, Clone)]
+pub struct CircuitBreaker {
+    /// The outcomes of recent fetches
+    circuit: Arc<Mutex<Circuit>>,
+    /// The number of consecutive failures after which the circuit breaker opens, if enabled
+    failure_threshold: Option<u32>,
+    /// The time for which the circuit breaker remains open
+    open_duration: Duration,
+}
+
+impl CircuitBreaker {
+    /// Creates a closed [`CircuitBreaker`], which never opens unless a failure threshold is configured
+    pub fn new(args: CircuitBreakerArgs) -> Self {
+        Self {
+            circuit: Arc::new(Mutex::new(Circuit {
+                state: CircuitState::Closed,
+                consecutive_failures: 0,
+                open_until: None,
+            })),
+            failure_threshold: args.circuit_breaker_failure_threshold,
+            open_duration: args.circuit_breaker_open_duration.into(),
+        }
+    }
+
+    /// Determines whether a fetch is permitted at the given time, moving to half open once the open duration has elapsed
+    pub fn permit(&self, now: Instant) -> bool {
+        let mut circuit = self.circuit.lock().unwrap();
+        match circuit.state {
+            CircuitState::Closed | CircuitState::HalfOpen => true,
+            CircuitState::Open if circuit.open_until.is_some_and(|until| now < until) => false,
+            CircuitState::Open => {
+                tracing::info!("Circuit breaker half open, probing ISPyB");
+                transition(&mut circuit, CircuitState::HalfOpen);
+                circuit.open_until = None;
+                true
+            }
+        }
+    }
+
+    /// Records a successful fetch, closing the circuit breaker
+    pub fn record_success(&self) {
+        let mut circuit = self.circuit.lock().unwrap();
+        if circuit.state != CircuitState::Closed {
+            tracing::info!("Circuit breaker closed, ISPyB has recovered");
+        }
+        transition(&mut circuit, CircuitState::Closed);
+        circuit.consecutive_failures = 0;
+        circuit.open_until = None;
+    }
+
+    /// Records a failed fetch at the given time, opening the circuit breaker if the failure threshold is reached or a probing fetch failed
+    pub fn record_failure(&self, now: Instant) {
+        let mut circuit = self.circuit.lock().unwrap();
+        circuit.consecutive_failures = circuit.consecutive_failures.saturating_add(1);
+        let Some(failure_threshold) = self.failure_threshold else {
+            return;
+        };
+        if circuit.state == CircuitState::HalfOpen
+            || circuit.consecutive_failures >= failure_threshold
+        {
+            if circuit.state != CircuitState::Open {
+                tracing::warn!(
+                    "Circuit breaker opened after {} consecutive failed fetches, suspending fetches for {}",
+                    circuit.consecutive_failures,
+                    humantime::format_duration(self.open_duration)
+                );
+            }
+            transition(&mut circuit, CircuitState::Open);
+            circuit.open_until = Some(now + self.open_duration);
+        }
+    }
+
+    /// Reports the state of the circuit breaker
+    pub fn report(&self) -> CircuitReport {
+        let circuit = self.circuit.lock().unwrap();
+        CircuitReport {
+            state: circuit.state,
+            consecutive_failures: circuit.consecutive_failures,
+            open_until: circuit.open_until.map(|open_until| {
+                SystemTime::now() + open_until.saturating_duration_since(Instant::now())
+            }),
+        }
+    }
+}
+
+/// Moves the [`Circuit`] to the state, updating the circuit breaker state metric
+fn transition(circuit: &mut Circuit, state: CircuitState) {
+    let change = state.level() - circuit.state.level();
+    if change != 0 {
+        tracing::info!(counter.circuit_breaker_state = change);
+    }
+    circuit.state = state;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CircuitBreaker, CircuitBreakerArgs, CircuitState};
+    use clap::Parser;
+    use std::time::Duration;
+    use tokio::time::Instant;
+
+    #[derive(Parser)]
+    struct Args {
+        #[command(flatten)]
+        circuit_breaker: CircuitBreakerArgs,
+    }
+
+    fn circuit_breaker(args: &[&str]) -> CircuitBreaker {
+        CircuitBreaker::new(Args::parse_from(args).circuit_breaker)
+    }
+
+    #[test]
+    fn opens_after_threshold() {
+        let circuit_breaker = circuit_breaker(&[
+            "bundler",
+            "--circuit-breaker-failure-threshold",
+            "2",
+            "--circuit-breaker-open-duration",
+            "60s",
+        ]);
+        let now = Instant::now();
+        circuit_breaker.record_failure(now);
+        assert!(circuit_breaker.permit(now));
+        circuit_breaker.record_failure(now);
+        assert_eq!(CircuitState::Open, circuit_breaker.report().state);
+        assert!(!circuit_breaker.permit(now + Duration::from_secs(59)));
+        assert!(circuit_breaker.permit(now + Duration::from_secs(60)));
+        assert_eq!(CircuitState::HalfOpen, circuit_breaker.report().state);
+        circuit_breaker.record_failure(now + Duration::from_secs(60));
+        assert!(!circuit_breaker.permit(now + Duration::from_secs(119)));
+        assert!(circuit_breaker.permit(now + Duration::from_secs(120)));
+        circuit_breaker.record_success();
+        assert_eq!(CircuitState::Closed, circuit_breaker.report().state);
+        assert_eq!(0, circuit_breaker.report().consecutive_failures);
+    }
+
+    #[test]
+    fn never_opens_without_threshold() {
+        let circuit_breaker = circuit_breaker(&["bundler"]);
+        let now = Instant::now();
+        for _ in 0..10 {
+            circuit_breaker.record_failure(now);
+        }
+        assert!(circuit_breaker.permit(now));
+        assert_eq!(CircuitState::Closed, circuit_breaker.report().state);
+    }
+}
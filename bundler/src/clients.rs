@@ -0,0 +1,357 @@
+use crate::bundle::ContentHasher;
+use axum::{
+    extract::{ConnectInfo, MatchedPath, Request, State},
+    http::{
+        header::{ETAG, USER_AGENT},
+        HeaderMap,
+    },
+    middleware::Next,
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use clap::Args;
+use headers::{
+    authorization::{Basic, Bearer},
+    Authorization, HeaderMapExt,
+};
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, HashMap},
+    net::{IpAddr, SocketAddr},
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+use utoipa::{OpenApi, ToSchema};
+
+/// The number of clients tracked, beyond which the least recently seen are forgotten
+const MAX_CLIENTS: usize = 1024;
+
+/// The number of hex digits of the digest of a bearer token by which it is identified
+const TOKEN_ID_LENGTH: usize = 12;
+
+/// Options for tracking the clients requesting bundles
+#[derive(Debug, Clone, Args)]
+pub struct ClientTrackingArgs {
+    /// The addresses of reverse proxies trusted to report the address of the client in the 'X-Forwarded-For' header, which is otherwise ignored
+    #[arg(
+        long = "trusted-proxy",
+        env = "BUNDLER_TRUSTED_PROXIES",
+        value_delimiter = ','
+    )]
+    trusted_proxies: Vec<IpAddr>,
+}
+
+/// The identity of a client, as reported by the clients endpoint
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, ToSchema)]
+struct ClientIdentity {
+    /// An identifier of the credentials presented, being the username of HTTP Basic credentials or a truncated digest of a bearer token, such that tokens are never disclosed
+    token_id: String,
+    /// The 'User-Agent' header sent by the client
+    user_agent: String,
+    /// The IP address from which the client connected, or the first listed in the 'X-Forwarded-For' header if it connected via a trusted proxy
+    remote_ip: String,
+}
+
+impl ClientIdentity {
+    /// Identifies the client from its request headers and the address from which it connected, if known, honouring the 'X-Forwarded-For' header only from trusted proxies
+    fn new(
+        headers: &HeaderMap,
+        remote_addr: Option<SocketAddr>,
+        trusted_proxies: &[IpAddr],
+    ) -> Self {
+        let token_id = if let Some(Authorization(basic)) =
+            headers.typed_get::<Authorization<Basic>>()
+        {
+            format!("basic:{}", basic.username())
+        } else if let Some(Authorization(bearer)) = headers.typed_get::<Authorization<Bearer>>() {
+            let mut hasher = ContentHasher::default();
+            hasher.update(&bearer.token());
+            let mut digest = hasher.finish();
+            digest.truncate(TOKEN_ID_LENGTH);
+            format!("bearer:{digest}")
+        } else {
+            "anonymous".to_string()
+        };
+        let header = |name| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::trim)
+        };
+        let remote_ip = header("x-forwarded-for")
+            .filter(|_| {
+                remote_addr.is_some_and(|remote_addr| trusted_proxies.contains(&remote_addr.ip()))
+            })
+            .and_then(|forwarded_for| forwarded_for.split(',').next())
+            .map(|forwarded_for| forwarded_for.trim().to_string())
+            .filter(|forwarded_for| !forwarded_for.is_empty())
+            .or_else(|| remote_addr.map(|remote_addr| remote_addr.ip().to_string()))
+            .unwrap_or_else(|| "unknown".to_string());
+        Self {
+            token_id,
+            user_agent: header(USER_AGENT.as_str()).unwrap_or("unknown").to_string(),
+            remote_ip,
+        }
+    }
+}
+
+/// The most recent request made by a client
+#[derive(Debug, Clone, Serialize, ToSchema)]
+struct ClientActivity {
+    /// The identity of the client
+    #[serde(flatten)]
+    client: ClientIdentity,
+    /// The time at which the client was last seen
+    #[serde(serialize_with = "crate::timestamp::serialize")]
+    #[schema(value_type = String, format = DateTime)]
+    last_seen: SystemTime,
+    /// The path most recently requested by the client
+    last_path: String,
+    /// The status of the most recent response to the client
+    last_status: u16,
+    /// The revision most recently served to, or held by, the client, if any
+    last_revision: Option<String>,
+    /// The number of requests made by the client since it was first seen
+    requests: u64,
+}
+
+/// The clients seen most recently, most recently seen first
+#[derive(Debug, Serialize, ToSchema)]
+struct ClientsReport {
+    /// The most recent request made by each client
+    clients: Vec<ClientActivity>,
+}
+
+/// The clients seen most recently, in order of when they were last seen
+#[derive(Debug, Default)]
+struct ClientRecords {
+    /// The sequence number of the next request recorded
+    next_sequence: u64,
+    /// The sequence number of the most recent request by each client, and its activity
+    activity: HashMap<ClientIdentity, (u64, ClientActivity)>,
+    /// Each client tracked, by the sequence number of its most recent request
+    recency: BTreeMap<u64, ClientIdentity>,
+}
+
+/// A thread safe record of the clients requesting bundles, identified by their credentials, user agent and address
+#[derive(Debug, Clone, Default)]
+pub struct Clients {
+    /// The clients seen most recently
+    records: Arc<Mutex<ClientRecords>>,
+    /// The addresses of reverse proxies trusted to report the address of the client
+    trusted_proxies: Arc<Vec<IpAddr>>,
+}
+
+impl From<ClientTrackingArgs> for Clients {
+    fn from(args: ClientTrackingArgs) -> Self {
+        Self {
+            records: Arc::default(),
+            trusted_proxies: Arc::new(args.trusted_proxies),
+        }
+    }
+}
+
+impl Clients {
+    /// Records a request by the client, forgetting the least recently seen client once too many are tracked
+    fn record(&self, client: ClientIdentity, path: String, status: u16, revision: Option<String>) {
+        let mut records = self.records.lock().unwrap();
+        let previous = records.activity.remove(&client);
+        if let Some((sequence, _)) = &previous {
+            records.recency.remove(sequence);
+        }
+        let (requests, last_revision) = match previous {
+            Some((_, activity)) => (activity.requests, revision.or(activity.last_revision)),
+            None => (0, revision),
+        };
+        let sequence = records.next_sequence;
+        records.next_sequence += 1;
+        records.recency.insert(sequence, client.clone());
+        records.activity.insert(
+            client.clone(),
+            (
+                sequence,
+                ClientActivity {
+                    client,
+                    last_seen: SystemTime::now(),
+                    last_path: path,
+                    last_status: status,
+                    last_revision,
+                    requests: requests + 1,
+                },
+            ),
+        );
+        if records.activity.len() > MAX_CLIENTS {
+            if let Some((_, least_recent)) = records.recency.pop_first() {
+                records.activity.remove(&least_recent);
+            }
+        }
+    }
+
+    /// Reports the most recent request made by each client, most recently seen first
+    fn report(&self) -> ClientsReport {
+        let records = self.records.lock().unwrap();
+        let clients = records
+            .recency
+            .values()
+            .rev()
+            .filter_map(|client| records.activity.get(client))
+            .map(|(_, activity)| activity.clone())
+            .collect();
+        ClientsReport { clients }
+    }
+}
+
+/// Records each request in the [`Clients`], and in download metrics labelled only with the route and status such that their cardinality is bounded
+///
+/// This should be applied within authentication, such that only the requests of authenticated clients are tracked
+pub async fn track_clients(
+    State(clients): State<Clients>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let client = ClientIdentity::new(
+        request.headers(),
+        connect_info.map(|ConnectInfo(remote_addr)| remote_addr),
+        &clients.trusted_proxies,
+    );
+    let path = request.uri().path().to_string();
+    let route = request.extensions().get::<MatchedPath>().map_or_else(
+        || "unmatched".to_string(),
+        |route| route.as_str().to_string(),
+    );
+    let response = next.run(request).await;
+    let status = response.status();
+    tracing::info!(
+        monotonic_counter.client_requests = 1,
+        route = route.as_str(),
+        status = status.as_u16(),
+    );
+    let revision = response
+        .headers()
+        .get(ETAG)
+        .and_then(|etag| etag.to_str().ok())
+        .map(|etag| etag.trim_start_matches("W/").trim_matches('"').to_string());
+    clients.record(client, path, status.as_u16(), revision);
+    response
+}
+
+/// The paths served by the clients endpoint
+#[derive(OpenApi)]
+#[openapi(
+    paths(clients_endpoint),
+    components(schemas(ClientsReport, ClientActivity, ClientIdentity))
+)]
+pub struct ClientsApi;
+
+/// Creates a [`Router`] serving the clients seen most recently
+pub fn router(clients: Clients) -> Router {
+    Router::new()
+        .route("/admin/clients", get(clients_endpoint))
+        .with_state(clients)
+}
+
+/// Returns the time, path, response status and revision of the most recent request made by each client, identified by its credentials, user agent and address
+///
+/// Clients are listed most recently seen first, such that agents still polling a decommissioned endpoint can be found
+#[utoipa::path(
+    get,
+    path = "/admin/clients",
+    tag = "admin",
+    responses((status = OK, description = "The most recent request made by each client", body = ClientsReport)),
+)]
+async fn clients_endpoint(State(clients): State<Clients>) -> impl IntoResponse {
+    Json(clients.report())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ClientIdentity, Clients, MAX_CLIENTS};
+    use axum::http::{
+        header::{AUTHORIZATION, USER_AGENT},
+        HeaderMap, HeaderValue,
+    };
+    use std::net::{IpAddr, SocketAddr};
+
+    #[test]
+    fn clients_identified() {
+        let remote_addr = "10.0.0.1:5000".parse::<SocketAddr>().ok();
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            USER_AGENT,
+            HeaderValue::from_static("Open Policy Agent/0.60.0"),
+        );
+        headers.insert(AUTHORIZATION, HeaderValue::from_static("Bearer secret"));
+        let client = ClientIdentity::new(&headers, remote_addr, &[]);
+        assert!(client.token_id.starts_with("bearer:"));
+        assert!(!client.token_id.contains("secret"));
+        assert_eq!("Open Policy Agent/0.60.0", client.user_agent);
+        assert_eq!("10.0.0.1", client.remote_ip);
+        headers.insert(
+            "x-forwarded-for",
+            HeaderValue::from_static("192.168.0.1, 10.0.0.2"),
+        );
+        assert_eq!(
+            "10.0.0.1",
+            ClientIdentity::new(&headers, remote_addr, &[]).remote_ip
+        );
+        let trusted_proxies = ["10.0.0.1".parse::<IpAddr>().unwrap()];
+        assert_eq!(
+            "192.168.0.1",
+            ClientIdentity::new(&headers, remote_addr, &trusted_proxies).remote_ip
+        );
+        assert_eq!(
+            "anonymous",
+            ClientIdentity::new(&HeaderMap::new(), None, &[]).token_id
+        );
+    }
+
+    #[test]
+    fn last_revision_retained() {
+        let clients = Clients::default();
+        let client = ClientIdentity::new(&HeaderMap::new(), None, &[]);
+        clients.record(
+            client.clone(),
+            "/bundle.tar.gz".to_string(),
+            200,
+            Some("0.1.0:abc".to_string()),
+        );
+        clients.record(client, "/data".to_string(), 200, None);
+        let report = clients.report();
+        assert_eq!(1, report.clients.len());
+        assert_eq!(2, report.clients[0].requests);
+        assert_eq!("/data", report.clients[0].last_path);
+        assert_eq!(
+            Some("0.1.0:abc"),
+            report.clients[0].last_revision.as_deref()
+        );
+    }
+
+    #[test]
+    fn least_recent_forgotten() {
+        let clients = Clients::default();
+        let client = |index: usize| {
+            let mut headers = HeaderMap::new();
+            headers.insert(USER_AGENT, HeaderValue::from(index));
+            ClientIdentity::new(&headers, None, &[])
+        };
+        for index in 0..=MAX_CLIENTS {
+            clients.record(client(index), "/bundle.tar.gz".to_string(), 200, None);
+            if index == 1 {
+                clients.record(client(0), "/bundle.tar.gz".to_string(), 200, None);
+            }
+        }
+        let report = clients.report();
+        assert_eq!(MAX_CLIENTS, report.clients.len());
+        assert_eq!(client(MAX_CLIENTS), report.clients[0].client);
+        assert!(report
+            .clients
+            .iter()
+            .any(|activity| activity.client == client(0)));
+        assert!(!report
+            .clients
+            .iter()
+            .any(|activity| activity.client == client(1)));
+    }
+}
@@ -0,0 +1,153 @@
+use axum::{
+    body::HttpBody,
+    http::{header::CONTENT_TYPE, Response},
+    Router,
+};
+use clap::Args;
+use std::sync::Arc;
+use tower_http::compression::{
+    predicate::{Predicate, SizeAbove},
+    CompressionLayer,
+};
+
+/// Options for compressing the responses of routes other than those serving bundles, which are already compressed or are intended to be served as is
+#[derive(Debug, Clone, Args)]
+pub struct CompressionArgs {
+    /// The size, in bytes, below which responses are not compressed
+    #[arg(long, env = "BUNDLER_COMPRESSION_MIN_SIZE", default_value_t = 1024)]
+    compression_min_size: u16,
+    /// The content types of responses which are compressed with gzip when requested via the 'Accept-Encoding' header
+    #[arg(
+        long = "compression-content-type",
+        env = "BUNDLER_COMPRESSION_CONTENT_TYPES",
+        value_delimiter = ',',
+        default_values_t = [
+            "application/json".to_string(),
+            "application/msgpack".to_string(),
+            "application/problem+json".to_string(),
+        ],
+    )]
+    compression_content_types: Vec<String>,
+    /// Disables compression of responses
+    #[arg(long, env = "BUNDLER_DISABLE_COMPRESSION")]
+    disable_compression: bool,
+}
+
+impl CompressionArgs {
+    /// Compresses the responses of the routes of the router with gzip, if they are large enough and of a configured content type
+    pub fn apply(&self, router: Router) -> Router {
+        if self.disable_compression || self.compression_content_types.is_empty() {
+            return router;
+        }
+        router.layer(
+            CompressionLayer::new().compress_when(
+                ForContentTypes::from(self.compression_content_types.clone())
+                    .and(SizeAbove::new(self.compression_min_size)),
+            ),
+        )
+    }
+}
+
+/// A [`Predicate`] permitting compression only of responses with one of the content types, ignoring any parameters
+#[derive(Debug, Clone)]
+struct ForContentTypes(Arc<[String]>);
+
+impl From<Vec<String>> for ForContentTypes {
+    fn from(content_types: Vec<String>) -> Self {
+        Self(
+            content_types
+                .into_iter()
+                .map(|content_type| content_type.trim().to_ascii_lowercase())
+                .collect(),
+        )
+    }
+}
+
+impl Predicate for ForContentTypes {
+    fn should_compress<B>(&self, response: &Response<B>) -> bool
+    where
+        B: HttpBody,
+    {
+        let Some(content_type) = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|content_type| content_type.to_str().ok())
+        else {
+            return false;
+        };
+        let media_type = content_type
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase();
+        self.0
+            .iter()
+            .any(|content_type| *content_type == media_type)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CompressionArgs;
+    use axum::{
+        body::Body,
+        extract::Request,
+        http::header::{ACCEPT_ENCODING, CONTENT_ENCODING, CONTENT_TYPE},
+        routing::get,
+        Router,
+    };
+    use clap::Parser;
+    use tower::ServiceExt;
+
+    #[derive(Parser)]
+    struct Args {
+        #[command(flatten)]
+        compression: CompressionArgs,
+    }
+
+    async fn content_encoding(args: &[&str], path: &str) -> Option<String> {
+        let router = Args::parse_from(args).compression.apply(
+            Router::new()
+                .route(
+                    "/json",
+                    get(|| async { ([(CONTENT_TYPE, "application/json")], "0".repeat(2048)) }),
+                )
+                .route(
+                    "/tar",
+                    get(|| async { ([(CONTENT_TYPE, "application/x-tar")], "0".repeat(2048)) }),
+                ),
+        );
+        let response = router
+            .oneshot(
+                Request::builder()
+                    .uri(path)
+                    .header(ACCEPT_ENCODING, "gzip")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        response
+            .headers()
+            .get(CONTENT_ENCODING)
+            .map(|encoding| encoding.to_str().unwrap().to_string())
+    }
+
+    #[tokio::test]
+    async fn configured_content_types_compressed() {
+        assert_eq!(
+            Some("gzip".to_string()),
+            content_encoding(&["bundler"], "/json").await
+        );
+        assert_eq!(None, content_encoding(&["bundler"], "/tar").await);
+        assert_eq!(
+            None,
+            content_encoding(&["bundler", "--compression-min-size", "4096"], "/json").await
+        );
+        assert_eq!(
+            None,
+            content_encoding(&["bundler", "--disable-compression"], "/json").await
+        );
+    }
+}
@@ -0,0 +1,225 @@
+use crate::{
+    dataset_roots::DatasetRoot, layout::DatasetLayout, permissionables::proposals::ProposalFilters,
+    polling::DatasetInterval, redaction::RedactionArgs,
+};
+use clap::{Args, CommandFactory, FromArgMatches, Parser};
+use futures_util::TryStreamExt;
+use k8s_openapi::api::core::v1::ConfigMap;
+use kube::{
+    runtime::watcher::{self, watcher, Event},
+    Api, Client,
+};
+use std::{collections::BTreeMap, pin::pin, sync::Arc, time::Duration};
+use tokio::{
+    sync::{watch, Notify},
+    time::sleep,
+};
+
+/// The time after which a failed watch of the ConfigMap is re-established
+const RETRY_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Options for reloading configuration from a Kubernetes ConfigMap at runtime
+#[derive(Debug, Clone, Args)]
+pub struct ConfigMapArgs {
+    /// The name of a Kubernetes ConfigMap from which polling settings, proposal filters and the shape of the bundle are reloaded whenever it changes, keyed by the names of their environment variables, such as 'BUNDLER_POLLING_INTERVAL'. Settings absent from the ConfigMap take their defaults
+    #[arg(long, env = "BUNDLER_K8S_CONFIG_MAP")]
+    k8s_config_map: Option<String>,
+    /// The namespace of the Kubernetes ConfigMap, defaulting to that of the service account
+    #[arg(long, env = "BUNDLER_K8S_CONFIG_MAP_NAMESPACE")]
+    k8s_config_map_namespace: Option<String>,
+}
+
+/// The settings which are reloaded from the ConfigMap, replacing those with which the service was started
+#[derive(Debug, Clone, Parser)]
+#[command(no_binary_name = true)]
+pub struct ReloadableConfig {
+    /// The interval at which ISPyB should be polled
+    #[arg(long, env = "BUNDLER_POLLING_INTERVAL", default_value_t = humantime::Duration::from(Duration::from_secs(60)))]
+    pub polling_interval: humantime::Duration,
+    /// Intervals at which individual datasets are polled, as '<dataset>=<interval>'
+    #[arg(
+        long = "dataset-polling-interval",
+        env = "BUNDLER_DATASET_POLLING_INTERVALS",
+        value_delimiter = ','
+    )]
+    pub dataset_polling_intervals: Vec<DatasetInterval>,
+    /// Filters excluding irrelevant proposals from the bundle
+    #[command(flatten)]
+    pub proposal_filters: ProposalFilters,
+    /// Options for redacting personal data from the bundle
+    #[command(flatten)]
+    pub redaction: RedactionArgs,
+    /// The layout in which individual datasets are written, as '<dataset>=<layout>'
+    #[arg(
+        long = "dataset-layout",
+        env = "BUNDLER_DATASET_LAYOUTS",
+        value_delimiter = ','
+    )]
+    pub dataset_layouts: Vec<DatasetLayout>,
+    /// The manifest root under which individual datasets are placed, as '<dataset>=<root>'
+    #[arg(
+        long = "dataset-root",
+        env = "BUNDLER_DATASET_ROOTS",
+        value_delimiter = ','
+    )]
+    pub dataset_roots: Vec<DatasetRoot>,
+    /// Whether an index of the subjects associated with each session is included in the bundle
+    #[arg(long, env = "BUNDLER_INCLUDE_SESSION_MEMBERS")]
+    pub include_session_members: bool,
+}
+
+impl ReloadableConfig {
+    /// Parses the settings from the data of a ConfigMap, keyed by the names of their environment variables, with any absent taking its default rather than that of the environment
+    pub fn from_data(data: &BTreeMap<String, String>) -> Result<Self, clap::Error> {
+        let mut command = Self::command();
+        let argv = command
+            .get_arguments()
+            .filter_map(|arg| {
+                let value = data.get(arg.get_env()?.to_str()?)?;
+                let long = arg.get_long()?;
+                if arg.get_action().takes_values() {
+                    Some(format!("--{long}={value}"))
+                } else {
+                    is_enabled(value).then(|| format!("--{long}"))
+                }
+            })
+            .collect::<Vec<_>>();
+        command = command.mut_args(|arg| arg.env(None::<&'static str>));
+        Self::from_arg_matches_mut(&mut command.try_get_matches_from(argv)?)
+    }
+}
+
+/// Whether the value of a flag enables it, as when read from the environment
+fn is_enabled(value: &str) -> bool {
+    !matches!(
+        value.trim().to_lowercase().as_str(),
+        "" | "0" | "false" | "f" | "no" | "n" | "off"
+    )
+}
+
+/// Watches the ConfigMap if configured, publishing the settings read from it whenever its data changes and requesting a refresh such that they are applied
+///
+/// Invalid settings are logged and ignored, such that the previous settings remain in use. Failures are logged and the watch re-established
+pub async fn watch_config_map(
+    args: ConfigMapArgs,
+    reloads: watch::Sender<Option<ReloadableConfig>>,
+    refresh_requested: Arc<Notify>,
+) {
+    let Some(name) = args.k8s_config_map else {
+        return std::future::pending().await;
+    };
+    let mut applied = None;
+    loop {
+        if let Err(err) = follow_config_map(
+            &name,
+            args.k8s_config_map_namespace.as_deref(),
+            &mut applied,
+            &reloads,
+            &refresh_requested,
+        )
+        .await
+        {
+            tracing::warn!(
+                monotonic_counter.config_map_watch_failures = 1,
+                "Watch of ConfigMap {name} failed: {err}"
+            );
+        }
+        sleep(RETRY_INTERVAL).await;
+    }
+}
+
+/// Follows changes to the named ConfigMap until the watch fails, publishing settings from data which differs from that last applied
+async fn follow_config_map(
+    name: &str,
+    namespace: Option<&str>,
+    applied: &mut Option<BTreeMap<String, String>>,
+    reloads: &watch::Sender<Option<ReloadableConfig>>,
+    refresh_requested: &Notify,
+) -> Result<(), anyhow::Error> {
+    let client = Client::try_default().await?;
+    let config_maps = match namespace {
+        Some(namespace) => Api::<ConfigMap>::namespaced(client, namespace),
+        None => Api::<ConfigMap>::default_namespaced(client),
+    };
+    let mut events = pin!(watcher(
+        config_maps,
+        watcher::Config::default().fields(&format!("metadata.name={name}")),
+    ));
+    while let Some(event) = events.try_next().await? {
+        let config_map = match event {
+            Event::Applied(config_map) => config_map,
+            Event::Restarted(config_maps) => match config_maps.into_iter().next() {
+                Some(config_map) => config_map,
+                None => continue,
+            },
+            Event::Deleted(_) => {
+                tracing::warn!("ConfigMap {name} was deleted, retaining the current settings");
+                continue;
+            }
+        };
+        let data = config_map.data.unwrap_or_default();
+        if applied.as_ref() == Some(&data) {
+            continue;
+        }
+        match ReloadableConfig::from_data(&data) {
+            Ok(config) => {
+                tracing::info!("Reloaded settings from ConfigMap {name}");
+                reloads.send_replace(Some(config));
+                *applied = Some(data);
+                refresh_requested.notify_one();
+            }
+            Err(err) => tracing::warn!(
+                monotonic_counter.config_map_reload_failures = 1,
+                "Could not reload settings from ConfigMap {name}, retaining the current settings: {err}"
+            ),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ReloadableConfig;
+    use std::{collections::BTreeMap, time::Duration};
+
+    #[test]
+    fn parsed_from_environment_names() {
+        let config = ReloadableConfig::from_data(&BTreeMap::from([
+            ("BUNDLER_POLLING_INTERVAL".to_string(), "5m".to_string()),
+            (
+                "BUNDLER_DATASET_LAYOUTS".to_string(),
+                "sessions=records,roles=records".to_string(),
+            ),
+            (
+                "BUNDLER_INCLUDE_SESSION_MEMBERS".to_string(),
+                "true".to_string(),
+            ),
+            ("UNRELATED".to_string(), "ignored".to_string()),
+        ]))
+        .unwrap();
+        assert_eq!(
+            Duration::from_secs(300),
+            Duration::from(config.polling_interval)
+        );
+        assert_eq!(2, config.dataset_layouts.len());
+        assert!(config.include_session_members);
+        assert!(config.dataset_roots.is_empty());
+    }
+
+    #[test]
+    fn invalid_settings_rejected() {
+        assert!(ReloadableConfig::from_data(&BTreeMap::from([(
+            "BUNDLER_DATASET_LAYOUTS".to_string(),
+            "visits=records".to_string(),
+        )]))
+        .is_err());
+        assert!(
+            !ReloadableConfig::from_data(&BTreeMap::from([(
+                "BUNDLER_INCLUDE_SESSION_MEMBERS".to_string(),
+                "false".to_string(),
+            )]))
+            .unwrap()
+            .include_session_members
+        );
+    }
+}
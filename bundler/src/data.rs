@@ -0,0 +1,281 @@
+use crate::{
+    bundle::{DATASETS, SCHEMA_PREFIX},
+    problem::ApiError,
+    scoped::Scope,
+    scoped_bundle_file, BundleFile, CurrentBundle,
+};
+use axum::{
+    extract::{Path, State},
+    http::{
+        header::{ACCEPT, CONTENT_TYPE, VARY},
+        HeaderMap, HeaderValue,
+    },
+    response::{IntoResponse, Response},
+    routing::get,
+    Extension, Router,
+};
+use serde::Serialize;
+use serde_json::Value;
+use std::{collections::BTreeMap, io::Read};
+use utoipa::OpenApi;
+
+/// The media types under which MessagePack is requested, the first of which is that of responses
+const MESSAGE_PACK_TYPES: [&str; 3] = [
+    "application/msgpack",
+    "application/x-msgpack",
+    "application/vnd.msgpack",
+];
+
+/// The paths served by the data endpoints
+#[derive(OpenApi)]
+#[openapi(paths(datasets_endpoint, dataset_endpoint))]
+pub struct DataApi;
+
+/// The representations in which datasets are served, as negotiated via the 'Accept' header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DataFormat {
+    /// JSON, as the datasets are written in the bundle
+    Json,
+    /// MessagePack, for consumers which ingest the datasets into engines other than Open Policy Agent
+    MessagePack,
+}
+
+impl DataFormat {
+    /// Negotiates the format from the 'Accept' header, serving MessagePack only if it is accepted at least as readily as JSON
+    fn negotiate(headers: &HeaderMap) -> Self {
+        let mut json_quality = None::<f32>;
+        let mut message_pack_quality = None::<f32>;
+        for media_range in headers
+            .get_all(ACCEPT)
+            .iter()
+            .filter_map(|accept| accept.to_str().ok())
+            .flat_map(|accept| accept.split(','))
+        {
+            let mut parameters = media_range.split(';').map(str::trim);
+            let media_type = parameters.next().unwrap_or_default().to_ascii_lowercase();
+            let quality = parameters
+                .filter_map(|parameter| parameter.strip_prefix("q="))
+                .find_map(|quality| quality.parse().ok())
+                .unwrap_or(1.0);
+            let accepted = if MESSAGE_PACK_TYPES.contains(&media_type.as_str()) {
+                &mut message_pack_quality
+            } else if matches!(
+                media_type.as_str(),
+                "application/json" | "application/*" | "*/*"
+            ) {
+                &mut json_quality
+            } else {
+                continue;
+            };
+            *accepted = Some(accepted.map_or(quality, |accepted| accepted.max(quality)));
+        }
+        match (message_pack_quality, json_quality) {
+            (Some(message_pack), json)
+                if message_pack > 0.0 && message_pack >= json.unwrap_or(0.0) =>
+            {
+                Self::MessagePack
+            }
+            _ => Self::Json,
+        }
+    }
+
+    /// The media type of responses in this format
+    fn content_type(self) -> &'static str {
+        match self {
+            Self::Json => "application/json",
+            Self::MessagePack => MESSAGE_PACK_TYPES[0],
+        }
+    }
+
+    /// Serializes the value in this format, with structs and maps written with named fields in either case
+    fn encode(self, value: &impl Serialize) -> Result<Vec<u8>, anyhow::Error> {
+        match self {
+            Self::Json => Ok(serde_json::to_vec(value)?),
+            Self::MessagePack => Ok(rmp_serde::to_vec_named(value)?),
+        }
+    }
+}
+
+/// Creates a [`Router`] serving the datasets of the current bundle, as JSON or MessagePack
+pub fn router(current_bundle: CurrentBundle) -> Router {
+    Router::new()
+        .route("/data", get(datasets_endpoint))
+        .route("/data/:dataset", get(dataset_endpoint))
+        .with_state(current_bundle)
+}
+
+/// Reads the datasets from the [`BundleFile`], keyed by the name of each dataset and written in its configured layout
+fn bundle_datasets(
+    bundle_file: &BundleFile,
+) -> Result<BTreeMap<&'static str, Value>, anyhow::Error> {
+    let mut datasets = BTreeMap::new();
+    let mut archive = tar::Archive::new(bundle_file.tar.as_ref());
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.to_string_lossy().into_owned();
+        if path.starts_with(SCHEMA_PREFIX) {
+            continue;
+        }
+        let Some(dataset) = DATASETS
+            .into_iter()
+            .find(|dataset| path.ends_with(&format!("/{dataset}/data.json")))
+        else {
+            continue;
+        };
+        let mut contents = Vec::new();
+        entry.read_to_end(&mut contents)?;
+        datasets.insert(dataset, serde_json::from_slice(&contents)?);
+    }
+    Ok(datasets)
+}
+
+/// Produces a response containing the selected datasets of the current bundle, restricted to the [`Scope`] of the requesting token, in the negotiated [`DataFormat`]
+async fn data_response(
+    current_bundle: &CurrentBundle,
+    scope: Option<Extension<Scope>>,
+    headers: &HeaderMap,
+    select: impl FnOnce(BTreeMap<&'static str, Value>) -> Result<Value, ApiError>,
+) -> Result<Response, ApiError> {
+    let bundle_file = current_bundle.as_ref().read().await;
+    if bundle_file.is_placeholder() {
+        return Err(ApiError::Unavailable);
+    }
+    let format = DataFormat::negotiate(headers);
+    let body = scoped_bundle_file(current_bundle, &bundle_file, scope)
+        .await
+        .and_then(|bundle_file| bundle_datasets(&bundle_file))
+        .map_err(|err| {
+            tracing::error!("Could not read datasets of bundle: {err}");
+            ApiError::Internal("Could not read datasets of bundle".to_string())
+        })
+        .and_then(select)?;
+    let body = format.encode(&body).map_err(|err| {
+        tracing::error!("Could not encode datasets as {format:?}: {err}");
+        ApiError::Internal(format!("Could not encode datasets as {format:?}"))
+    })?;
+    Ok((
+        [
+            (
+                CONTENT_TYPE,
+                HeaderValue::from_static(format.content_type()),
+            ),
+            (VARY, HeaderValue::from_static("accept")),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+/// Returns every dataset of the current bundle, keyed by the name of each dataset
+///
+/// Datasets are returned as JSON unless MessagePack is requested via the 'Accept' header, as is preferable for consumers which parse the whole of the data
+#[utoipa::path(
+    get,
+    path = "/data",
+    tag = "data",
+    params(
+        ("Accept" = Option<String>, Header, description = "'application/msgpack' to receive MessagePack in place of JSON"),
+    ),
+    responses(
+        (status = OK, description = "The datasets of the current bundle, as JSON or MessagePack", content_type = "application/json"),
+        (status = SERVICE_UNAVAILABLE, description = "No bundle has been fetched yet"),
+        (status = UNAUTHORIZED, description = "A valid bearer token was not provided"),
+    ),
+)]
+async fn datasets_endpoint(
+    State(current_bundle): State<CurrentBundle>,
+    scope: Option<Extension<Scope>>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    data_response(&current_bundle, scope, &headers, |datasets| {
+        Ok(Value::Object(
+            datasets
+                .into_iter()
+                .map(|(dataset, entries)| (dataset.to_string(), entries))
+                .collect(),
+        ))
+    })
+    .await
+}
+
+/// Returns the named dataset of the current bundle, in its configured layout
+///
+/// The dataset is returned as JSON unless MessagePack is requested via the 'Accept' header
+#[utoipa::path(
+    get,
+    path = "/data/{dataset}",
+    tag = "data",
+    params(
+        ("dataset" = String, Path, description = "The name of the dataset"),
+        ("Accept" = Option<String>, Header, description = "'application/msgpack' to receive MessagePack in place of JSON"),
+    ),
+    responses(
+        (status = OK, description = "The dataset, as JSON or MessagePack", content_type = "application/json"),
+        (status = NOT_FOUND, description = "No dataset of this name is included in the bundle"),
+        (status = SERVICE_UNAVAILABLE, description = "No bundle has been fetched yet"),
+        (status = UNAUTHORIZED, description = "A valid bearer token was not provided"),
+    ),
+)]
+async fn dataset_endpoint(
+    State(current_bundle): State<CurrentBundle>,
+    Path(dataset): Path<String>,
+    scope: Option<Extension<Scope>>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    data_response(&current_bundle, scope, &headers, |mut datasets| {
+        datasets
+            .remove(dataset.as_str())
+            .ok_or_else(|| ApiError::NotFound(format!("No dataset named {dataset} is included")))
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{bundle_datasets, DataFormat};
+    use crate::{
+        bundle::{Bundle, NoMetadata},
+        BundleFile,
+    };
+    use axum::http::{header::ACCEPT, HeaderMap, HeaderValue};
+    use serde_json::{json, Value};
+
+    fn accepting(accept: &'static str) -> HeaderMap {
+        HeaderMap::from_iter([(ACCEPT, HeaderValue::from_static(accept))])
+    }
+
+    #[test]
+    fn format_negotiated() {
+        assert_eq!(DataFormat::Json, DataFormat::negotiate(&HeaderMap::new()));
+        assert_eq!(DataFormat::Json, DataFormat::negotiate(&accepting("*/*")));
+        assert_eq!(
+            DataFormat::MessagePack,
+            DataFormat::negotiate(&accepting("application/msgpack"))
+        );
+        assert_eq!(
+            DataFormat::MessagePack,
+            DataFormat::negotiate(&accepting("application/x-msgpack, application/json"))
+        );
+        assert_eq!(
+            DataFormat::Json,
+            DataFormat::negotiate(&accepting("application/msgpack;q=0.5, application/json"))
+        );
+        assert_eq!(
+            DataFormat::Json,
+            DataFormat::negotiate(&accepting("application/msgpack;q=0"))
+        );
+    }
+
+    #[test]
+    fn datasets_roundtrip_as_message_pack() {
+        let bundle = Bundle::<NoMetadata>::default();
+        let datasets = bundle_datasets(&BundleFile::try_from(&bundle).unwrap()).unwrap();
+        assert_eq!(Some(&json!({})), datasets.get("sessions"));
+        assert!(!datasets.contains_key("people"));
+        let encoded = DataFormat::MessagePack.encode(&datasets).unwrap();
+        assert_eq!(
+            serde_json::to_value(&datasets).unwrap(),
+            rmp_serde::from_slice::<Value>(&encoded).unwrap()
+        );
+    }
+}
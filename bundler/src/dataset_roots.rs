@@ -0,0 +1,211 @@
+use crate::{
+    bundle::{BUNDLE_PREFIX, DATASETS, SCHEMA_PREFIX},
+    policy_source::POLICY_PREFIX,
+};
+use serde::Serialize;
+use std::{collections::BTreeMap, str::FromStr};
+
+/// The datasets placed under a root other than the default bundle prefix unless configured otherwise, alongside the data of other services describing users
+const DEFAULT_ROOTS: [(&str, &str); 2] = [("people", "users"), ("instrument_scientists", "users")];
+
+/// The root under which the named dataset is placed unless configured otherwise
+fn default_root(dataset: &str) -> &'static str {
+    DEFAULT_ROOTS
+        .into_iter()
+        .find(|(default_dataset, _)| *default_dataset == dataset)
+        .map_or(BUNDLE_PREFIX, |(_, root)| root)
+}
+
+/// The manifest root under which a dataset is placed, in place of its default root
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DatasetRoot {
+    /// The name of the dataset
+    dataset: String,
+    /// The root under which the dataset is placed
+    root: String,
+}
+
+impl FromStr for DatasetRoot {
+    type Err = anyhow::Error;
+
+    fn from_str(dataset_root: &str) -> Result<Self, Self::Err> {
+        let (dataset, root) = dataset_root.split_once('=').ok_or_else(|| {
+            anyhow::anyhow!("Dataset root '{dataset_root}' is not of the form '<dataset>=<root>'")
+        })?;
+        if !DATASETS.contains(&dataset) {
+            anyhow::bail!(
+                "Dataset root '{dataset_root}' names unknown dataset '{dataset}', expected one of {}",
+                DATASETS.join(", ")
+            );
+        }
+        let root = root.trim_matches('/');
+        if root.is_empty() || root.split('/').any(str::is_empty) {
+            anyhow::bail!("Dataset root '{dataset_root}' is not a valid path");
+        }
+        for reserved in [SCHEMA_PREFIX, POLICY_PREFIX] {
+            if overlaps(root, reserved) {
+                anyhow::bail!(
+                    "Dataset root '{dataset_root}' overlaps the reserved root '{reserved}'"
+                );
+            }
+        }
+        if is_within(BUNDLE_PREFIX, root) && root != BUNDLE_PREFIX {
+            anyhow::bail!(
+                "Dataset root '{dataset_root}' contains the default root '{BUNDLE_PREFIX}'"
+            );
+        }
+        Ok(Self {
+            dataset: dataset.to_string(),
+            root: root.to_string(),
+        })
+    }
+}
+
+/// The manifest root under which each dataset is placed, with any other dataset placed under its default root
+///
+/// Placing datasets under separate roots allows Open Policy Agent to load them alongside bundles owning neighbouring roots
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct DatasetRoots(BTreeMap<String, String>);
+
+impl From<Vec<DatasetRoot>> for DatasetRoots {
+    fn from(dataset_roots: Vec<DatasetRoot>) -> Self {
+        Self(
+            dataset_roots
+                .into_iter()
+                .map(|dataset_root| (dataset_root.dataset, dataset_root.root))
+                .collect(),
+        )
+    }
+}
+
+impl DatasetRoots {
+    /// Whether every dataset is placed under its default root
+    pub fn is_empty(&self) -> bool {
+        self.0
+            .iter()
+            .all(|(dataset, root)| root == default_root(dataset))
+    }
+
+    /// The root under which the named dataset is placed
+    pub fn root(&self, dataset: &str) -> &str {
+        self.0
+            .get(dataset)
+            .map_or_else(|| default_root(dataset), String::as_str)
+    }
+
+    /// The manifest roots of a bundle containing the named datasets, in the order the datasets are named, omitting any root contained within another
+    pub fn manifest_roots<'a>(&self, datasets: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+        let mut roots = Vec::<String>::new();
+        for dataset in datasets {
+            let root = self.root(dataset);
+            if !roots.iter().any(|existing| is_within(root, existing)) {
+                roots.retain(|existing| !is_within(existing, root));
+                roots.push(root.to_string());
+            }
+        }
+        roots
+    }
+
+    /// Describes the root of each dataset placed under a root other than its default, as reported in the effective configuration
+    pub fn describe(&self) -> BTreeMap<String, String> {
+        self.0
+            .iter()
+            .filter(|(dataset, root)| *root != default_root(dataset))
+            .map(|(dataset, root)| (dataset.clone(), root.clone()))
+            .collect()
+    }
+}
+
+/// Whether the path is the root, or lies beneath it
+fn is_within(path: &str, root: &str) -> bool {
+    path.strip_prefix(root)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+/// Whether either root lies within the other, such that Open Policy Agent considers them to overlap
+fn overlaps(a: &str, b: &str) -> bool {
+    is_within(a, b) || is_within(b, a)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DatasetRoot, DatasetRoots};
+    use std::str::FromStr;
+
+    fn dataset_roots(dataset_roots: &[&str]) -> DatasetRoots {
+        DatasetRoots::from(
+            dataset_roots
+                .iter()
+                .map(|dataset_root| DatasetRoot::from_str(dataset_root).unwrap())
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    #[test]
+    fn default_root() {
+        let dataset_roots = dataset_roots(&[]);
+        assert!(dataset_roots.is_empty());
+        assert_eq!("diamond/data", dataset_roots.root("sessions"));
+        assert_eq!(
+            vec!["diamond/data"],
+            dataset_roots.manifest_roots(["subjects", "sessions"])
+        );
+    }
+
+    #[test]
+    fn datasets_mapped_to_roots() {
+        let dataset_roots = dataset_roots(&[
+            "beamlines=diamond/instruments/",
+            "roles=diamond/instruments",
+            "people=diamond/data/people",
+        ]);
+        assert_eq!("diamond/instruments", dataset_roots.root("beamlines"));
+        assert_eq!(
+            vec!["diamond/data", "diamond/instruments"],
+            dataset_roots.manifest_roots(["subjects", "beamlines", "roles", "people"])
+        );
+        assert_eq!(
+            vec!["diamond/instruments"],
+            dataset_roots.manifest_roots(["beamlines", "roles"])
+        );
+    }
+
+    #[test]
+    fn user_datasets_placed_under_users() {
+        let default_roots = dataset_roots(&[]);
+        assert_eq!("users", default_roots.root("instrument_scientists"));
+        assert_eq!(
+            vec!["diamond/data", "users"],
+            default_roots.manifest_roots(["subjects", "instrument_scientists"])
+        );
+        assert!(dataset_roots(&["instrument_scientists=users"]).is_empty());
+        assert_eq!("users", default_roots.root("people"));
+        assert!(dataset_roots(&["people=users"]).is_empty());
+        let configured_roots = dataset_roots(&["instrument_scientists=diamond/data"]);
+        assert!(!configured_roots.is_empty());
+        assert_eq!(
+            "diamond/data",
+            configured_roots.root("instrument_scientists")
+        );
+    }
+
+    #[test]
+    fn nested_roots_collapsed() {
+        let dataset_roots = dataset_roots(&["people=diamond/data/people"]);
+        assert_eq!(
+            vec!["diamond/data"],
+            dataset_roots.manifest_roots(["people", "subjects"])
+        );
+    }
+
+    #[test]
+    fn parse_dataset_roots() {
+        assert!(DatasetRoot::from_str("beamlines").is_err());
+        assert!(DatasetRoot::from_str("instruments=diamond/instruments").is_err());
+        assert!(DatasetRoot::from_str("beamlines=").is_err());
+        assert!(DatasetRoot::from_str("beamlines=diamond//instruments").is_err());
+        assert!(DatasetRoot::from_str("beamlines=diamond/schemas/beamlines").is_err());
+        assert!(DatasetRoot::from_str("beamlines=diamond").is_err());
+        assert!(DatasetRoot::from_str("beamlines=diamond/data").is_ok());
+    }
+}
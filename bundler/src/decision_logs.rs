@@ -0,0 +1,319 @@
+use crate::problem::ApiError;
+use axum::{body::Bytes, extract::State, http::StatusCode, routing::post, Router};
+use clap::Args;
+use serde::{Deserialize, Serialize};
+use std::{path::PathBuf, str::FromStr, time::Duration};
+use tokio::{
+    io::AsyncWriteExt,
+    sync::mpsc,
+    time::{interval, MissedTickBehavior},
+};
+use url::Url;
+use utoipa::{OpenApi, ToSchema};
+
+/// The number of events held awaiting forwarding, beyond which further uploads are refused
+pub const QUEUE_CAPACITY: usize = 10_000;
+
+/// Options for receiving Open Policy Agent decision logs and forwarding them to a sink
+#[derive(Debug, Clone, Args)]
+pub struct DecisionLogArgs {
+    /// The sink to which decision logs uploaded to '/logs' are forwarded, as 'file://<path>' to append JSON lines, 'http(s)://<url>' to post JSON arrays or 'kafka://<brokers>/<topic>' to produce to a topic. Decision logs are not accepted if unset
+    #[arg(long, env = "BUNDLER_DECISION_LOG_SINK")]
+    decision_log_sink: Option<DecisionLogSink>,
+    /// The number of events forwarded to the sink together
+    #[arg(long, env = "BUNDLER_DECISION_LOG_BATCH_SIZE", default_value_t = 500)]
+    decision_log_batch_size: usize,
+    /// The longest time an event is held before being forwarded to the sink
+    #[arg(long, env = "BUNDLER_DECISION_LOG_FLUSH_INTERVAL", default_value_t = humantime::Duration::from(Duration::from_secs(5)))]
+    decision_log_flush_interval: humantime::Duration,
+}
+
+/// A destination to which decision log events are forwarded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecisionLogSink {
+    /// Events are appended to the file as JSON lines
+    File(PathBuf),
+    /// Events are posted to the URL in batches, as JSON arrays
+    Http(Url),
+    /// Events are produced to the Kafka topic, keyed by decision ID
+    Kafka {
+        /// The comma separated addresses of the bootstrap brokers
+        brokers: String,
+        /// The topic to which events are produced
+        topic: String,
+    },
+}
+
+impl FromStr for DecisionLogSink {
+    type Err = String;
+
+    fn from_str(sink: &str) -> Result<Self, Self::Err> {
+        if let Some(path) = sink.strip_prefix("file://") {
+            return Ok(Self::File(PathBuf::from(path)));
+        }
+        if let Some(address) = sink.strip_prefix("kafka://") {
+            return match address.split_once('/') {
+                Some((brokers, topic)) if !brokers.is_empty() && !topic.is_empty() => {
+                    Ok(Self::Kafka {
+                        brokers: brokers.to_string(),
+                        topic: topic.to_string(),
+                    })
+                }
+                _ => Err(format!(
+                    "Kafka sink '{sink}' must be of the form 'kafka://<brokers>/<topic>'"
+                )),
+            };
+        }
+        match Url::parse(sink) {
+            Ok(url) if matches!(url.scheme(), "http" | "https") => Ok(Self::Http(url)),
+            _ => Err(format!(
+                "Decision log sink '{sink}' must begin with 'file://', 'http://', 'https://' or 'kafka://'"
+            )),
+        }
+    }
+}
+
+/// A decision log event, as uploaded by an Open Policy Agent instance
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct DecisionEvent {
+    /// The unique identifier of the decision
+    decision_id: String,
+    /// The remaining fields of the event, which are forwarded unaltered
+    #[serde(flatten)]
+    #[schema(value_type = Object)]
+    fields: serde_json::Map<String, serde_json::Value>,
+}
+
+/// The paths served by the decision log endpoint
+#[derive(OpenApi)]
+#[openapi(paths(logs_receiver), components(schemas(DecisionEvent)))]
+pub struct DecisionLogsApi;
+
+/// Creates a [`Router`] receiving decision logs onto the queue, if a sink is configured
+pub fn router(args: &DecisionLogArgs, events: mpsc::Sender<DecisionEvent>) -> Router {
+    if args.decision_log_sink.is_none() {
+        return Router::new();
+    }
+    Router::new()
+        .route("/logs", post(logs_receiver))
+        .with_state(events)
+}
+
+/// Receives a batch of decision log events from an Open Policy Agent instance, optionally gzipped, and queues them for forwarding to the sink
+///
+/// Gzipped uploads are decompressed before they are received, up to the configured size limit
+#[utoipa::path(
+    post,
+    path = "/logs",
+    tag = "decision logs",
+    request_body = [DecisionEvent],
+    responses(
+        (status = NO_CONTENT, description = "The events were queued for forwarding"),
+        (status = BAD_REQUEST, description = "The upload was not a valid array of decision log events"),
+        (status = SERVICE_UNAVAILABLE, description = "Too many events are awaiting forwarding"),
+        (status = UNAUTHORIZED, description = "A valid bearer token was not provided"),
+    ),
+)]
+async fn logs_receiver(
+    State(events): State<mpsc::Sender<DecisionEvent>>,
+    body: Bytes,
+) -> Result<StatusCode, ApiError> {
+    let uploaded = parse_events(&body).map_err(|err| {
+        tracing::warn!(
+            monotonic_counter.decision_log_rejections = 1,
+            "Rejecting decision log upload: {err}"
+        );
+        ApiError::BadRequest(err)
+    })?;
+    let count = uploaded.len();
+    if events.capacity() < count {
+        tracing::warn!(
+            monotonic_counter.decision_log_events_refused = count,
+            "Refusing decision log upload, as the queue is full"
+        );
+        return Err(ApiError::Overloaded);
+    }
+    for event in uploaded {
+        if events.try_send(event).is_err() {
+            return Err(ApiError::Overloaded);
+        }
+    }
+    tracing::info!(monotonic_counter.decision_log_events_received = count);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Parses an upload of decision log events
+fn parse_events(body: &[u8]) -> Result<Vec<DecisionEvent>, String> {
+    serde_json::from_slice(body).map_err(|err| err.to_string())
+}
+
+/// A connection to the sink, to which batches of events are written
+enum SinkWriter {
+    /// An append-only handle to the file
+    File(tokio::fs::File),
+    /// A client posting to the URL
+    Http(reqwest::Client, Url),
+    /// A producer to the topic
+    #[cfg(feature = "kafka")]
+    Kafka(rdkafka::producer::FutureProducer, String),
+}
+
+impl SinkWriter {
+    /// Opens a connection to the sink
+    async fn open(sink: DecisionLogSink) -> Result<Self, anyhow::Error> {
+        match sink {
+            DecisionLogSink::File(path) => Ok(Self::File(
+                tokio::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .await?,
+            )),
+            DecisionLogSink::Http(url) => Ok(Self::Http(reqwest::Client::new(), url)),
+            #[cfg(feature = "kafka")]
+            DecisionLogSink::Kafka { brokers, topic } => Ok(Self::Kafka(
+                rdkafka::ClientConfig::new()
+                    .set("bootstrap.servers", brokers)
+                    .create()?,
+                topic,
+            )),
+            #[cfg(not(feature = "kafka"))]
+            DecisionLogSink::Kafka { brokers, topic } => anyhow::bail!(
+                "Forwarding decision logs to topic {topic} at {brokers} requires the kafka feature"
+            ),
+        }
+    }
+
+    /// Writes a batch of events to the sink
+    async fn write(&mut self, batch: &[DecisionEvent]) -> Result<(), anyhow::Error> {
+        match self {
+            Self::File(file) => {
+                let mut lines = Vec::new();
+                for event in batch {
+                    serde_json::to_writer(&mut lines, event)?;
+                    lines.push(b'\n');
+                }
+                file.write_all(&lines).await?;
+                file.flush().await?;
+            }
+            Self::Http(http_client, url) => {
+                http_client
+                    .post(url.clone())
+                    .json(batch)
+                    .send()
+                    .await?
+                    .error_for_status()?;
+            }
+            #[cfg(feature = "kafka")]
+            Self::Kafka(producer, topic) => {
+                for event in batch {
+                    let payload = serde_json::to_vec(event)?;
+                    producer
+                        .send(
+                            rdkafka::producer::FutureRecord::to(topic)
+                                .key(&event.decision_id)
+                                .payload(&payload),
+                            Duration::ZERO,
+                        )
+                        .await
+                        .map_err(|(err, _)| err)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Forwards queued events to the sink in batches, once a batch fills or the flush interval elapses, if a sink is configured
+///
+/// Batches which cannot be written are logged and dropped, such that a failing sink does not exhaust the memory of the service
+pub async fn forward_decision_logs(
+    args: DecisionLogArgs,
+    mut events: mpsc::Receiver<DecisionEvent>,
+) {
+    let Some(sink) = args.decision_log_sink else {
+        return std::future::pending().await;
+    };
+    let mut writer = match SinkWriter::open(sink.clone()).await {
+        Ok(writer) => writer,
+        Err(err) => {
+            tracing::error!("Could not open decision log sink {sink:?}: {err}");
+            return std::future::pending().await;
+        }
+    };
+    let batch_size = args.decision_log_batch_size.max(1);
+    let mut flush = interval(args.decision_log_flush_interval.into());
+    flush.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let mut batch = Vec::with_capacity(batch_size);
+    loop {
+        let (due, closed) = tokio::select! {
+            received = events.recv() => match received {
+                Some(event) => {
+                    batch.push(event);
+                    (batch.len() >= batch_size, false)
+                }
+                None => (true, true),
+            },
+            _ = flush.tick() => (true, false),
+        };
+        if due && !batch.is_empty() {
+            match writer.write(&batch).await {
+                Ok(()) => {
+                    tracing::info!(monotonic_counter.decision_log_events_forwarded = batch.len())
+                }
+                Err(err) => tracing::warn!(
+                    monotonic_counter.decision_log_events_dropped = batch.len(),
+                    "Could not forward decision logs to {sink:?}: {err}"
+                ),
+            }
+            batch.clear();
+        }
+        if closed {
+            return std::future::pending().await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_events, DecisionLogSink};
+    use std::path::PathBuf;
+
+    #[test]
+    fn parse_sinks() {
+        assert_eq!(
+            Ok(DecisionLogSink::File(PathBuf::from(
+                "/var/log/decisions.jsonl"
+            ))),
+            "file:///var/log/decisions.jsonl".parse()
+        );
+        assert_eq!(
+            Ok(DecisionLogSink::Http(
+                "https://collector.example.com/logs".parse().unwrap()
+            )),
+            "https://collector.example.com/logs".parse()
+        );
+        assert_eq!(
+            Ok(DecisionLogSink::Kafka {
+                brokers: "kafka-0:9092,kafka-1:9092".to_string(),
+                topic: "decisions".to_string()
+            }),
+            "kafka://kafka-0:9092,kafka-1:9092/decisions".parse()
+        );
+        assert!("kafka://kafka-0:9092".parse::<DecisionLogSink>().is_err());
+        assert!("ftp://example.com".parse::<DecisionLogSink>().is_err());
+    }
+
+    #[test]
+    fn events_parsed() {
+        let events = br#"[{"decision_id": "a", "path": "example/allow", "result": true}]"#;
+        let parsed = parse_events(events).unwrap();
+        assert_eq!(1, parsed.len());
+        assert_eq!("a", parsed[0].decision_id);
+        assert_eq!(
+            Some(&serde_json::json!(true)),
+            parsed[0].fields.get("result")
+        );
+        assert!(parse_events(br#"[{"path": "example/allow"}]"#).is_err());
+    }
+}
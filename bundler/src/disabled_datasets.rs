@@ -0,0 +1,42 @@
+use serde::Serialize;
+use std::collections::BTreeSet;
+
+/// The datasets which are neither fetched from ISPyB nor included in the bundle, for deployments which should not serve them
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct DisabledDatasets(BTreeSet<String>);
+
+impl From<Vec<String>> for DisabledDatasets {
+    fn from(datasets: Vec<String>) -> Self {
+        Self(datasets.into_iter().collect())
+    }
+}
+
+impl DisabledDatasets {
+    /// Whether the named dataset is disabled
+    pub fn disables(&self, dataset: &str) -> bool {
+        self.0.contains(dataset)
+    }
+
+    /// Whether no datasets are disabled
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Describes the disabled datasets, as reported in the effective configuration
+    pub fn describe(&self) -> Vec<String> {
+        self.0.iter().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DisabledDatasets;
+
+    #[test]
+    fn disables_configured_datasets() {
+        let disabled_datasets = DisabledDatasets::from(vec!["roles".to_string()]);
+        assert!(disabled_datasets.disables("roles"));
+        assert!(!disabled_datasets.disables("sessions"));
+        assert!(!DisabledDatasets::default().disables("roles"));
+    }
+}
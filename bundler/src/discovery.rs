@@ -0,0 +1,130 @@
+use crate::{
+    bundle::{AppendJson, ContentHasher},
+    BundleFile,
+};
+use clap::Args;
+use flate2::{write::GzEncoder, Compression};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+/// Options for serving an Open Policy Agent discovery bundle
+#[derive(Debug, Clone, Args)]
+pub struct DiscoveryArgs {
+    /// The path of a JSON Open Policy Agent configuration template, in which `${VAR}` is substituted with the environment variable VAR, to be served as a discovery bundle
+    #[arg(long, env = "BUNDLER_DISCOVERY_CONFIG_TEMPLATE")]
+    discovery_config_template: Option<PathBuf>,
+    /// The decision under which agents are configured to find their configuration, as a slash separated path
+    #[arg(long, env = "BUNDLER_DISCOVERY_DECISION", default_value = "discovery")]
+    discovery_decision: String,
+}
+
+/// The manifest file of the discovery bundle
+#[derive(Debug, Serialize)]
+struct DiscoveryManifest {
+    /// The revision of the bundle, comprising of the crate version number and the configuration hash
+    revision: String,
+    /// The directory prefixes of the data contained within the bundle
+    roots: Vec<String>,
+}
+
+/// Renders the discovery bundle from the configuration template, if configured
+pub fn render(args: &DiscoveryArgs) -> Result<Option<BundleFile>, anyhow::Error> {
+    let Some(template_path) = &args.discovery_config_template else {
+        return Ok(None);
+    };
+    let bundle_file = render_template(template_path, &args.discovery_decision)?;
+    tracing::info!(
+        "Using discovery bundle with revision: {}",
+        bundle_file.revision
+    );
+    Ok(Some(bundle_file))
+}
+
+/// Reads the configuration template and renders it as a discovery bundle
+fn render_template(template_path: &Path, decision: &str) -> Result<BundleFile, anyhow::Error> {
+    let template = std::fs::read_to_string(template_path)?;
+    let config = serde_json::from_str::<serde_json::Value>(&substitute_env(&template, |name| {
+        std::env::var(name).ok()
+    })?)?;
+    let decision = decision.trim_matches('/');
+
+    let mut hasher = ContentHasher::default();
+    hasher.update(&decision);
+    hasher.update(&config);
+    let manifest = DiscoveryManifest {
+        revision: format!("{}:{}", crate::built_info::PKG_VERSION, hasher.finish()),
+        roots: vec![decision.to_string()],
+    };
+
+    let mut bundle_builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::best()));
+    bundle_builder.append_json(".manifest", &manifest)?;
+    bundle_builder.append_json(format!("{decision}/data.json"), &config)?;
+
+    BundleFile::new(
+        manifest.revision,
+        bundle_builder.into_inner()?.finish()?.into(),
+        false,
+    )
+}
+
+/// Substitutes each `${VAR}` in the template with the value of VAR, failing if it is not set
+fn substitute_env(
+    template: &str,
+    lookup: impl Fn(&str) -> Option<String>,
+) -> Result<String, anyhow::Error> {
+    let mut rendered = String::with_capacity(template.len());
+    let mut remaining = template;
+    while let Some(start) = remaining.find("${") {
+        rendered.push_str(&remaining[..start]);
+        let end = remaining[start..]
+            .find('}')
+            .ok_or(anyhow::anyhow!("Unterminated substitution in template"))?;
+        let name = &remaining[start + 2..start + end];
+        rendered.push_str(
+            &lookup(name).ok_or(anyhow::anyhow!("Environment variable {name} is not set"))?,
+        );
+        remaining = &remaining[start + end + 1..];
+    }
+    rendered.push_str(remaining);
+    Ok(rendered)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{render_template, substitute_env};
+    use crate::bundle::{Bundle, NoMetadata};
+    use std::io::Write;
+
+    #[test]
+    fn substitute_known() {
+        let rendered = substitute_env(r#"{"url": "${BUNDLE_URL}/bundle"}"#, |name| {
+            (name == "BUNDLE_URL").then(|| "http://bundler".to_string())
+        })
+        .unwrap();
+        assert_eq!(r#"{"url": "http://bundler/bundle"}"#, rendered);
+    }
+
+    #[test]
+    fn substitute_unknown() {
+        assert!(substitute_env("${MISSING}", |_| None).is_err());
+    }
+
+    #[test]
+    fn substitute_unterminated() {
+        assert!(substitute_env("${UNTERMINATED", |_| Some(String::new())).is_err());
+    }
+
+    #[test]
+    fn render_revision_roundtrip() {
+        let template_path = std::env::temp_dir().join("bundler-discovery-template.json");
+        std::fs::File::create(&template_path)
+            .unwrap()
+            .write_all(br#"{"bundles": {"permissionables": {"service": "bundler"}}}"#)
+            .unwrap();
+        let bundle_file = render_template(&template_path, "/config/discovery/").unwrap();
+        assert_eq!(
+            bundle_file.revision,
+            Bundle::<NoMetadata>::read_revision(&bundle_file.file).unwrap()
+        );
+    }
+}
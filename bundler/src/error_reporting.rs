@@ -0,0 +1,75 @@
+use crate::{built_info, effective_config::EffectiveConfig, CurrentBundle, ServeArgs};
+use clap::Args;
+use sentry::{
+    integrations::tracing::EventFilter,
+    protocol::{Context, Map},
+    types::Dsn,
+    ClientInitGuard, ClientOptions,
+};
+use serde_json::Value;
+use std::sync::{Arc, RwLock};
+use tracing_subscriber::Layer;
+
+/// Options for reporting errors to Sentry
+#[derive(Debug, Clone, Args)]
+pub struct SentryArgs {
+    /// The Sentry DSN to which panics, errors and warnings are reported, if any
+    #[arg(long, env = "BUNDLER_SENTRY_DSN")]
+    sentry_dsn: Option<Dsn>,
+    /// The environment under which errors are reported
+    #[arg(long, env = "BUNDLER_SENTRY_ENVIRONMENT")]
+    sentry_environment: Option<String>,
+}
+
+/// The revision of the bundle currently being served, attached to each reported event
+#[derive(Debug, Clone, Default)]
+pub struct ReportedRevision(Arc<RwLock<Option<String>>>);
+
+/// Initializes the Sentry client if a DSN is configured, attaching the served revision and the configuration to each event
+///
+/// The returned guard flushes pending events when dropped, so must be held for the lifetime of the service
+pub fn init(args: &ServeArgs, revision: ReportedRevision) -> Option<ClientInitGuard> {
+    let dsn = args.sentry.sentry_dsn.clone()?;
+    let config = match serde_json::to_value(EffectiveConfig::from_args(args)) {
+        Ok(Value::Object(config)) => config.into_iter().collect(),
+        _ => Map::new(),
+    };
+    Some(sentry::init(ClientOptions {
+        dsn: Some(dsn),
+        environment: args.sentry.sentry_environment.clone().map(Into::into),
+        release: Some(built_info::PKG_VERSION.into()),
+        before_send: Some(Arc::new(move |mut event| {
+            if let Some(revision) = revision.0.read().unwrap().clone() {
+                event.tags.insert("revision".to_string(), revision);
+            }
+            event
+                .contexts
+                .insert("config".to_string(), Context::Other(config.clone()));
+            Some(event)
+        })),
+        ..Default::default()
+    }))
+}
+
+/// A [`Layer`] reporting errors and warnings as Sentry events, with preceding informational logs attached as breadcrumbs
+pub fn layer<S>() -> impl Layer<S>
+where
+    S: tracing::Subscriber + for<'span> tracing_subscriber::registry::LookupSpan<'span>,
+{
+    sentry::integrations::tracing::layer().event_filter(|metadata| match *metadata.level() {
+        tracing::Level::ERROR | tracing::Level::WARN => EventFilter::Event,
+        tracing::Level::INFO => EventFilter::Breadcrumb,
+        _ => EventFilter::Ignore,
+    })
+}
+
+/// Keeps the [`ReportedRevision`] up to date with the revision of the bundle being served
+pub async fn follow_revisions(revision: ReportedRevision, current_bundle: CurrentBundle) {
+    let mut revisions = current_bundle.revisions.subscribe();
+    loop {
+        *revision.0.write().unwrap() = Some(revisions.borrow_and_update().clone());
+        if revisions.changed().await.is_err() {
+            return std::future::pending().await;
+        }
+    }
+}
@@ -0,0 +1,169 @@
+use crate::{BundleFile, CurrentBundle};
+use clap::Args;
+use std::{
+    io,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+/// The name of the symbolic link to the most recently exported bundle
+const LATEST: &str = "latest.tar.gz";
+
+/// Options for exporting each new bundle to a directory, for Open Policy Agent instances which read bundles from a filesystem
+#[derive(Debug, Clone, Args)]
+pub struct ExportArgs {
+    /// The directory to which each new bundle is written, alongside a 'latest.tar.gz' symbolic link to the most recent
+    #[arg(long, env = "BUNDLER_EXPORT_DIR")]
+    export_dir: Option<PathBuf>,
+    /// The number of exported bundles retained in the export directory, always including the latest
+    #[arg(long, env = "BUNDLER_EXPORT_REVISIONS", default_value_t = 5)]
+    export_revisions: usize,
+}
+
+impl ExportArgs {
+    /// The directory to which bundles are exported, if any
+    pub fn export_dir(&self) -> Option<&Path> {
+        self.export_dir.as_deref()
+    }
+}
+
+/// Exports each revision of the bundle as it is served, if an export directory is configured
+pub async fn export_revisions(args: ExportArgs, current_bundle: CurrentBundle) {
+    let Some(export_dir) = args.export_dir else {
+        return std::future::pending().await;
+    };
+    let mut revisions = current_bundle.revisions.subscribe();
+    loop {
+        revisions.borrow_and_update();
+        let bundle_file = current_bundle.as_ref().read().await.clone();
+        if !bundle_file.is_placeholder() {
+            match export(&export_dir, &bundle_file, args.export_revisions).await {
+                Ok(path) => tracing::info!(
+                    monotonic_counter.bundle_exports = 1,
+                    "Exported bundle {} to {}",
+                    bundle_file.revision,
+                    path.display()
+                ),
+                Err(err) => tracing::warn!(
+                    monotonic_counter.bundle_export_failures = 1,
+                    "Could not export bundle {}: {err}",
+                    bundle_file.revision
+                ),
+            }
+        }
+        if revisions.changed().await.is_err() {
+            return std::future::pending().await;
+        }
+    }
+}
+
+/// Writes the bundle into the directory via a temporary file, then points the 'latest.tar.gz' link at it and removes all but the most recent exports, returning the path of the exported bundle
+async fn export(
+    export_dir: &Path,
+    bundle_file: &BundleFile,
+    retained: usize,
+) -> Result<PathBuf, io::Error> {
+    tokio::fs::create_dir_all(export_dir).await?;
+    let file_name = export_file_name(&bundle_file.revision);
+    let path = export_dir.join(&file_name);
+    let temporary_path = export_dir.join(format!(".{file_name}.tmp"));
+    tokio::fs::write(&temporary_path, &bundle_file.file).await?;
+    tokio::fs::rename(&temporary_path, &path).await?;
+
+    let temporary_link = export_dir.join(format!(".{LATEST}.tmp"));
+    let _ = tokio::fs::remove_file(&temporary_link).await;
+    tokio::fs::symlink(&file_name, &temporary_link).await?;
+    tokio::fs::rename(&temporary_link, export_dir.join(LATEST)).await?;
+
+    let mut exports = Vec::new();
+    let mut entries = tokio::fs::read_dir(export_dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name != file_name && name.starts_with("bundle-") && name.ends_with(".tar.gz") {
+            let modified = entry
+                .metadata()
+                .await?
+                .modified()
+                .unwrap_or(SystemTime::UNIX_EPOCH);
+            exports.push((modified, entry.path()));
+        }
+    }
+    exports.sort_by_key(|(modified, _)| std::cmp::Reverse(*modified));
+    for (_, stale_path) in exports.into_iter().skip(retained.saturating_sub(1)) {
+        tokio::fs::remove_file(stale_path).await?;
+    }
+    Ok(path)
+}
+
+/// The name of the file to which a revision is exported, with any character which is not portable in file names replaced
+fn export_file_name(revision: &str) -> String {
+    let revision = revision
+        .chars()
+        .map(|char| {
+            if char.is_ascii_alphanumeric() || matches!(char, '.' | '-' | '_') {
+                char
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+    format!("bundle-{revision}.tar.gz")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{export, export_file_name, LATEST};
+    use crate::{
+        bundle::{gzip, Bundle, NoMetadata},
+        BundleFile,
+    };
+    use std::{collections::BTreeSet, time::Duration};
+
+    fn bundle_file(revision: u32) -> BundleFile {
+        let bundle = Bundle::<NoMetadata>::default();
+        BundleFile::new(
+            format!("0.1.0:{revision}"),
+            gzip(&bundle.to_tar().unwrap()).unwrap().into(),
+            false,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn revisions_sanitized() {
+        assert_eq!(
+            "bundle-0.1.0_abc_def.tar.gz",
+            export_file_name("0.1.0:abc+def")
+        );
+    }
+
+    #[tokio::test]
+    async fn latest_linked_and_old_removed() {
+        let export_dir = std::env::temp_dir().join("bundler-export-test");
+        let _ = std::fs::remove_dir_all(&export_dir);
+        for revision in 0..4 {
+            export(&export_dir, &bundle_file(revision), 2)
+                .await
+                .unwrap();
+            // Exports are ordered by modification time, which is recorded at a coarse granularity
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        let files = std::fs::read_dir(&export_dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect::<BTreeSet<_>>();
+        assert_eq!(
+            BTreeSet::from([
+                "bundle-0.1.0_2.tar.gz".to_string(),
+                "bundle-0.1.0_3.tar.gz".to_string(),
+                LATEST.to_string()
+            ]),
+            files
+        );
+        assert_eq!(
+            std::fs::read(export_dir.join("bundle-0.1.0_3.tar.gz")).unwrap(),
+            std::fs::read(export_dir.join(LATEST)).unwrap()
+        );
+        std::fs::remove_dir_all(&export_dir).unwrap();
+    }
+}
@@ -0,0 +1,172 @@
+use crate::{bundle::dataset_volume, permissionables::FetchError};
+use axum::{extract::State, response::IntoResponse, routing::get, Json, Router};
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    future::Future,
+    ops::Deref,
+    sync::{Arc, Mutex},
+    time::{Instant, SystemTime},
+};
+use tracing::{field::Empty, Instrument};
+use utoipa::{OpenApi, ToSchema};
+
+/// The outcome of the most recent fetch of a dataset from ISPyB
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+struct DatasetFetch {
+    /// The time at which the fetch began
+    #[serde(serialize_with = "crate::timestamp::serialize")]
+    #[schema(value_type = String, format = DateTime)]
+    fetched_at: SystemTime,
+    /// The time taken by the fetch, in milliseconds
+    duration_ms: u64,
+    /// The number of entries in the fetched dataset, if the fetch succeeded
+    rows: Option<usize>,
+    /// The error encountered, if the fetch failed
+    error: Option<String>,
+}
+
+/// The outcome of the most recent fetch of each dataset from ISPyB
+#[derive(Debug, Serialize, ToSchema)]
+struct FetchStatusReport {
+    /// The outcome of the most recent fetch of each dataset, keyed by dataset name
+    datasets: BTreeMap<String, DatasetFetch>,
+}
+
+/// A thread safe record of the most recent fetch of each dataset from ISPyB
+///
+/// Datasets are fetched concurrently, so those cancelled by the failure of another retain the outcome of their previous fetch
+#[derive(Debug, Clone, Default)]
+pub struct FetchStatus(Arc<Mutex<BTreeMap<&'static str, DatasetFetch>>>);
+
+impl FetchStatus {
+    /// Fetches a dataset, recording the time taken, the number of entries fetched and any error encountered
+    ///
+    /// The fetch is performed within a span of its own, on which the time taken, the number of entries and the size of their serialization are recorded, such that slow queries can be identified
+    pub async fn record<T, K, V>(
+        &self,
+        dataset: &'static str,
+        fetch: impl Future<Output = Result<T, FetchError>>,
+    ) -> Result<T, FetchError>
+    where
+        T: Deref<Target = BTreeMap<K, V>> + Serialize,
+    {
+        let span = tracing::info_span!(
+            "fetch_dataset",
+            dataset,
+            rows = Empty,
+            bytes = Empty,
+            db.duration_ms = Empty,
+            error = Empty,
+            otel.status_code = Empty,
+        );
+        let fetched_at = SystemTime::now();
+        let start = Instant::now();
+        let result = fetch.instrument(span.clone()).await;
+        let duration_ms = start.elapsed().as_millis().try_into().unwrap_or(u64::MAX);
+        span.record("db.duration_ms", duration_ms);
+        match &result {
+            Ok(entries) => {
+                span.record("rows", entries.len());
+                if !span.is_disabled() {
+                    if let Ok(volume) = dataset_volume(entries.len(), entries) {
+                        span.record("bytes", volume.bytes);
+                    }
+                }
+            }
+            Err(err) => {
+                span.record("error", err.to_string());
+                span.record("otel.status_code", "ERROR");
+            }
+        }
+        self.0.lock().unwrap().insert(
+            dataset,
+            DatasetFetch {
+                fetched_at,
+                duration_ms,
+                rows: result.as_ref().ok().map(|entries| entries.len()),
+                error: result.as_ref().err().map(ToString::to_string),
+            },
+        );
+        result
+    }
+
+    /// Reports the outcome of the most recent fetch of each dataset
+    fn report(&self) -> FetchStatusReport {
+        FetchStatusReport {
+            datasets: self
+                .0
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|(dataset, fetch)| (dataset.to_string(), fetch.clone()))
+                .collect(),
+        }
+    }
+}
+
+/// The paths served by the fetch status endpoint
+#[derive(OpenApi)]
+#[openapi(
+    paths(fetch_status_endpoint),
+    components(schemas(FetchStatusReport, DatasetFetch))
+)]
+pub struct FetchStatusApi;
+
+/// Creates a [`Router`] serving the outcome of the most recent fetch of each dataset
+pub fn router(fetch_status: FetchStatus) -> Router {
+    Router::new()
+        .route("/admin/fetch-status", get(fetch_status_endpoint))
+        .with_state(fetch_status)
+}
+
+/// Returns the time, duration, number of entries and error, if any, of the most recent fetch of each dataset from ISPyB
+#[utoipa::path(
+    get,
+    path = "/admin/fetch-status",
+    tag = "admin",
+    responses((status = OK, description = "The outcome of the most recent fetch of each dataset", body = FetchStatusReport)),
+)]
+async fn fetch_status_endpoint(State(fetch_status): State<FetchStatus>) -> impl IntoResponse {
+    Json(fetch_status.report())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FetchStatus;
+    use crate::permissionables::FetchError;
+    use derive_more::Deref;
+    use serde::Serialize;
+    use std::{collections::BTreeMap, time::Duration};
+
+    #[derive(Debug, Deref, Serialize)]
+    struct Dataset(BTreeMap<u32, ()>);
+
+    #[tokio::test]
+    async fn records_latest_fetch() {
+        let fetch_status = FetchStatus::default();
+        fetch_status
+            .record("sessions", async {
+                Ok::<_, FetchError>(Dataset(BTreeMap::from([(1, ()), (2, ())])))
+            })
+            .await
+            .unwrap();
+        assert_eq!(Some(2), fetch_status.report().datasets["sessions"].rows);
+
+        fetch_status
+            .record("sessions", async {
+                Err::<Dataset, _>(FetchError::Timeout {
+                    dataset: "sessions",
+                    timeout: Duration::from_secs(1),
+                })
+            })
+            .await
+            .unwrap_err();
+        let report = fetch_status.report();
+        assert_eq!(None, report.datasets["sessions"].rows);
+        assert_eq!(
+            Some("Fetching sessions timed out after 1s"),
+            report.datasets["sessions"].error.as_deref()
+        );
+    }
+}
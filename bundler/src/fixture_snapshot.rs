@@ -0,0 +1,525 @@
+use crate::{
+    database::{connect_limited, DatabaseArgs},
+    schema_check::ColumnKind,
+};
+use clap::Parser;
+use sqlx::{mysql::MySqlRow, query, MySqlPool, Row};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::PathBuf,
+};
+
+/// Arguments to write test fixtures sampled from a live ISPyB instance
+#[derive(Debug, Parser)]
+pub struct SnapshotFixturesArgs {
+    /// Options for connecting to ISPyB, of which only the first instance is sampled
+    #[command(flatten)]
+    database: DatabaseArgs,
+    /// The number of most recent sessions sampled, along with their proposals, members and roles
+    #[arg(long, default_value_t = 100)]
+    limit: u32,
+    /// If enabled, the login, names and email address of each person and the name of each laboratory are replaced with placeholders derived from their identifiers
+    #[arg(long)]
+    anonymize: bool,
+    /// The directory to which a script is written per table, replacing those of the same name
+    #[arg(long, default_value = "tests/fixtures")]
+    output: PathBuf,
+}
+
+/// A script of `tests/fixtures`, populating the queried columns of a table
+struct Fixture {
+    /// The name of the script, without extension
+    name: &'static str,
+    /// The table populated by the script
+    table: &'static str,
+    /// The columns populated by the script, of which the first identifies the row where it is not a membership
+    columns: &'static [(&'static str, ColumnKind)],
+}
+
+/// The sessions, including the proposal and beamline of each
+const BEAMLINE_SESSIONS: Fixture = Fixture {
+    name: "beamline_sessions",
+    table: "BLSession",
+    columns: &[
+        ("sessionId", ColumnKind::UnsignedInteger),
+        ("proposalId", ColumnKind::UnsignedInteger),
+        ("visit_number", ColumnKind::UnsignedInteger),
+        ("beamLineName", ColumnKind::Text),
+    ],
+};
+
+/// The proposals of the sampled sessions
+const PROPOSALS: Fixture = Fixture {
+    name: "proposals",
+    table: "Proposal",
+    columns: &[
+        ("proposalId", ColumnKind::UnsignedInteger),
+        ("proposalNumber", ColumnKind::Text),
+        ("externalId", ColumnKind::Binary),
+        ("proposalCode", ColumnKind::Text),
+        ("state", ColumnKind::Text),
+    ],
+};
+
+/// The members of the sampled sessions
+const SESSION_MEMBERSHIP: Fixture = Fixture {
+    name: "session_membership",
+    table: "Session_has_Person",
+    columns: &[
+        ("sessionId", ColumnKind::UnsignedInteger),
+        ("personId", ColumnKind::UnsignedInteger),
+        ("role", ColumnKind::Text),
+        ("remote", ColumnKind::Boolean),
+    ],
+};
+
+/// The people who are members of the sampled sessions
+const PERSONS: Fixture = Fixture {
+    name: "persons",
+    table: "Person",
+    columns: &[
+        ("personId", ColumnKind::UnsignedInteger),
+        ("login", ColumnKind::Text),
+        ("laboratoryId", ColumnKind::UnsignedInteger),
+        ("title", ColumnKind::Text),
+        ("givenName", ColumnKind::Text),
+        ("familyName", ColumnKind::Text),
+        ("emailAddress", ColumnKind::Text),
+    ],
+};
+
+/// The memberships of the sampled proposals held by the sampled people
+const PROPOSAL_MEMBERSHIP: Fixture = Fixture {
+    name: "proposal_membership",
+    table: "ProposalHasPerson",
+    columns: &[
+        ("proposalId", ColumnKind::UnsignedInteger),
+        ("personId", ColumnKind::UnsignedInteger),
+    ],
+};
+
+/// The laboratories of the sampled people
+const LABORATORIES: Fixture = Fixture {
+    name: "laboratories",
+    table: "Laboratory",
+    columns: &[
+        ("laboratoryId", ColumnKind::UnsignedInteger),
+        ("name", ColumnKind::Text),
+    ],
+};
+
+/// The user groups of which the sampled people are members
+const USER_GROUP_MEMBERSHIP: Fixture = Fixture {
+    name: "user_group_membership",
+    table: "UserGroup_has_Person",
+    columns: &[
+        ("personId", ColumnKind::UnsignedInteger),
+        ("userGroupId", ColumnKind::UnsignedInteger),
+    ],
+};
+
+/// The user groups of the sampled people
+const USER_GROUPS: Fixture = Fixture {
+    name: "user_groups",
+    table: "UserGroup",
+    columns: &[
+        ("userGroupId", ColumnKind::UnsignedInteger),
+        ("name", ColumnKind::Text),
+    ],
+};
+
+/// The permissions granted by the sampled user groups
+const GROUP_PERMISSIONS: Fixture = Fixture {
+    name: "group_permissions",
+    table: "UserGroup_has_Permission",
+    columns: &[
+        ("userGroupId", ColumnKind::UnsignedInteger),
+        ("permissionId", ColumnKind::UnsignedInteger),
+    ],
+};
+
+/// The permissions granted to the sampled user groups
+const PERMISSIONS: Fixture = Fixture {
+    name: "permissions",
+    table: "Permission",
+    columns: &[
+        ("permissionId", ColumnKind::UnsignedInteger),
+        ("type", ColumnKind::Text),
+    ],
+};
+
+/// A value of a sampled column, as written into a script
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum SqlValue {
+    /// A null value
+    Null,
+    /// An unsigned integer
+    Integer(u64),
+    /// Text, written as a quoted string
+    Text(String),
+    /// Binary data, written as a hexadecimal literal
+    Binary(Vec<u8>),
+}
+
+impl SqlValue {
+    /// Decodes the value of the column at the index as the kind of column
+    fn decode(row: &MySqlRow, index: usize, kind: ColumnKind) -> Result<Self, sqlx::Error> {
+        Ok(match kind {
+            ColumnKind::UnsignedInteger => row
+                .try_get::<Option<u64>, _>(index)?
+                .map_or(Self::Null, Self::Integer),
+            ColumnKind::Text | ColumnKind::Timestamp => row
+                .try_get::<Option<String>, _>(index)?
+                .map_or(Self::Null, Self::Text),
+            ColumnKind::Binary => row
+                .try_get::<Option<Vec<u8>>, _>(index)?
+                .map_or(Self::Null, Self::Binary),
+            ColumnKind::Boolean => row
+                .try_get::<Option<bool>, _>(index)?
+                .map_or(Self::Null, |flag| Self::Integer(flag.into())),
+        })
+    }
+
+    /// Renders the value as an SQL literal
+    fn literal(&self) -> String {
+        match self {
+            Self::Null => "NULL".to_string(),
+            Self::Integer(integer) => integer.to_string(),
+            Self::Text(text) => format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\"")),
+            Self::Binary(bytes) => format!(
+                "X'{}'",
+                bytes
+                    .iter()
+                    .map(|byte| format!("{byte:02X}"))
+                    .collect::<String>()
+            ),
+        }
+    }
+}
+
+/// The rows sampled from a table, with the value of each populated column
+type Rows = Vec<Vec<SqlValue>>;
+
+/// Samples the most recent sessions from the first ISPyB instance, then writes a script per table containing them and the rows they reference
+pub async fn run(args: SnapshotFixturesArgs) -> bool {
+    let Some(database_url) = args.database.database_urls().first() else {
+        eprintln!("No database URLs were provided");
+        return false;
+    };
+    let ispyb_pool = match connect_limited(database_url, &args.database).await {
+        Ok(ispyb_pool) => ispyb_pool,
+        Err(err) => {
+            eprintln!("Could not connect to ISPyB: {err}");
+            return false;
+        }
+    };
+    let snapshot = snapshot(&ispyb_pool, args.limit, args.anonymize).await;
+    ispyb_pool.close().await;
+    let scripts = match snapshot {
+        Ok(scripts) => scripts,
+        Err(err) => {
+            eprintln!("Could not sample ISPyB: {err}");
+            return false;
+        }
+    };
+    if let Err(err) = std::fs::create_dir_all(&args.output) {
+        eprintln!("Could not create {}: {err}", args.output.display());
+        return false;
+    }
+    for (name, script) in scripts {
+        let path = args.output.join(name).with_extension("sql");
+        match script {
+            Some(script) => {
+                if let Err(err) = std::fs::write(&path, script) {
+                    eprintln!("Could not write {}: {err}", path.display());
+                    return false;
+                }
+                println!("Wrote {}", path.display());
+            }
+            None => println!("Skipped {}, as no rows were sampled", path.display()),
+        }
+    }
+    true
+}
+
+/// Samples the most recent sessions, then the rows they reference, producing the script of each fixture, or none if no rows were sampled
+async fn snapshot(
+    ispyb_pool: &MySqlPool,
+    limit: u32,
+    anonymize: bool,
+) -> Result<BTreeMap<&'static str, Option<String>>, sqlx::Error> {
+    let sessions = fetch_rows(
+        ispyb_pool,
+        &BEAMLINE_SESSIONS,
+        &format!("ORDER BY sessionId DESC LIMIT {limit}"),
+    )
+    .await?;
+    let session_ids = identifiers(&sessions, 0);
+    let proposal_ids = identifiers(&sessions, 1);
+    let proposals = fetch_rows(
+        ispyb_pool,
+        &PROPOSALS,
+        &format!("WHERE proposalId IN ({proposal_ids})"),
+    )
+    .await?;
+    let session_membership = fetch_rows(
+        ispyb_pool,
+        &SESSION_MEMBERSHIP,
+        &format!("WHERE sessionId IN ({session_ids})"),
+    )
+    .await?;
+    let person_ids = identifiers(&session_membership, 1);
+    let mut persons = fetch_rows(
+        ispyb_pool,
+        &PERSONS,
+        &format!("WHERE personId IN ({person_ids})"),
+    )
+    .await?;
+    let proposal_membership = fetch_rows(
+        ispyb_pool,
+        &PROPOSAL_MEMBERSHIP,
+        &format!("WHERE proposalId IN ({proposal_ids}) AND personId IN ({person_ids})"),
+    )
+    .await?;
+    let laboratory_ids = identifiers(&persons, 2);
+    let mut laboratories = fetch_rows(
+        ispyb_pool,
+        &LABORATORIES,
+        &format!("WHERE laboratoryId IN ({laboratory_ids})"),
+    )
+    .await?;
+    let user_group_membership = fetch_rows(
+        ispyb_pool,
+        &USER_GROUP_MEMBERSHIP,
+        &format!("WHERE personId IN ({person_ids})"),
+    )
+    .await?;
+    let user_group_ids = identifiers(&user_group_membership, 1);
+    let user_groups = fetch_rows(
+        ispyb_pool,
+        &USER_GROUPS,
+        &format!("WHERE userGroupId IN ({user_group_ids})"),
+    )
+    .await?;
+    let group_permissions = fetch_rows(
+        ispyb_pool,
+        &GROUP_PERMISSIONS,
+        &format!("WHERE userGroupId IN ({user_group_ids})"),
+    )
+    .await?;
+    let permission_ids = identifiers(&group_permissions, 1);
+    let permissions = fetch_rows(
+        ispyb_pool,
+        &PERMISSIONS,
+        &format!("WHERE permissionId IN ({permission_ids})"),
+    )
+    .await?;
+    if anonymize {
+        anonymize_rows(&PERSONS, &mut persons);
+        anonymize_rows(&LABORATORIES, &mut laboratories);
+    }
+    Ok([
+        (&BEAMLINE_SESSIONS, sessions),
+        (&PROPOSALS, proposals),
+        (&SESSION_MEMBERSHIP, session_membership),
+        (&PERSONS, persons),
+        (&PROPOSAL_MEMBERSHIP, proposal_membership),
+        (&LABORATORIES, laboratories),
+        (&USER_GROUP_MEMBERSHIP, user_group_membership),
+        (&USER_GROUPS, user_groups),
+        (&GROUP_PERMISSIONS, group_permissions),
+        (&PERMISSIONS, permissions),
+    ]
+    .into_iter()
+    .map(|(fixture, rows)| (fixture.name, script(fixture, &rows)))
+    .collect())
+}
+
+/// Fetches the populated columns of the rows of the fixture's table selected by the clause
+async fn fetch_rows(
+    ispyb_pool: &MySqlPool,
+    fixture: &Fixture,
+    clause: &str,
+) -> Result<Rows, sqlx::Error> {
+    let columns = fixture
+        .columns
+        .iter()
+        .map(|(column, _)| format!("`{column}`"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    query(&format!(
+        "SELECT {columns} FROM `{}` {clause}",
+        fixture.table
+    ))
+    .fetch_all(ispyb_pool)
+    .await?
+    .iter()
+    .map(|row| {
+        fixture
+            .columns
+            .iter()
+            .enumerate()
+            .map(|(index, (_, kind))| SqlValue::decode(row, index, *kind))
+            .collect()
+    })
+    .collect()
+}
+
+/// The distinct, non-null, integers in the column at the index, as a list for an 'IN' clause which matches nothing if empty
+fn identifiers(rows: &[Vec<SqlValue>], index: usize) -> String {
+    let identifiers = rows
+        .iter()
+        .filter_map(|row| match row.get(index) {
+            Some(SqlValue::Integer(identifier)) => Some(identifier.to_string()),
+            _ => None,
+        })
+        .collect::<BTreeSet<_>>();
+    if identifiers.is_empty() {
+        "NULL".to_string()
+    } else {
+        identifiers.into_iter().collect::<Vec<_>>().join(", ")
+    }
+}
+
+/// Replaces the personal data in the rows of the fixture with placeholders derived from the identifier of each row, leaving null values null
+fn anonymize_rows(fixture: &Fixture, rows: &mut [Vec<SqlValue>]) {
+    for row in rows {
+        let identifier = row[0].literal();
+        for ((column, _), value) in fixture.columns.iter().zip(row.iter_mut()) {
+            if *value == SqlValue::Null {
+                continue;
+            }
+            let placeholder = match (fixture.table, *column) {
+                ("Person", "login") => format!("user{identifier}"),
+                ("Person", "givenName") => "Given".to_string(),
+                ("Person", "familyName") => format!("Family{identifier}"),
+                ("Person", "emailAddress") => format!("user{identifier}@example.com"),
+                ("Laboratory", "name") => format!("Laboratory {identifier}"),
+                _ => continue,
+            };
+            *value = SqlValue::Text(placeholder);
+        }
+    }
+}
+
+/// Renders the script inserting the rows into the fixture's table, or none if there are no rows
+fn script(fixture: &Fixture, rows: &[Vec<SqlValue>]) -> Option<String> {
+    if rows.is_empty() {
+        return None;
+    }
+    let columns = fixture
+        .columns
+        .iter()
+        .map(|(column, _)| format!("`{column}`"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let values = rows
+        .iter()
+        .map(|row| {
+            format!(
+                "({})",
+                row.iter()
+                    .map(SqlValue::literal)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    Some(format!(
+        "INSERT INTO\n    `{}` ({columns})\nVALUES {values};\n",
+        fixture.table
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{anonymize_rows, script, snapshot, SqlValue, PERSONS, USER_GROUPS};
+    use sqlx::MySqlPool;
+
+    #[test]
+    fn script_matches_fixtures() {
+        assert_eq!(
+            Some(
+                "INSERT INTO\n    `UserGroup` (`userGroupId`, `name`)\nVALUES (50, \"data\"), (51, \"say \\\"proc\\\"\");\n"
+                    .to_string()
+            ),
+            script(
+                &USER_GROUPS,
+                &[
+                    vec![SqlValue::Integer(50), SqlValue::Text("data".to_string())],
+                    vec![
+                        SqlValue::Integer(51),
+                        SqlValue::Text("say \"proc\"".to_string())
+                    ],
+                ]
+            )
+        );
+        assert_eq!(None, script(&USER_GROUPS, &[]));
+    }
+
+    #[test]
+    fn personal_data_anonymized() {
+        let mut rows = vec![vec![
+            SqlValue::Integer(20),
+            SqlValue::Text("foo".to_string()),
+            SqlValue::Integer(70),
+            SqlValue::Text("Dr".to_string()),
+            SqlValue::Text("Foo".to_string()),
+            SqlValue::Text("Fighter".to_string()),
+            SqlValue::Null,
+        ]];
+        anonymize_rows(&PERSONS, &mut rows);
+        assert_eq!(
+            vec![vec![
+                SqlValue::Integer(20),
+                SqlValue::Text("user20".to_string()),
+                SqlValue::Integer(70),
+                SqlValue::Text("Dr".to_string()),
+                SqlValue::Text("Given".to_string()),
+                SqlValue::Text("Family20".to_string()),
+                SqlValue::Null,
+            ]],
+            rows
+        );
+    }
+
+    #[sqlx::test(
+        migrations = "tests/migrations",
+        fixtures(
+            path = "../tests/fixtures",
+            scripts(
+                "beamline_sessions",
+                "proposals",
+                "session_membership",
+                "persons",
+                "laboratories"
+            )
+        )
+    )]
+    async fn recent_sessions_sampled(ispyb_pool: MySqlPool) {
+        let scripts = snapshot(&ispyb_pool, 2, true).await.unwrap();
+        assert_eq!(
+            Some(
+                "INSERT INTO\n    `BLSession` (`sessionId`, `proposalId`, `visit_number`, `beamLineName`)\nVALUES (44, 31, 11, \"i22\"), (43, 31, 10, \"p99\");\n"
+                    .to_string()
+            ),
+            scripts["beamline_sessions"]
+        );
+        assert_eq!(
+            Some(
+                "INSERT INTO\n    `Session_has_Person` (`sessionId`, `personId`, `role`, `remote`)\nVALUES (43, 21, NULL, 0);\n"
+                    .to_string()
+            ),
+            scripts["session_membership"]
+        );
+        assert_eq!(
+            Some(
+                "INSERT INTO\n    `Person` (`personId`, `login`, `laboratoryId`, `title`, `givenName`, `familyName`, `emailAddress`)\nVALUES (21, \"user21\", NULL, NULL, \"Given\", NULL, NULL);\n"
+                    .to_string()
+            ),
+            scripts["persons"]
+        );
+        assert_eq!(None, scripts["laboratories"]);
+    }
+}
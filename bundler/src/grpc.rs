@@ -0,0 +1,142 @@
+// tonic::Status is dictated by the generated service traits
+#![allow(clippy::result_large_err)]
+use crate::{
+    require_bearer::{Authentication, RequireBearerLayer},
+    scoped::{self, Scope},
+    scoped_bundle_file, CurrentBundle,
+};
+use axum::{
+    body::Bytes,
+    http::{header::AUTHORIZATION, HeaderMap, HeaderValue},
+    Extension,
+};
+use clap::Args;
+use proto::{
+    bundle_service_server::{BundleService, BundleServiceServer},
+    GetBundleRequest, GetBundleResponse, Revision, WatchRevisionsRequest,
+};
+use std::{
+    net::{Ipv4Addr, SocketAddr, SocketAddrV4},
+    pin::Pin,
+};
+use tokio_stream::{wrappers::WatchStream, Stream, StreamExt};
+use tonic::{transport::Server, Request, Response, Status};
+
+/// Types and services generated from the protocol buffer definitions
+#[allow(missing_docs, clippy::missing_docs_in_private_items)]
+mod proto {
+    tonic::include_proto!("bundler.v1");
+}
+
+/// Options for serving bundles via gRPC
+#[derive(Debug, Clone, Args)]
+pub struct GrpcArgs {
+    /// The port to which the gRPC server should bind, if it is to be served
+    #[arg(long, env = "BUNDLER_GRPC_PORT")]
+    grpc_port: Option<u16>,
+}
+
+/// A gRPC [`BundleService`] serving the current bundle
+struct GrpcBundleService {
+    /// The bundle currently being served
+    current_bundle: CurrentBundle,
+    /// The credentials accepted from clients, as on the HTTP API
+    bearer_layer: RequireBearerLayer,
+}
+
+impl GrpcBundleService {
+    /// Checks the credentials in the metadata of the request, returning the [`Scope`] of the token if it has one
+    async fn authenticate<T>(&self, request: &Request<T>) -> Result<Option<Scope>, Status> {
+        // tonic uses an older version of the http crate than axum, so the header is copied across
+        let mut headers = HeaderMap::new();
+        if let Some(authorization) = request
+            .metadata()
+            .get(AUTHORIZATION.as_str())
+            .and_then(|authorization| HeaderValue::from_bytes(authorization.as_bytes()).ok())
+        {
+            headers.insert(AUTHORIZATION, authorization);
+        }
+        match self.bearer_layer.authenticate(&headers).await {
+            Authentication::Authenticated(scope) => Ok(scope),
+            Authentication::Unauthenticated => {
+                Err(Status::unauthenticated("A valid bearer token is required"))
+            }
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl BundleService for GrpcBundleService {
+    async fn get_bundle(
+        &self,
+        request: Request<GetBundleRequest>,
+    ) -> Result<Response<GetBundleResponse>, Status> {
+        let scope = self.authenticate(&request).await?;
+        let bundle_file = self.current_bundle.as_ref().read().await.clone();
+        if bundle_file.is_placeholder() {
+            return Err(Status::unavailable("No bundle has been fetched yet"));
+        }
+        let bundle_file =
+            scoped_bundle_file(&self.current_bundle, &bundle_file, scope.map(Extension))
+                .await
+                .map_err(|err| {
+                    tracing::error!("Could not build scoped bundle: {err}");
+                    Status::internal("Could not build scoped bundle")
+                })?;
+        let not_modified =
+            request.get_ref().if_none_match.as_deref() == Some(bundle_file.revision.as_str());
+        Ok(Response::new(GetBundleResponse {
+            revision: bundle_file.revision.clone(),
+            not_modified,
+            bundle: if not_modified {
+                Bytes::new()
+            } else {
+                bundle_file.file.clone()
+            },
+        }))
+    }
+
+    type WatchRevisionsStream = Pin<Box<dyn Stream<Item = Result<Revision, Status>> + Send>>;
+
+    async fn watch_revisions(
+        &self,
+        request: Request<WatchRevisionsRequest>,
+    ) -> Result<Response<Self::WatchRevisionsStream>, Status> {
+        let scope = self
+            .authenticate(&request)
+            .await?
+            .filter(|scope| scope.beamlines().is_some());
+        let revisions =
+            WatchStream::new(self.current_bundle.revisions.subscribe()).map(move |revision| {
+                Ok(Revision {
+                    revision: match &scope {
+                        Some(scope) => scoped::variant_revision(&revision, scope),
+                        None => revision,
+                    },
+                })
+            });
+        Ok(Response::new(Box::pin(revisions)))
+    }
+}
+
+/// Serves the [`BundleService`] if a port is configured, refusing any requests which do not carry credentials accepted by the bearer layer and restricting scoped tokens to their variant of the bundle
+pub async fn serve(
+    args: GrpcArgs,
+    current_bundle: CurrentBundle,
+    bearer_layer: RequireBearerLayer,
+) {
+    let Some(port) = args.grpc_port else {
+        return std::future::pending().await;
+    };
+    let service = BundleServiceServer::new(GrpcBundleService {
+        current_bundle,
+        bearer_layer,
+    });
+    let socket_addr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, port));
+    tracing::info!("Serving gRPC API on {}", socket_addr);
+    Server::builder()
+        .add_service(service)
+        .serve(socket_addr)
+        .await
+        .unwrap()
+}
@@ -0,0 +1,379 @@
+use crate::{
+    pause::PollingPause,
+    supervisor::{TaskHealth, TaskState},
+};
+use axum::{extract::State, http::StatusCode, response::IntoResponse, routing::get, Json, Router};
+use clap::Args;
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::time::Instant;
+use utoipa::{OpenApi, ToSchema};
+
+/// Options for reporting the service as unready, or unhealthy, once the bundle can no longer be refreshed
+#[derive(Debug, Clone, Args)]
+pub struct HealthArgs {
+    /// The number of consecutive failed polls of ISPyB after which the service reports itself as not ready. If any threshold is set, failed polls are retried rather than restarting the update task
+    #[arg(long, env = "BUNDLER_UNREADY_AFTER_FAILED_POLLS")]
+    unready_after_failed_polls: Option<u32>,
+    /// The time since the bundle was last refreshed after which the service reports itself as not ready
+    #[arg(long, env = "BUNDLER_UNREADY_AFTER_BUNDLE_AGE")]
+    unready_after_bundle_age: Option<humantime::Duration>,
+    /// If enabled, liveness fails alongside readiness, such that the orchestrator restarts the service
+    #[arg(long, env = "BUNDLER_UNHEALTHY_WHEN_UNREADY")]
+    unhealthy_when_unready: bool,
+    /// The number of times a background task may be restarted without running stably in between before liveness fails, such that the orchestrator restarts the service
+    #[arg(long, env = "BUNDLER_UNHEALTHY_AFTER_TASK_RESTARTS")]
+    unhealthy_after_task_restarts: Option<u32>,
+}
+
+/// The outcomes of recent attempts to refresh the bundle
+struct FetchRecord {
+    /// The number of consecutive failed polls
+    consecutive_failures: u32,
+    /// Whether no bundle has been fetched since the service started without one
+    awaiting_first_fetch: bool,
+    /// The time at which the bundle was last refreshed, or the service started
+    last_refreshed: Instant,
+    /// The reason the latest update was refused, in favour of serving the previous bundle, if it was
+    degraded: Option<String>,
+}
+
+/// A thread safe record of bundle refreshes, from which readiness is determined
+#[derive(Clone)]
+pub struct FetchHealth {
+    /// The outcomes of recent attempts to refresh the bundle
+    record: Arc<Mutex<FetchRecord>>,
+    /// The number of consecutive failed polls after which the service is not ready
+    max_failed_polls: Option<u32>,
+    /// The time since the last refresh after which the service is not ready
+    max_bundle_age: Option<Duration>,
+    /// Whether liveness fails alongside readiness
+    unhealthy_when_unready: bool,
+    /// The number of consecutive restarts of a background task after which liveness fails
+    max_task_restarts: Option<u32>,
+    /// The switch pausing polling, whilst which the readiness thresholds are not applied
+    polling_pause: PollingPause,
+}
+
+impl FetchHealth {
+    /// Creates a [`FetchHealth`], treating the bundle as refreshed now unless the service started without one
+    pub fn new(args: HealthArgs, awaiting_first_fetch: bool) -> Self {
+        Self {
+            record: Arc::new(Mutex::new(FetchRecord {
+                consecutive_failures: 0,
+                awaiting_first_fetch,
+                last_refreshed: Instant::now(),
+                degraded: None,
+            })),
+            max_failed_polls: args.unready_after_failed_polls,
+            max_bundle_age: args.unready_after_bundle_age.map(Into::into),
+            unhealthy_when_unready: args.unhealthy_when_unready,
+            max_task_restarts: args.unhealthy_after_task_restarts,
+            polling_pause: PollingPause::default(),
+        }
+    }
+
+    /// Suspends the readiness thresholds whilst polling is paused by the [`PollingPause`]
+    pub fn with_polling_pause(self, polling_pause: PollingPause) -> Self {
+        Self {
+            polling_pause,
+            ..self
+        }
+    }
+
+    /// Whether failed polls should be tolerated, as readiness thresholds are configured to report them
+    pub fn tolerates_failures(&self) -> bool {
+        self.max_failed_polls.is_some() || self.max_bundle_age.is_some()
+    }
+
+    /// Records a successful refresh of the bundle
+    pub fn record_success(&self) {
+        let mut record = self.record.lock().unwrap();
+        record.consecutive_failures = 0;
+        record.awaiting_first_fetch = false;
+        record.last_refreshed = Instant::now();
+    }
+
+    /// Records a failed poll of ISPyB
+    pub fn record_failure(&self) {
+        self.record.lock().unwrap().consecutive_failures += 1;
+    }
+
+    /// Records whether the service is degraded, serving a previous bundle in place of a refused update, along with the reason
+    pub fn record_degraded(&self, reason: Option<String>) {
+        let mut record = self.record.lock().unwrap();
+        match (record.degraded.is_some(), reason.is_some()) {
+            (false, true) => tracing::info!(counter.bundle_degraded = 1, "Service is degraded"),
+            (true, false) => {
+                tracing::info!(
+                    counter.bundle_degraded = -1,
+                    "Service is no longer degraded"
+                )
+            }
+            _ => {}
+        }
+        record.degraded = reason;
+    }
+
+    /// The reason the service is degraded, if it is
+    fn degradation(&self) -> Option<String> {
+        self.record.lock().unwrap().degraded.clone()
+    }
+
+    /// Determines whether the service is ready at the given time, otherwise returning the reason it is not
+    ///
+    /// Whilst polling is paused, the service is ready as long as a bundle has been fetched
+    fn readiness(&self, now: Instant) -> Result<(), String> {
+        let record = self.record.lock().unwrap();
+        if record.awaiting_first_fetch {
+            return Err("No bundle has been fetched".to_string());
+        }
+        if self.polling_pause.is_paused() {
+            return Ok(());
+        }
+        if let Some(max_failed_polls) = self.max_failed_polls {
+            if record.consecutive_failures >= max_failed_polls {
+                return Err(format!(
+                    "{} consecutive polls of ISPyB have failed",
+                    record.consecutive_failures
+                ));
+            }
+        }
+        if let Some(max_bundle_age) = self.max_bundle_age {
+            let bundle_age = now.duration_since(record.last_refreshed);
+            if bundle_age >= max_bundle_age {
+                return Err(format!(
+                    "Bundle was last refreshed {} ago",
+                    humantime::format_duration(Duration::from_secs(bundle_age.as_secs()))
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Determines whether the service is live at the given time, otherwise returning the reason it is not
+    fn liveness(&self, task_health: &TaskHealth, now: Instant) -> Result<(), String> {
+        if let Some(max_task_restarts) = self.max_task_restarts {
+            let consecutive_restarts = task_health.max_consecutive_restarts();
+            if consecutive_restarts >= max_task_restarts {
+                return Err(format!(
+                    "A background task has restarted {consecutive_restarts} times without running stably"
+                ));
+            }
+        }
+        match self.readiness(now) {
+            Err(reason) if self.unhealthy_when_unready => Err(reason),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The liveness of the service and the state of each supervised background task
+#[derive(Debug, Serialize, ToSchema)]
+struct HealthReport {
+    /// The reason the service is not live, if it is not
+    reason: Option<String>,
+    /// The state of each supervised background task, keyed by task name
+    tasks: BTreeMap<String, TaskState>,
+}
+
+/// The state from which liveness and readiness are determined
+#[derive(Clone)]
+struct HealthState {
+    /// The record of bundle refreshes
+    fetch_health: FetchHealth,
+    /// The record of supervised background tasks
+    task_health: TaskHealth,
+}
+
+/// The paths served by the health endpoints
+#[derive(OpenApi)]
+#[openapi(
+    paths(health_endpoint, ready_endpoint),
+    components(schemas(HealthReport, TaskState))
+)]
+pub struct HealthApi;
+
+/// Creates a [`Router`] serving the liveness and readiness endpoints
+pub fn router(fetch_health: FetchHealth, task_health: TaskHealth) -> Router {
+    Router::new()
+        .route("/healthz", get(health_endpoint))
+        .route("/readyz", get(ready_endpoint))
+        .with_state(HealthState {
+            fetch_health,
+            task_health,
+        })
+}
+
+/// Returns the state of each supervised background task, with an HTTP 200 response whilst the service is live
+///
+/// Failures of background tasks result in their restart, and failure to serve the API in service crash, so ability to serve this endpoint implies liveness, unless a task is repeatedly restarted or configured to fail alongside readiness
+#[utoipa::path(
+    get,
+    path = "/healthz",
+    tag = "meta",
+    security(()),
+    responses(
+        (status = OK, description = "The service is live", body = HealthReport),
+        (status = SERVICE_UNAVAILABLE, description = "A background task is repeatedly restarting, or the service is not ready and configured to fail liveness alongside readiness", body = HealthReport),
+    ),
+)]
+async fn health_endpoint(State(health): State<HealthState>) -> impl IntoResponse {
+    let liveness = health
+        .fetch_health
+        .liveness(&health.task_health, Instant::now());
+    let report = HealthReport {
+        reason: liveness.as_ref().err().cloned(),
+        tasks: health
+            .task_health
+            .report()
+            .into_iter()
+            .map(|(task, state)| (task.to_string(), state))
+            .collect(),
+    };
+    let status = match liveness {
+        Ok(()) => StatusCode::OK,
+        Err(_) => StatusCode::SERVICE_UNAVAILABLE,
+    };
+    (status, Json(report))
+}
+
+/// Returns an HTTP 200 response if the bundle has been refreshed within the configured thresholds
+///
+/// A degraded service, serving a previous bundle in place of a refused update, remains ready, as does one whose polling of ISPyB is paused
+#[utoipa::path(
+    get,
+    path = "/readyz",
+    tag = "meta",
+    security(()),
+    responses(
+        (status = OK, description = "The service is ready, with the time since which polling has been paused, or the reason it is degraded, if either"),
+        (status = SERVICE_UNAVAILABLE, description = "No bundle has been fetched, or it has not been refreshed within the configured thresholds"),
+    ),
+)]
+async fn ready_endpoint(State(health): State<HealthState>) -> impl IntoResponse {
+    let fetch_health = health.fetch_health;
+    match fetch_health.readiness(Instant::now()) {
+        Ok(()) => (
+            StatusCode::OK,
+            fetch_health
+                .polling_pause
+                .paused_since()
+                .map(|paused_since| {
+                    format!(
+                        "Paused: polling of ISPyB paused since {}",
+                        humantime::format_rfc3339_seconds(paused_since)
+                    )
+                })
+                .or_else(|| {
+                    fetch_health
+                        .degradation()
+                        .map(|reason| format!("Degraded: {reason}"))
+                })
+                .unwrap_or_default(),
+        ),
+        Err(reason) => (StatusCode::SERVICE_UNAVAILABLE, reason),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FetchHealth, HealthArgs};
+    use crate::pause::PollingPause;
+    use std::time::Duration;
+    use tokio::time::Instant;
+
+    fn fetch_health(
+        unready_after_failed_polls: Option<u32>,
+        unready_after_bundle_age: Option<Duration>,
+    ) -> FetchHealth {
+        FetchHealth::new(
+            HealthArgs {
+                unready_after_failed_polls,
+                unready_after_bundle_age: unready_after_bundle_age.map(Into::into),
+                unhealthy_when_unready: false,
+                unhealthy_after_task_restarts: None,
+            },
+            false,
+        )
+    }
+
+    #[test]
+    fn unready_after_failed_polls() {
+        let fetch_health = fetch_health(Some(2), None);
+        assert!(fetch_health.tolerates_failures());
+        fetch_health.record_failure();
+        assert!(fetch_health.readiness(Instant::now()).is_ok());
+        fetch_health.record_failure();
+        assert!(fetch_health.readiness(Instant::now()).is_err());
+        fetch_health.record_success();
+        assert!(fetch_health.readiness(Instant::now()).is_ok());
+    }
+
+    #[test]
+    fn unready_after_bundle_age() {
+        let fetch_health = fetch_health(None, Some(Duration::from_secs(60)));
+        let now = Instant::now();
+        assert!(fetch_health.readiness(now).is_ok());
+        assert!(fetch_health
+            .readiness(now + Duration::from_secs(61))
+            .is_err());
+    }
+
+    #[test]
+    fn unready_until_first_fetch() {
+        let fetch_health = FetchHealth::new(
+            HealthArgs {
+                unready_after_failed_polls: None,
+                unready_after_bundle_age: None,
+                unhealthy_when_unready: false,
+                unhealthy_after_task_restarts: None,
+            },
+            true,
+        );
+        assert!(fetch_health.readiness(Instant::now()).is_err());
+        fetch_health.record_success();
+        assert!(fetch_health.readiness(Instant::now()).is_ok());
+    }
+
+    #[test]
+    fn ready_whilst_degraded() {
+        let fetch_health = fetch_health(None, None);
+        fetch_health.record_degraded(Some("sessions shrank".to_string()));
+        assert!(fetch_health.readiness(Instant::now()).is_ok());
+        assert_eq!(
+            Some("sessions shrank".to_string()),
+            fetch_health.degradation()
+        );
+        fetch_health.record_degraded(None);
+        assert_eq!(None, fetch_health.degradation());
+    }
+
+    #[test]
+    fn ready_whilst_paused() {
+        let polling_pause = PollingPause::default();
+        let fetch_health = fetch_health(Some(1), None).with_polling_pause(polling_pause.clone());
+        fetch_health.record_failure();
+        assert!(fetch_health.readiness(Instant::now()).is_err());
+        polling_pause.pause();
+        assert!(fetch_health.readiness(Instant::now()).is_ok());
+        polling_pause.resume();
+        assert!(fetch_health.readiness(Instant::now()).is_err());
+    }
+
+    #[test]
+    fn ready_without_thresholds() {
+        let fetch_health = fetch_health(None, None);
+        assert!(!fetch_health.tolerates_failures());
+        for _ in 0..10 {
+            fetch_health.record_failure();
+        }
+        assert!(fetch_health
+            .readiness(Instant::now() + Duration::from_secs(3600))
+            .is_ok());
+    }
+}
@@ -0,0 +1,147 @@
+use crate::tls::Tls;
+use axum::{
+    body::{Body, Bytes},
+    extract::Request,
+    http::{HeaderValue, Response},
+    Router,
+};
+use bytes::Buf;
+use clap::Args;
+use h3::server::RequestStream;
+use http_body_util::{BodyExt, Limited};
+use std::{
+    net::{Ipv4Addr, SocketAddr, SocketAddrV4},
+    sync::Arc,
+};
+use tower::ServiceExt;
+
+/// The application protocol negotiated by HTTP/3 clients
+const ALPN: &[u8] = b"h3";
+
+/// The time, in seconds, for which clients may remember that HTTP/3 is available
+const ALT_SVC_MAX_AGE: u64 = 86400;
+
+/// Options for serving the HTTP API over HTTP/3
+#[derive(Debug, Clone, Args)]
+pub struct Http3Args {
+    /// The UDP port to which an HTTP/3 (QUIC) listener should bind, if it is to be served. It serves the same endpoints and certificate as the TCP listener, and is advertised to its clients via the 'Alt-Svc' header
+    #[arg(long, env = "BUNDLER_HTTP3_PORT", requires = "tls_certificate")]
+    http3_port: Option<u16>,
+}
+
+impl Http3Args {
+    /// The 'Alt-Svc' header advertising the HTTP/3 listener to clients of the TCP listener, if one is configured
+    pub fn alt_svc(&self) -> Option<HeaderValue> {
+        let port = self.http3_port?;
+        Some(HeaderValue::from_str(&format!("h3=\":{port}\"; ma={ALT_SVC_MAX_AGE}")).unwrap())
+    }
+}
+
+/// Serves the application endpoints over HTTP/3 if a port is configured, accepting each connection and request concurrently
+///
+/// Request bodies are streamed into the router, failing once they exceed the size limit in bytes
+pub async fn serve(args: Http3Args, app: Router, tls: Option<Tls>, body_limit: usize) {
+    let (Some(port), Some(tls)) = (args.http3_port, tls) else {
+        return std::future::pending().await;
+    };
+    let server_config = tls
+        .server_config(&[&rustls::version::TLS13], &[ALPN])
+        .unwrap();
+    let socket_addr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, port));
+    let endpoint = quinn::Endpoint::server(
+        quinn::ServerConfig::with_crypto(Arc::new(server_config)),
+        socket_addr,
+    )
+    .unwrap();
+    tracing::info!("Serving HTTP/3 API on {}", socket_addr);
+    while let Some(connecting) = endpoint.accept().await {
+        let app = app.clone();
+        tokio::spawn(async move {
+            if let Err(err) = serve_connection(connecting, app, body_limit).await {
+                tracing::debug!("HTTP/3 connection closed with error: {err}");
+            }
+        });
+    }
+}
+
+/// Serves the requests of a single QUIC connection until it is closed
+async fn serve_connection(
+    connecting: quinn::Connecting,
+    app: Router,
+    body_limit: usize,
+) -> Result<(), anyhow::Error> {
+    let mut connection =
+        h3::server::Connection::<_, Bytes>::new(h3_quinn::Connection::new(connecting.await?))
+            .await?;
+    while let Some((request, stream)) = connection.accept().await? {
+        let app = app.clone();
+        tokio::spawn(async move {
+            if let Err(err) = serve_request(request, stream, app, body_limit).await {
+                tracing::debug!("HTTP/3 request failed: {err}");
+            }
+        });
+    }
+    Ok(())
+}
+
+/// Serves a single request via the router, streaming both the request body, limited in size, and the response body
+async fn serve_request(
+    request: Request<()>,
+    stream: RequestStream<h3_quinn::BidiStream<Bytes>, Bytes>,
+    app: Router,
+    body_limit: usize,
+) -> Result<(), anyhow::Error> {
+    let (mut stream, request_stream) = stream.split();
+    let request_body = futures_util::stream::unfold(Some(request_stream), |request_stream| async {
+        let mut request_stream = request_stream?;
+        match request_stream.recv_data().await {
+            Ok(Some(mut chunk)) => Some((
+                Ok(chunk.copy_to_bytes(chunk.remaining())),
+                Some(request_stream),
+            )),
+            Ok(None) => None,
+            Err(err) => Some((Err(err), None)),
+        }
+    });
+    let (parts, ()) = request.into_parts();
+    let response = app
+        .oneshot(Request::from_parts(
+            parts,
+            Body::new(Limited::new(Body::from_stream(request_body), body_limit)),
+        ))
+        .await?;
+    let (parts, mut response_body) = response.into_parts();
+    stream
+        .send_response(Response::from_parts(parts, ()))
+        .await?;
+    while let Some(frame) = response_body.frame().await {
+        match frame?.into_data() {
+            Ok(data) => stream.send_data(data).await?,
+            Err(frame) => {
+                if let Ok(trailers) = frame.into_trailers() {
+                    stream.send_trailers(trailers).await?;
+                }
+            }
+        }
+    }
+    stream.finish().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Http3Args;
+
+    #[test]
+    fn advertised_when_configured() {
+        assert_eq!(
+            "h3=\":8443\"; ma=86400",
+            Http3Args {
+                http3_port: Some(8443)
+            }
+            .alt_svc()
+            .unwrap()
+        );
+        assert!(Http3Args { http3_port: None }.alt_svc().is_none());
+    }
+}
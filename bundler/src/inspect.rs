@@ -0,0 +1,169 @@
+use crate::bundle::{gunzip, DATASETS, SCHEMA_PREFIX};
+use clap::Parser;
+use serde::Serialize;
+use serde_json::Value;
+use std::{collections::BTreeMap, io::Read, path::PathBuf};
+
+/// Arguments to summarise a bundle archive
+#[derive(Debug, Parser)]
+pub struct InspectArgs {
+    /// The path of the bundle, either gzipped or as an uncompressed tar archive
+    bundle: PathBuf,
+    /// Print the summary as JSON, rather than as text
+    #[arg(long)]
+    json: bool,
+}
+
+/// A summary of the contents of a bundle archive
+#[derive(Debug, Serialize)]
+struct BundleSummary {
+    /// The revision recorded in the manifest, if any
+    revision: Option<String>,
+    /// The manifest of the bundle, if it has one
+    manifest: Option<Value>,
+    /// Each entry in the archive, in the order written
+    files: Vec<FileSummary>,
+    /// The number of rows in each dataset in the bundle, keyed by dataset name
+    datasets: BTreeMap<String, usize>,
+}
+
+/// An entry in a bundle archive
+#[derive(Debug, Serialize)]
+struct FileSummary {
+    /// The path of the entry
+    path: String,
+    /// The size of the entry, in bytes
+    size: u64,
+}
+
+/// Summarises the bundle, printing the summary as text or JSON and returning whether the bundle could be read
+pub fn run(args: InspectArgs) -> bool {
+    let summary = match std::fs::read(&args.bundle)
+        .map_err(anyhow::Error::from)
+        .and_then(|archive| summarize(&archive))
+    {
+        Ok(summary) => summary,
+        Err(err) => {
+            eprintln!("Could not inspect {}: {err}", args.bundle.display());
+            return false;
+        }
+    };
+    if args.json {
+        match serde_json::to_string_pretty(&summary) {
+            Ok(summary) => println!("{summary}"),
+            Err(err) => {
+                eprintln!("Could not serialize summary: {err}");
+                return false;
+            }
+        }
+    } else {
+        print_summary(&summary);
+    }
+    true
+}
+
+/// Reads the manifest, entries and dataset row counts from a bundle, decompressing it first if gzipped
+fn summarize(archive: &[u8]) -> Result<BundleSummary, anyhow::Error> {
+    let tar = if archive.starts_with(&[0x1f, 0x8b]) {
+        gunzip(archive)?
+    } else {
+        archive.to_vec()
+    };
+    let mut summary = BundleSummary {
+        revision: None,
+        manifest: None,
+        files: Vec::new(),
+        datasets: BTreeMap::new(),
+    };
+    let mut archive = tar::Archive::new(tar.as_slice());
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.to_string_lossy().into_owned();
+        summary.files.push(FileSummary {
+            path: path.clone(),
+            size: entry.size(),
+        });
+        let dataset = DATASETS.into_iter().find(|dataset| {
+            !path.starts_with(SCHEMA_PREFIX) && path.ends_with(&format!("/{dataset}/data.json"))
+        });
+        if path != ".manifest" && dataset.is_none() {
+            continue;
+        }
+        let mut contents = Vec::new();
+        entry.read_to_end(&mut contents)?;
+        let value = serde_json::from_slice::<Value>(&contents)?;
+        match dataset {
+            Some(dataset) => {
+                let rows = match &value {
+                    Value::Object(entries) => entries.len(),
+                    Value::Array(records) => records.len(),
+                    _ => 0,
+                };
+                summary.datasets.insert(dataset.to_string(), rows);
+            }
+            None => {
+                summary.revision = value["revision"].as_str().map(ToString::to_string);
+                summary.manifest = Some(value);
+            }
+        }
+    }
+    Ok(summary)
+}
+
+/// Prints the summary as text, for reading by operators
+fn print_summary(summary: &BundleSummary) {
+    println!(
+        "Revision: {}",
+        summary.revision.as_deref().unwrap_or("<none>")
+    );
+    match &summary.manifest {
+        Some(manifest) => println!(
+            "Manifest:\n{}",
+            serde_json::to_string_pretty(manifest).unwrap_or_default()
+        ),
+        None => println!("Manifest: <none>"),
+    }
+    println!("Files:");
+    for file in &summary.files {
+        println!("  {:>12}  {}", file.size, file.path);
+    }
+    println!("Datasets:");
+    for (dataset, rows) in &summary.datasets {
+        println!("  {dataset}: {rows} rows");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::summarize;
+    use crate::bundle::{gzip, Bundle, NoMetadata};
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn summarize_bundle() {
+        let bundle = Bundle::<NoMetadata>::default();
+        let tar = bundle.to_tar().unwrap();
+        for archive in [gzip(&tar).unwrap(), tar] {
+            let summary = summarize(&archive).unwrap();
+            assert_eq!(Some(bundle.revision()), summary.revision.as_deref());
+            assert_eq!(".manifest", summary.files[0].path);
+            assert!(summary
+                .files
+                .iter()
+                .any(|file| file.path == "diamond/schemas/sessions/data.json"));
+            assert_eq!(
+                BTreeMap::from([
+                    ("beamlines".to_string(), 0),
+                    ("instrument_scientists".to_string(), 0),
+                    ("lab_contacts".to_string(), 0),
+                    ("proposals".to_string(), 0),
+                    ("roles".to_string(), 0),
+                    ("session_participants".to_string(), 0),
+                    ("sessions".to_string(), 0),
+                    ("subjects".to_string(), 0),
+                ]),
+                summary.datasets
+            );
+        }
+    }
+}
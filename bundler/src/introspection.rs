@@ -0,0 +1,223 @@
+use crate::{bundle::ContentHasher, scoped::Scope};
+use clap::Args;
+use serde::Deserialize;
+use std::{
+    collections::{BTreeSet, HashMap},
+    sync::Mutex,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::time::Instant;
+use url::Url;
+
+/// Options for authenticating requests by introspecting bearer tokens with an OAuth2 authorization server
+#[derive(Debug, Clone, Args)]
+pub struct IntrospectionArgs {
+    /// The URL of an OAuth2 token introspection endpoint (RFC 7662) with which bearer tokens are verified. If set, tokens carrying a beamlines claim are served a bundle restricted to those beamlines
+    #[arg(
+        long,
+        env = "BUNDLER_INTROSPECTION_URL",
+        conflicts_with = "jwt_decoding_key"
+    )]
+    introspection_url: Option<Url>,
+    /// The client ID with which requests to the introspection endpoint are authenticated
+    #[arg(
+        long,
+        env = "BUNDLER_INTROSPECTION_CLIENT_ID",
+        requires = "introspection_client_secret"
+    )]
+    introspection_client_id: Option<String>,
+    /// The client secret with which requests to the introspection endpoint are authenticated
+    #[arg(long, env = "BUNDLER_INTROSPECTION_CLIENT_SECRET")]
+    introspection_client_secret: Option<String>,
+    /// Scopes which must all be granted to an introspected token for it to be accepted
+    #[arg(
+        long = "introspection-required-scope",
+        env = "BUNDLER_INTROSPECTION_REQUIRED_SCOPES",
+        value_delimiter = ','
+    )]
+    introspection_required_scopes: Vec<String>,
+    /// The longest time for which the outcome of an introspection is cached, curtailed by the expiry of the token
+    #[arg(long, env = "BUNDLER_INTROSPECTION_CACHE_TTL", default_value_t = humantime::Duration::from(Duration::from_secs(60)))]
+    introspection_cache_ttl: humantime::Duration,
+}
+
+impl IntrospectionArgs {
+    /// Whether an introspection endpoint is configured, such that bearer tokens are introspected
+    pub fn is_configured(&self) -> bool {
+        self.introspection_url.is_some()
+    }
+}
+
+/// The response of a token introspection endpoint, as defined in RFC 7662
+#[derive(Debug, Deserialize)]
+struct IntrospectionResponse {
+    /// Whether the token is currently active
+    active: bool,
+    /// The space delimited scopes granted to the token
+    #[serde(default)]
+    scope: Option<String>,
+    /// The time at which the token expires, in seconds since the Unix epoch
+    #[serde(default)]
+    exp: Option<u64>,
+    /// The claims of the token which restrict the data served to its holder
+    #[serde(flatten)]
+    claims: Scope,
+}
+
+/// A verifier of bearer tokens which introspects them with an OAuth2 authorization server, caching the outcome
+pub struct TokenIntrospector {
+    /// The client with which the introspection endpoint is called
+    http_client: reqwest::Client,
+    /// The URL of the introspection endpoint
+    url: Url,
+    /// The client ID and secret with which requests are authenticated, if any
+    client_credentials: Option<(String, String)>,
+    /// The scopes which must all be granted to a token
+    required_scopes: BTreeSet<String>,
+    /// The longest time for which the outcome of an introspection is cached
+    cache_ttl: Duration,
+    /// The time until which the [`Scope`] of each token, or its rejection, is cached, keyed by the digest of the token
+    cache: Mutex<HashMap<String, (Instant, Option<Scope>)>>,
+}
+
+impl TokenIntrospector {
+    /// Creates a [`TokenIntrospector`], if an introspection endpoint is configured
+    pub fn from_args(args: IntrospectionArgs) -> Option<Self> {
+        Some(Self {
+            http_client: reqwest::Client::new(),
+            url: args.introspection_url?,
+            client_credentials: args
+                .introspection_client_id
+                .zip(args.introspection_client_secret),
+            required_scopes: args.introspection_required_scopes.into_iter().collect(),
+            cache_ttl: args.introspection_cache_ttl.into(),
+            cache: Mutex::default(),
+        })
+    }
+
+    /// Verifies the token, returning its [`Scope`] if it is active and granted each required scope
+    ///
+    /// Tokens which could not be introspected are rejected, but the outcome is not cached
+    pub async fn introspect(&self, token: &str) -> Option<Scope> {
+        let mut hasher = ContentHasher::default();
+        hasher.update(&token);
+        let key = hasher.finish();
+        if let Some((expiry, scope)) = self.cache.lock().unwrap().get(&key) {
+            if *expiry > Instant::now() {
+                return scope.clone();
+            }
+        }
+        let response = match self.request(token).await {
+            Ok(response) => response,
+            Err(err) => {
+                tracing::warn!(
+                    monotonic_counter.token_introspection_failures = 1,
+                    "Could not introspect bearer token: {err}"
+                );
+                return None;
+            }
+        };
+        let expiry =
+            Instant::now() + cache_duration(self.cache_ttl, response.exp, SystemTime::now());
+        let scope = accept(response, &self.required_scopes);
+        let mut cache = self.cache.lock().unwrap();
+        let now = Instant::now();
+        cache.retain(|_, (expiry, _)| *expiry > now);
+        cache.insert(key, (expiry, scope.clone()));
+        scope
+    }
+
+    /// Requests introspection of the token from the introspection endpoint
+    async fn request(&self, token: &str) -> Result<IntrospectionResponse, anyhow::Error> {
+        let mut request = self
+            .http_client
+            .post(self.url.clone())
+            .form(&[("token", token), ("token_type_hint", "access_token")]);
+        if let Some((client_id, client_secret)) = &self.client_credentials {
+            request = request.basic_auth(client_id, Some(client_secret));
+        }
+        let body = request.send().await?.error_for_status()?.bytes().await?;
+        Ok(serde_json::from_slice(&body)?)
+    }
+}
+
+/// The [`Scope`] of an introspected token, if it is active and granted each required scope
+fn accept(response: IntrospectionResponse, required_scopes: &BTreeSet<String>) -> Option<Scope> {
+    let granted_scopes = response
+        .scope
+        .as_deref()
+        .unwrap_or_default()
+        .split_whitespace()
+        .collect::<BTreeSet<_>>();
+    (response.active
+        && required_scopes
+            .iter()
+            .all(|scope| granted_scopes.contains(scope.as_str())))
+    .then_some(response.claims)
+}
+
+/// The time for which the outcome of an introspection is cached, being the cache TTL or the remaining lifetime of the token, whichever is shorter
+fn cache_duration(cache_ttl: Duration, exp: Option<u64>, now: SystemTime) -> Duration {
+    let Some(exp) = exp else {
+        return cache_ttl;
+    };
+    (UNIX_EPOCH + Duration::from_secs(exp))
+        .duration_since(now)
+        .unwrap_or_default()
+        .min(cache_ttl)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{accept, cache_duration, IntrospectionResponse};
+    use serde_json::json;
+    use std::{
+        collections::BTreeSet,
+        time::{Duration, UNIX_EPOCH},
+    };
+
+    fn response(value: serde_json::Value) -> IntrospectionResponse {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn accept_active_with_scopes() {
+        let required_scopes = BTreeSet::from(["bundle:read".to_string()]);
+        let scope = accept(
+            response(json!({"active": true, "scope": "openid bundle:read", "beamlines": ["i03"]})),
+            &required_scopes,
+        )
+        .unwrap();
+        assert_eq!(Some(["i03".to_string()].into()), scope.beamlines().cloned());
+        assert!(accept(
+            response(json!({"active": true, "scope": "openid"})),
+            &required_scopes
+        )
+        .is_none());
+        assert!(accept(
+            response(json!({"active": false, "scope": "bundle:read"})),
+            &required_scopes
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn accept_without_required_scopes() {
+        let scope = accept(response(json!({"active": true})), &BTreeSet::new()).unwrap();
+        assert!(scope.beamlines().is_none());
+        assert!(accept(response(json!({"active": false})), &BTreeSet::new()).is_none());
+    }
+
+    #[test]
+    fn cache_curtailed_by_expiry() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_000);
+        let ttl = Duration::from_secs(60);
+        assert_eq!(ttl, cache_duration(ttl, None, now));
+        assert_eq!(ttl, cache_duration(ttl, Some(2_000), now));
+        assert_eq!(
+            Duration::from_secs(10),
+            cache_duration(ttl, Some(1_010), now)
+        );
+        assert_eq!(Duration::ZERO, cache_duration(ttl, Some(500), now));
+    }
+}
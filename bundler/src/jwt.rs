@@ -0,0 +1,142 @@
+use crate::scoped::Scope;
+use clap::Args;
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use std::path::PathBuf;
+
+/// Options for authenticating requests with JSON Web Tokens
+#[derive(Debug, Clone, Args)]
+pub struct JwtArgs {
+    /// The path of a PEM encoded public key, or a secret for HMAC algorithms, with which bearer JSON Web Tokens are verified. If set, tokens carrying a beamlines claim are served a bundle restricted to those beamlines
+    #[arg(long, env = "BUNDLER_JWT_DECODING_KEY")]
+    jwt_decoding_key: Option<PathBuf>,
+    /// The algorithm with which JSON Web Tokens are signed
+    #[arg(long, env = "BUNDLER_JWT_ALGORITHM", default_value = "RS256")]
+    jwt_algorithm: Algorithm,
+    /// The audience JSON Web Tokens must be issued for, if any
+    #[arg(long, env = "BUNDLER_JWT_AUDIENCE")]
+    jwt_audience: Option<String>,
+    /// The issuer JSON Web Tokens must be issued by, if any
+    #[arg(long, env = "BUNDLER_JWT_ISSUER")]
+    jwt_issuer: Option<String>,
+}
+
+/// A verifier of bearer JSON Web Tokens, which extracts the [`Scope`] of valid tokens
+pub struct JwtValidator {
+    /// The key with which token signatures are verified
+    decoding_key: DecodingKey,
+    /// The validation applied to token claims
+    validation: Validation,
+}
+
+impl JwtArgs {
+    /// Whether a decoding key is configured, such that JSON Web Tokens are accepted
+    pub fn is_configured(&self) -> bool {
+        self.jwt_decoding_key.is_some()
+    }
+}
+
+impl JwtValidator {
+    /// Reads the decoding key, if configured
+    pub fn from_args(args: JwtArgs) -> Result<Option<Self>, anyhow::Error> {
+        let Some(decoding_key_path) = args.jwt_decoding_key else {
+            return Ok(None);
+        };
+        let key = std::fs::read(decoding_key_path)?;
+        let decoding_key = match args.jwt_algorithm {
+            Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512 => {
+                DecodingKey::from_secret(&key)
+            }
+            Algorithm::ES256 | Algorithm::ES384 => DecodingKey::from_ec_pem(&key)?,
+            Algorithm::EdDSA => DecodingKey::from_ed_pem(&key)?,
+            Algorithm::RS256
+            | Algorithm::RS384
+            | Algorithm::RS512
+            | Algorithm::PS256
+            | Algorithm::PS384
+            | Algorithm::PS512 => DecodingKey::from_rsa_pem(&key)?,
+        };
+        let mut validation = Validation::new(args.jwt_algorithm);
+        match args.jwt_audience {
+            Some(audience) => validation.set_audience(&[audience]),
+            None => validation.validate_aud = false,
+        }
+        if let Some(issuer) = args.jwt_issuer {
+            validation.set_issuer(&[issuer]);
+        }
+        Ok(Some(Self::new(decoding_key, validation)))
+    }
+
+    /// Creates a [`JwtValidator`] from a decoding key and validation
+    fn new(decoding_key: DecodingKey, validation: Validation) -> Self {
+        Self {
+            decoding_key,
+            validation,
+        }
+    }
+
+    /// Verifies the token, returning its [`Scope`] if valid
+    pub fn validate(&self, token: &str) -> Option<Scope> {
+        match jsonwebtoken::decode::<Scope>(token, &self.decoding_key, &self.validation) {
+            Ok(token_data) => Some(token_data.claims),
+            Err(err) => {
+                tracing::debug!("Rejected JSON Web Token: {err}");
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::JwtValidator;
+    use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, Validation};
+    use serde_json::json;
+
+    fn validator() -> JwtValidator {
+        let mut validation = Validation::new(Algorithm::HS256);
+        validation.validate_aud = false;
+        JwtValidator::new(DecodingKey::from_secret(b"secret"), validation)
+    }
+
+    fn token(secret: &[u8], claims: serde_json::Value) -> String {
+        jsonwebtoken::encode(
+            &Header::new(Algorithm::HS256),
+            &claims,
+            &EncodingKey::from_secret(secret),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn validate_scoped() {
+        let scope = validator()
+            .validate(&token(
+                b"secret",
+                json!({"exp": u32::MAX, "beamlines": ["i03"]}),
+            ))
+            .unwrap();
+        assert_eq!(Some(["i03".to_string()].into()), scope.beamlines().cloned());
+    }
+
+    #[test]
+    fn validate_unscoped() {
+        let scope = validator()
+            .validate(&token(b"secret", json!({"exp": u32::MAX})))
+            .unwrap();
+        assert!(scope.beamlines().is_none());
+    }
+
+    #[test]
+    fn validate_wrong_secret() {
+        assert!(validator()
+            .validate(&token(b"other", json!({"exp": u32::MAX})))
+            .is_none());
+    }
+
+    #[test]
+    fn validate_expired() {
+        assert!(validator()
+            .validate(&token(b"secret", json!({"exp": 1})))
+            .is_none());
+    }
+}
@@ -0,0 +1,245 @@
+use crate::bundle::DATASETS;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::{collections::BTreeMap, str::FromStr};
+
+/// The shape in which a dataset is written into the bundle
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Layout {
+    /// An object mapping the key of each entry to its fields, as in `{"alice": {"sessions": [10]}}`
+    Keyed,
+    /// An array of entries, each carrying its key as a field, as in `[{"subject": "alice", "sessions": [10]}]`
+    Records,
+}
+
+impl Layout {
+    /// The name of the layout, as configured
+    fn name(self) -> &'static str {
+        match self {
+            Self::Keyed => "keyed",
+            Self::Records => "records",
+        }
+    }
+}
+
+impl FromStr for Layout {
+    type Err = anyhow::Error;
+
+    fn from_str(layout: &str) -> Result<Self, Self::Err> {
+        match layout {
+            "keyed" => Ok(Self::Keyed),
+            "records" => Ok(Self::Records),
+            _ => Err(anyhow::anyhow!(
+                "Unknown layout '{layout}', expected one of keyed, records"
+            )),
+        }
+    }
+}
+
+/// The layout in which a dataset is written, in place of the keyed layout
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DatasetLayout {
+    /// The name of the dataset
+    dataset: String,
+    /// The layout in which the dataset is written
+    layout: Layout,
+}
+
+impl FromStr for DatasetLayout {
+    type Err = anyhow::Error;
+
+    fn from_str(dataset_layout: &str) -> Result<Self, Self::Err> {
+        let (dataset, layout) = dataset_layout.split_once('=').ok_or_else(|| {
+            anyhow::anyhow!(
+                "Dataset layout '{dataset_layout}' is not of the form '<dataset>=<layout>'"
+            )
+        })?;
+        if !DATASETS.contains(&dataset) {
+            anyhow::bail!(
+                "Dataset layout '{dataset_layout}' names unknown dataset '{dataset}', expected one of {}",
+                DATASETS.join(", ")
+            );
+        }
+        Ok(Self {
+            dataset: dataset.to_string(),
+            layout: Layout::from_str(layout)?,
+        })
+    }
+}
+
+/// The layout of individual datasets, with any other dataset written as a keyed map
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct DatasetLayouts(BTreeMap<String, Layout>);
+
+impl From<Vec<DatasetLayout>> for DatasetLayouts {
+    fn from(dataset_layouts: Vec<DatasetLayout>) -> Self {
+        Self(
+            dataset_layouts
+                .into_iter()
+                .map(|dataset_layout| (dataset_layout.dataset, dataset_layout.layout))
+                .collect(),
+        )
+    }
+}
+
+impl DatasetLayouts {
+    /// Whether every dataset is written as a keyed map
+    pub fn is_empty(&self) -> bool {
+        self.0.values().all(|layout| *layout == Layout::Keyed)
+    }
+
+    /// Whether the named dataset is written in a layout other than a keyed map
+    pub fn applies_to(&self, dataset: &str) -> bool {
+        self.0
+            .get(dataset)
+            .is_some_and(|layout| *layout != Layout::Keyed)
+    }
+
+    /// Lays out the serialized entries of the named dataset as configured
+    pub fn apply(&self, dataset: &str, entries: &mut Value) {
+        if self.applies_to(dataset) {
+            *entries = to_records(dataset, entries.take());
+        }
+    }
+
+    /// Describes the layout of each dataset written as other than a keyed map, as reported in the effective configuration
+    pub fn describe(&self) -> BTreeMap<String, String> {
+        self.0
+            .iter()
+            .filter(|(_, layout)| **layout != Layout::Keyed)
+            .map(|(dataset, layout)| (dataset.clone(), layout.name().to_string()))
+            .collect()
+    }
+}
+
+/// The field holding the key of each entry of the named dataset when written as records, and whether the key is numeric
+fn key_field(dataset: &str) -> (&'static str, bool) {
+    match dataset {
+        "subjects" | "people" => ("subject", false),
+        "sessions" | "session_members" | "session_participants" => ("session", true),
+        "proposals" | "lab_contacts" => ("proposal", true),
+        "beamlines" | "instrument_scientists" => ("beamline", false),
+        "roles" => ("role", false),
+        _ => ("key", false),
+    }
+}
+
+/// The field holding the value of each entry of the named dataset when written as records, for entries which are not objects
+fn value_field(dataset: &str) -> &'static str {
+    match dataset {
+        "session_members" => "subjects",
+        _ => "value",
+    }
+}
+
+/// Converts the keyed entries of the named dataset to an array of records, each carrying its key
+pub fn to_records(dataset: &str, entries: Value) -> Value {
+    let Value::Object(entries) = entries else {
+        return entries;
+    };
+    let (key_field, numeric) = key_field(dataset);
+    Value::Array(
+        entries
+            .into_iter()
+            .map(|(key, entry)| {
+                let mut record = match entry {
+                    Value::Object(fields) => fields,
+                    value => Map::from_iter([(value_field(dataset).to_string(), value)]),
+                };
+                let key = match key.parse::<u64>() {
+                    Ok(key) if numeric => Value::from(key),
+                    _ => Value::String(key),
+                };
+                record.insert(key_field.to_string(), key);
+                Value::Object(record)
+            })
+            .collect(),
+    )
+}
+
+/// Converts an array of records of the named dataset back to keyed entries, being the inverse of [`to_records`]
+pub fn to_keyed(dataset: &str, records: Value) -> Value {
+    let Value::Array(records) = records else {
+        return records;
+    };
+    let (key_field, _) = key_field(dataset);
+    let value_field = value_field(dataset);
+    Value::Object(
+        records
+            .into_iter()
+            .filter_map(|record| {
+                let Value::Object(mut record) = record else {
+                    return None;
+                };
+                let key = match record.remove(key_field)? {
+                    Value::String(key) => key,
+                    key => key.to_string(),
+                };
+                let entry = match record.remove(value_field) {
+                    Some(value) if record.is_empty() => value,
+                    Some(value) => {
+                        record.insert(value_field.to_string(), value);
+                        Value::Object(record)
+                    }
+                    None => Value::Object(record),
+                };
+                Some((key, entry))
+            })
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{to_keyed, to_records, DatasetLayout, DatasetLayouts};
+    use serde_json::json;
+    use std::str::FromStr;
+
+    #[test]
+    fn records_carry_keys() {
+        let layouts = DatasetLayouts::from(vec![
+            DatasetLayout::from_str("sessions=records").unwrap(),
+            DatasetLayout::from_str("session_members=records").unwrap(),
+            DatasetLayout::from_str("roles=keyed").unwrap(),
+        ]);
+        let mut sessions = json!({"10": {"beamline": "i03"}});
+        layouts.apply("sessions", &mut sessions);
+        assert_eq!(json!([{"session": 10, "beamline": "i03"}]), sessions);
+        let mut session_members = json!({"10": ["alice", "bob"]});
+        layouts.apply("session_members", &mut session_members);
+        assert_eq!(
+            json!([{"session": 10, "subjects": ["alice", "bob"]}]),
+            session_members
+        );
+        let mut roles = json!({"admin": {"permissions": []}});
+        layouts.apply("roles", &mut roles);
+        assert_eq!(json!({"admin": {"permissions": []}}), roles);
+    }
+
+    #[test]
+    fn records_roundtrip() {
+        for (dataset, entries) in [
+            ("subjects", json!({"alice": {"sessions": [10]}, "123": {}})),
+            ("proposals", json!({"1": {"sessions": {"1": 10}}})),
+            ("session_members", json!({"10": ["alice"]})),
+        ] {
+            assert_eq!(
+                entries,
+                to_keyed(dataset, to_records(dataset, entries.clone())),
+                "{dataset}"
+            );
+        }
+    }
+
+    #[test]
+    fn parse_dataset_layouts() {
+        assert!(DatasetLayout::from_str("sessions").is_err());
+        assert!(DatasetLayout::from_str("visits=records").is_err());
+        assert!(DatasetLayout::from_str("sessions=rows").is_err());
+        assert!(
+            DatasetLayouts::from(vec![DatasetLayout::from_str("sessions=keyed").unwrap()])
+                .is_empty()
+        );
+    }
+}
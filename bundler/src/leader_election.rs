@@ -0,0 +1,224 @@
+use crate::{
+    bundle::{Bundle, NoMetadata},
+    verification, BundleFile, CurrentBundle,
+};
+use clap::Args;
+use k8s_openapi::{
+    api::coordination::v1::{Lease, LeaseSpec},
+    apimachinery::pkg::apis::meta::v1::{MicroTime, ObjectMeta},
+    chrono::{Duration as ChronoDuration, Utc},
+};
+use kube::{
+    api::{PostParams, ResourceExt},
+    Api, Client,
+};
+use reqwest::{header::IF_NONE_MATCH, StatusCode};
+use std::time::Duration;
+use tracing::instrument;
+use url::Url;
+
+/// Options for electing a leader amongst replicas via a Kubernetes Lease
+#[derive(Debug, Clone, Args)]
+pub struct LeaderElectionArgs {
+    /// The name of a Kubernetes Lease via which replicas elect a leader to poll ISPyB, from which the others fetch the bundle
+    #[arg(long, env = "BUNDLER_K8S_LEASE_NAME", requires = "k8s_advertise_url")]
+    k8s_lease_name: Option<String>,
+    /// The namespace of the Kubernetes Lease, defaulting to that of the service account
+    #[arg(long, env = "BUNDLER_K8S_LEASE_NAMESPACE")]
+    k8s_lease_namespace: Option<String>,
+    /// The URL at which this replica can be reached by the other replicas
+    #[arg(long, env = "BUNDLER_K8S_ADVERTISE_URL")]
+    k8s_advertise_url: Option<Url>,
+    /// The time for which leadership is held without renewal, which should exceed the polling interval
+    #[arg(long, env = "BUNDLER_K8S_LEASE_DURATION", default_value_t = humantime::Duration::from(Duration::from_secs(180)))]
+    k8s_lease_duration: humantime::Duration,
+}
+
+/// Leadership amongst replicas elected via a Kubernetes Lease, of which only the leader polls ISPyB
+pub struct LeaderElection {
+    /// The Lease API in the namespace of the lease
+    leases: Api<Lease>,
+    /// The name of the Lease
+    lease_name: String,
+    /// The URL at which this replica can be reached, used as the holder identity
+    advertise_url: Url,
+    /// The time for which leadership is held without renewal
+    lease_duration: Duration,
+    /// Whether this replica held the lease when last checked
+    leading: bool,
+    /// The client with which bundles are fetched from the leader
+    http_client: reqwest::Client,
+    /// The bearer token presented to the leader, if one is required
+    require_token: Option<String>,
+}
+
+impl LeaderElection {
+    /// Connects to the Kubernetes API if configured
+    pub async fn connect(
+        args: LeaderElectionArgs,
+        require_token: Option<String>,
+    ) -> Result<Option<Self>, kube::Error> {
+        let (Some(lease_name), Some(advertise_url)) = (args.k8s_lease_name, args.k8s_advertise_url)
+        else {
+            return Ok(None);
+        };
+        let client = Client::try_default().await?;
+        let leases = match args.k8s_lease_namespace {
+            Some(namespace) => Api::namespaced(client, &namespace),
+            None => Api::default_namespaced(client),
+        };
+        Ok(Some(Self {
+            leases,
+            lease_name,
+            advertise_url,
+            lease_duration: args.k8s_lease_duration.into(),
+            leading: false,
+            http_client: reqwest::Client::new(),
+            require_token,
+        }))
+    }
+
+    /// Acquires or renews the Lease if it is free, expired, or already held, returning the URL of the leader
+    #[instrument(skip(self), fields(lease_name = self.lease_name))]
+    async fn try_lead(&mut self) -> Result<Option<Url>, kube::Error> {
+        let now = Utc::now();
+        let identity = self.advertise_url.to_string();
+        let lease = self.leases.get_opt(&self.lease_name).await?;
+        let spec = lease
+            .as_ref()
+            .and_then(|lease| lease.spec.clone())
+            .unwrap_or_default();
+        let held_by_self = spec.holder_identity.as_deref() == Some(identity.as_str());
+        let expired = match (&spec.renew_time, spec.lease_duration_seconds) {
+            (Some(MicroTime(renew_time)), Some(duration)) => {
+                *renew_time + ChronoDuration::seconds(duration.into()) < now
+            }
+            _ => true,
+        };
+        if !held_by_self && !expired {
+            self.record_leadership(false);
+            return Ok(spec
+                .holder_identity
+                .and_then(|holder| Url::parse(&holder).ok()));
+        }
+
+        let new_spec = LeaseSpec {
+            holder_identity: Some(identity),
+            lease_duration_seconds: Some(self.lease_duration.as_secs() as i32),
+            acquire_time: if held_by_self {
+                spec.acquire_time
+            } else {
+                Some(MicroTime(now))
+            },
+            renew_time: Some(MicroTime(now)),
+            lease_transitions: Some(
+                spec.lease_transitions.unwrap_or_default() + i32::from(!held_by_self),
+            ),
+        };
+        let result = match lease {
+            Some(mut lease) => {
+                lease.spec = Some(new_spec);
+                self.leases
+                    .replace(&self.lease_name, &PostParams::default(), &lease)
+                    .await
+            }
+            None => {
+                self.leases
+                    .create(
+                        &PostParams::default(),
+                        &Lease {
+                            metadata: ObjectMeta {
+                                name: Some(self.lease_name.clone()),
+                                ..Default::default()
+                            },
+                            spec: Some(new_spec),
+                        },
+                    )
+                    .await
+            }
+        };
+        match result {
+            Ok(lease) => {
+                tracing::debug!("Holding lease at version {:?}", lease.resource_version());
+                self.record_leadership(true);
+                Ok(Some(self.advertise_url.clone()))
+            }
+            Err(kube::Error::Api(response)) if response.code == 409 => {
+                tracing::debug!("Lease acquired by another replica");
+                self.record_leadership(false);
+                Ok(None)
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Records a change in leadership, if any
+    fn record_leadership(&mut self, leading: bool) {
+        if leading != self.leading {
+            tracing::info!(counter.k8s_leader = if leading { 1 } else { -1 });
+            tracing::info!("Leadership {}", if leading { "acquired" } else { "lost" });
+        }
+        self.leading = leading;
+    }
+
+    /// Fetches the bundle from the leader if its revision differs from the current revision
+    #[instrument(skip(self))]
+    async fn fetch(
+        &self,
+        leader_url: &Url,
+        current_revision: &str,
+    ) -> Result<Option<BundleFile>, anyhow::Error> {
+        let mut request = self
+            .http_client
+            .get(leader_url.join("bundle.tar.gz")?)
+            .header(IF_NONE_MATCH, format!(r#""{current_revision}""#));
+        if let Some(require_token) = &self.require_token {
+            request = request.bearer_auth(require_token);
+        }
+        let response = request.send().await?.error_for_status()?;
+        if response.status() == StatusCode::NOT_MODIFIED {
+            return Ok(None);
+        }
+        let file = response.bytes().await?;
+        let bundle_file =
+            BundleFile::new(Bundle::<NoMetadata>::read_revision(&file)?, file, false)?;
+        verification::verify(&bundle_file)
+            .map_err(|err| anyhow::anyhow!("Bundle served by leader failed verification: {err}"))?;
+        Ok(Some(bundle_file))
+    }
+
+    /// Determines whether this replica should poll ISPyB, otherwise replacing the current bundle with that served by the leader
+    ///
+    /// Should the Kubernetes API be unavailable the replica polls ISPyB itself, such that it continues to update the bundle independently
+    pub async fn lead_or_follow(&mut self, current_bundle: &CurrentBundle) -> bool {
+        let leader_url = match self.try_lead().await {
+            Ok(Some(leader_url)) if leader_url == self.advertise_url => return true,
+            Ok(Some(leader_url)) => leader_url,
+            Ok(None) => return false,
+            Err(err) => {
+                tracing::warn!(
+                    monotonic_counter.k8s_leader_election_failures = 1,
+                    "Could not determine leader, polling ISPyB independently: {err}"
+                );
+                return true;
+            }
+        };
+        let current_revision = current_bundle.as_ref().read().await.revision.clone();
+        match self.fetch(&leader_url, &current_revision).await {
+            Ok(Some(bundle_file)) => {
+                tracing::info!(
+                    "Updated bundle from {} to {} served by leader",
+                    current_revision,
+                    bundle_file.revision
+                );
+                current_bundle.replace(bundle_file).await;
+            }
+            Ok(None) => {}
+            Err(err) => tracing::warn!(
+                monotonic_counter.k8s_leader_election_failures = 1,
+                "Could not fetch bundle from leader: {err}"
+            ),
+        }
+        false
+    }
+}
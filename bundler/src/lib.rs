@@ -0,0 +1,2321 @@
+#![forbid(unsafe_code)]
+#![doc=include_str!("../README.md")]
+#![warn(missing_docs)]
+#![warn(clippy::missing_docs_in_private_items)]
+/// Refusal of bundle updates which suspiciously shrink a dataset
+mod anomaly_guard;
+/// Hooks contributing additional data to each bundle fetched from ISPyB
+mod augmenter;
+/// Verification of HTTP Basic credentials, for clients unable to send bearer tokens
+mod basic_auth;
+/// Metadata about the crate, courtesy of built
+mod built_info;
+/// An Open Policy Agent bundle containing permissionables
+mod bundle;
+/// Persistence of the latest bundle to disk, for use when ISPyB is unavailable at startup
+mod bundle_cache;
+/// Change data capture from the ISPyB binlog
+#[cfg(feature = "cdc")]
+mod cdc;
+/// Detection of changes to ISPyB, such that unchanged data need not be fetched
+mod change_detection;
+/// Stable and canary channels, of which stable lags behind the current bundle
+mod channels;
+/// Injection of failures into the bundle endpoints, for chaos testing the Open Policy Agent fleet
+#[cfg(feature = "chaos")]
+mod chaos;
+/// Pre-flight validation of the configuration and queries
+mod check;
+/// A circuit breaker suspending fetches from ISPyB after repeated failures
+mod circuit_breaker;
+/// Identification of the clients requesting bundles, for metrics and the clients endpoint
+mod clients;
+/// Compression of the responses of routes other than those serving bundles
+mod compression;
+/// Reloading of settings from a Kubernetes ConfigMap at runtime
+#[cfg(feature = "k8s")]
+mod config_map;
+/// Inspection of the datasets of the served bundle, as JSON or MessagePack
+mod data;
+/// Connections to ISPyB, with failover between replicas
+mod database;
+/// The manifest roots under which each dataset is placed
+mod dataset_roots;
+/// Receipt of Open Policy Agent decision logs, which are forwarded to a sink
+#[cfg(feature = "decision-logs")]
+mod decision_logs;
+/// Datasets which are neither fetched nor included in the bundle
+mod disabled_datasets;
+/// An Open Policy Agent discovery bundle rendered from a configuration template
+mod discovery;
+/// The configuration the service is running with, excluding any credentials
+mod effective_config;
+/// Reporting of errors to Sentry
+#[cfg(feature = "sentry")]
+mod error_reporting;
+/// Export of each new bundle to a directory
+mod export;
+/// The outcome of the most recent fetch of each dataset from ISPyB
+mod fetch_status;
+/// Generation of test fixtures from a sample of a live ISPyB instance
+mod fixture_snapshot;
+/// Distribution of bundles via gRPC
+#[cfg(feature = "grpc")]
+mod grpc;
+/// Liveness and readiness of the service, according to the freshness of the bundle
+mod health;
+/// Serving of the HTTP API over HTTP/3
+#[cfg(feature = "http3")]
+mod http3;
+/// Summaries of the contents of local bundle archives
+mod inspect;
+/// Verification of bearer tokens via OAuth2 token introspection
+#[cfg(feature = "introspection")]
+mod introspection;
+/// Verification of bearer JSON Web Tokens
+mod jwt;
+/// The layout of each dataset in the bundle, as a keyed map or an array of records
+mod layout;
+/// Election of a leader amongst replicas via a Kubernetes Lease
+#[cfg(feature = "k8s")]
+mod leader_election;
+/// Logging to a file rotated by size and age
+mod log_file;
+/// Receipt of status reports from Open Policy Agent instances
+mod opa_status;
+/// An OpenAPI document describing the HTTP API
+mod openapi;
+/// Optimization of the bundle by `opa build`, for faster evaluation by Open Policy Agent
+mod optimization;
+/// Updates which carry over datasets that failed to fetch from the previous poll
+mod partial_update;
+/// Pausing of ISPyB polling, for planned maintenance of the database
+mod pause;
+/// Permissionable relations from the ISPyB database
+mod permissionables;
+/// Synchronization of Rego policies from a git repository, for inclusion in the bundle
+mod policy_source;
+/// Evaluation of policy test cases against a built bundle
+mod policy_test;
+/// The intervals at which ISPyB and its individual datasets are polled
+mod polling;
+/// RFC 7807 problem details describing error responses
+mod problem;
+/// Authentication with ISPyB via AWS RDS IAM authentication tokens
+mod rds_iam;
+/// Redaction of personal data from datasets before serialization
+mod redaction;
+/// Decompression of gzipped request bodies, up to a size limit
+mod request_decompression;
+/// Limits on the duration and concurrency of bundle requests
+mod request_limits;
+/// A [`tower::Service`] which enforces a bearer token requirement
+mod require_bearer;
+/// Publication of an event to Kafka or NATS whenever a new bundle is activated
+#[cfg(any(feature = "kafka", feature = "nats"))]
+mod revision_events;
+/// A bounded history of previously served bundles
+mod revision_history;
+/// Pinning of the served bundle to a previous revision
+mod rollback;
+/// Sampling of the traces exported to the OpenTelemetry collector
+mod sampling;
+/// Compatibility of the ISPyB schema with the columns read by each permissionable query
+mod schema_check;
+/// JSON Schemas describing the datasets in the bundle
+mod schemas;
+/// Bundles restricted to the scope of the requesting token
+mod scoped;
+/// A bundle cache shared between replicas via Redis
+#[cfg(feature = "redis")]
+mod shared_cache;
+/// Detached signatures of the served bundle
+mod signature;
+/// Refusal of bundle updates which exceed a size limit
+mod size_guard;
+/// Bundles containing individual datasets, published alongside the complete bundle
+mod split_bundles;
+/// Supervision of background tasks, restarting them when they fail
+mod supervisor;
+/// Readiness and watchdog notifications to systemd
+mod systemd;
+/// Serialization of timestamps
+mod timestamp;
+/// Serving of the HTTP API over TLS
+#[cfg(feature = "tls")]
+mod tls;
+/// Propagation of trace context from the callers of the HTTP API
+mod trace_context;
+/// Reshaping of datasets before serialization
+mod transformation;
+/// Mirroring of the bundle from an upstream bundler, in place of ISPyB
+#[cfg(feature = "upstream")]
+mod upstream;
+/// Validation of datasets against their JSON Schemas
+mod validation;
+/// ISPyB credentials issued by HashiCorp Vault
+#[cfg(feature = "vault")]
+mod vault;
+/// Verification of each built bundle before it is served
+mod verification;
+/// The build of the service, reported for fleet tooling
+mod version;
+/// Monitoring of the number of entries and serialized size of each dataset
+mod volume;
+
+pub use crate::augmenter::{Augmentation, BundleAugmenter, BundleAugmenters};
+pub use crate::bundle::{Bundle, NoMetadata};
+use crate::bundle::{RetainedDatasets, SessionSnapshot};
+#[cfg(feature = "http3")]
+use axum::http::header::ALT_SVC;
+use axum::{
+    body::Bytes,
+    extract::{Query, State},
+    http::{
+        header::{CONTENT_TYPE, WARNING},
+        HeaderMap, HeaderName, HeaderValue, StatusCode,
+    },
+    response::{IntoResponse, Response},
+    routing::get,
+    Extension, Router,
+};
+use axum_extra::TypedHeader;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use clap::{builder::PossibleValuesParser, Parser, ValueEnum};
+use clio::ClioPath;
+use database::{DatabaseArgs, IspybPool};
+use headers::{ETag, HeaderMapExt, IfNoneMatch};
+use opentelemetry_otlp::WithExportConfig;
+use permissionables::{proposals::ProposalFilters, with_timeout};
+use problem::ApiError;
+use require_bearer::RequireBearerLayer;
+use revision_history::RevisionHistory;
+use scoped::{Scope, ScopedVariants};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    borrow::Cow,
+    fmt::Debug,
+    fs::File,
+    future::Future,
+    io::Write,
+    net::{Ipv4Addr, SocketAddr, SocketAddrV4},
+    ops::Add,
+    path::{Path, PathBuf},
+    pin::Pin,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime},
+};
+use tokio::{
+    net::TcpListener,
+    sync::{watch, Mutex, Notify, RwLock, RwLockWriteGuard},
+    time::{sleep_until, Instant},
+};
+use tower::ServiceBuilder;
+#[cfg(feature = "http3")]
+use tower_http::set_header::SetResponseHeaderLayer;
+use tower_http::{
+    request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer},
+    trace::{DefaultOnFailure, DefaultOnRequest, DefaultOnResponse, TraceLayer},
+};
+use tracing::instrument;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+use url::Url;
+
+/// A serialized gzipped [`Bundle`] archive and its revision
+#[derive(Clone)]
+struct BundleFile {
+    /// The revision of the bundle, as recorded in its manifest
+    revision: String,
+    /// The [`ETag`] of the bundle, derived from its revision
+    etag: ETag,
+    /// The base64 encoded SHA-256 digest of the serialized bundle
+    digest: String,
+    /// The base64 encoded SHA-256 digest of the uncompressed serialized bundle
+    tar_digest: String,
+    /// The serialized bundle as a gzipped tar archive
+    file: Bytes,
+    /// The serialized bundle as an uncompressed tar archive
+    tar: Bytes,
+    /// Whether the bundle was loaded from the cache, rather than fetched from ISPyB
+    stale: bool,
+}
+
+impl BundleFile {
+    /// Creates a [`BundleFile`] from a serialized bundle and its revision, decompressing it and computing its digest
+    fn new(revision: String, file: Bytes, stale: bool) -> Result<Self, anyhow::Error> {
+        let tar = bundle::gunzip(&file)?.into();
+        Self::from_archives(revision, tar, file, stale)
+    }
+
+    /// Creates a [`BundleFile`] from a serialized bundle, both uncompressed and gzipped, and its revision, computing its digest
+    fn from_archives(
+        revision: String,
+        tar: Bytes,
+        file: Bytes,
+        stale: bool,
+    ) -> Result<Self, anyhow::Error> {
+        let etag = ETag::from_str(&format!(r#""{revision}""#))
+            .map_err(|_| anyhow::anyhow!("Revision {revision} is not a valid ETag"))?;
+        Ok(Self {
+            revision,
+            etag,
+            digest: BASE64.encode(Sha256::digest(&file)),
+            tar_digest: BASE64.encode(Sha256::digest(&tar)),
+            file,
+            tar,
+            stale,
+        })
+    }
+
+    /// Creates a stale placeholder [`BundleFile`] without content, served as unavailable until replaced by a fetched bundle
+    fn placeholder() -> Self {
+        Self::from_archives(String::new(), Bytes::new(), Bytes::new(), true).unwrap()
+    }
+
+    /// Whether the [`BundleFile`] is a placeholder, awaiting the first fetched bundle
+    fn is_placeholder(&self) -> bool {
+        self.revision.is_empty()
+    }
+
+    /// The serialized bundle in the requested [`ArchiveFormat`] and its digest
+    fn archive(&self, format: ArchiveFormat) -> (&Bytes, &str) {
+        match format {
+            ArchiveFormat::TarGz => (&self.file, &self.digest),
+            ArchiveFormat::Tar => (&self.tar, &self.tar_digest),
+        }
+    }
+}
+
+/// The formats in which a [`BundleFile`] may be served
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ArchiveFormat {
+    /// A gzipped tar archive, as imported by Open Policy Agent
+    TarGz,
+    /// An uncompressed tar archive, for consumers lacking gzip support
+    Tar,
+}
+
+impl ArchiveFormat {
+    /// The media type of archives in this format
+    fn content_type(self) -> &'static str {
+        match self {
+            Self::TarGz => "application/gzip",
+            Self::Tar => "application/x-tar",
+        }
+    }
+}
+
+impl<Metadata> TryFrom<&Bundle<Metadata>> for BundleFile
+where
+    Metadata: Debug + Serialize,
+{
+    type Error = anyhow::Error;
+
+    fn try_from(bundle: &Bundle<Metadata>) -> Result<Self, Self::Error> {
+        let tar = bundle.to_tar()?;
+        let file = bundle::gzip(&tar)?;
+        Self::from_archives(
+            bundle.revision().to_string(),
+            tar.into(),
+            file.into(),
+            false,
+        )
+    }
+}
+
+/// A thread safe, mutable, wrapper around the [`BundleFile`], which publishes the revision of each replacement
+#[derive(Clone)]
+struct CurrentBundle {
+    /// The bundle currently being served
+    bundle: Arc<RwLock<BundleFile>>,
+    /// A channel on which the revision of the bundle currently being served is published
+    revisions: Arc<watch::Sender<String>>,
+    /// The previously served bundles
+    history: Arc<RwLock<RevisionHistory>>,
+    /// Whether the served bundle is pinned, such that it is not replaced by updates
+    pinned: Arc<AtomicBool>,
+    /// Variants of the served bundle restricted to the scopes of requesting tokens
+    scoped_variants: Arc<Mutex<ScopedVariants>>,
+}
+
+impl CurrentBundle {
+    /// Creates a [`CurrentBundle`] serving the provided [`BundleFile`], retaining up to history_capacity previous bundles
+    fn new(bundle_file: BundleFile, history_capacity: usize) -> Self {
+        let (revisions, _) = watch::channel(bundle_file.revision.clone());
+        Self {
+            bundle: Arc::new(RwLock::new(bundle_file)),
+            revisions: Arc::new(revisions),
+            history: Arc::new(RwLock::new(RevisionHistory::new(history_capacity))),
+            pinned: Arc::new(AtomicBool::new(false)),
+            scoped_variants: Arc::new(Mutex::new(ScopedVariants::default())),
+        }
+    }
+
+    /// Caches variants of the served bundle restricted to the scopes of requesting tokens in the provided [`ScopedVariants`]
+    fn with_scoped_variants(mut self, scoped_variants: ScopedVariants) -> Self {
+        self.scoped_variants = Arc::new(Mutex::new(scoped_variants));
+        self
+    }
+
+    /// Replaces the [`BundleFile`] being served, publishing its revision and retaining the replaced bundle, unless pinned
+    ///
+    /// Returns whether the bundle was replaced
+    async fn replace(&self, bundle_file: BundleFile) -> bool {
+        let current = self.bundle.write().await;
+        if self.pinned.load(Ordering::Acquire) {
+            return false;
+        }
+        self.swap(current, bundle_file).await;
+        true
+    }
+
+    /// Pins the served bundle to the current or a retained previous revision, returning whether it was found
+    async fn pin(&self, revision: &str) -> bool {
+        let current = self.bundle.write().await;
+        if current.revision != revision {
+            let Some(bundle_file) = self.history.read().await.get(revision).cloned() else {
+                return false;
+            };
+            self.swap(current, bundle_file).await;
+        }
+        self.pinned.store(true, Ordering::Release);
+        true
+    }
+
+    /// Unpins the served bundle, such that it is replaced by subsequent updates
+    fn unpin(&self) {
+        self.pinned.store(false, Ordering::Release);
+    }
+
+    /// Swaps the [`BundleFile`] being served whilst the write lock is held, publishing its revision and retaining the replaced bundle
+    async fn swap(&self, mut current: RwLockWriteGuard<'_, BundleFile>, bundle_file: BundleFile) {
+        let revision = bundle_file.revision.clone();
+        let replaced = std::mem::replace(&mut *current, bundle_file);
+        self.history.write().await.push(replaced, SystemTime::now());
+        drop(current);
+        self.revisions.send_replace(revision);
+    }
+}
+
+impl AsRef<RwLock<BundleFile>> for CurrentBundle {
+    fn as_ref(&self) -> &RwLock<BundleFile> {
+        &self.bundle
+    }
+}
+/// Bundler acts as a Open Policy Agent bundle server, providing permissionable data from the ISPyB database
+
+#[derive(Debug, Parser)]
+#[command(author, version, about, long_about= None)]
+enum Cli {
+    /// Run the service providing bundle data
+    Serve(Box<ServeArgs>),
+    /// Output the bundle schema
+    BundleSchema(BundleSchemaArgs),
+    /// Validate the service configuration and the queries run against each ISPyB instance, then exit
+    Check(Box<ServeArgs>),
+    /// Check that the tables and columns read by each permissionable query exist with compatible types in each ISPyB instance, then exit
+    CheckDb(schema_check::CheckDbArgs),
+    /// Write test fixtures containing a sample of the permissionable tables of a live ISPyB instance, then exit
+    SnapshotFixtures(fixture_snapshot::SnapshotFixturesArgs),
+    /// Evaluate named policy test cases against a built bundle with Open Policy Agent, then exit
+    Test(policy_test::TestArgs),
+    /// Print the manifest, files and dataset row counts of a local bundle archive, then exit
+    Inspect(inspect::InspectArgs),
+}
+
+/// Arguments to run the service with
+#[derive(Debug, Parser)]
+#[cfg_attr(
+    feature = "upstream",
+    command(mut_arg("database_url", |arg| {
+        arg.required(false).required_unless_present("upstream_url")
+    }))
+)]
+pub struct ServeArgs {
+    /// The port to which this application should bind
+    #[arg(short, long, env = "BUNDLER_PORT", default_value_t = 80)]
+    port: u16,
+    /// Options for serving the HTTP API over TLS
+    #[cfg(feature = "tls")]
+    #[command(flatten)]
+    tls: tls::TlsArgs,
+    /// Options for serving the HTTP API over HTTP/3
+    #[cfg(feature = "http3")]
+    #[command(flatten)]
+    http3: http3::Http3Args,
+    /// If enabled, refuse any bundle requests which do not contain this bearer token
+    #[arg(long, env = "BUNDLER_REQUIRE_TOKEN")]
+    require_token: Option<String>,
+    /// Options for limiting the duration and concurrency of bundle requests
+    #[command(flatten)]
+    request_limits: request_limits::RequestLimitArgs,
+    /// Options for decompressing gzipped request bodies
+    #[command(flatten)]
+    request_decompression: request_decompression::RequestDecompressionArgs,
+    /// Options for compressing the responses of routes other than those serving bundles
+    #[command(flatten)]
+    compression: compression::CompressionArgs,
+    /// If set, administrative endpoints, such as those requesting refreshes or rollbacks and reporting configuration, require this bearer token in place of the bundle credentials
+    #[arg(long, env = "BUNDLER_REQUIRE_ADMIN_TOKEN")]
+    require_admin_token: Option<String>,
+    /// Users accepted via HTTP Basic authentication in place of the bearer token, as '<username>:<bcrypt hash of password>'. Hex encoded SHA-256 digests of passwords are accepted in place of bcrypt hashes, though are unsalted and so discouraged
+    #[arg(
+        long = "basic-auth-user",
+        env = "BUNDLER_BASIC_AUTH_USERS",
+        value_delimiter = ','
+    )]
+    basic_auth_users: Vec<basic_auth::BasicAuthUser>,
+    /// Options for authenticating requests with JSON Web Tokens
+    #[command(flatten)]
+    jwt: jwt::JwtArgs,
+    /// Options for authenticating requests by introspecting bearer tokens
+    #[cfg(feature = "introspection")]
+    #[command(flatten)]
+    introspection: introspection::IntrospectionArgs,
+    /// Options for caching variants of the bundle restricted to the scopes of requesting tokens
+    #[command(flatten)]
+    scoped_variants: scoped::ScopedVariantArgs,
+    /// Options for tracking the clients requesting bundles
+    #[command(flatten)]
+    client_tracking: clients::ClientTrackingArgs,
+    /// Options for connecting to ISPyB
+    #[command(flatten)]
+    database: DatabaseArgs,
+    /// The [`tracing::Level`] to log at
+    #[arg(long, env = "BUNDLER_LOG_LEVEL", default_value_t = tracing::Level::INFO)]
+    log_level: tracing::Level,
+    /// The format in which logs are written
+    #[arg(long, env = "BUNDLER_LOG_FORMAT", value_enum, default_value_t = LogFormat::Pretty)]
+    log_format: LogFormat,
+    /// Options for additionally writing logs to a rotating file
+    #[command(flatten)]
+    log_file: log_file::LogFileArgs,
+    /// The interval at which ISPyB should be polled
+    #[arg(long, env = "BUNDLER_POLLING_INTERVAL", default_value_t=humantime::Duration::from(Duration::from_secs(60)))]
+    polling_interval: humantime::Duration,
+    /// If set, the polling interval doubles after each fetch which leaves the bundle unchanged, up to this maximum, returning to the base interval once the bundle changes or a refresh is requested
+    #[arg(long, env = "BUNDLER_MAX_POLLING_INTERVAL")]
+    max_polling_interval: Option<humantime::Duration>,
+    /// How polls missed whilst the host was suspended, or whilst a fetch overran the polling interval, are made up
+    #[arg(long, env = "BUNDLER_MISSED_POLLS", value_enum, default_value_t = polling::MissedPolls::Delay)]
+    missed_polls: polling::MissedPolls,
+    /// Intervals at which individual datasets are polled, as '<dataset>=<interval>', with any other dataset fetched at every poll. Datasets are refetched at the first poll after their interval has elapsed
+    #[arg(
+        long = "dataset-polling-interval",
+        env = "BUNDLER_DATASET_POLLING_INTERVALS",
+        value_delimiter = ',',
+        conflicts_with = "full_refresh_interval"
+    )]
+    dataset_polling_intervals: Vec<polling::DatasetInterval>,
+    /// Datasets which, should their fetch fail, are carried over from the previous poll and marked as stale in the manifest metadata, such that the remaining datasets are still updated
+    #[arg(
+        long = "partial-update-dataset",
+        env = "BUNDLER_PARTIAL_UPDATE_DATASETS",
+        value_delimiter = ',',
+        value_parser = PossibleValuesParser::new(partial_update::FETCHED_DATASETS),
+        conflicts_with = "full_refresh_interval"
+    )]
+    partial_update_datasets: Vec<String>,
+    /// Datasets which are neither fetched from ISPyB nor included in the bundle, for deployments which should not serve them
+    #[arg(
+        long = "disable-dataset",
+        env = "BUNDLER_DISABLED_DATASETS",
+        value_delimiter = ',',
+        value_parser = PossibleValuesParser::new(partial_update::FETCHED_DATASETS)
+    )]
+    disabled_datasets: Vec<String>,
+    /// The percentage by which the row count or serialized size of a dataset may change between polls before a warning is logged
+    #[arg(long, env = "BUNDLER_VOLUME_CHANGE_THRESHOLD", default_value_t = 50.0)]
+    volume_change_threshold: f64,
+    /// If set, updates which shrink the row count of any dataset by more than this percentage are refused, with the previous bundle served and the service reported as degraded until the update is accepted via '/admin/force-update'
+    #[arg(long, env = "BUNDLER_MAX_DATASET_SHRINK")]
+    max_dataset_shrink: Option<f64>,
+    /// If set, updates which produce an uncompressed bundle larger than this many bytes are refused, with the previous bundle served and the service reported as degraded until the bundle shrinks
+    #[arg(long, env = "BUNDLER_MAX_BUNDLE_BYTES")]
+    max_bundle_bytes: Option<u64>,
+    /// If set, a lightweight query detects whether ISPyB has changed before each poll, skipping the fetch if it has not. Requested refreshes are always fetched
+    #[arg(long, env = "BUNDLER_CHANGE_DETECTION", value_enum)]
+    change_detection: Option<change_detection::ChangeDetection>,
+    /// If set, only sessions created since the previous poll are fetched, with a full refresh performed at this interval
+    #[arg(long, env = "BUNDLER_FULL_REFRESH_INTERVAL")]
+    full_refresh_interval: Option<humantime::Duration>,
+    /// The maximum time a single ISPyB query may take before it is cancelled
+    #[arg(long, env = "BUNDLER_QUERY_TIMEOUT", default_value_t=humantime::Duration::from(Duration::from_secs(30)))]
+    query_timeout: humantime::Duration,
+    /// If enabled, the name, title, email address and home institution of each subject are included in the bundle. This is personal data, so should only be enabled where its processing is permitted
+    #[arg(long, env = "BUNDLER_INCLUDE_PERSONAL_DATA")]
+    include_personal_data: bool,
+    /// If enabled, an index of the subjects associated with each session is included in the bundle, such that policies need not iterate over every subject to find the members of a session
+    #[arg(long, env = "BUNDLER_INCLUDE_SESSION_MEMBERS")]
+    include_session_members: bool,
+    /// Options for redacting personal data from datasets before they are serialized
+    #[command(flatten)]
+    redaction: redaction::RedactionArgs,
+    /// Options for excluding irrelevant proposals from the bundle
+    #[command(flatten)]
+    proposal_filters: ProposalFilters,
+    /// Manifest roots under which individual datasets are placed, as '<dataset>=<root>', with any other dataset placed under its default root, being 'users' for people and instrument_scientists and 'diamond/data' for all others
+    #[arg(
+        long = "dataset-root",
+        env = "BUNDLER_DATASET_ROOTS",
+        value_delimiter = ','
+    )]
+    dataset_roots: Vec<dataset_roots::DatasetRoot>,
+    /// Layouts in which individual datasets are written, as '<dataset>=<layout>' where the layout is 'keyed' or 'records', with any other dataset written as a map keyed by the identifier of each entry
+    #[arg(
+        long = "dataset-layout",
+        env = "BUNDLER_DATASET_LAYOUTS",
+        value_delimiter = ','
+    )]
+    dataset_layouts: Vec<layout::DatasetLayout>,
+    /// The path of a JSON file mapping dataset names to the transformations applied to each entry, in order, before it is written into the bundle
+    #[arg(long, env = "BUNDLER_TRANSFORMATIONS")]
+    transformations: Option<PathBuf>,
+    /// The path at which the latest bundle is stored, to be served whilst ISPyB is unavailable at startup
+    #[arg(long, env = "BUNDLER_BUNDLE_CACHE_PATH")]
+    bundle_cache_path: Option<PathBuf>,
+    /// If enabled, startup succeeds whilst neither ISPyB nor a cached bundle is available, serving 503 Service Unavailable until the first bundle is fetched
+    #[arg(long, env = "BUNDLER_LAZY_CONNECT")]
+    lazy_connect: bool,
+    /// The number of previously served bundles to retain, such that they may be fetched by revision
+    #[arg(long, env = "BUNDLER_REVISION_HISTORY", default_value_t = 0)]
+    revision_history: usize,
+    /// Options for reporting the service as unready once the bundle can no longer be refreshed
+    #[command(flatten)]
+    health: health::HealthArgs,
+    /// Options for suspending fetches from ISPyB after repeated failures
+    #[command(flatten)]
+    circuit_breaker: circuit_breaker::CircuitBreakerArgs,
+    /// The delay before the bundle update task is restarted after it fails, doubling with each consecutive failure
+    #[arg(long, env = "BUNDLER_TASK_RESTART_DELAY", default_value_t=humantime::Duration::from(Duration::from_secs(1)))]
+    task_restart_delay: humantime::Duration,
+    /// The longest delay before the bundle update task is restarted, after which a task which has run for longer is considered stable
+    #[arg(long, env = "BUNDLER_MAX_TASK_RESTART_DELAY", default_value_t=humantime::Duration::from(Duration::from_secs(60)))]
+    max_task_restart_delay: humantime::Duration,
+    /// Options for serving the bundle via stable and canary channels
+    #[command(flatten)]
+    channels: channels::ChannelArgs,
+    /// Options for serving bundles via gRPC
+    #[cfg(feature = "grpc")]
+    #[command(flatten)]
+    grpc: grpc::GrpcArgs,
+    /// Options for including Rego policies from a git repository in the bundle
+    #[command(flatten)]
+    policy_source: policy_source::PolicySourceArgs,
+    /// Options for exporting each new bundle to a directory
+    #[command(flatten)]
+    export: export::ExportArgs,
+    /// Options for serving detached signatures of the bundle
+    #[command(flatten)]
+    signing: signature::SigningArgs,
+    /// Options for receiving Open Policy Agent decision logs and forwarding them to a sink
+    #[cfg(feature = "decision-logs")]
+    #[command(flatten)]
+    decision_logs: decision_logs::DecisionLogArgs,
+    /// Options for publishing an event to Kafka or NATS whenever a new bundle is activated
+    #[cfg(any(feature = "kafka", feature = "nats"))]
+    #[command(flatten)]
+    revision_events: revision_events::RevisionEventArgs,
+    /// Options for serving an Open Policy Agent discovery bundle
+    #[command(flatten)]
+    discovery: discovery::DiscoveryArgs,
+    /// Options for publishing bundles containing individual datasets
+    #[command(flatten)]
+    split_bundles: split_bundles::SplitBundleArgs,
+    /// Options for receiving status reports from Open Policy Agent instances
+    #[command(flatten)]
+    opa_status: opa_status::OpaStatusArgs,
+    /// Options for serving a bundle optimized by `opa build`
+    #[command(flatten)]
+    optimization: optimization::OptimizationArgs,
+    /// The URL of the OpenTelemetry collector to send traces to
+    #[arg(long, env = "BUNDLER_OTEL_COLLECTOR_URL")]
+    otel_collector_url: Option<Url>,
+    /// Options for sampling the traces sent to the OpenTelemetry collector
+    #[command(flatten)]
+    trace_sampling: sampling::TraceSamplingArgs,
+    /// Options for following changes to ISPyB via the binlog
+    #[cfg(feature = "cdc")]
+    #[command(flatten)]
+    cdc: cdc::CdcArgs,
+    /// Options for sharing bundles between replicas via Redis
+    #[cfg(feature = "redis")]
+    #[command(flatten)]
+    shared_cache: shared_cache::SharedCacheArgs,
+    /// Options for electing a leader amongst replicas via a Kubernetes Lease
+    #[cfg(feature = "k8s")]
+    #[command(flatten)]
+    leader_election: leader_election::LeaderElectionArgs,
+    /// Options for reloading settings from a Kubernetes ConfigMap at runtime
+    #[cfg(feature = "k8s")]
+    #[command(flatten)]
+    config_map: config_map::ConfigMapArgs,
+    /// Options for mirroring the bundle from an upstream bundler, in place of ISPyB
+    #[cfg(feature = "upstream")]
+    #[command(flatten)]
+    upstream: upstream::UpstreamArgs,
+    /// Options for reporting errors to Sentry
+    #[cfg(feature = "sentry")]
+    #[command(flatten)]
+    sentry: error_reporting::SentryArgs,
+}
+
+/// The format in which logs are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum LogFormat {
+    /// Human readable lines
+    Pretty,
+    /// JSON lines, including the fields of the enclosing spans
+    Json,
+}
+
+/// Arguments to output the schema with
+#[derive(Debug, Parser)]
+struct BundleSchemaArgs {
+    /// The path to write the schema to
+    #[arg(short, long, value_parser = clap::value_parser!(ClioPath).exists().is_dir())]
+    path: Option<ClioPath>,
+}
+
+/// Parses the command line and runs the requested subcommand, as the `bundler` binary does
+pub async fn run() {
+    dotenvy::dotenv().ok();
+    let args = Cli::parse();
+
+    match args {
+        Cli::Serve(args) => serve(*args, augmenter::BundleAugmenters::default()).await,
+        Cli::BundleSchema(args) => bundle_schema(args),
+        Cli::Check(args) => {
+            if !check::run(*args).await {
+                std::process::exit(1)
+            }
+        }
+        Cli::CheckDb(args) => {
+            if !schema_check::run(args).await {
+                std::process::exit(1)
+            }
+        }
+        Cli::SnapshotFixtures(args) => {
+            if !fixture_snapshot::run(args).await {
+                std::process::exit(1)
+            }
+        }
+        Cli::Test(args) => {
+            if !policy_test::run(args) {
+                std::process::exit(1)
+            }
+        }
+        Cli::Inspect(args) => {
+            if !inspect::run(args) {
+                std::process::exit(1)
+            }
+        }
+    }
+}
+
+/// Runs the service, pulling fresh bundles from ISPyB, passing them through the [`augmenter::BundleAugmenters`] and serving them via the API
+async fn serve(args: ServeArgs, augmenters: augmenter::BundleAugmenters) {
+    #[cfg(feature = "sentry")]
+    let reported_revision = error_reporting::ReportedRevision::default();
+    #[cfg(feature = "sentry")]
+    let _sentry_guard = error_reporting::init(&args, reported_revision.clone());
+    let _log_file_guard = setup_telemetry(
+        args.log_level,
+        args.log_format,
+        &args.log_file,
+        args.otel_collector_url.clone(),
+        args.trace_sampling.sampler(),
+    )
+    .unwrap();
+    let port = args.port;
+    #[cfg(feature = "tls")]
+    let tls = tls::Tls::from_args(args.tls.clone()).unwrap();
+    #[cfg(feature = "http3")]
+    let http3 = args.http3.clone();
+    #[cfg(feature = "http3")]
+    let http3_body_limit = args.request_decompression.body_limit();
+
+    #[cfg_attr(not(feature = "http3"), allow(unused_mut))]
+    let mut state = BundlerState::build(
+        args,
+        augmenters,
+        #[cfg(feature = "sentry")]
+        Some(reported_revision),
+    )
+    .await;
+    let app = with_common_layers(state.routes.clone().fallback(fallback_endpoint));
+    #[cfg(feature = "http3")]
+    let app = match http3.alt_svc() {
+        Some(alt_svc) => app.layer(SetResponseHeaderLayer::if_not_present(ALT_SVC, alt_svc)),
+        None => app,
+    };
+    #[cfg(feature = "http3")]
+    state.tasks.push(Box::pin(http3::serve(
+        http3,
+        app.clone(),
+        tls.clone(),
+        http3_body_limit,
+    )));
+    tokio::select! {
+        _ = serve_endpoints(
+            port,
+            app,
+            #[cfg(feature = "tls")]
+            tls,
+        ) => panic!("HTTP API exited unexpectedly"),
+        _ = run_tasks(state) => unreachable!("Background tasks never exit"),
+    }
+}
+
+/// A background task of the service, which runs for as long as the service does
+type BackgroundTask = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// The HTTP API and background tasks of the service, prepared such that they can be embedded within another service
+///
+/// The HTTP API is served by the [`Router`] created by [`router`], whilst the bundle it serves is kept up to date by the future of [`run_tasks`], which must be polled for as long as the API is served
+pub struct BundlerState {
+    /// The routes of the HTTP API, without a fallback or the layers common to all routes
+    routes: Router,
+    /// The background tasks, including that polling ISPyB for bundle updates
+    tasks: Vec<BackgroundTask>,
+}
+
+impl BundlerState {
+    /// Fetches the initial bundle, then prepares the HTTP API and background tasks configured by the [`ServeArgs`]
+    ///
+    /// Logging, tracing and error reporting are left to the embedding service
+    pub async fn new(args: ServeArgs) -> Self {
+        Self::with_augmenters(args, BundleAugmenters::default()).await
+    }
+
+    /// Fetches the initial bundle, then prepares the HTTP API and background tasks configured by the [`ServeArgs`], passing each bundle fetched from ISPyB through the [`BundleAugmenters`]
+    ///
+    /// ```rust,no_run
+    /// use bundler::{Augmentation, Bundle, BundleAugmenter, BundleAugmenters, NoMetadata};
+    /// use clap::Parser;
+    ///
+    /// #[derive(Debug)]
+    /// struct Facilities;
+    ///
+    /// impl BundleAugmenter for Facilities {
+    ///     fn name(&self) -> &str {
+    ///         "facilities"
+    ///     }
+    ///
+    ///     fn augment(&self, bundle: Bundle<NoMetadata>) -> Augmentation<'_> {
+    ///         Box::pin(async move {
+    ///             bundle.with_augmented_dataset("facilities", &serde_json::json!({"diamond": {}}))
+    ///         })
+    ///     }
+    /// }
+    ///
+    /// # async fn embed() {
+    /// let state = bundler::BundlerState::with_augmenters(
+    ///     bundler::ServeArgs::parse(),
+    ///     BundleAugmenters::default().register(Facilities),
+    /// )
+    /// .await;
+    /// tokio::spawn(bundler::run_tasks(state));
+    /// # }
+    /// ```
+    pub async fn with_augmenters(args: ServeArgs, augmenters: BundleAugmenters) -> Self {
+        Self::build(
+            args,
+            augmenters,
+            #[cfg(feature = "sentry")]
+            None,
+        )
+        .await
+    }
+
+    /// Fetches the initial bundle, then prepares the HTTP API and background tasks configured by the [`ServeArgs`], passing each bundle fetched from ISPyB through the [`BundleAugmenters`]
+    ///
+    /// The revision reported to Sentry, if any, follows that of the current bundle
+    async fn build(
+        args: ServeArgs,
+        augmenters: augmenter::BundleAugmenters,
+        #[cfg(feature = "sentry")] reported_revision: Option<error_reporting::ReportedRevision>,
+    ) -> Self {
+        let effective_config = effective_config::EffectiveConfig::from_args(&args);
+        let fetch_status = fetch_status::FetchStatus::default();
+        let mut volume_monitor = volume::VolumeMonitor::new(args.volume_change_threshold);
+        let redactions = redaction::Redactions::from(args.redaction.clone());
+        let transformations =
+            transformation::Transformations::load(args.transformations.as_deref()).unwrap();
+        let policy_source = policy_source::PolicySource::from_args(args.policy_source);
+        if let Some(policy_source) = &policy_source {
+            if let Err(err) = policy_source.sync().await {
+                tracing::warn!("Could not synchronize policies, retrying at next interval: {err}");
+            }
+        }
+        let dataset_roots = dataset_roots::DatasetRoots::from(args.dataset_roots.clone());
+        let layouts = layout::DatasetLayouts::from(args.dataset_layouts.clone());
+        let disabled_datasets =
+            disabled_datasets::DisabledDatasets::from(args.disabled_datasets.clone());
+        let split_bundles = split_bundles::SplitBundles::new(args.split_bundles.clone());
+        let policies = policy_source
+            .as_ref()
+            .map(policy_source::PolicySource::current)
+            .unwrap_or_default();
+
+        #[cfg(feature = "upstream")]
+        let upstream = upstream::Upstream::from_args(args.upstream).unwrap();
+        #[cfg(feature = "upstream")]
+        let mirrored_bundle = match &upstream {
+            Some(upstream) => Some(upstream.fetch(None).await.and_then(|bundle_file| {
+                bundle_file.ok_or_else(|| anyhow::anyhow!("Upstream served no bundle"))
+            })),
+            None => None,
+        };
+        #[cfg(not(feature = "upstream"))]
+        let mirrored_bundle = None::<Result<BundleFile, anyhow::Error>>;
+        let (ispyb_pool, initial_bundle) = if let Some(initial_bundle) = mirrored_bundle {
+            if let (Ok(bundle_file), Some(bundle_cache_path)) =
+                (&initial_bundle, args.bundle_cache_path.as_deref())
+            {
+                cache_bundle(bundle_cache_path, bundle_file).await;
+            }
+            (IspybPool::unconnected(args.database), initial_bundle)
+        } else {
+            match IspybPool::connect(args.database.clone()).await {
+                Ok(mut ispyb_pool) => {
+                    let initial_bundle = fetch_initial_bundle(
+                        &mut ispyb_pool,
+                        args.query_timeout.into(),
+                        args.include_personal_data,
+                        args.include_session_members,
+                        &args.proposal_filters,
+                        &disabled_datasets,
+                        &augmenters,
+                        &fetch_status,
+                        &mut volume_monitor,
+                        &redactions,
+                        &transformations,
+                        &layouts,
+                        &dataset_roots,
+                        &policies,
+                        &split_bundles,
+                        size_guard::SizeGuard::new(args.max_bundle_bytes),
+                        args.bundle_cache_path.as_deref(),
+                    )
+                    .await;
+                    (ispyb_pool, initial_bundle)
+                }
+                Err(err) => (
+                    IspybPool::connect_lazy(args.database).unwrap(),
+                    Err(err.into()),
+                ),
+            }
+        };
+        let current_bundle = CurrentBundle::new(
+            match initial_bundle {
+                Ok(bundle_file) => bundle_file,
+                Err(err) => {
+                    fallback_bundle(err, args.bundle_cache_path.as_deref(), args.lazy_connect).await
+                }
+            },
+            args.revision_history,
+        )
+        .with_scoped_variants(ScopedVariants::from(args.scoped_variants));
+        #[cfg(feature = "redis")]
+        let shared_cache = {
+            let mut shared_cache = shared_cache::SharedCache::connect(args.shared_cache)
+                .await
+                .unwrap();
+            if let Some(shared_cache) = shared_cache.as_mut() {
+                if shared_cache.lead_or_follow(&current_bundle).await
+                    && !current_bundle.as_ref().read().await.stale
+                {
+                    shared_cache
+                        .publish(&*current_bundle.as_ref().read().await)
+                        .await;
+                }
+            }
+            shared_cache
+        };
+        #[cfg(feature = "k8s")]
+        let leader_election = {
+            let mut leader_election = leader_election::LeaderElection::connect(
+                args.leader_election,
+                args.require_token.clone(),
+            )
+            .await
+            .unwrap();
+            if let Some(leader_election) = leader_election.as_mut() {
+                leader_election.lead_or_follow(&current_bundle).await;
+            }
+            leader_election
+        };
+        let jwt_validator = jwt::JwtValidator::from_args(args.jwt)
+            .unwrap()
+            .map(Arc::new);
+        let polling_pause = pause::PollingPause::default();
+        let circuit_breaker = circuit_breaker::CircuitBreaker::new(args.circuit_breaker);
+        let fetch_health = health::FetchHealth::new(
+            args.health,
+            current_bundle.as_ref().read().await.is_placeholder(),
+        )
+        .with_polling_pause(polling_pause.clone());
+        let task_health = supervisor::TaskHealth::default();
+        let refresh_requested = Arc::new(Notify::new());
+        let anomaly_guard = anomaly_guard::AnomalyGuard::new(args.max_dataset_shrink);
+        let stable_bundle = CurrentBundle::new(current_bundle.as_ref().read().await.clone(), 0);
+        let bundle_signer = signature::BundleSigner::from_args(args.signing).unwrap();
+        let discovery_routes = match discovery::render(&args.discovery).unwrap() {
+            Some(discovery_bundle) => Router::new()
+                .route("/discovery.tar.gz", get(bundle_endpoint))
+                .with_state(CurrentBundle::new(discovery_bundle, 0)),
+            None => Router::new(),
+        };
+        let bearer_layer = RequireBearerLayer::new(args.require_token.clone(), jwt_validator)
+            .with_basic_auth_users(basic_auth::BasicAuthUsers::from(
+                args.basic_auth_users.clone(),
+            ));
+        #[cfg(feature = "introspection")]
+        let bearer_layer = bearer_layer.with_token_introspector(
+            introspection::TokenIntrospector::from_args(args.introspection).map(Arc::new),
+        );
+        let tracked_clients = clients::Clients::from(args.client_tracking.clone());
+        let admin_routes = Router::new()
+            .merge(rollback::router(
+                current_bundle.clone(),
+                refresh_requested.clone(),
+            ))
+            .merge(anomaly_guard::router(
+                anomaly_guard.clone(),
+                refresh_requested.clone(),
+            ))
+            .merge(pause::router(
+                polling_pause.clone(),
+                refresh_requested.clone(),
+            ))
+            .merge(channels::admin_router(
+                current_bundle.clone(),
+                stable_bundle.clone(),
+            ))
+            .merge(fetch_status::router(fetch_status.clone()))
+            .merge(clients::router(tracked_clients.clone()))
+            .merge(effective_config::router(effective_config));
+        #[cfg(feature = "chaos")]
+        let faults = chaos::Faults::default();
+        #[cfg(feature = "chaos")]
+        let admin_routes =
+            admin_routes.merge(chaos::router(faults.clone(), current_bundle.clone()));
+        let admin_routes = args
+            .request_decompression
+            .apply(admin_routes)
+            .route_layer(bearer_layer.for_admin(args.require_admin_token.clone()));
+        #[cfg(feature = "decision-logs")]
+        let (decision_log_events, decision_log_queue) =
+            tokio::sync::mpsc::channel(decision_logs::QUEUE_CAPACITY);
+        let bundle_routes = Router::new()
+            .route("/bundle.tar.gz", get(bundle_endpoint))
+            .route("/bundle.tar", get(uncompressed_bundle_endpoint))
+            .with_state(current_bundle.clone())
+            .merge(signature::router(current_bundle.clone(), bundle_signer))
+            .merge(discovery_routes)
+            .merge(split_bundles.router())
+            .merge(channels::router(
+                current_bundle.clone(),
+                stable_bundle.clone(),
+            ))
+            .merge(revision_history::router(current_bundle.clone()))
+            .merge(args.compression.apply(data::router(current_bundle.clone())))
+            .merge(args.request_decompression.apply(opa_status::router(
+                args.opa_status,
+                current_bundle.clone(),
+                polling_pause.clone(),
+                circuit_breaker.clone(),
+            )))
+            .merge(
+                optimization::BundleOptimizer::from_args(args.optimization)
+                    .map(|optimizer| optimizer.router(current_bundle.clone()))
+                    .unwrap_or_default(),
+            );
+        #[cfg(feature = "decision-logs")]
+        let bundle_routes = bundle_routes.merge(args.request_decompression.apply(
+            decision_logs::router(&args.decision_logs, decision_log_events),
+        ));
+        #[cfg(feature = "chaos")]
+        let bundle_routes = bundle_routes.route_layer(axum::middleware::from_fn_with_state(
+            faults,
+            chaos::inject_faults,
+        ));
+        let bundle_routes = bundle_routes
+            .route_layer(axum::middleware::from_fn_with_state(
+                tracked_clients,
+                clients::track_clients,
+            ))
+            .route_layer(bearer_layer.clone());
+        let routes = args.request_limits.apply(bundle_routes).merge(
+            args.compression.apply(
+                admin_routes
+                    .merge(health::router(fetch_health.clone(), task_health.clone()))
+                    .merge(schemas::router())
+                    .merge(version::router())
+                    .merge(openapi::router()),
+            ),
+        );
+
+        let mut tasks = Vec::<BackgroundTask>::new();
+        tasks.push(Box::pin(export::export_revisions(
+            args.export,
+            current_bundle.clone(),
+        )));
+        tasks.push(Box::pin(policy_source::sync_periodically(
+            policy_source,
+            refresh_requested.clone(),
+        )));
+        tasks.push(Box::pin(scoped::warm_variants(current_bundle.clone())));
+        tasks.push(Box::pin(channels::promote_periodically(
+            args.channels,
+            current_bundle.clone(),
+            stable_bundle,
+        )));
+        #[cfg(feature = "decision-logs")]
+        tasks.push(Box::pin(decision_logs::forward_decision_logs(
+            args.decision_logs,
+            decision_log_queue,
+        )));
+        #[cfg(any(feature = "kafka", feature = "nats"))]
+        tasks.push(Box::pin(revision_events::publish_revisions(
+            args.revision_events,
+            current_bundle.clone(),
+        )));
+        #[cfg(feature = "sentry")]
+        if let Some(reported_revision) = reported_revision {
+            tasks.push(Box::pin(error_reporting::follow_revisions(
+                reported_revision,
+                current_bundle.clone(),
+            )));
+        }
+        #[cfg(feature = "cdc")]
+        tasks.push(Box::pin(cdc::follow_binlog(
+            args.cdc,
+            refresh_requested.clone(),
+        )));
+        #[cfg(feature = "k8s")]
+        let config_reloads = {
+            let (reloads, config_reloads) = tokio::sync::watch::channel(None);
+            tasks.push(Box::pin(config_map::watch_config_map(
+                args.config_map,
+                reloads,
+                refresh_requested.clone(),
+            )));
+            config_reloads
+        };
+        #[cfg(feature = "grpc")]
+        tasks.push(Box::pin(grpc::serve(
+            args.grpc,
+            current_bundle.clone(),
+            bearer_layer.clone(),
+        )));
+        let bundle_updater = Arc::new(Mutex::new(BundleUpdater {
+            current_bundle,
+            ispyb_pool,
+            refresh_requested,
+            fetch_health,
+            polling_pause,
+            circuit_breaker,
+            polling_interval: polling::AdaptiveInterval::new(
+                args.polling_interval.into(),
+                args.max_polling_interval.map(Into::into),
+            ),
+            missed_polls: args.missed_polls,
+            full_refresh_interval: args.full_refresh_interval.map(Into::into),
+            dataset_intervals: polling::DatasetIntervals::from(args.dataset_polling_intervals),
+            partial_updates: partial_update::PartialUpdates::from(args.partial_update_datasets),
+            change_detection: args.change_detection,
+            query_timeout: args.query_timeout.into(),
+            include_personal_data: args.include_personal_data,
+            include_session_members: args.include_session_members,
+            proposal_filters: args.proposal_filters,
+            disabled_datasets,
+            augmenters,
+            fetch_status,
+            volume_monitor,
+            anomaly_guard,
+            size_guard: size_guard::SizeGuard::new(args.max_bundle_bytes),
+            redactions,
+            transformations,
+            layouts,
+            dataset_roots,
+            policies,
+            split_bundles,
+            bundle_cache_path: args.bundle_cache_path,
+            #[cfg(feature = "redis")]
+            shared_cache,
+            #[cfg(feature = "k8s")]
+            leader_election,
+            #[cfg(feature = "k8s")]
+            config_reloads,
+            #[cfg(feature = "upstream")]
+            upstream,
+            systemd: systemd::SystemdNotifier::from_env(),
+        }));
+        tasks.push(Box::pin(supervisor::supervise(
+            "update_bundle",
+            task_health,
+            polling::AdaptiveInterval::new(
+                args.task_restart_delay.into(),
+                Some(args.max_task_restart_delay.into()),
+            ),
+            move || update_bundle(bundle_updater.clone()),
+        )));
+        Self { routes, tasks }
+    }
+}
+
+/// Creates a [`Router`] serving the HTTP API of the [`BundlerState`], such that it can be merged into or nested within the router of another service
+///
+/// Unlike that served by the `bundler` binary, the router has no fallback, leaving unmatched requests to the embedding service
+pub fn router(state: &BundlerState) -> Router {
+    with_common_layers(state.routes.clone())
+}
+
+/// Runs the background tasks of the [`BundlerState`], including that polling ISPyB for bundle updates, which never exit
+///
+/// # Panics
+///
+/// Panics should any background task exit
+pub async fn run_tasks(state: BundlerState) {
+    let mut tasks = tokio::task::JoinSet::new();
+    for task in state.tasks {
+        tasks.spawn(task);
+    }
+    if let Some(result) = tasks.join_next().await {
+        result.unwrap();
+    }
+    panic!("Background task exited unexpectedly")
+}
+
+/// Applies the layers common to all routes of the HTTP API, which describe errors as problem details and trace each request by its 'X-Request-Id'
+fn with_common_layers(routes: Router) -> Router {
+    routes
+        .layer(axum::middleware::from_fn(problem::problem_details))
+        .layer(
+            ServiceBuilder::new()
+                .layer(SetRequestIdLayer::x_request_id(MakeRequestUuid))
+                .layer(
+                    TraceLayer::new_for_http()
+                        .make_span_with(make_request_span)
+                        .on_request(DefaultOnRequest::default().level(tracing::Level::INFO))
+                        .on_response(DefaultOnResponse::new().level(tracing::Level::INFO))
+                        .on_failure(DefaultOnFailure::new().level(tracing::Level::INFO)),
+                )
+                .layer(PropagateRequestIdLayer::x_request_id()),
+        )
+}
+
+/// Creates the span of a request, recording its 'X-Request-Id' such that requests can be correlated with those of clients
+///
+/// Any W3C trace context propagated by the caller becomes the parent of the span, such that requests appear within the trace of the caller
+fn make_request_span(request: &axum::extract::Request) -> tracing::Span {
+    let span = tracing::info_span!(
+        "request",
+        method = %request.method(),
+        uri = %request.uri(),
+        version = ?request.version(),
+        request_id = request
+            .headers()
+            .get("x-request-id")
+            .and_then(|request_id| request_id.to_str().ok())
+            .unwrap_or_default(),
+    );
+    span.set_parent(trace_context::remote_context(request.headers()));
+    span
+}
+
+/// Produces the bundle to serve when the initial bundle could not be fetched, falling back to the cache, then to a placeholder if lazily connecting
+async fn fallback_bundle(
+    err: anyhow::Error,
+    bundle_cache_path: Option<&Path>,
+    lazy_connect: bool,
+) -> BundleFile {
+    let cached_bundle = match bundle_cache_path {
+        Some(bundle_cache_path) => {
+            tracing::warn!("Could not fetch initial bundle, falling back to cache: {err}");
+            bundle_cache::load(bundle_cache_path).await
+        }
+        None => Err(err),
+    };
+    match cached_bundle {
+        Ok(bundle_file) => {
+            tracing::info!("Using stale bundle with revision: {}", bundle_file.revision);
+            bundle_file
+        }
+        Err(err) if lazy_connect => {
+            tracing::warn!("No bundle available, serving none until the first fetch: {err}");
+            BundleFile::placeholder()
+        }
+        Err(err) => panic!("Could not fetch initial bundle: {err}"),
+    }
+}
+
+/// Sets up Logging & Tracing using jaeger if available
+///
+/// Returns the guard of the log file writer, if logging to a file, which must be held until exit such that buffered logs are flushed
+fn setup_telemetry(
+    log_level: tracing::Level,
+    log_format: LogFormat,
+    log_file: &log_file::LogFileArgs,
+    otel_collector_url: Option<Url>,
+    sampler: opentelemetry_sdk::trace::Sampler,
+) -> Result<Option<tracing_appender::non_blocking::WorkerGuard>, anyhow::Error> {
+    let level_filter = tracing_subscriber::filter::LevelFilter::from_level(log_level);
+    let (log_layer, json_log_layer) = match log_format {
+        LogFormat::Pretty => (Some(tracing_subscriber::fmt::layer()), None),
+        LogFormat::Json => (
+            None,
+            Some(
+                tracing_subscriber::fmt::layer()
+                    .json()
+                    .with_current_span(true)
+                    .with_span_list(true),
+            ),
+        ),
+    };
+    let (log_file_writer, log_file_guard) = log_file.open()?.unzip();
+    let (file_log_layer, json_file_log_layer) = match (log_file_writer, log_format) {
+        (None, _) => (None, None),
+        (Some(writer), LogFormat::Pretty) => (
+            Some(
+                tracing_subscriber::fmt::layer()
+                    .with_ansi(false)
+                    .with_writer(writer),
+            ),
+            None,
+        ),
+        (Some(writer), LogFormat::Json) => (
+            None,
+            Some(
+                tracing_subscriber::fmt::layer()
+                    .json()
+                    .with_current_span(true)
+                    .with_span_list(true)
+                    .with_writer(writer),
+            ),
+        ),
+    };
+    let service_name_resource = opentelemetry_sdk::Resource::new(vec![
+        opentelemetry::KeyValue::new(
+            opentelemetry_semantic_conventions::resource::SERVICE_NAME,
+            built_info::PKG_NAME,
+        ),
+        opentelemetry::KeyValue::new(
+            opentelemetry_semantic_conventions::resource::SERVICE_VERSION,
+            built_info::PKG_VERSION,
+        ),
+    ]);
+    let (metrics_layer, tracing_layer) = if let Some(otel_collector_url) = otel_collector_url {
+        (
+            Some(tracing_opentelemetry::MetricsLayer::new(
+                opentelemetry_otlp::new_pipeline()
+                    .metrics(opentelemetry_sdk::runtime::Tokio)
+                    .with_exporter(
+                        opentelemetry_otlp::new_exporter()
+                            .tonic()
+                            .with_endpoint(otel_collector_url.clone()),
+                    )
+                    .with_resource(service_name_resource.clone())
+                    .with_period(Duration::from_secs(10))
+                    .build()?,
+            )),
+            Some(
+                tracing_opentelemetry::layer().with_tracer(
+                    opentelemetry_otlp::new_pipeline()
+                        .tracing()
+                        .with_exporter(
+                            opentelemetry_otlp::new_exporter()
+                                .tonic()
+                                .with_endpoint(otel_collector_url),
+                        )
+                        .with_trace_config(
+                            opentelemetry_sdk::trace::config()
+                                .with_resource(service_name_resource)
+                                .with_sampler(sampler),
+                        )
+                        .install_batch(opentelemetry_sdk::runtime::Tokio)?,
+                ),
+            ),
+        )
+    } else {
+        (None, None)
+    };
+
+    let registry = tracing_subscriber::Registry::default()
+        .with(level_filter)
+        .with(log_layer)
+        .with(json_log_layer)
+        .with(file_log_layer)
+        .with(json_file_log_layer)
+        .with(metrics_layer)
+        .with(tracing_layer);
+    #[cfg(feature = "sentry")]
+    let registry = registry.with(error_reporting::layer());
+    registry.init();
+    trace_context::install_propagator();
+
+    Ok(log_file_guard)
+}
+
+/// Fetches the intial [`Bundle`] from ISPyB and produces the correspoinding [`BundleFile`]
+#[allow(clippy::too_many_arguments)]
+#[instrument(skip(ispyb_pool, split_bundles))]
+async fn fetch_initial_bundle(
+    ispyb_pool: &mut IspybPool,
+    query_timeout: Duration,
+    include_personal_data: bool,
+    include_session_members: bool,
+    proposal_filters: &ProposalFilters,
+    disabled_datasets: &disabled_datasets::DisabledDatasets,
+    augmenters: &augmenter::BundleAugmenters,
+    fetch_status: &fetch_status::FetchStatus,
+    volume_monitor: &mut volume::VolumeMonitor,
+    redactions: &redaction::Redactions,
+    transformations: &transformation::Transformations,
+    layouts: &layout::DatasetLayouts,
+    dataset_roots: &dataset_roots::DatasetRoots,
+    policies: &policy_source::CurrentPolicies,
+    split_bundles: &split_bundles::SplitBundles,
+    size_guard: size_guard::SizeGuard,
+    bundle_cache_path: Option<&Path>,
+) -> Result<BundleFile, anyhow::Error> {
+    tracing::info!("Fetching initial bundle");
+    let bundle = ispyb_pool
+        .with_failover(|pool| async move {
+            Bundle::fetch(
+                NoMetadata,
+                &pool,
+                query_timeout,
+                include_personal_data,
+                proposal_filters,
+                disabled_datasets,
+                fetch_status,
+            )
+            .await
+        })
+        .await?;
+    let bundle = augmenters.augment(bundle).await?;
+    bundle.validate()?;
+    volume_monitor.observe(bundle.volumes()?);
+    let bundle = bundle
+        .redact(redactions.clone())
+        .transform(transformations.clone())
+        .with_layouts(layouts.clone())
+        .with_session_members(include_session_members)
+        .with_dataset_roots(dataset_roots.clone())
+        .with_policies(policies.get());
+    let bundle_file = BundleFile::try_from(&bundle)?;
+    if let Err(reason) = size_guard.check(bundle_file.tar.len()) {
+        tracing::error!(monotonic_counter.bundle_size_refusals = 1, "{reason}");
+        anyhow::bail!("Refusing initial bundle: {reason}");
+    }
+    if let Err(err) = verification::verify(&bundle_file) {
+        tracing::error!(monotonic_counter.bundle_verification_failures = 1, "{err}");
+        anyhow::bail!("Refusing initial bundle as it failed verification: {err}");
+    }
+    split_bundles.publish(&bundle).await;
+    tracing::info!("Using bundle with revison: {}", bundle_file.revision);
+    if let Some(bundle_cache_path) = bundle_cache_path {
+        cache_bundle(bundle_cache_path, &bundle_file).await;
+    }
+    Ok(bundle_file)
+}
+
+/// Stores the [`BundleFile`] in the cache, logging any failure
+async fn cache_bundle(bundle_cache_path: &Path, bundle_file: &BundleFile) {
+    if let Err(err) = bundle_cache::store(bundle_cache_path, &bundle_file.file).await {
+        tracing::warn!("Could not write bundle to cache: {err}");
+    }
+}
+
+/// Bind to the provided socket address and serve the application endpoints, over TLS if configured
+async fn serve_endpoints(port: u16, app: Router, #[cfg(feature = "tls")] tls: Option<tls::Tls>) {
+    let socket_addr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, port));
+    #[cfg(feature = "tls")]
+    if let Some(tls) = tls {
+        return tls::serve(socket_addr, app, &tls).await;
+    }
+    let listener = TcpListener::bind(socket_addr).await.unwrap();
+    tracing::info!("Serving HTTP API on {}", socket_addr);
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await
+    .unwrap()
+}
+
+/// The state with which the bundle is updated, retained across restarts of the update task
+struct BundleUpdater {
+    /// The bundle being served, which is replaced by each update
+    current_bundle: CurrentBundle,
+    /// The connection pool to ISPyB
+    ispyb_pool: IspybPool,
+    /// Notified when a refresh is requested, ahead of the next poll
+    refresh_requested: Arc<Notify>,
+    /// The record of bundle refreshes, from which readiness is determined
+    fetch_health: health::FetchHealth,
+    /// The switch pausing polling of ISPyB
+    polling_pause: pause::PollingPause,
+    /// The circuit breaker suspending fetches from ISPyB after repeated failures
+    circuit_breaker: circuit_breaker::CircuitBreaker,
+    /// The interval at which ISPyB is polled
+    polling_interval: polling::AdaptiveInterval,
+    /// How missed polls are made up
+    missed_polls: polling::MissedPolls,
+    /// The interval at which a full refresh is performed, if sessions are fetched incrementally
+    full_refresh_interval: Option<Duration>,
+    /// The intervals at which individual datasets are polled
+    dataset_intervals: polling::DatasetIntervals,
+    /// The datasets which may be carried over from the previous poll should their fetch fail
+    partial_updates: partial_update::PartialUpdates,
+    /// The means by which changes are detected before each fetch, if any
+    change_detection: Option<change_detection::ChangeDetection>,
+    /// The maximum time a single ISPyB query may take
+    query_timeout: Duration,
+    /// Whether personal data is included in the bundle
+    include_personal_data: bool,
+    /// Whether an index of the subjects associated with each session is included in the bundle
+    include_session_members: bool,
+    /// The filters excluding irrelevant proposals from the bundle
+    proposal_filters: ProposalFilters,
+    /// The datasets which are neither fetched nor included in the bundle
+    disabled_datasets: disabled_datasets::DisabledDatasets,
+    /// The hooks contributing additional data to each bundle fetched
+    augmenters: augmenter::BundleAugmenters,
+    /// The record of the most recent fetch of each dataset
+    fetch_status: fetch_status::FetchStatus,
+    /// The record of the volume of each dataset
+    volume_monitor: volume::VolumeMonitor,
+    /// The guard refusing updates which suspiciously shrink a dataset
+    anomaly_guard: anomaly_guard::AnomalyGuard,
+    /// The guard refusing updates which exceed the size limit
+    size_guard: size_guard::SizeGuard,
+    /// The redactions applied to datasets before serialization
+    redactions: redaction::Redactions,
+    /// The transformations applied to datasets before serialization
+    transformations: transformation::Transformations,
+    /// The layout in which each dataset is written
+    layouts: layout::DatasetLayouts,
+    /// The manifest root under which each dataset is placed
+    dataset_roots: dataset_roots::DatasetRoots,
+    /// The policies included in the bundle
+    policies: policy_source::CurrentPolicies,
+    /// The bundles containing individual datasets, built from each update
+    split_bundles: split_bundles::SplitBundles,
+    /// The path at which the latest bundle is stored, if any
+    bundle_cache_path: Option<PathBuf>,
+    /// The bundle cache shared between replicas, if configured
+    #[cfg(feature = "redis")]
+    shared_cache: Option<shared_cache::SharedCache>,
+    /// The election of a leader amongst replicas, if configured
+    #[cfg(feature = "k8s")]
+    leader_election: Option<leader_election::LeaderElection>,
+    /// The settings most recently reloaded from the Kubernetes ConfigMap, if any
+    #[cfg(feature = "k8s")]
+    config_reloads: tokio::sync::watch::Receiver<Option<config_map::ReloadableConfig>>,
+    /// The upstream bundler from which the bundle is mirrored in place of ISPyB, if configured
+    #[cfg(feature = "upstream")]
+    upstream: Option<upstream::Upstream>,
+    /// The notifier signalling readiness and liveness to systemd
+    systemd: systemd::SystemdNotifier,
+}
+
+/// Periodically update the bundle with new data from ISPyB, or sooner if a refresh is requested, unless polling is paused or the circuit breaker is open
+///
+/// Failures are retried at the next poll whilst a stale bundle is being served, or if readiness thresholds are configured to report them, otherwise the task panics and is restarted by its supervisor
+///
+/// Readiness is signalled to systemd once a bundle is being served, and its watchdog pinged from this loop, such that systemd restarts the service should the loop become wedged
+async fn update_bundle(bundle_updater: Arc<Mutex<BundleUpdater>>) {
+    let mut bundle_updater = bundle_updater.lock().await;
+    let BundleUpdater {
+        current_bundle,
+        ispyb_pool,
+        refresh_requested,
+        fetch_health,
+        polling_pause,
+        circuit_breaker,
+        polling_interval,
+        missed_polls,
+        full_refresh_interval,
+        dataset_intervals,
+        partial_updates,
+        change_detection,
+        query_timeout,
+        include_personal_data,
+        include_session_members,
+        proposal_filters,
+        disabled_datasets,
+        augmenters,
+        fetch_status,
+        volume_monitor,
+        anomaly_guard,
+        size_guard,
+        redactions,
+        transformations,
+        layouts,
+        dataset_roots,
+        policies,
+        split_bundles,
+        bundle_cache_path,
+        #[cfg(feature = "redis")]
+        shared_cache,
+        #[cfg(feature = "k8s")]
+        leader_election,
+        #[cfg(feature = "k8s")]
+        config_reloads,
+        #[cfg(feature = "upstream")]
+        upstream,
+        systemd,
+    } = &mut *bundle_updater;
+    let (full_refresh_interval, change_detection, query_timeout, include_personal_data) = (
+        *full_refresh_interval,
+        *change_detection,
+        *query_timeout,
+        *include_personal_data,
+    );
+    let mut poll_schedule = polling::PollSchedule::new(
+        if current_bundle.as_ref().read().await.stale {
+            Instant::now()
+        } else {
+            Instant::now().add(polling_interval.current())
+        },
+        polling_interval.current(),
+        *missed_polls,
+    );
+    let mut next_full_refresh = Instant::now();
+    let mut snapshot = None::<SessionSnapshot>;
+    let mut retained = None::<RetainedDatasets>;
+    let mut fingerprint = None::<String>;
+    let watchdog_interval = systemd.watchdog_interval();
+    let mut next_watchdog_ping = Instant::now();
+
+    loop {
+        {
+            let bundle_file = current_bundle.as_ref().read().await;
+            if !bundle_file.is_placeholder() {
+                systemd.ready(&bundle_file.revision);
+            }
+        }
+        poll_schedule.set_period(polling_interval.current());
+        let refresh = tokio::select! {
+            _ = sleep_until(next_watchdog_ping), if watchdog_interval.is_some() => {
+                systemd.ping_watchdog();
+                next_watchdog_ping = Instant::now().add(watchdog_interval.unwrap_or_default());
+                continue;
+            }
+            _ = poll_schedule.tick() => false,
+            _ = refresh_requested.notified() => {
+                tracing::info!("Refresh requested");
+                polling_interval.reset();
+                poll_schedule.bring_forward(polling_interval.current());
+                true
+            }
+        };
+        if polling_pause.is_paused() {
+            tracing::debug!("Skipping poll of ISPyB, as polling is paused");
+            continue;
+        }
+        #[cfg(feature = "k8s")]
+        if config_reloads.has_changed().unwrap_or(false) {
+            if let Some(config) = config_reloads.borrow_and_update().clone() {
+                polling_interval.rebase(config.polling_interval.into());
+                *dataset_intervals =
+                    polling::DatasetIntervals::from(config.dataset_polling_intervals);
+                *proposal_filters = config.proposal_filters;
+                *redactions = redaction::Redactions::from(config.redaction);
+                *layouts = layout::DatasetLayouts::from(config.dataset_layouts);
+                *dataset_roots = dataset_roots::DatasetRoots::from(config.dataset_roots);
+                *include_session_members = config.include_session_members;
+                tracing::info!("Applying reloaded settings");
+                snapshot = None;
+                retained = None;
+                fingerprint = None;
+            }
+        }
+        #[cfg(feature = "upstream")]
+        if let Some(upstream) = upstream.as_ref() {
+            match upstream
+                .mirror(current_bundle, bundle_cache_path.as_deref())
+                .await
+            {
+                Ok(true) => {
+                    fetch_health.record_success();
+                    if polling_interval.reset() {
+                        poll_schedule.bring_forward(polling_interval.current());
+                    }
+                }
+                Ok(false) => {
+                    fetch_health.record_success();
+                    polling_interval.back_off();
+                }
+                Err(err)
+                    if current_bundle.as_ref().read().await.stale
+                        || fetch_health.tolerates_failures() =>
+                {
+                    fetch_health.record_failure();
+                    tracing::warn!(
+                        monotonic_counter.upstream_fetch_failures = 1,
+                        "Could not mirror bundle from upstream, retrying at next poll: {err}"
+                    );
+                }
+                Err(err) => panic!("Could not mirror bundle from upstream: {err}"),
+            }
+            continue;
+        }
+        #[cfg(feature = "redis")]
+        if let Some(shared_cache) = shared_cache.as_mut() {
+            if !shared_cache.lead_or_follow(&current_bundle).await {
+                fetch_health.record_success();
+                snapshot = None;
+                retained = None;
+                fingerprint = None;
+                continue;
+            }
+        }
+        #[cfg(feature = "k8s")]
+        if let Some(leader_election) = leader_election.as_mut() {
+            if !leader_election.lead_or_follow(&current_bundle).await {
+                fetch_health.record_success();
+                snapshot = None;
+                retained = None;
+                fingerprint = None;
+                continue;
+            }
+        }
+        if !circuit_breaker.permit(Instant::now()) {
+            tracing::debug!("Skipping poll of ISPyB, as the circuit breaker is open");
+            continue;
+        }
+        let previous_fingerprint = fingerprint.take();
+        if let Some(change_detection) = change_detection {
+            fingerprint = ispyb_pool
+                .with_failover(|pool| async move {
+                    with_timeout(
+                        "change_fingerprint",
+                        query_timeout,
+                        change_detection.fingerprint(&pool),
+                    )
+                    .await
+                })
+                .await
+                .inspect_err(|err| tracing::warn!("Could not detect changes to ISPyB: {err}"))
+                .ok();
+            if !refresh
+                && fingerprint.is_some()
+                && fingerprint == previous_fingerprint
+                && !current_bundle.as_ref().read().await.stale
+            {
+                tracing::info!("No changes detected, skipping fetch");
+                fetch_health.record_success();
+                polling_interval.back_off();
+                continue;
+            }
+        }
+        tracing::info!("Updating bundle");
+        let bundle = if let Some(full_refresh_interval) = full_refresh_interval {
+            if Instant::now() >= next_full_refresh {
+                tracing::info!("Performing full refresh");
+                snapshot = None;
+                next_full_refresh = Instant::now().add(full_refresh_interval);
+            }
+            ispyb_pool
+                .with_failover(|pool| {
+                    let snapshot = snapshot.as_ref();
+                    let proposal_filters = &proposal_filters;
+                    let disabled_datasets = &disabled_datasets;
+                    let fetch_status = &fetch_status;
+                    async move {
+                        Bundle::fetch_incremental(
+                            NoMetadata,
+                            &pool,
+                            query_timeout,
+                            include_personal_data,
+                            proposal_filters,
+                            disabled_datasets,
+                            fetch_status,
+                            snapshot,
+                        )
+                        .await
+                    }
+                })
+                .await
+                .map(|(bundle, new_snapshot)| {
+                    snapshot = Some(new_snapshot);
+                    bundle
+                })
+        } else {
+            ispyb_pool
+                .with_failover(|pool| {
+                    let retained = retained.as_ref();
+                    let proposal_filters = &proposal_filters;
+                    let disabled_datasets = &disabled_datasets;
+                    let fetch_status = &fetch_status;
+                    let dataset_intervals = &dataset_intervals;
+                    let partial_updates = &partial_updates;
+                    async move {
+                        Bundle::fetch_retaining(
+                            NoMetadata,
+                            &pool,
+                            query_timeout,
+                            include_personal_data,
+                            proposal_filters,
+                            disabled_datasets,
+                            fetch_status,
+                            dataset_intervals,
+                            partial_updates,
+                            retained,
+                        )
+                        .await
+                    }
+                })
+                .await
+                .map(|(bundle, new_retained)| {
+                    retained = Some(new_retained);
+                    bundle
+                })
+        };
+        match &bundle {
+            Ok(_) => circuit_breaker.record_success(),
+            Err(_) => circuit_breaker.record_failure(Instant::now()),
+        }
+        let bundle = match bundle {
+            Ok(bundle) => {
+                fetch_health.record_success();
+                bundle
+            }
+            Err(err)
+                if current_bundle.as_ref().read().await.stale
+                    || fetch_health.tolerates_failures() =>
+            {
+                fetch_health.record_failure();
+                tracing::warn!(
+                    monotonic_counter.bundle_fetch_failures = 1,
+                    "Could not update bundle, retrying at next poll: {err}"
+                );
+                fingerprint = None;
+                continue;
+            }
+            Err(err) => panic!("Could not update bundle: {err}"),
+        };
+        let bundle = match augmenters.augment(bundle).await {
+            Ok(bundle) => bundle,
+            Err(err) => {
+                tracing::error!(
+                    monotonic_counter.bundle_augmentation_failures = 1,
+                    "Refusing bundle update, retrying at next poll: {err}"
+                );
+                fingerprint = None;
+                continue;
+            }
+        };
+        if let Err(err) = bundle.validate() {
+            tracing::error!(
+                monotonic_counter.bundle_validation_failures = 1,
+                "Refusing bundle update, retrying at next poll: {err}"
+            );
+            snapshot = None;
+            retained = None;
+            fingerprint = None;
+            continue;
+        }
+        match bundle.volumes() {
+            Ok(volumes) => {
+                if let Err(reason) = anomaly_guard.check(volume_monitor.previous(), &volumes) {
+                    tracing::error!(
+                        monotonic_counter.bundle_anomalies_refused = 1,
+                        "Refusing bundle update, serving the previous bundle until accepted via /admin/force-update: {reason}"
+                    );
+                    fetch_health.record_degraded(Some(reason));
+                    snapshot = None;
+                    retained = None;
+                    fingerprint = None;
+                    continue;
+                }
+                volume_monitor.observe(volumes);
+            }
+            Err(err) => tracing::warn!("Could not measure dataset volumes: {err}"),
+        }
+        let bundle = bundle
+            .redact(redactions.clone())
+            .transform(transformations.clone())
+            .with_layouts(layouts.clone())
+            .with_session_members(*include_session_members)
+            .with_dataset_roots(dataset_roots.clone())
+            .with_policies(policies.get());
+        let bundle_file = BundleFile::try_from(&bundle).unwrap();
+        if let Err(reason) = size_guard.check(bundle_file.tar.len()) {
+            tracing::error!(
+                monotonic_counter.bundle_size_refusals = 1,
+                "Refusing bundle update, serving the previous bundle until the bundle shrinks: {reason}"
+            );
+            fetch_health.record_degraded(Some(reason));
+            snapshot = None;
+            retained = None;
+            fingerprint = None;
+            continue;
+        }
+        if let Err(err) = verification::verify(&bundle_file) {
+            tracing::error!(
+                monotonic_counter.bundle_verification_failures = 1,
+                "Refusing bundle update, serving the previous bundle as the built bundle failed verification: {err}"
+            );
+            fetch_health.record_degraded(Some(format!("Bundle failed verification: {err}")));
+            snapshot = None;
+            retained = None;
+            fingerprint = None;
+            continue;
+        }
+        fetch_health.record_degraded(None);
+        split_bundles.publish(&bundle).await;
+        if let Some(bundle_cache_path) = bundle_cache_path.as_deref() {
+            cache_bundle(bundle_cache_path, &bundle_file).await;
+        }
+        #[cfg(feature = "redis")]
+        if let Some(shared_cache) = shared_cache.as_mut() {
+            shared_cache.publish(&bundle_file).await;
+        }
+        let old_revision = current_bundle.as_ref().read().await.revision.clone();
+        let new_revision = bundle_file.revision.clone();
+        if new_revision == old_revision {
+            polling_interval.back_off();
+            tracing::debug!(
+                "Bundle unchanged, polling every {}",
+                humantime::format_duration(polling_interval.current())
+            );
+        } else if polling_interval.reset() {
+            tracing::info!(
+                "Bundle changed, polling every {}",
+                humantime::format_duration(polling_interval.current())
+            );
+            poll_schedule.bring_forward(polling_interval.current());
+        }
+        if current_bundle.replace(bundle_file).await {
+            tracing::info!("Updated bundle from {} to {}", old_revision, new_revision);
+        } else {
+            tracing::warn!(
+                "Bundle pinned to {}, not updating to {}",
+                old_revision,
+                new_revision
+            );
+        }
+    }
+}
+
+/// The query parameters accepted by the bundle endpoints
+#[derive(Debug, Deserialize)]
+struct BundleQuery {
+    /// The revision of a previously served bundle to return in place of the current bundle
+    revision: Option<String>,
+}
+
+/// Returns the Open Policy Agent bundle in gzipped tar format
+///
+/// ETag matching is supported via the 'If-None-Match' header, requests containing this header will not recieve any data if it matches the current bundle version
+///
+/// A previously served revision may be requested via the 'revision' query parameter, for reproducing past decisions, and is returned whilst it remains in the revision history. No data is returned if the 'If-None-Match' header lists the ETag of the requested revision, even once it is no longer retained
+#[utoipa::path(
+    get,
+    path = "/bundle.tar.gz",
+    tag = "bundle",
+    params(
+        ("revision" = Option<String>, Query, description = "The revision of a previously served bundle to return in place of the current bundle"),
+        ("If-None-Match" = Option<String>, Header, description = "The ETags of previously fetched bundles, any of which is matched using weak comparison"),
+    ),
+    responses(
+        (status = OK, description = "The bundle in gzipped tar format", content_type = "application/gzip", body = [u8]),
+        (status = NOT_MODIFIED, description = "The bundle matches the 'If-None-Match' header"),
+        (status = GONE, description = "The requested revision is no longer retained"),
+        (status = SERVICE_UNAVAILABLE, description = "No bundle has been fetched yet"),
+        (status = UNAUTHORIZED, description = "A valid bearer token was not provided"),
+    ),
+)]
+async fn bundle_endpoint(
+    State(current_bundle): State<CurrentBundle>,
+    Query(query): Query<BundleQuery>,
+    scope: Option<Extension<Scope>>,
+    if_none_match: Option<TypedHeader<IfNoneMatch>>,
+) -> Response {
+    requested_bundle_response(
+        &current_bundle,
+        query.revision.as_deref(),
+        ArchiveFormat::TarGz,
+        scope,
+        if_none_match,
+    )
+    .await
+}
+
+/// Returns the Open Policy Agent bundle in uncompressed tar format, sharing the ETag of the gzipped bundle
+///
+/// ETag matching is supported via the 'If-None-Match' header, requests containing this header will not recieve any data if it matches the current bundle version
+#[utoipa::path(
+    get,
+    path = "/bundle.tar",
+    tag = "bundle",
+    params(
+        ("revision" = Option<String>, Query, description = "The revision of a previously served bundle to return in place of the current bundle"),
+        ("If-None-Match" = Option<String>, Header, description = "The ETags of previously fetched bundles, any of which is matched using weak comparison"),
+    ),
+    responses(
+        (status = OK, description = "The bundle in uncompressed tar format", content_type = "application/x-tar", body = [u8]),
+        (status = NOT_MODIFIED, description = "The bundle matches the 'If-None-Match' header"),
+        (status = GONE, description = "The requested revision is no longer retained"),
+        (status = SERVICE_UNAVAILABLE, description = "No bundle has been fetched yet"),
+        (status = UNAUTHORIZED, description = "A valid bearer token was not provided"),
+    ),
+)]
+async fn uncompressed_bundle_endpoint(
+    State(current_bundle): State<CurrentBundle>,
+    Query(query): Query<BundleQuery>,
+    scope: Option<Extension<Scope>>,
+    if_none_match: Option<TypedHeader<IfNoneMatch>>,
+) -> Response {
+    requested_bundle_response(
+        &current_bundle,
+        query.revision.as_deref(),
+        ArchiveFormat::Tar,
+        scope,
+        if_none_match,
+    )
+    .await
+}
+
+/// Produces a response containing the current [`BundleFile`], or the retained bundle of the requested revision, or HTTP 410 Gone if the revision is no longer retained
+async fn requested_bundle_response(
+    current_bundle: &CurrentBundle,
+    revision: Option<&str>,
+    format: ArchiveFormat,
+    scope: Option<Extension<Scope>>,
+    if_none_match: Option<TypedHeader<IfNoneMatch>>,
+) -> Response {
+    let bundle_file = current_bundle.as_ref().read().await.clone();
+    let Some(revision) = revision.filter(|revision| *revision != bundle_file.revision) else {
+        return scoped_bundle_response(current_bundle, &bundle_file, format, scope, if_none_match)
+            .await;
+    };
+    if let Some(not_modified) =
+        revision_not_modified(revision, scope.as_ref(), if_none_match.as_ref())
+    {
+        return not_modified;
+    }
+    let Some(previous) = current_bundle.history.read().await.get(revision).cloned() else {
+        return ApiError::Gone(revision.to_string()).into_response();
+    };
+    scoped_bundle_response(current_bundle, &previous, format, scope, if_none_match).await
+}
+
+/// Produces a response containing the [`BundleFile`], restricted to the [`Scope`] of the requesting token if it is restricted
+async fn scoped_bundle_response(
+    current_bundle: &CurrentBundle,
+    bundle_file: &BundleFile,
+    format: ArchiveFormat,
+    scope: Option<Extension<Scope>>,
+    if_none_match: Option<TypedHeader<IfNoneMatch>>,
+) -> Response {
+    match scoped_bundle_file(current_bundle, bundle_file, scope).await {
+        Ok(variant) => bundle_response(&variant, format, if_none_match).into_response(),
+        Err(err) => {
+            tracing::error!("Could not build scoped bundle: {err}");
+            ApiError::Internal("Could not build scoped bundle".to_string()).into_response()
+        }
+    }
+}
+
+/// The [`BundleFile`] served to the holder of the [`Scope`], being a variant restricted to its beamlines if it has any
+async fn scoped_bundle_file<'a>(
+    current_bundle: &CurrentBundle,
+    bundle_file: &'a BundleFile,
+    scope: Option<Extension<Scope>>,
+) -> Result<Cow<'a, BundleFile>, anyhow::Error> {
+    let Some(Extension(scope)) = scope
+        .filter(|Extension(scope)| scope.beamlines().is_some() && !bundle_file.is_placeholder())
+    else {
+        return Ok(Cow::Borrowed(bundle_file));
+    };
+    scoped::get_or_build(&current_bundle.scoped_variants, bundle_file, &scope)
+        .await
+        .map(Cow::Owned)
+}
+
+/// Produces a response containing no data if the 'If-None-Match' header lists the ETag under which the requested revision is served to the holder of the [`Scope`]
+///
+/// The contents of a revision never change, so a client holding it need not refetch it whether or not it is still retained. A wildcard is not matched, as the revision may no longer exist
+fn revision_not_modified(
+    revision: &str,
+    scope: Option<&Extension<Scope>>,
+    if_none_match: Option<&TypedHeader<IfNoneMatch>>,
+) -> Option<Response> {
+    let TypedHeader(if_none_match) = if_none_match?;
+    if *if_none_match == IfNoneMatch::any() {
+        return None;
+    }
+    let revision = match scope {
+        Some(Extension(scope)) if scope.beamlines().is_some() => {
+            Cow::Owned(scoped::variant_revision(revision, scope))
+        }
+        _ => Cow::Borrowed(revision),
+    };
+    let etag = ETag::from_str(&format!(r#""{revision}""#)).ok()?;
+    if if_none_match.precondition_passes(&etag) {
+        return None;
+    }
+    let mut headers = HeaderMap::new();
+    headers.typed_insert(etag);
+    Some((StatusCode::NOT_MODIFIED, headers).into_response())
+}
+
+/// The legacy 'Digest' header of RFC 3230, carrying the digest of the bundle
+static DIGEST: HeaderName = HeaderName::from_static("digest");
+
+/// The 'Repr-Digest' header of RFC 9530, carrying the digest of the bundle
+static REPR_DIGEST: HeaderName = HeaderName::from_static("repr-digest");
+
+/// Produces a response containing the [`BundleFile`] in the requested [`ArchiveFormat`], or no data if the 'If-None-Match' header matches its ETag or it is a placeholder
+///
+/// The SHA-256 digest of the bundle is included via the 'Digest' and 'Repr-Digest' headers, such that clients may detect truncation or corruption
+fn bundle_response(
+    bundle_file: &BundleFile,
+    format: ArchiveFormat,
+    if_none_match: Option<TypedHeader<IfNoneMatch>>,
+) -> (StatusCode, HeaderMap, Bytes) {
+    if bundle_file.is_placeholder() {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            HeaderMap::new(),
+            Bytes::new(),
+        );
+    }
+    let (archive, digest) = bundle_file.archive(format);
+    let mut headers = HeaderMap::new();
+    headers.typed_insert(bundle_file.etag.clone());
+    headers.insert(
+        CONTENT_TYPE,
+        HeaderValue::from_static(format.content_type()),
+    );
+    if let Ok(digest) = HeaderValue::from_str(&format!("sha-256={digest}")) {
+        headers.insert(DIGEST.clone(), digest);
+    }
+    if let Ok(repr_digest) = HeaderValue::from_str(&format!("sha-256=:{digest}:")) {
+        headers.insert(REPR_DIGEST.clone(), repr_digest);
+    }
+    if bundle_file.stale {
+        headers.insert(
+            WARNING,
+            HeaderValue::from_static(r#"110 - "Response is Stale""#),
+        );
+    }
+    tracing::info!(
+        "Request had If-None-Match of {:?}, current ETag is {:?}",
+        if_none_match,
+        bundle_file.etag
+    );
+    match if_none_match {
+        Some(TypedHeader(if_none_match))
+            if !if_none_match.precondition_passes(&bundle_file.etag) =>
+        {
+            (StatusCode::NOT_MODIFIED, headers, Bytes::new())
+        }
+        _ => (StatusCode::OK, headers, archive.clone()),
+    }
+}
+
+/// Returns a HTTP 404 status code when a non-existant route is queried
+async fn fallback_endpoint() -> impl IntoResponse {
+    ApiError::NotFound("No route matches the requested path".to_string())
+}
+
+/// Outputs the bundle schema as a set of files or to standard output
+fn bundle_schema(args: BundleSchemaArgs) {
+    let schemas = Bundle::<NoMetadata>::schemas()
+        .into_iter()
+        .map(|(name, schema)| (name, serde_json::to_string_pretty(&schema).unwrap()));
+    if let Some(path) = args.path {
+        for (name, schema) in schemas {
+            let mut schema_file =
+                File::create(path.clone().join(name).with_extension("json")).unwrap();
+            schema_file.write_all(schema.as_bytes()).unwrap();
+        }
+    } else {
+        println!(
+            "{}",
+            schemas
+                .map(|(_, schema)| schema)
+                .collect::<Vec<_>>()
+                .join("\n\n---\n\n")
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        bundle_response, requested_bundle_response, ArchiveFormat, BundleFile, CurrentBundle,
+        REPR_DIGEST,
+    };
+    use axum::http::{
+        header::{CONTENT_TYPE, ETAG},
+        HeaderValue, StatusCode,
+    };
+    use axum_extra::TypedHeader;
+    use headers::{Header, IfNoneMatch};
+
+    fn bundle_file(revision: &str) -> BundleFile {
+        BundleFile::from_archives(
+            revision.to_string(),
+            Default::default(),
+            Default::default(),
+            false,
+        )
+        .unwrap()
+    }
+
+    fn if_none_match(value: &'static str) -> Option<TypedHeader<IfNoneMatch>> {
+        let values = [HeaderValue::from_static(value)];
+        Some(TypedHeader(
+            IfNoneMatch::decode(&mut values.iter()).unwrap(),
+        ))
+    }
+
+    #[tokio::test]
+    async fn pinned_bundle_not_replaced() {
+        let current_bundle = CurrentBundle::new(bundle_file("a"), 2);
+        assert!(current_bundle.replace(bundle_file("b")).await);
+        assert!(current_bundle.pin("a").await);
+        assert_eq!("a", current_bundle.as_ref().read().await.revision);
+        assert!(!current_bundle.replace(bundle_file("c")).await);
+        assert_eq!("a", current_bundle.as_ref().read().await.revision);
+        current_bundle.unpin();
+        assert!(current_bundle.replace(bundle_file("c")).await);
+        assert_eq!("c", current_bundle.as_ref().read().await.revision);
+    }
+
+    #[tokio::test]
+    async fn pin_unknown_revision() {
+        let current_bundle = CurrentBundle::new(bundle_file("a"), 2);
+        assert!(!current_bundle.pin("b").await);
+        assert!(current_bundle.replace(bundle_file("b")).await);
+    }
+
+    #[tokio::test]
+    async fn requested_revision_served_while_retained() {
+        let current_bundle = CurrentBundle::new(bundle_file("a"), 1);
+        assert!(current_bundle.replace(bundle_file("b")).await);
+        assert!(current_bundle.replace(bundle_file("c")).await);
+        for (revision, status, etag) in [
+            (None, StatusCode::OK, Some(r#""c""#)),
+            (Some("c"), StatusCode::OK, Some(r#""c""#)),
+            (Some("b"), StatusCode::OK, Some(r#""b""#)),
+            (Some("a"), StatusCode::GONE, None),
+        ] {
+            let response = requested_bundle_response(
+                &current_bundle,
+                revision,
+                ArchiveFormat::TarGz,
+                None,
+                None,
+            )
+            .await;
+            assert_eq!(status, response.status(), "{revision:?}");
+            assert_eq!(
+                etag,
+                response
+                    .headers()
+                    .get(ETAG)
+                    .map(|etag| etag.to_str().unwrap()),
+                "{revision:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn any_listed_etag_matched_weakly() {
+        let bundle_file = bundle_file("a");
+        for (header, status) in [
+            (r#""a""#, StatusCode::NOT_MODIFIED),
+            (r#"W/"a""#, StatusCode::NOT_MODIFIED),
+            (r#""x", W/"a", "y""#, StatusCode::NOT_MODIFIED),
+            ("*", StatusCode::NOT_MODIFIED),
+            (r#""x", "y""#, StatusCode::OK),
+        ] {
+            let (response_status, _, _) =
+                bundle_response(&bundle_file, ArchiveFormat::TarGz, if_none_match(header));
+            assert_eq!(status, response_status, "{header}");
+        }
+    }
+
+    #[tokio::test]
+    async fn requested_revision_not_modified_once_evicted() {
+        let current_bundle = CurrentBundle::new(bundle_file("a"), 1);
+        assert!(current_bundle.replace(bundle_file("b")).await);
+        assert!(current_bundle.replace(bundle_file("c")).await);
+        for (revision, header, status) in [
+            ("b", r#""x", W/"b""#, StatusCode::NOT_MODIFIED),
+            ("a", r#""c", "a""#, StatusCode::NOT_MODIFIED),
+            ("a", r#""c""#, StatusCode::GONE),
+            ("a", "*", StatusCode::GONE),
+        ] {
+            let response = requested_bundle_response(
+                &current_bundle,
+                Some(revision),
+                ArchiveFormat::TarGz,
+                None,
+                if_none_match(header),
+            )
+            .await;
+            assert_eq!(status, response.status(), "{revision} {header}");
+        }
+    }
+
+    #[tokio::test]
+    async fn placeholder_unavailable_until_replaced() {
+        let current_bundle = CurrentBundle::new(BundleFile::placeholder(), 2);
+        let (status, _, _) = bundle_response(
+            &*current_bundle.as_ref().read().await,
+            ArchiveFormat::TarGz,
+            None,
+        );
+        assert_eq!(StatusCode::SERVICE_UNAVAILABLE, status);
+        assert!(current_bundle.replace(bundle_file("a")).await);
+        let (status, _, _) = bundle_response(
+            &*current_bundle.as_ref().read().await,
+            ArchiveFormat::TarGz,
+            None,
+        );
+        assert_eq!(StatusCode::OK, status);
+        assert!(!current_bundle.pin("").await);
+    }
+
+    #[test]
+    fn response_contains_digest() {
+        let bundle_file =
+            BundleFile::from_archives("a".to_string(), "def".into(), "abc".into(), false).unwrap();
+        let (_, headers, _) = bundle_response(&bundle_file, ArchiveFormat::TarGz, None);
+        assert_eq!(
+            "sha-256=:ungWv48Bz+pBQUDeXa4iI7ADYaOWF3qctBD/YfIAFa0=:",
+            headers[&REPR_DIGEST]
+        );
+    }
+
+    #[test]
+    fn uncompressed_response_shares_etag() {
+        let bundle_file =
+            BundleFile::from_archives("a".to_string(), "def".into(), "abc".into(), false).unwrap();
+        let (_, gzipped_headers, gzipped) =
+            bundle_response(&bundle_file, ArchiveFormat::TarGz, None);
+        let (_, headers, uncompressed) = bundle_response(&bundle_file, ArchiveFormat::Tar, None);
+        assert_eq!("abc", gzipped);
+        assert_eq!("def", uncompressed);
+        assert_eq!(gzipped_headers[ETAG], headers[ETAG]);
+        assert_eq!("application/gzip", gzipped_headers[CONTENT_TYPE]);
+        assert_eq!("application/x-tar", headers[CONTENT_TYPE]);
+    }
+}
@@ -0,0 +1,170 @@
+use clap::Args;
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+use tracing_appender::non_blocking::{NonBlocking, WorkerGuard};
+
+/// Options for writing logs to a rotating file, in addition to standard output
+#[derive(Debug, Clone, Args)]
+pub struct LogFileArgs {
+    /// The path of a file to which logs are additionally written, for deployments without a log shipper
+    #[arg(long, env = "BUNDLER_LOG_FILE")]
+    log_file: Option<PathBuf>,
+    /// The size, in bytes, beyond which the log file is rotated
+    #[arg(long, env = "BUNDLER_LOG_FILE_MAX_BYTES")]
+    log_file_max_bytes: Option<u64>,
+    /// The age beyond which the log file is rotated, such as '1d'
+    #[arg(long, env = "BUNDLER_LOG_FILE_MAX_AGE")]
+    log_file_max_age: Option<humantime::Duration>,
+    /// The number of rotated log files retained, suffixed '.1' for the most recent, beyond which the oldest are deleted
+    #[arg(long, env = "BUNDLER_LOG_FILES_RETAINED", default_value_t = 5)]
+    log_files_retained: usize,
+}
+
+impl LogFileArgs {
+    /// Opens the log file, if configured, returning a writer which writes to it from a background thread and the guard which flushes it when dropped
+    pub fn open(&self) -> Result<Option<(NonBlocking, WorkerGuard)>, io::Error> {
+        let Some(path) = &self.log_file else {
+            return Ok(None);
+        };
+        let rotating_file = RotatingFile::open(
+            path.clone(),
+            self.log_file_max_bytes,
+            self.log_file_max_age.map(Into::into),
+            self.log_files_retained,
+        )?;
+        Ok(Some(tracing_appender::non_blocking(rotating_file)))
+    }
+}
+
+/// A log file which is rotated once it exceeds its maximum size or age, retaining a bounded number of rotated files
+struct RotatingFile {
+    /// The path of the current log file
+    path: PathBuf,
+    /// The current log file, opened for appending
+    file: File,
+    /// The number of bytes in the current log file
+    size: u64,
+    /// The time at which the current log file was opened
+    opened_at: Instant,
+    /// The size beyond which the log file is rotated, if any
+    max_bytes: Option<u64>,
+    /// The age beyond which the log file is rotated, if any
+    max_age: Option<Duration>,
+    /// The number of rotated log files retained
+    retained: usize,
+}
+
+impl RotatingFile {
+    /// Opens the log file at the path for appending, creating it if it does not exist
+    fn open(
+        path: PathBuf,
+        max_bytes: Option<u64>,
+        max_age: Option<Duration>,
+        retained: usize,
+    ) -> Result<Self, io::Error> {
+        let file = append(&path)?;
+        Ok(Self {
+            size: file.metadata()?.len(),
+            path,
+            file,
+            opened_at: Instant::now(),
+            max_bytes,
+            max_age,
+            retained,
+        })
+    }
+
+    /// Whether the log file should be rotated before the given number of bytes are written to it
+    fn is_due(&self, incoming: usize) -> bool {
+        self.size > 0
+            && (self
+                .max_bytes
+                .is_some_and(|max_bytes| self.size + incoming as u64 > max_bytes)
+                || self
+                    .max_age
+                    .is_some_and(|max_age| self.opened_at.elapsed() >= max_age))
+    }
+
+    /// Moves each retained log file to the next suffix, deleting the oldest, and starts a new log file
+    fn rotate(&mut self) -> Result<(), io::Error> {
+        for index in (1..self.retained).rev() {
+            ignore_missing(std::fs::rename(
+                self.rotated(index),
+                self.rotated(index + 1),
+            ))?;
+        }
+        if self.retained > 0 {
+            std::fs::rename(&self.path, self.rotated(1))?;
+        } else {
+            std::fs::remove_file(&self.path)?;
+        }
+        self.file = append(&self.path)?;
+        self.size = 0;
+        self.opened_at = Instant::now();
+        Ok(())
+    }
+
+    /// The path of the rotated log file with the given suffix
+    fn rotated(&self, index: usize) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{index}"));
+        PathBuf::from(path)
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.is_due(buf.len()) {
+            self.rotate()?;
+        }
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// Opens the file at the path for appending, creating it if it does not exist
+fn append(path: &Path) -> Result<File, io::Error> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+/// Treats the absence of a file as success
+fn ignore_missing(result: Result<(), io::Error>) -> Result<(), io::Error> {
+    match result {
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RotatingFile;
+    use std::io::Write;
+
+    #[test]
+    fn rotated_by_size() {
+        let directory = std::env::temp_dir().join("bundler-log-file-test");
+        let _ = std::fs::remove_dir_all(&directory);
+        std::fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("bundler.log");
+        let mut log_file = RotatingFile::open(path.clone(), Some(8), None, 2).unwrap();
+        for line in ["first\n", "second\n", "third\n", "fourth\n"] {
+            log_file.write_all(line.as_bytes()).unwrap();
+        }
+        log_file.flush().unwrap();
+        let read = |name: &str| std::fs::read_to_string(directory.join(name)).unwrap();
+        assert_eq!("fourth\n", read("bundler.log"));
+        assert_eq!("third\n", read("bundler.log.1"));
+        assert_eq!("second\n", read("bundler.log.2"));
+        assert!(!directory.join("bundler.log.3").exists());
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,362 @@
+use crate::{
+    circuit_breaker::{CircuitBreaker, CircuitReport, CircuitState},
+    pause::PollingPause,
+    CurrentBundle,
+};
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::IntoResponse,
+    routing::{get, post},
+    Json, Router,
+};
+use clap::Args;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+use tokio::sync::RwLock;
+use utoipa::{OpenApi, ToSchema};
+
+/// Options for receiving status reports from Open Policy Agent instances
+#[derive(Debug, Clone, Args)]
+pub struct OpaStatusArgs {
+    /// The name under which agents are configured to load the bundle served by this application
+    #[arg(
+        long,
+        env = "BUNDLER_OPA_BUNDLE_NAME",
+        default_value = "permissionables"
+    )]
+    opa_bundle_name: String,
+    /// The time an agent may report an out of date revision before it is considered stuck
+    #[arg(long, env = "BUNDLER_OPA_REVISION_LAG_THRESHOLD", default_value_t = humantime::Duration::from(Duration::from_secs(300)))]
+    opa_revision_lag_threshold: humantime::Duration,
+}
+
+/// A status report, as sent by an Open Policy Agent instance
+#[derive(Debug, Deserialize, ToSchema)]
+struct StatusReport {
+    /// Labels identifying the agent
+    labels: AgentLabels,
+    /// The status of each bundle loaded by the agent, keyed by bundle name
+    #[serde(default)]
+    bundles: HashMap<String, BundleStatus>,
+}
+
+/// Labels identifying an Open Policy Agent instance
+#[derive(Debug, Deserialize, ToSchema)]
+struct AgentLabels {
+    /// The unique identifier of the agent
+    id: String,
+    /// The version of Open Policy Agent run by the agent
+    version: Option<String>,
+}
+
+/// The status of a single bundle, as reported by an Open Policy Agent instance
+#[derive(Debug, Default, Deserialize, ToSchema)]
+struct BundleStatus {
+    /// The revision of the currently active bundle
+    active_revision: Option<String>,
+    /// The error code of the last activation, if it failed
+    code: Option<String>,
+    /// The error message of the last activation, if it failed
+    message: Option<String>,
+}
+
+/// The last known bundle activation state of an Open Policy Agent instance
+#[derive(Debug, Clone, Serialize, ToSchema)]
+struct AgentStatus {
+    /// The version of Open Policy Agent run by the agent
+    version: Option<String>,
+    /// The revision of the bundle active on the agent
+    active_revision: Option<String>,
+    /// Whether the last bundle activation succeeded
+    activation_succeeded: bool,
+    /// The error message of the last activation, if it failed
+    message: Option<String>,
+    /// The time at which the agent last reported its status
+    #[serde(serialize_with = "crate::timestamp::serialize")]
+    #[schema(value_type = String, format = DateTime)]
+    last_report: SystemTime,
+    /// The time since which the agent has reported an out of date revision
+    #[serde(serialize_with = "crate::timestamp::serialize_optional")]
+    #[schema(value_type = Option<String>, format = DateTime)]
+    behind_since: Option<SystemTime>,
+    /// Whether the agent has reported an out of date revision for longer than the lag threshold
+    stuck: bool,
+}
+
+impl AgentStatus {
+    /// Produces the updated status of an agent following a report, given its previous status
+    fn update(
+        previous: Option<&Self>,
+        version: Option<String>,
+        bundle_status: BundleStatus,
+        current_revision: &str,
+        lag_threshold: Duration,
+        now: SystemTime,
+    ) -> Self {
+        let behind = bundle_status.active_revision.as_deref() != Some(current_revision);
+        let behind_since = behind.then(|| {
+            previous
+                .and_then(|previous| previous.behind_since)
+                .unwrap_or(now)
+        });
+        let stuck = behind_since.is_some_and(|behind_since| {
+            now.duration_since(behind_since).unwrap_or_default() >= lag_threshold
+        });
+        Self {
+            version,
+            active_revision: bundle_status.active_revision,
+            activation_succeeded: bundle_status.code.is_none(),
+            message: bundle_status.message,
+            last_report: now,
+            behind_since,
+            stuck,
+        }
+    }
+}
+
+/// The last known status of each Open Policy Agent instance, keyed by agent id
+type AgentStatuses = Arc<RwLock<BTreeMap<String, AgentStatus>>>;
+
+/// The paths served by the status endpoints
+#[derive(OpenApi)]
+#[openapi(
+    paths(status_receiver, status_endpoint),
+    components(schemas(
+        StatusReport,
+        AgentLabels,
+        BundleStatus,
+        ServiceStatus,
+        ServedBundle,
+        CircuitReport,
+        CircuitState,
+        AgentStatus
+    ))
+)]
+pub struct OpaStatusApi;
+
+/// Shared state of the status endpoints
+#[derive(Clone)]
+struct StatusState {
+    /// The last known status of each agent
+    agents: AgentStatuses,
+    /// The bundle currently being served, against which agent revisions are compared
+    current_bundle: CurrentBundle,
+    /// The name under which agents load the bundle
+    bundle_name: Arc<str>,
+    /// The time an agent may report an out of date revision before it is considered stuck
+    lag_threshold: Duration,
+    /// The switch pausing polling of ISPyB
+    polling_pause: PollingPause,
+    /// The circuit breaker around fetches from ISPyB
+    circuit_breaker: CircuitBreaker,
+}
+
+/// Creates a [`Router`] serving the status receiver and the status of known agents
+pub fn router(
+    args: OpaStatusArgs,
+    current_bundle: CurrentBundle,
+    polling_pause: PollingPause,
+    circuit_breaker: CircuitBreaker,
+) -> Router {
+    Router::new()
+        .route("/status/opa", post(status_receiver))
+        .route("/status", get(status_endpoint))
+        .with_state(StatusState {
+            agents: AgentStatuses::default(),
+            current_bundle,
+            bundle_name: args.opa_bundle_name.into(),
+            lag_threshold: args.opa_revision_lag_threshold.into(),
+            polling_pause,
+            circuit_breaker,
+        })
+}
+
+/// Records a status report sent by an Open Policy Agent instance
+///
+/// Agents whose active revision has lagged behind the current revision for longer than the threshold are logged as stuck
+#[utoipa::path(
+    post,
+    path = "/status/opa",
+    tag = "status",
+    request_body = StatusReport,
+    responses((status = OK, description = "The status report was recorded")),
+)]
+async fn status_receiver(
+    State(state): State<StatusState>,
+    Json(mut report): Json<StatusReport>,
+) -> impl IntoResponse {
+    let agent_id = report.labels.id;
+    let bundle_status = report
+        .bundles
+        .remove(state.bundle_name.as_ref())
+        .unwrap_or_default();
+    let current_revision = state.current_bundle.as_ref().read().await.revision.clone();
+    let mut agents = state.agents.write().await;
+    let previous = agents.get(&agent_id);
+    let status = AgentStatus::update(
+        previous,
+        report.labels.version,
+        bundle_status,
+        &current_revision,
+        state.lag_threshold,
+        SystemTime::now(),
+    );
+    tracing::info!(
+        monotonic_counter.opa_status_reports = 1,
+        activation_succeeded = status.activation_succeeded,
+    );
+    if !status.activation_succeeded {
+        tracing::warn!(
+            "Agent {agent_id} failed to activate bundle: {}",
+            status.message.as_deref().unwrap_or_default()
+        );
+    }
+    let was_stuck = previous.is_some_and(|previous| previous.stuck);
+    if status.stuck && !was_stuck {
+        tracing::info!(counter.opa_agents_stuck = 1);
+        tracing::warn!(
+            "Agent {agent_id} stuck on revision {:?}, current revision is {current_revision}",
+            status.active_revision
+        );
+    } else if !status.stuck && was_stuck {
+        tracing::info!(counter.opa_agents_stuck = -1);
+        tracing::info!("Agent {agent_id} caught up to revision {current_revision}");
+    }
+    agents.insert(agent_id, status);
+    StatusCode::OK
+}
+
+/// The status of the service, comprising the bundle being served and the agents consuming it
+#[derive(Debug, Serialize, ToSchema)]
+struct ServiceStatus {
+    /// The bundle currently being served
+    bundle: ServedBundle,
+    /// The time since which polling of ISPyB has been paused, if it is
+    #[serde(serialize_with = "crate::timestamp::serialize_optional")]
+    #[schema(value_type = Option<String>, format = DateTime)]
+    polling_paused_since: Option<SystemTime>,
+    /// The state of the circuit breaker around fetches from ISPyB
+    circuit_breaker: CircuitReport,
+    /// The last known status of each agent, keyed by agent id
+    agents: BTreeMap<String, AgentStatus>,
+}
+
+/// The identity of the bundle currently being served
+#[derive(Debug, Serialize, ToSchema)]
+struct ServedBundle {
+    /// The revision of the bundle
+    revision: String,
+    /// The SHA-256 digest of the bundle, as in the 'Repr-Digest' header
+    digest: String,
+}
+
+/// Returns the revision and digest of the current bundle, whether polling of ISPyB is paused or suspended by the circuit breaker, and the last known status of each Open Policy Agent instance
+#[utoipa::path(
+    get,
+    path = "/status",
+    tag = "status",
+    responses((status = OK, description = "The status of the service", body = ServiceStatus)),
+)]
+async fn status_endpoint(State(state): State<StatusState>) -> impl IntoResponse {
+    let bundle = {
+        let bundle_file = state.current_bundle.as_ref().read().await;
+        ServedBundle {
+            revision: bundle_file.revision.clone(),
+            digest: format!("sha-256=:{}:", bundle_file.digest),
+        }
+    };
+    Json(ServiceStatus {
+        bundle,
+        polling_paused_since: state.polling_pause.paused_since(),
+        circuit_breaker: state.circuit_breaker.report(),
+        agents: state.agents.read().await.clone(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AgentStatus, BundleStatus};
+    use std::time::{Duration, SystemTime};
+
+    fn bundle_status(active_revision: &str) -> BundleStatus {
+        BundleStatus {
+            active_revision: Some(active_revision.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn update_up_to_date() {
+        let now = SystemTime::now();
+        let status = AgentStatus::update(
+            None,
+            None,
+            bundle_status("current"),
+            "current",
+            Duration::from_secs(300),
+            now,
+        );
+        assert!(status.activation_succeeded);
+        assert_eq!(None, status.behind_since);
+        assert!(!status.stuck);
+    }
+
+    #[test]
+    fn update_stuck_after_threshold() {
+        let start = SystemTime::now();
+        let threshold = Duration::from_secs(300);
+        let first = AgentStatus::update(
+            None,
+            None,
+            bundle_status("old"),
+            "current",
+            threshold,
+            start,
+        );
+        assert_eq!(Some(start), first.behind_since);
+        assert!(!first.stuck);
+        let second = AgentStatus::update(
+            Some(&first),
+            None,
+            bundle_status("old"),
+            "current",
+            threshold,
+            start + threshold,
+        );
+        assert_eq!(Some(start), second.behind_since);
+        assert!(second.stuck);
+        let third = AgentStatus::update(
+            Some(&second),
+            None,
+            bundle_status("current"),
+            "current",
+            threshold,
+            start + threshold * 2,
+        );
+        assert_eq!(None, third.behind_since);
+        assert!(!third.stuck);
+    }
+
+    #[test]
+    fn update_failed_activation() {
+        let status = AgentStatus::update(
+            None,
+            None,
+            BundleStatus {
+                active_revision: None,
+                code: Some("bundle_error".to_string()),
+                message: Some("activation failed".to_string()),
+            },
+            "current",
+            Duration::from_secs(300),
+            SystemTime::now(),
+        );
+        assert!(!status.activation_succeeded);
+        assert_eq!(Some("activation failed"), status.message.as_deref());
+    }
+}
@@ -0,0 +1,145 @@
+use crate::{
+    anomaly_guard, bundle::DATASETS, channels, clients, data, effective_config, fetch_status,
+    health, opa_status, optimization, pause, revision_history, rollback, schemas, signature,
+    version,
+};
+use axum::{extract::State, response::IntoResponse, routing::get, Json, Router};
+use utoipa::{
+    openapi::{
+        security::{HttpAuthScheme, HttpBuilder, SecurityScheme},
+        OpenApi as OpenApiDocument,
+    },
+    Modify, OpenApi,
+};
+
+/// The paths served at the root of the API
+#[derive(OpenApi)]
+#[openapi(
+    paths(
+        crate::bundle_endpoint,
+        crate::uncompressed_bundle_endpoint,
+        openapi_endpoint
+    ),
+    components(schemas(crate::problem::Problem)),
+    modifiers(&SecuritySchemes),
+    security(("bearer" = []), ("basic" = [])),
+)]
+struct ApiDoc;
+
+/// Adds the bearer token and HTTP Basic security schemes, either of which is required by all paths unless otherwise stated
+struct SecuritySchemes;
+
+impl Modify for SecuritySchemes {
+    fn modify(&self, openapi: &mut OpenApiDocument) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "bearer",
+            SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+        );
+        components.add_security_scheme(
+            "basic",
+            SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Basic).build()),
+        );
+    }
+}
+
+/// Produces the OpenAPI document describing the HTTP API
+fn document() -> OpenApiDocument {
+    let mut document = ApiDoc::openapi();
+    document.merge(channels::ChannelsApi::openapi());
+    document.merge(revision_history::RevisionHistoryApi::openapi());
+    document.merge(data::DataApi::openapi());
+    document.merge(rollback::RollbackApi::openapi());
+    document.merge(anomaly_guard::AnomalyGuardApi::openapi());
+    document.merge(pause::PauseApi::openapi());
+    document.merge(opa_status::OpaStatusApi::openapi());
+    document.merge(health::HealthApi::openapi());
+    document.merge(fetch_status::FetchStatusApi::openapi());
+    document.merge(clients::ClientsApi::openapi());
+    document.merge(effective_config::EffectiveConfigApi::openapi());
+    document.merge(schemas::SchemasApi::openapi());
+    document.merge(version::VersionApi::openapi());
+    document.merge(signature::SignatureApi::openapi());
+    document.merge(optimization::OptimizationApi::openapi());
+    #[cfg(feature = "decision-logs")]
+    document.merge(crate::decision_logs::DecisionLogsApi::openapi());
+    #[cfg(feature = "chaos")]
+    document.merge(crate::chaos::ChaosApi::openapi());
+    for (path, operation_id) in [
+        ("/discovery.tar.gz", "discovery_endpoint"),
+        ("/channels/canary/bundle.tar.gz", "canary_bundle_endpoint"),
+        ("/channels/stable/bundle.tar.gz", "stable_bundle_endpoint"),
+    ] {
+        if let Some(mut path_item) = document.paths.paths.get("/bundle.tar.gz").cloned() {
+            for operation in path_item.operations.values_mut() {
+                operation.operation_id = Some(operation_id.to_string());
+            }
+            document.paths.paths.insert(path.to_string(), path_item);
+        }
+    }
+    for dataset in DATASETS {
+        if let Some(mut path_item) = document.paths.paths.get("/bundle.tar.gz").cloned() {
+            for operation in path_item.operations.values_mut() {
+                operation.operation_id = Some(format!("{dataset}_bundle_endpoint"));
+                operation.description = Some(format!(
+                    "Returns the bundle containing only {dataset}, if configured to be split"
+                ));
+            }
+            document
+                .paths
+                .paths
+                .insert(format!("/bundles/{dataset}.tar.gz"), path_item);
+        }
+    }
+    document
+}
+
+/// Creates a [`Router`] serving the OpenAPI document
+pub fn router() -> Router {
+    Router::new()
+        .route("/openapi.json", get(openapi_endpoint))
+        .with_state(document())
+}
+
+/// Returns the OpenAPI document describing the HTTP API
+#[utoipa::path(
+    get,
+    path = "/openapi.json",
+    tag = "meta",
+    security(()),
+    responses((status = OK, description = "The OpenAPI document", content_type = "application/json")),
+)]
+async fn openapi_endpoint(State(document): State<OpenApiDocument>) -> impl IntoResponse {
+    Json(document)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::document;
+
+    #[test]
+    fn document_contains_routes() {
+        let document = document();
+        for path in [
+            "/bundle.tar.gz",
+            "/bundle.tar.gz.sig",
+            "/channels/stable/bundle.tar.gz",
+            "/channels/stable/promote",
+            "/bundles/revisions/{file_name}",
+            "/rollback/{revision}",
+            "/status",
+            "/healthz",
+            "/readyz",
+            "/version",
+            "/admin/fetch-status",
+            "/admin/config",
+            "/admin/force-update",
+            "/admin/pause",
+            "/admin/resume",
+            "/schemas/{file_name}",
+            "/data/{dataset}",
+        ] {
+            assert!(document.paths.paths.contains_key(path), "{path} missing");
+        }
+    }
+}
@@ -0,0 +1,258 @@
+use crate::{
+    bundle_response, problem::ApiError, scoped::Scope, scoped_bundle_file, ArchiveFormat,
+    BundleFile, CurrentBundle,
+};
+use axum::{
+    extract::State,
+    response::{IntoResponse, Response},
+    routing::get,
+    Extension, Router,
+};
+use axum_extra::TypedHeader;
+use clap::Args;
+use headers::IfNoneMatch;
+use std::{
+    borrow::Cow,
+    collections::VecDeque,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+use tokio::sync::Mutex;
+use utoipa::OpenApi;
+
+/// The number of optimized bundles retained, such that recent revisions and scoped variants need not be rebuilt
+const CACHE_CAPACITY: usize = 8;
+
+/// A counter distinguishing the working directories of concurrent builds
+static BUILDS: AtomicU64 = AtomicU64::new(0);
+
+/// Options for serving a bundle optimized by `opa build`, for faster evaluation by Open Policy Agent
+#[derive(Debug, Clone, Args)]
+pub struct OptimizationArgs {
+    /// Entrypoints for which the bundle is optimized by `opa build`, and served at '/bundle.optimized.tar.gz'. Optimization is disabled unless at least one entrypoint is given
+    #[arg(
+        long = "optimization-entrypoint",
+        env = "BUNDLER_OPTIMIZATION_ENTRYPOINTS",
+        value_delimiter = ','
+    )]
+    optimization_entrypoints: Vec<String>,
+    /// The optimization level passed to `opa build`
+    #[arg(long, env = "BUNDLER_OPTIMIZATION_LEVEL", default_value_t = 2, value_parser = clap::value_parser!(u8).range(0..=2))]
+    optimization_level: u8,
+    /// The Open Policy Agent binary with which bundles are optimized
+    #[arg(long, env = "BUNDLER_OPA_PATH", default_value = "opa")]
+    opa: PathBuf,
+}
+
+/// The paths served by the optimized bundle endpoint
+#[derive(OpenApi)]
+#[openapi(paths(optimized_bundle_endpoint))]
+pub struct OptimizationApi;
+
+/// Builds bundles optimized by `opa build`, caching the optimized bundle of each revision
+#[derive(Debug, Clone)]
+pub struct BundleOptimizer(Arc<Optimizer>);
+
+/// The configuration and cache of a [`BundleOptimizer`]
+#[derive(Debug)]
+struct Optimizer {
+    /// The Open Policy Agent binary with which bundles are optimized
+    opa: PathBuf,
+    /// The optimization level passed to `opa build`
+    level: u8,
+    /// The entrypoints for which bundles are optimized
+    entrypoints: Vec<String>,
+    /// The most recently optimized bundles, most recent first, held whilst a bundle is optimized such that each revision is built once
+    cache: Mutex<VecDeque<BundleFile>>,
+}
+
+impl BundleOptimizer {
+    /// Creates a [`BundleOptimizer`] if any entrypoints are configured
+    pub fn from_args(args: OptimizationArgs) -> Option<Self> {
+        if args.optimization_entrypoints.is_empty() {
+            return None;
+        }
+        Some(Self(Arc::new(Optimizer {
+            opa: args.opa,
+            level: args.optimization_level,
+            entrypoints: args.optimization_entrypoints,
+            cache: Mutex::new(VecDeque::new()),
+        })))
+    }
+
+    /// The optimized [`BundleFile`] of the same revision as the [`BundleFile`], built with `opa build` unless cached
+    pub async fn optimize(&self, bundle_file: &BundleFile) -> Result<BundleFile, anyhow::Error> {
+        let mut cache = self.0.cache.lock().await;
+        if let Some(optimized) = cache
+            .iter()
+            .position(|optimized| optimized.revision == bundle_file.revision)
+            .and_then(|position| cache.remove(position))
+        {
+            cache.push_front(optimized.clone());
+            return Ok(optimized);
+        }
+        let optimizer = self.0.clone();
+        let unoptimized = bundle_file.clone();
+        let optimized =
+            tokio::task::spawn_blocking(move || optimizer.build(&unoptimized)).await??;
+        tracing::info!(
+            monotonic_counter.bundle_optimizations = 1,
+            "Optimized bundle of revision {}",
+            optimized.revision
+        );
+        cache.push_front(optimized.clone());
+        cache.truncate(CACHE_CAPACITY);
+        Ok(optimized)
+    }
+
+    /// Creates a [`Router`] serving the optimized bundle
+    pub fn router(&self, current_bundle: CurrentBundle) -> Router {
+        Router::new().route(
+            "/bundle.optimized.tar.gz",
+            get(optimized_bundle_endpoint).with_state((current_bundle, self.clone())),
+        )
+    }
+}
+
+impl Optimizer {
+    /// Optimizes the [`BundleFile`] with `opa build` in a working directory of its own, which is removed afterwards
+    fn build(&self, bundle_file: &BundleFile) -> Result<BundleFile, anyhow::Error> {
+        let working_dir = std::env::temp_dir().join(format!(
+            "bundler-optimize-{}-{}",
+            std::process::id(),
+            BUILDS.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::create_dir_all(&working_dir)?;
+        let optimized = self.build_in(&working_dir, bundle_file);
+        if let Err(err) = std::fs::remove_dir_all(&working_dir) {
+            tracing::warn!(
+                "Could not remove optimization directory {}: {err}",
+                working_dir.display()
+            );
+        }
+        optimized
+    }
+
+    /// Optimizes the [`BundleFile`] with `opa build`, writing the input and output bundles to the working directory
+    fn build_in(
+        &self,
+        working_dir: &Path,
+        bundle_file: &BundleFile,
+    ) -> Result<BundleFile, anyhow::Error> {
+        let input = working_dir.join("bundle.tar.gz");
+        let output = working_dir.join("optimized.tar.gz");
+        std::fs::write(&input, &bundle_file.file)?;
+        let mut opa = Command::new(&self.opa);
+        opa.arg("build")
+            .args(["--optimize", &self.level.to_string()])
+            .args(["--revision", &bundle_file.revision])
+            .arg("--output")
+            .arg(&output);
+        for entrypoint in &self.entrypoints {
+            opa.args(["--entrypoint", entrypoint]);
+        }
+        let result = opa
+            .arg("--bundle")
+            .arg(&input)
+            .output()
+            .map_err(|err| anyhow::anyhow!("Could not run {}: {err}", self.opa.display()))?;
+        if !result.status.success() {
+            anyhow::bail!(
+                "opa build failed with {}: {}",
+                result.status,
+                String::from_utf8_lossy(&result.stderr).trim()
+            );
+        }
+        BundleFile::new(
+            bundle_file.revision.clone(),
+            std::fs::read(&output)?.into(),
+            bundle_file.stale,
+        )
+    }
+}
+
+/// Returns the Open Policy Agent bundle optimized for the configured entrypoints by `opa build`, in gzipped tar format
+///
+/// The optimized bundle shares the revision and ETag of the bundle it was built from, and is built on the first request for each revision. ETag matching is supported via the 'If-None-Match' header
+#[utoipa::path(
+    get,
+    path = "/bundle.optimized.tar.gz",
+    tag = "bundle",
+    params(
+        ("If-None-Match" = Option<String>, Header, description = "The ETags of previously fetched bundles, any of which is matched using weak comparison"),
+    ),
+    responses(
+        (status = OK, description = "The optimized bundle in gzipped tar format", content_type = "application/gzip", body = [u8]),
+        (status = NOT_MODIFIED, description = "The bundle matches the 'If-None-Match' header"),
+        (status = SERVICE_UNAVAILABLE, description = "No bundle has been fetched yet"),
+        (status = UNAUTHORIZED, description = "A valid bearer token was not provided"),
+    ),
+)]
+async fn optimized_bundle_endpoint(
+    State((current_bundle, optimizer)): State<(CurrentBundle, BundleOptimizer)>,
+    scope: Option<Extension<Scope>>,
+    if_none_match: Option<TypedHeader<IfNoneMatch>>,
+) -> Response {
+    let bundle_file = current_bundle.as_ref().read().await;
+    if bundle_file.is_placeholder() {
+        return ApiError::Unavailable.into_response();
+    }
+    let variant = scoped_bundle_file(&current_bundle, &bundle_file, scope)
+        .await
+        .map(Cow::into_owned);
+    drop(bundle_file);
+    let optimized = match variant {
+        Ok(variant) => optimizer.optimize(&variant).await,
+        Err(err) => Err(err),
+    };
+    match optimized {
+        Ok(optimized) => {
+            bundle_response(&optimized, ArchiveFormat::TarGz, if_none_match).into_response()
+        }
+        Err(err) => {
+            tracing::error!(
+                monotonic_counter.bundle_optimization_failures = 1,
+                "Could not optimize bundle: {err}"
+            );
+            ApiError::Internal("Could not optimize bundle".to_string()).into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BundleOptimizer, OptimizationArgs};
+    use clap::Parser;
+
+    #[derive(Parser)]
+    struct Args {
+        #[command(flatten)]
+        optimization: OptimizationArgs,
+    }
+
+    #[test]
+    fn disabled_without_entrypoints() {
+        assert!(BundleOptimizer::from_args(Args::parse_from(["bundler"]).optimization).is_none());
+        assert!(BundleOptimizer::from_args(
+            Args::parse_from([
+                "bundler",
+                "--optimization-entrypoint",
+                "diamond/policy/allow"
+            ])
+            .optimization
+        )
+        .is_some());
+        assert!(Args::try_parse_from([
+            "bundler",
+            "--optimization-entrypoint",
+            "diamond/policy/allow",
+            "--optimization-level",
+            "3"
+        ])
+        .is_err());
+    }
+}
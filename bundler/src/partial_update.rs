@@ -0,0 +1,49 @@
+use std::collections::BTreeSet;
+
+/// The datasets which may be carried over from the previous fetch when their own fetch fails, such that the remaining datasets are still updated
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PartialUpdates(BTreeSet<String>);
+
+impl From<Vec<String>> for PartialUpdates {
+    fn from(datasets: Vec<String>) -> Self {
+        Self(datasets.into_iter().collect())
+    }
+}
+
+impl PartialUpdates {
+    /// Whether the previous fetch of the named dataset may be served in place of a failed fetch
+    pub fn permits(&self, dataset: &str) -> bool {
+        self.0.contains(dataset)
+    }
+
+    /// Describes the datasets which may be carried over, as reported in the effective configuration
+    pub fn describe(&self) -> Vec<String> {
+        self.0.iter().cloned().collect()
+    }
+}
+
+/// The names of the datasets fetched from ISPyB, which may be carried over by a partial update
+pub const FETCHED_DATASETS: [&str; 9] = [
+    "subjects",
+    "sessions",
+    "proposals",
+    "beamlines",
+    "roles",
+    "people",
+    "session_participants",
+    "lab_contacts",
+    "instrument_scientists",
+];
+
+#[cfg(test)]
+mod tests {
+    use super::PartialUpdates;
+
+    #[test]
+    fn permits_configured_datasets() {
+        let partial_updates = PartialUpdates::from(vec!["roles".to_string()]);
+        assert!(partial_updates.permits("roles"));
+        assert!(!partial_updates.permits("sessions"));
+        assert!(!PartialUpdates::default().permits("roles"));
+    }
+}
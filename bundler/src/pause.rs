@@ -0,0 +1,131 @@
+use axum::{extract::State, response::IntoResponse, routing::post, Json, Router};
+use serde::Serialize;
+use std::{
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+use tokio::sync::Notify;
+use utoipa::{OpenApi, ToSchema};
+
+/// A thread safe switch pausing polling of ISPyB, such that planned maintenance of the database does not result in failed polls whilst the current bundle continues to be served
+#[derive(Debug, Clone, Default)]
+pub struct PollingPause(Arc<Mutex<Option<SystemTime>>>);
+
+impl PollingPause {
+    /// The time since which polling has been paused, if it is
+    pub fn paused_since(&self) -> Option<SystemTime> {
+        *self.0.lock().unwrap()
+    }
+
+    /// Whether polling is paused
+    pub fn is_paused(&self) -> bool {
+        self.paused_since().is_some()
+    }
+
+    /// Pauses polling, returning the time since which it has been paused, which is unchanged if it was already paused
+    pub fn pause(&self) -> SystemTime {
+        let mut paused_since = self.0.lock().unwrap();
+        if paused_since.is_none() {
+            tracing::info!(counter.polling_paused = 1);
+        }
+        *paused_since.get_or_insert_with(SystemTime::now)
+    }
+
+    /// Resumes polling, returning whether it was paused
+    pub fn resume(&self) -> bool {
+        let resumed = self.0.lock().unwrap().take().is_some();
+        if resumed {
+            tracing::info!(counter.polling_paused = -1);
+        }
+        resumed
+    }
+}
+
+/// Whether polling of ISPyB is paused
+#[derive(Debug, Serialize, ToSchema)]
+struct PollingState {
+    /// The time since which polling has been paused, if it is
+    #[serde(serialize_with = "crate::timestamp::serialize_optional")]
+    #[schema(value_type = Option<String>, format = DateTime)]
+    paused_since: Option<SystemTime>,
+}
+
+/// The paths served by the pause endpoints
+#[derive(OpenApi)]
+#[openapi(
+    paths(pause_endpoint, resume_endpoint),
+    components(schemas(PollingState))
+)]
+pub struct PauseApi;
+
+/// Shared state of the pause endpoints
+#[derive(Clone)]
+struct PauseState {
+    /// The switch pausing polling
+    polling_pause: PollingPause,
+    /// A notification which wakes the update loop
+    refresh_requested: Arc<Notify>,
+}
+
+/// Creates a [`Router`] serving the endpoints which pause and resume polling of ISPyB
+pub fn router(polling_pause: PollingPause, refresh_requested: Arc<Notify>) -> Router {
+    Router::new()
+        .route("/admin/pause", post(pause_endpoint))
+        .route("/admin/resume", post(resume_endpoint))
+        .with_state(PauseState {
+            polling_pause,
+            refresh_requested,
+        })
+}
+
+/// Pauses polling of ISPyB, such that the current bundle is served without refreshes until polling is resumed
+///
+/// Requested refreshes are ignored whilst paused, and the service remains ready regardless of the age of the bundle
+#[utoipa::path(
+    post,
+    path = "/admin/pause",
+    tag = "admin",
+    responses((status = OK, description = "Polling is paused", body = PollingState)),
+)]
+async fn pause_endpoint(State(state): State<PauseState>) -> impl IntoResponse {
+    let paused_since = state.polling_pause.pause();
+    tracing::warn!(
+        "Polling of ISPyB paused since {}",
+        humantime::format_rfc3339_seconds(paused_since)
+    );
+    Json(PollingState {
+        paused_since: Some(paused_since),
+    })
+}
+
+/// Resumes polling of ISPyB, refreshing the bundle immediately if polling was paused
+#[utoipa::path(
+    post,
+    path = "/admin/resume",
+    tag = "admin",
+    responses((status = OK, description = "Polling is resumed", body = PollingState)),
+)]
+async fn resume_endpoint(State(state): State<PauseState>) -> impl IntoResponse {
+    if state.polling_pause.resume() {
+        tracing::info!("Polling of ISPyB resumed");
+        state.refresh_requested.notify_one();
+    }
+    Json(PollingState { paused_since: None })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PollingPause;
+
+    #[test]
+    fn paused_until_resumed() {
+        let polling_pause = PollingPause::default();
+        assert!(!polling_pause.is_paused());
+        assert!(!polling_pause.resume());
+        let paused_since = polling_pause.pause();
+        assert_eq!(paused_since, polling_pause.pause());
+        assert_eq!(Some(paused_since), polling_pause.paused_since());
+        assert!(polling_pause.resume());
+        assert!(!polling_pause.is_paused());
+    }
+}
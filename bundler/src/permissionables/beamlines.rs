@@ -1,12 +1,14 @@
 use derive_more::{Deref, DerefMut};
 use schemars::JsonSchema;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use sqlx::{query_as, MySqlPool};
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashSet};
 use tracing::instrument;
 
 /// A mapping of beamlines to their various attributes
-#[derive(Debug, Default, Deref, DerefMut, PartialEq, Eq, Hash, Serialize, JsonSchema)]
+#[derive(
+    Debug, Default, Clone, Deref, DerefMut, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema,
+)]
 pub struct Beamlines(BTreeMap<String, Beamline>);
 
 impl Beamlines {
@@ -28,10 +30,60 @@ impl Beamlines {
 
         Ok(session_rows.into_iter().collect())
     }
+
+    /// Fetches the [`Beamlines`] of sessions created in ISPyB at or after the given unix timestamp
+    #[instrument(name = "fetch_changed_beamlines")]
+    pub async fn fetch_changed(ispyb_pool: &MySqlPool, since: i64) -> Result<Self, sqlx::Error> {
+        let session_rows = query_as!(
+            RawBeamlineRow,
+            "
+            SELECT
+                beamLineName as beamline,
+                sessionId as session_id
+            FROM
+                BLSession
+            WHERE
+                bltimeStamp >= FROM_UNIXTIME(?)
+            ",
+            since
+        )
+        .fetch_all(ispyb_pool)
+        .await?;
+
+        Ok(session_rows.into_iter().collect())
+    }
+
+    /// Updates the [`Beamlines`] with newly fetched changes, moving any sessions which have changed beamline
+    pub fn merge(&mut self, changes: Self) {
+        let changed_sessions = changes
+            .values()
+            .flat_map(|beamline| beamline.sessions.iter().copied())
+            .collect::<HashSet<_>>();
+        for beamline in self.values_mut() {
+            beamline
+                .sessions
+                .retain(|session_id| !changed_sessions.contains(session_id));
+        }
+        for (name, beamline) in changes.0 {
+            self.entry(name)
+                .or_default()
+                .sessions
+                .extend(beamline.sessions);
+        }
+        self.retain(|_, beamline| !beamline.sessions.is_empty());
+        self.sort_sessions();
+    }
+
+    /// Sorts the sessions of each beamline, such that they are serialized identically regardless of the order in which rows were returned
+    fn sort_sessions(&mut self) {
+        for beamline in self.values_mut() {
+            beamline.sessions.sort_unstable();
+        }
+    }
 }
 
 /// The various attributes of a beamline
-#[derive(Debug, Default, PartialEq, Eq, Hash, Serialize, JsonSchema)]
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
 pub struct Beamline {
     /// The sessions which occured on this beamline
     sessions: Vec<u32>,
@@ -77,6 +129,7 @@ impl FromIterator<RawBeamlineRow> for Beamlines {
                     .push(beamline.session_id)
             }
         }
+        beamlines.sort_sessions();
         beamlines
     }
 }
@@ -112,4 +165,48 @@ mod tests {
         expected.insert("p99".to_string(), Beamline { sessions: vec![43] });
         assert_eq!(expected, beamlines.0);
     }
+
+    #[sqlx::test(
+        migrations = "tests/migrations",
+        fixtures(path = "../../tests/fixtures", scripts("beamline_sessions"))
+    )]
+    async fn fetch_changed_all(ispyb_pool: MySqlPool) {
+        let beamlines = Beamlines::fetch_changed(&ispyb_pool, 0).await.unwrap();
+        let expected = Beamlines::fetch(&ispyb_pool).await.unwrap();
+        assert_eq!(expected, beamlines);
+    }
+
+    #[test]
+    fn merge_moves_changed() {
+        let mut beamlines = Beamlines(BTreeMap::from([
+            ("i12".to_string(), Beamline { sessions: vec![40] }),
+            (
+                "i22".to_string(),
+                Beamline {
+                    sessions: vec![41, 44],
+                },
+            ),
+        ]));
+        beamlines.merge(Beamlines(BTreeMap::from([(
+            "b13".to_string(),
+            Beamline {
+                sessions: vec![40, 45],
+            },
+        )])));
+        let expected = BTreeMap::from([
+            (
+                "b13".to_string(),
+                Beamline {
+                    sessions: vec![40, 45],
+                },
+            ),
+            (
+                "i22".to_string(),
+                Beamline {
+                    sessions: vec![41, 44],
+                },
+            ),
+        ]);
+        assert_eq!(expected, beamlines.0);
+    }
 }
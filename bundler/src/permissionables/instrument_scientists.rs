@@ -0,0 +1,120 @@
+use derive_more::{Deref, DerefMut};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sqlx::{query_as, MySqlPool};
+use std::collections::BTreeMap;
+use tracing::instrument;
+
+/// A mapping of beamlines to the instrument scientists assigned to them, such that policies can grant beamline staff access to all sessions on their instrument
+///
+/// Instrument scientists are those subjects assigned as a local contact of any session on the beamline
+#[derive(
+    Debug, Default, Clone, Deref, DerefMut, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema,
+)]
+pub struct InstrumentScientists(BTreeMap<String, Instrument>);
+
+impl InstrumentScientists {
+    /// Fetches [`InstrumentScientists`] from ISPyB
+    #[instrument(name = "fetch_instrument_scientists")]
+    pub async fn fetch(ispyb_pool: &MySqlPool) -> Result<Self, sqlx::Error> {
+        let assignment_rows = query_as!(
+            AssignmentRow,
+            "
+            SELECT DISTINCT
+                BLSession.beamLineName as beamline,
+                Person.login as subject
+            FROM
+                Session_has_Person
+                JOIN BLSession ON BLSession.sessionId = Session_has_Person.sessionId
+                JOIN Person ON Person.personId = Session_has_Person.personId
+            WHERE
+                Session_has_Person.role IN ('Local Contact', 'Local Contact 2')
+            "
+        )
+        .fetch_all(ispyb_pool)
+        .await?;
+
+        Ok(assignment_rows.into_iter().collect())
+    }
+}
+
+/// The various attributes of an instrument
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+pub struct Instrument {
+    /// The subjects assigned as instrument scientists of the beamline
+    scientists: Vec<String>,
+}
+
+/// A row from ISPyB detailing the assignment of a subject as a local contact on a beamline
+struct AssignmentRow {
+    /// The name of the beamline
+    beamline: Option<String>,
+    /// The unique identifier of the subject
+    subject: Option<String>,
+}
+
+impl FromIterator<AssignmentRow> for InstrumentScientists {
+    fn from_iter<T: IntoIterator<Item = AssignmentRow>>(iter: T) -> Self {
+        let mut instrument_scientists = Self::default();
+        for assignment_row in iter {
+            if let (Some(beamline), Some(subject)) =
+                (assignment_row.beamline, assignment_row.subject)
+            {
+                instrument_scientists
+                    .entry(beamline)
+                    .or_default()
+                    .scientists
+                    .push(subject);
+            }
+        }
+        for instrument in instrument_scientists.values_mut() {
+            instrument.scientists.sort_unstable();
+            instrument.scientists.dedup();
+        }
+        instrument_scientists
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Instrument, InstrumentScientists};
+    use sqlx::MySqlPool;
+    use std::collections::BTreeMap;
+
+    #[sqlx::test(migrations = "tests/migrations")]
+    async fn fetch_empty(ispyb_pool: MySqlPool) {
+        let instrument_scientists = InstrumentScientists::fetch(&ispyb_pool).await.unwrap();
+        let expected = InstrumentScientists(BTreeMap::new());
+        assert_eq!(expected, instrument_scientists);
+    }
+
+    #[sqlx::test(
+        migrations = "tests/migrations",
+        fixtures(
+            path = "../../tests/fixtures",
+            scripts(
+                "beamline_sessions",
+                "persons",
+                "session_membership",
+                "instrument_scientists"
+            )
+        )
+    )]
+    async fn fetch_some(ispyb_pool: MySqlPool) {
+        let instrument_scientists = InstrumentScientists::fetch(&ispyb_pool).await.unwrap();
+        let mut expected = BTreeMap::new();
+        expected.insert(
+            "b13".to_string(),
+            Instrument {
+                scientists: vec!["bar".to_string()],
+            },
+        );
+        expected.insert(
+            "i22".to_string(),
+            Instrument {
+                scientists: vec!["bar".to_string(), "foo".to_string()],
+            },
+        );
+        assert_eq!(expected, instrument_scientists.0);
+    }
+}
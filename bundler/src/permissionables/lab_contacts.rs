@@ -0,0 +1,146 @@
+use derive_more::{Deref, DerefMut};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sqlx::{query_as, MySqlPool};
+use std::collections::BTreeMap;
+use tracing::instrument;
+
+/// A mapping of proposals to the subjects acting as their lab contacts, with the home institution of each
+///
+/// Lab contacts send and receive the shipments of a proposal, so need not be members of the proposal
+#[derive(
+    Debug, Default, Clone, Deref, DerefMut, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema,
+)]
+pub struct LabContacts(BTreeMap<u32, BTreeMap<String, LabContact>>);
+
+impl LabContacts {
+    /// Fetches [`LabContacts`] from ISPyB
+    #[instrument(name = "fetch_lab_contacts")]
+    pub async fn fetch(ispyb_pool: &MySqlPool) -> Result<Self, sqlx::Error> {
+        let lab_contact_rows = query_as!(
+            RawLabContactRow,
+            "
+            SELECT
+                proposalNumber as proposal_number,
+                login as subject,
+                Laboratory.name as institution
+            FROM
+                LabContact
+                JOIN Proposal ON Proposal.proposalId = LabContact.proposalId
+                JOIN Person ON Person.personId = LabContact.personId
+                LEFT JOIN Laboratory ON Laboratory.laboratoryId = Person.laboratoryId
+            "
+        )
+        .fetch_all(ispyb_pool)
+        .await?;
+
+        Ok(lab_contact_rows.into_iter().collect())
+    }
+}
+
+/// The attributes of a lab contact of a proposal
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+pub struct LabContact {
+    /// The name of the home institution of the lab contact
+    institution: Option<String>,
+}
+
+/// A row from ISPyB detailing a lab contact of a proposal
+struct LabContactRow {
+    /// The proposal number
+    proposal_number: u32,
+    /// The unique identifier of the subject
+    subject: String,
+    /// The name of the home institution of the subject
+    institution: Option<String>,
+}
+
+#[allow(clippy::missing_docs_in_private_items)]
+struct RawLabContactRow {
+    proposal_number: Option<String>,
+    subject: Option<String>,
+    institution: Option<String>,
+}
+
+impl TryFrom<RawLabContactRow> for LabContactRow {
+    type Error = anyhow::Error;
+
+    fn try_from(value: RawLabContactRow) -> Result<Self, Self::Error> {
+        Ok(Self {
+            proposal_number: value
+                .proposal_number
+                .ok_or(anyhow::anyhow!("Proposal Number was NULL"))?
+                .parse()?,
+            subject: value.subject.ok_or(anyhow::anyhow!("Login was NULL"))?,
+            institution: value.institution,
+        })
+    }
+}
+
+impl FromIterator<RawLabContactRow> for LabContacts {
+    fn from_iter<T: IntoIterator<Item = RawLabContactRow>>(iter: T) -> Self {
+        let mut lab_contacts = Self::default();
+        for lab_contact_row in iter {
+            if let Ok(lab_contact_row) = LabContactRow::try_from(lab_contact_row) {
+                lab_contacts
+                    .entry(lab_contact_row.proposal_number)
+                    .or_default()
+                    .insert(
+                        lab_contact_row.subject,
+                        LabContact {
+                            institution: lab_contact_row.institution,
+                        },
+                    );
+            }
+        }
+        lab_contacts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LabContact, LabContacts};
+    use sqlx::MySqlPool;
+    use std::collections::BTreeMap;
+
+    #[sqlx::test(migrations = "tests/migrations")]
+    async fn fetch_empty(ispyb_pool: MySqlPool) {
+        let lab_contacts = LabContacts::fetch(&ispyb_pool).await.unwrap();
+        let expected = LabContacts(BTreeMap::new());
+        assert_eq!(expected, lab_contacts);
+    }
+
+    #[sqlx::test(
+        migrations = "tests/migrations",
+        fixtures(
+            path = "../../tests/fixtures",
+            scripts("laboratories", "persons", "proposals", "lab_contacts")
+        )
+    )]
+    async fn fetch_some(ispyb_pool: MySqlPool) {
+        let lab_contacts = LabContacts::fetch(&ispyb_pool).await.unwrap();
+        let mut expected = BTreeMap::new();
+        expected.insert(
+            10030,
+            BTreeMap::from([
+                ("bar".to_string(), LabContact { institution: None }),
+                (
+                    "foo".to_string(),
+                    LabContact {
+                        institution: Some("Diamond Light Source".to_string()),
+                    },
+                ),
+            ]),
+        );
+        expected.insert(
+            10032,
+            BTreeMap::from([(
+                "foo".to_string(),
+                LabContact {
+                    institution: Some("Diamond Light Source".to_string()),
+                },
+            )]),
+        );
+        assert_eq!(expected, lab_contacts.0);
+    }
+}
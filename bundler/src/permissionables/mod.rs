@@ -1,8 +1,97 @@
 /// A mapping of beamlines to their attributes
 pub mod beamlines;
+/// A mapping of beamlines to the instrument scientists assigned to them
+pub mod instrument_scientists;
+/// A mapping of proposals to their lab contacts
+pub mod lab_contacts;
+/// A mapping of subjects to their personal details
+pub mod people;
 /// A mapping of proposals to their attributes
 pub mod proposals;
+/// A mapping of roles to the permissions they grant
+pub mod roles;
+/// A mapping of sessions to the subjects associated with them
+pub mod session_members;
+/// A mapping of sessions to the subjects registered on them
+pub mod session_participants;
 /// A mapping of sessions to their attributes
 pub mod sessions;
 /// A mapping of subjects to their attributes
 pub mod subjects;
+
+use crate::database::MaybeConnectionError;
+use derive_more::{Display, Error, From};
+use std::{future::Future, time::Duration};
+
+/// An error encountered whilst fetching a permissionable from ISPyB
+#[derive(Debug, Display, Error, From)]
+pub enum FetchError {
+    /// The database returned an error
+    #[display(fmt = "{}", _0)]
+    Database(sqlx::Error),
+    /// The query did not complete within the allotted time and was cancelled
+    #[display(fmt = "Fetching {} timed out after {:?}", dataset, timeout)]
+    #[from(ignore)]
+    Timeout {
+        /// The name of the dataset being fetched
+        dataset: &'static str,
+        /// The time allotted to the query
+        timeout: Duration,
+    },
+}
+
+impl MaybeConnectionError for FetchError {
+    fn is_connection_error(&self) -> bool {
+        match self {
+            Self::Database(err) => err.is_connection_error(),
+            Self::Timeout { .. } => false,
+        }
+    }
+}
+
+/// Fetches a permissionable, cancelling the query if it does not complete within the timeout
+pub async fn with_timeout<T>(
+    dataset: &'static str,
+    timeout: Duration,
+    fetch: impl Future<Output = Result<T, sqlx::Error>>,
+) -> Result<T, FetchError> {
+    match tokio::time::timeout(timeout, fetch).await {
+        Ok(result) => Ok(result?),
+        Err(_) => {
+            tracing::warn!(
+                monotonic_counter.ispyb_query_timeouts = 1,
+                dataset,
+                "Fetching {dataset} timed out after {timeout:?}"
+            );
+            Err(FetchError::Timeout { dataset, timeout })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{with_timeout, FetchError};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn fetch_within_timeout() {
+        let result = with_timeout("test", Duration::from_secs(1), async { Ok(42) }).await;
+        assert_eq!(42, result.unwrap());
+    }
+
+    #[tokio::test]
+    async fn fetch_exceeding_timeout() {
+        let result = with_timeout("test", Duration::from_millis(10), async {
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            Ok(42)
+        })
+        .await;
+        assert!(matches!(
+            result,
+            Err(FetchError::Timeout {
+                dataset: "test",
+                ..
+            })
+        ));
+    }
+}
@@ -0,0 +1,134 @@
+use derive_more::{Deref, DerefMut};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sqlx::{query_as, MySqlPool};
+use std::collections::BTreeMap;
+use tracing::instrument;
+
+/// A mapping of subjects to their personal details
+#[derive(
+    Debug, Default, Clone, Deref, DerefMut, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema,
+)]
+pub struct People(BTreeMap<String, Person>);
+
+impl People {
+    /// Fetches [`People`] from ISPyB
+    #[instrument(name = "fetch_people")]
+    pub async fn fetch(ispyb_pool: &MySqlPool) -> Result<Self, sqlx::Error> {
+        let person_rows = query_as!(
+            PersonRow,
+            "
+            SELECT
+                login as subject,
+                title,
+                givenName as given_name,
+                familyName as family_name,
+                emailAddress as email,
+                Laboratory.name as institution
+            FROM
+                Person
+                LEFT JOIN Laboratory USING (laboratoryId)
+            "
+        )
+        .fetch_all(ispyb_pool)
+        .await?;
+
+        Ok(person_rows.into_iter().collect())
+    }
+}
+
+/// The personal details of a subject, as may be displayed when auditing decisions
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+pub struct Person {
+    /// The title of the person
+    title: Option<String>,
+    /// The given and family names of the person
+    name: Option<String>,
+    /// The email address of the person
+    email: Option<String>,
+    /// The name of the home institution of the person
+    institution: Option<String>,
+}
+
+/// A row from ISPyB detailing the personal details of a subject
+struct PersonRow {
+    /// The unique identifier of the subject
+    subject: Option<String>,
+    /// The title of the person
+    title: Option<String>,
+    /// The given name of the person
+    given_name: Option<String>,
+    /// The family name of the person
+    family_name: Option<String>,
+    /// The email address of the person
+    email: Option<String>,
+    /// The name of the home institution of the person
+    institution: Option<String>,
+}
+
+impl FromIterator<PersonRow> for People {
+    fn from_iter<T: IntoIterator<Item = PersonRow>>(iter: T) -> Self {
+        let mut people = Self::default();
+        for person_row in iter {
+            if let Some(fed_id) = person_row.subject {
+                let name = [person_row.given_name, person_row.family_name]
+                    .into_iter()
+                    .flatten()
+                    .filter(|name| !name.is_empty())
+                    .collect::<Vec<_>>();
+                people.insert(
+                    fed_id,
+                    Person {
+                        title: person_row.title,
+                        name: (!name.is_empty()).then(|| name.join(" ")),
+                        email: person_row.email,
+                        institution: person_row.institution,
+                    },
+                );
+            }
+        }
+        people
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{People, Person};
+    use sqlx::MySqlPool;
+    use std::collections::BTreeMap;
+
+    #[sqlx::test(migrations = "tests/migrations")]
+    async fn fetch_empty(ispyb_pool: MySqlPool) {
+        let people = People::fetch(&ispyb_pool).await.unwrap();
+        let expected = People(BTreeMap::new());
+        assert_eq!(expected, people);
+    }
+
+    #[sqlx::test(
+        migrations = "tests/migrations",
+        fixtures(path = "../../tests/fixtures", scripts("laboratories", "persons"))
+    )]
+    async fn fetch_some(ispyb_pool: MySqlPool) {
+        let people = People::fetch(&ispyb_pool).await.unwrap();
+        let mut expected = BTreeMap::new();
+        expected.insert(
+            "foo".to_string(),
+            Person {
+                title: Some("Dr".to_string()),
+                name: Some("Foo Fighter".to_string()),
+                email: Some("foo@example.com".to_string()),
+                institution: Some("Diamond Light Source".to_string()),
+            },
+        );
+        expected.insert(
+            "bar".to_string(),
+            Person {
+                title: None,
+                name: Some("Bar".to_string()),
+                email: None,
+                institution: None,
+            },
+        );
+        assert_eq!(expected, people.0);
+    }
+}
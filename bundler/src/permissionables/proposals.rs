@@ -1,18 +1,115 @@
+use clap::{Args, ValueEnum};
 use derive_more::{Deref, DerefMut};
 use schemars::JsonSchema;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use sqlx::{query_as, MySqlPool};
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
 use tracing::instrument;
 
+/// Options for excluding irrelevant proposals, such as those cancelled or used for testing, from the proposals dataset
+#[derive(Debug, Clone, Default, Args)]
+pub struct ProposalFilters {
+    /// The states of proposals to exclude, such as 'Cancelled'
+    #[arg(long, env = "BUNDLER_EXCLUDE_PROPOSAL_STATES", value_delimiter = ',')]
+    exclude_proposal_states: Vec<String>,
+    /// The prefixes of the codes of proposals to exclude, such as 'nt' for those used in testing
+    #[arg(long, env = "BUNDLER_EXCLUDE_PROPOSAL_CODES", value_delimiter = ',')]
+    exclude_proposal_codes: Vec<String>,
+    /// Whether academic proposals, industrial proposals, or both are included
+    #[arg(long, env = "BUNDLER_PROPOSAL_KIND", value_enum, default_value_t)]
+    proposal_kind: ProposalKind,
+    /// The prefixes of the codes of industrial proposals, all others being academic
+    #[arg(
+        long,
+        env = "BUNDLER_INDUSTRIAL_PROPOSAL_CODES",
+        value_delimiter = ',',
+        default_value = "in"
+    )]
+    industrial_proposal_codes: Vec<String>,
+}
+
+/// The kinds of proposal which may be included, as distinguished by their code
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ProposalKind {
+    /// Both academic and industrial proposals
+    #[default]
+    All,
+    /// Only proposals without an industrial code
+    Academic,
+    /// Only proposals with an industrial code
+    Industrial,
+}
+
+/// A regular expression which matches no proposal code
+const MATCH_NONE: &str = r"[^\s\S]";
+
+impl ProposalFilters {
+    /// The excluded proposal states, as a comma separated list for use with `FIND_IN_SET`
+    fn excluded_states(&self) -> String {
+        self.exclude_proposal_states.join(",")
+    }
+
+    /// A regular expression matching the codes of proposals of the included kind
+    fn included_codes(&self) -> String {
+        match self.proposal_kind {
+            ProposalKind::Industrial => prefix_pattern(&self.industrial_proposal_codes),
+            ProposalKind::All | ProposalKind::Academic => "^".to_string(),
+        }
+    }
+
+    /// A regular expression matching the codes of proposals which are excluded, by prefix or by kind
+    fn excluded_codes(&self) -> String {
+        let industrial = match self.proposal_kind {
+            ProposalKind::Academic => self.industrial_proposal_codes.as_slice(),
+            ProposalKind::All | ProposalKind::Industrial => &[],
+        };
+        prefix_pattern(
+            &self
+                .exclude_proposal_codes
+                .iter()
+                .chain(industrial)
+                .cloned()
+                .collect::<Vec<_>>(),
+        )
+    }
+}
+
+/// A regular expression matching strings starting with any of the prefixes, or nothing if there are none
+fn prefix_pattern(prefixes: &[String]) -> String {
+    if prefixes.is_empty() {
+        return MATCH_NONE.to_string();
+    }
+    let alternatives = prefixes
+        .iter()
+        .map(|prefix| {
+            prefix
+                .chars()
+                .map(|char| {
+                    if char.is_alphanumeric() {
+                        char.to_string()
+                    } else {
+                        format!("\\{char}")
+                    }
+                })
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>();
+    format!("^({})", alternatives.join("|"))
+}
+
 /// A mapping of proposals to their various attributes
-#[derive(Debug, Default, Deref, DerefMut, PartialEq, Eq, Hash, Serialize, JsonSchema)]
+#[derive(
+    Debug, Default, Clone, Deref, DerefMut, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema,
+)]
 pub struct Proposals(BTreeMap<u32, Proposal>);
 
 impl Proposals {
-    /// Fetches [`Proposals`] from ISPyB
+    /// Fetches [`Proposals`] from ISPyB, excluding those removed by the [`ProposalFilters`], along with the aliases of merged or re-coded proposals
     #[instrument(name = "fetch_proposals")]
-    pub async fn fetch(ispyb_pool: &MySqlPool) -> Result<Self, sqlx::Error> {
+    pub async fn fetch(
+        ispyb_pool: &MySqlPool,
+        filters: &ProposalFilters,
+    ) -> Result<Self, sqlx::Error> {
         let proposal_rows = query_as!(
             RawProposalRow,
             "
@@ -25,20 +122,167 @@ impl Proposals {
                 JOIN Proposal USING (proposalId)
             WHERE
                 Proposal.externalId IS NOT NULL
+                AND NOT FIND_IN_SET(COALESCE(Proposal.state, ''), ?)
+                AND COALESCE(Proposal.proposalCode, '') REGEXP ?
+                AND NOT COALESCE(Proposal.proposalCode, '') REGEXP ?
+            ",
+            filters.excluded_states(),
+            filters.included_codes(),
+            filters.excluded_codes()
+        )
+        .fetch_all(ispyb_pool)
+        .await?;
+
+        let mut proposals = proposal_rows.into_iter().collect::<Self>();
+        proposals.resolve_aliases(fetch_aliases(ispyb_pool, filters).await?);
+        Ok(proposals)
+    }
+
+    /// Fetches the [`Proposals`] of sessions created in ISPyB at or after the given unix timestamp, excluding those removed by the [`ProposalFilters`], along with the aliases of all merged or re-coded proposals
+    #[instrument(name = "fetch_changed_proposals")]
+    pub async fn fetch_changed(
+        ispyb_pool: &MySqlPool,
+        filters: &ProposalFilters,
+        since: i64,
+    ) -> Result<Self, sqlx::Error> {
+        let proposal_rows = query_as!(
+            RawProposalRow,
             "
+            SELECT
+                proposalNumber as proposal_number,
+                visit_number,
+                sessionId as session_id
+            FROM
+                BLSession
+                JOIN Proposal USING (proposalId)
+            WHERE
+                Proposal.externalId IS NOT NULL
+                AND BLSession.bltimeStamp >= FROM_UNIXTIME(?)
+                AND NOT FIND_IN_SET(COALESCE(Proposal.state, ''), ?)
+                AND COALESCE(Proposal.proposalCode, '') REGEXP ?
+                AND NOT COALESCE(Proposal.proposalCode, '') REGEXP ?
+            ",
+            since,
+            filters.excluded_states(),
+            filters.included_codes(),
+            filters.excluded_codes()
         )
         .fetch_all(ispyb_pool)
         .await?;
 
-        Ok(proposal_rows.into_iter().collect())
+        let mut proposals = proposal_rows.into_iter().collect::<Self>();
+        proposals.resolve_aliases(fetch_aliases(ispyb_pool, filters).await?);
+        Ok(proposals)
+    }
+
+    /// Updates the [`Proposals`] with newly fetched changes, moving any sessions which have changed proposal or visit
+    ///
+    /// The aliases of the changes, being fetched in full, replace those previously recorded
+    pub fn merge(&mut self, changes: Self) {
+        let changed_sessions = changes
+            .values()
+            .flat_map(|proposal| proposal.sessions.values().copied())
+            .collect::<HashSet<_>>();
+        for proposal in self.values_mut() {
+            proposal
+                .sessions
+                .retain(|_, session_id| !changed_sessions.contains(session_id));
+            proposal.aliases.clear();
+            proposal.canonical_number = None;
+        }
+        for (proposal_number, proposal) in changes.0 {
+            let merged = self.entry(proposal_number).or_default();
+            merged.sessions.extend(proposal.sessions);
+            merged.aliases = proposal.aliases;
+            merged.canonical_number = proposal.canonical_number;
+        }
+        self.retain(|_, proposal| !proposal.sessions.is_empty() || proposal.is_aliased());
+    }
+
+    /// Records each alias against its canonical proposal, and the canonical proposal against each alias, such that proposals can be found by any of their numbers
+    fn resolve_aliases(&mut self, aliases: BTreeMap<u32, u32>) {
+        for (alias, canonical) in aliases {
+            if alias == canonical {
+                continue;
+            }
+            self.entry(canonical).or_default().aliases.insert(alias);
+            self.entry(alias).or_default().canonical_number = Some(canonical);
+        }
     }
 }
 
+/// Fetches the aliases of proposals which have been merged or re-coded, being superseded by a later proposal with the same external identifier, mapped to the number of the latest such proposal
+///
+/// Neither aliases nor the canonical proposals to which they are resolved may be removed by the [`ProposalFilters`]
+#[instrument(name = "fetch_proposal_aliases")]
+async fn fetch_aliases(
+    ispyb_pool: &MySqlPool,
+    filters: &ProposalFilters,
+) -> Result<BTreeMap<u32, u32>, sqlx::Error> {
+    let alias_rows = query_as!(
+        RawProposalAliasRow,
+        "
+        SELECT
+            alias.proposalNumber as alias_number,
+            canonical.proposalNumber as canonical_number
+        FROM
+            Proposal AS alias
+            JOIN Proposal AS canonical ON canonical.externalId = alias.externalId
+                AND canonical.proposalId > alias.proposalId
+        WHERE
+            NOT EXISTS (
+                SELECT 1
+                FROM Proposal AS later
+                WHERE
+                    later.externalId = canonical.externalId
+                    AND later.proposalId > canonical.proposalId
+            )
+            AND NOT FIND_IN_SET(COALESCE(canonical.state, ''), ?)
+            AND COALESCE(canonical.proposalCode, '') REGEXP ?
+            AND NOT COALESCE(canonical.proposalCode, '') REGEXP ?
+            AND NOT FIND_IN_SET(COALESCE(alias.state, ''), ?)
+            AND COALESCE(alias.proposalCode, '') REGEXP ?
+            AND NOT COALESCE(alias.proposalCode, '') REGEXP ?
+        ",
+        filters.excluded_states(),
+        filters.included_codes(),
+        filters.excluded_codes(),
+        filters.excluded_states(),
+        filters.included_codes(),
+        filters.excluded_codes()
+    )
+    .fetch_all(ispyb_pool)
+    .await?;
+
+    Ok(alias_rows
+        .into_iter()
+        .filter_map(|alias_row| {
+            Some((
+                alias_row.alias_number?.parse().ok()?,
+                alias_row.canonical_number?.parse().ok()?,
+            ))
+        })
+        .collect())
+}
+
 /// The various attributes of a proposal
-#[derive(Debug, Default, PartialEq, Eq, Hash, Serialize, JsonSchema)]
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
 pub struct Proposal {
     /// The sessions which took place within the proposal
     sessions: BTreeMap<u32, u32>,
+    /// The numbers of proposals which were merged into, or re-coded as, this proposal
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    aliases: BTreeSet<u32>,
+    /// The number of the proposal into which this proposal was merged, or as which it was re-coded, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    canonical_number: Option<u32>,
+}
+
+impl Proposal {
+    /// Whether the proposal is known by other numbers, or is itself an alias
+    fn is_aliased(&self) -> bool {
+        !self.aliases.is_empty() || self.canonical_number.is_some()
+    }
 }
 
 /// A row from ISPyB detailing the sessions in a proposal
@@ -58,6 +302,12 @@ struct RawProposalRow {
     session_id: u32,
 }
 
+#[allow(clippy::missing_docs_in_private_items)]
+struct RawProposalAliasRow {
+    alias_number: Option<String>,
+    canonical_number: Option<String>,
+}
+
 impl TryFrom<RawProposalRow> for ProposalRow {
     type Error = anyhow::Error;
 
@@ -91,13 +341,15 @@ impl FromIterator<RawProposalRow> for Proposals {
 
 #[cfg(test)]
 mod tests {
-    use super::{Proposal, Proposals};
+    use super::{prefix_pattern, Proposal, ProposalFilters, ProposalKind, Proposals};
     use sqlx::MySqlPool;
-    use std::collections::BTreeMap;
+    use std::collections::{BTreeMap, BTreeSet};
 
     #[sqlx::test(migrations = "tests/migrations")]
     async fn fetch_empty(ispyb_pool: MySqlPool) {
-        let proposals = Proposals::fetch(&ispyb_pool).await.unwrap();
+        let proposals = Proposals::fetch(&ispyb_pool, &ProposalFilters::default())
+            .await
+            .unwrap();
         let expected = Proposals(BTreeMap::new());
         assert_eq!(expected, proposals);
     }
@@ -110,20 +362,244 @@ mod tests {
         )
     )]
     async fn fetch_some(ispyb_pool: MySqlPool) {
-        let beamlines = Proposals::fetch(&ispyb_pool).await.unwrap();
+        let beamlines = Proposals::fetch(&ispyb_pool, &ProposalFilters::default())
+            .await
+            .unwrap();
         let mut expected = BTreeMap::new();
         expected.insert(
             10030,
             Proposal {
                 sessions: BTreeMap::from([(10, 40), (11, 41), (12, 42)]),
+                ..Default::default()
             },
         );
         expected.insert(
             10031,
             Proposal {
                 sessions: BTreeMap::from([(10, 43), (11, 44)]),
+                ..Default::default()
             },
         );
         assert_eq!(expected, beamlines.0);
     }
+
+    #[sqlx::test(
+        migrations = "tests/migrations",
+        fixtures(
+            path = "../../tests/fixtures",
+            scripts("beamline_sessions", "proposals", "proposal_aliases")
+        )
+    )]
+    async fn fetch_aliased(ispyb_pool: MySqlPool) {
+        let proposals = Proposals::fetch(&ispyb_pool, &ProposalFilters::default())
+            .await
+            .unwrap();
+        assert_eq!(Some(10033), proposals[&10030].canonical_number);
+        assert_eq!(3, proposals[&10030].sessions.len());
+        assert_eq!(BTreeSet::from([10030]), proposals[&10033].aliases);
+        assert!(proposals[&10033].sessions.is_empty());
+        assert_eq!(None, proposals[&10031].canonical_number);
+    }
+
+    #[sqlx::test(
+        migrations = "tests/migrations",
+        fixtures(
+            path = "../../tests/fixtures",
+            scripts("beamline_sessions", "proposals", "proposal_aliases")
+        )
+    )]
+    async fn fetch_aliased_filtered(ispyb_pool: MySqlPool) {
+        let proposals = Proposals::fetch(
+            &ispyb_pool,
+            &ProposalFilters {
+                exclude_proposal_codes: vec!["mx".to_string()],
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert!(!proposals.contains_key(&10030));
+        assert!(!proposals.contains_key(&10033));
+        assert!(proposals.values().all(|proposal| !proposal.is_aliased()));
+    }
+
+    #[sqlx::test(
+        migrations = "tests/migrations",
+        fixtures(
+            path = "../../tests/fixtures",
+            scripts("beamline_sessions", "proposals")
+        )
+    )]
+    async fn fetch_changed_all(ispyb_pool: MySqlPool) {
+        let proposals = Proposals::fetch_changed(&ispyb_pool, &ProposalFilters::default(), 0)
+            .await
+            .unwrap();
+        let expected = Proposals::fetch(&ispyb_pool, &ProposalFilters::default())
+            .await
+            .unwrap();
+        assert_eq!(expected, proposals);
+    }
+
+    #[sqlx::test(
+        migrations = "tests/migrations",
+        fixtures(
+            path = "../../tests/fixtures",
+            scripts("beamline_sessions", "proposals")
+        )
+    )]
+    async fn fetch_filtered(ispyb_pool: MySqlPool) {
+        let fetch_numbers = |filters: ProposalFilters| {
+            let ispyb_pool = ispyb_pool.clone();
+            async move {
+                Proposals::fetch(&ispyb_pool, &filters)
+                    .await
+                    .unwrap()
+                    .keys()
+                    .copied()
+                    .collect::<Vec<_>>()
+            }
+        };
+        assert_eq!(
+            vec![10030],
+            fetch_numbers(ProposalFilters {
+                exclude_proposal_states: vec!["Cancelled".to_string()],
+                ..Default::default()
+            })
+            .await
+        );
+        assert_eq!(
+            vec![10031],
+            fetch_numbers(ProposalFilters {
+                exclude_proposal_codes: vec!["m".to_string()],
+                ..Default::default()
+            })
+            .await
+        );
+        let industrial_proposal_codes = vec!["in".to_string()];
+        assert_eq!(
+            vec![10030],
+            fetch_numbers(ProposalFilters {
+                proposal_kind: ProposalKind::Academic,
+                industrial_proposal_codes: industrial_proposal_codes.clone(),
+                ..Default::default()
+            })
+            .await
+        );
+        assert_eq!(
+            vec![10031],
+            fetch_numbers(ProposalFilters {
+                proposal_kind: ProposalKind::Industrial,
+                industrial_proposal_codes,
+                ..Default::default()
+            })
+            .await
+        );
+    }
+
+    #[test]
+    fn prefixes_escaped() {
+        assert_eq!(
+            r"^(cm|n\.t)",
+            prefix_pattern(&["cm".to_string(), "n.t".to_string()])
+        );
+    }
+
+    #[test]
+    fn merge_moves_changed() {
+        let mut proposals = Proposals(BTreeMap::from([
+            (
+                10030,
+                Proposal {
+                    sessions: BTreeMap::from([(10, 40), (11, 41)]),
+                    ..Default::default()
+                },
+            ),
+            (
+                10031,
+                Proposal {
+                    sessions: BTreeMap::from([(10, 43)]),
+                    ..Default::default()
+                },
+            ),
+        ]));
+        proposals.merge(Proposals(BTreeMap::from([(
+            10032,
+            Proposal {
+                sessions: BTreeMap::from([(10, 43)]),
+                ..Default::default()
+            },
+        )])));
+        let expected = BTreeMap::from([
+            (
+                10030,
+                Proposal {
+                    sessions: BTreeMap::from([(10, 40), (11, 41)]),
+                    ..Default::default()
+                },
+            ),
+            (
+                10032,
+                Proposal {
+                    sessions: BTreeMap::from([(10, 43)]),
+                    ..Default::default()
+                },
+            ),
+        ]);
+        assert_eq!(expected, proposals.0);
+    }
+
+    #[test]
+    fn merge_replaces_aliases() {
+        let mut proposals = Proposals(BTreeMap::from([
+            (
+                10030,
+                Proposal {
+                    sessions: BTreeMap::from([(10, 40)]),
+                    canonical_number: Some(10033),
+                    ..Default::default()
+                },
+            ),
+            (
+                10033,
+                Proposal {
+                    aliases: BTreeSet::from([10030]),
+                    ..Default::default()
+                },
+            ),
+        ]));
+        proposals.merge(Proposals(BTreeMap::from([
+            (
+                10030,
+                Proposal {
+                    canonical_number: Some(10034),
+                    ..Default::default()
+                },
+            ),
+            (
+                10034,
+                Proposal {
+                    aliases: BTreeSet::from([10030]),
+                    ..Default::default()
+                },
+            ),
+        ])));
+        let expected = BTreeMap::from([
+            (
+                10030,
+                Proposal {
+                    sessions: BTreeMap::from([(10, 40)]),
+                    canonical_number: Some(10034),
+                    ..Default::default()
+                },
+            ),
+            (
+                10034,
+                Proposal {
+                    aliases: BTreeSet::from([10030]),
+                    ..Default::default()
+                },
+            ),
+        ]);
+        assert_eq!(expected, proposals.0);
+    }
 }
@@ -0,0 +1,106 @@
+use derive_more::{Deref, DerefMut};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sqlx::{query_as, MySqlPool};
+use std::collections::BTreeMap;
+use tracing::instrument;
+
+/// A mapping of roles to the permissions they grant, such that policies can resolve roles without duplicating the mapping
+#[derive(
+    Debug, Default, Clone, Deref, DerefMut, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema,
+)]
+pub struct Roles(BTreeMap<String, Role>);
+
+impl Roles {
+    /// Fetches [`Roles`] from ISPyB
+    #[instrument(name = "fetch_roles")]
+    pub async fn fetch(ispyb_pool: &MySqlPool) -> Result<Self, sqlx::Error> {
+        let role_rows = query_as!(
+            RoleRow,
+            "
+            SELECT
+                UserGroup.name as role,
+                type as permission
+            FROM UserGroup
+                JOIN UserGroup_has_Permission USING (userGroupId)
+                JOIN Permission USING (permissionId)
+            "
+        )
+        .fetch_all(ispyb_pool)
+        .await?;
+
+        Ok(role_rows.into_iter().collect())
+    }
+}
+
+/// The various attributes of a role
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+pub struct Role {
+    /// The permissions granted to members of the role
+    permissions: Vec<String>,
+}
+
+/// A row from ISPyB detailing a permission granted by a role
+struct RoleRow {
+    /// The name of the user group acting as the role
+    role: String,
+    /// The permission granted by the role
+    permission: String,
+}
+
+impl FromIterator<RoleRow> for Roles {
+    fn from_iter<T: IntoIterator<Item = RoleRow>>(iter: T) -> Self {
+        let mut roles = Self::default();
+        for role_row in iter {
+            roles
+                .entry(role_row.role)
+                .or_default()
+                .permissions
+                .push(role_row.permission);
+        }
+        for role in roles.values_mut() {
+            role.permissions.sort_unstable();
+            role.permissions.dedup();
+        }
+        roles
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Role, Roles};
+    use sqlx::MySqlPool;
+    use std::collections::BTreeMap;
+
+    #[sqlx::test(migrations = "tests/migrations")]
+    async fn fetch_empty(ispyb_pool: MySqlPool) {
+        let roles = Roles::fetch(&ispyb_pool).await.unwrap();
+        let expected = Roles(BTreeMap::new());
+        assert_eq!(expected, roles);
+    }
+
+    #[sqlx::test(
+        migrations = "tests/migrations",
+        fixtures(
+            path = "../../tests/fixtures",
+            scripts("user_groups", "permissions", "group_permissions")
+        )
+    )]
+    async fn fetch_some(ispyb_pool: MySqlPool) {
+        let roles = Roles::fetch(&ispyb_pool).await.unwrap();
+        let mut expected = BTreeMap::new();
+        expected.insert(
+            "data".to_string(),
+            Role {
+                permissions: vec!["read_data".to_string(), "write_data".to_string()],
+            },
+        );
+        expected.insert(
+            "proc".to_string(),
+            Role {
+                permissions: vec!["read_proc".to_string()],
+            },
+        );
+        assert_eq!(expected, roles.0);
+    }
+}
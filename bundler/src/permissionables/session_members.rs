@@ -0,0 +1,50 @@
+use super::subjects::Subjects;
+use derive_more::{Deref, DerefMut};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// A mapping of sessions to the subjects associated with them, being the inverse of the sessions of each subject
+///
+/// This allows policies to find the members of a session by lookup, rather than by iterating over every subject
+#[derive(
+    Debug, Default, Clone, Deref, DerefMut, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema,
+)]
+pub struct SessionMembers(BTreeMap<u32, Vec<String>>);
+
+impl From<&Subjects> for SessionMembers {
+    fn from(subjects: &Subjects) -> Self {
+        let mut session_members = Self::default();
+        for (subject, attributes) in subjects.iter() {
+            for session in attributes.sessions() {
+                let members = session_members.entry(*session).or_insert_with(Vec::new);
+                if members.last() != Some(subject) {
+                    members.push(subject.clone());
+                }
+            }
+        }
+        session_members
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SessionMembers;
+    use crate::permissionables::subjects::Subjects;
+    use serde_json::json;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn sessions_inverted() {
+        let subjects = serde_json::from_value::<Subjects>(json!({
+            "alice": {"permissions": [], "proposals": [1], "sessions": [10, 10, 20]},
+            "bob": {"permissions": [], "proposals": [], "sessions": [20]},
+            "carol": {"permissions": ["super_admin"], "proposals": [], "sessions": []},
+        }))
+        .unwrap();
+        let mut expected = BTreeMap::new();
+        expected.insert(10, vec!["alice".to_string()]);
+        expected.insert(20, vec!["alice".to_string(), "bob".to_string()]);
+        assert_eq!(expected, SessionMembers::from(&subjects).0);
+    }
+}
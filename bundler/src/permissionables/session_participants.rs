@@ -0,0 +1,137 @@
+use derive_more::{Deref, DerefMut};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sqlx::{query_as, MySqlPool};
+use std::collections::BTreeMap;
+use tracing::instrument;
+
+/// A mapping of sessions to the subjects registered on them, with the role and attendance of each
+///
+/// Subjects are registered on a session individually, such as visiting scientists attending a single visit, so may participate in sessions of proposals they are not a member of
+#[derive(
+    Debug, Default, Clone, Deref, DerefMut, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema,
+)]
+pub struct SessionParticipants(BTreeMap<u32, BTreeMap<String, Participant>>);
+
+impl SessionParticipants {
+    /// Fetches [`SessionParticipants`] from ISPyB
+    #[instrument(name = "fetch_session_participants")]
+    pub async fn fetch(ispyb_pool: &MySqlPool) -> Result<Self, sqlx::Error> {
+        let participant_rows = query_as!(
+            ParticipantRow,
+            "
+            SELECT
+                sessionId as session_id,
+                login as subject,
+                role,
+                remote as `remote: bool`
+            FROM
+                Session_has_Person
+                JOIN Person USING (personId)
+            "
+        )
+        .fetch_all(ispyb_pool)
+        .await?;
+
+        Ok(participant_rows.into_iter().collect())
+    }
+}
+
+/// The participation of a subject in a session
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+pub struct Participant {
+    /// The role of the subject in the session, such as 'Co-Investigator' or 'Local Contact'
+    role: Option<String>,
+    /// Whether the subject participates remotely, rather than on-site
+    remote: bool,
+}
+
+/// A row from ISPyB detailing the participation of a subject in a session
+struct ParticipantRow {
+    /// The unique identifier of the session
+    session_id: u32,
+    /// The unique identifier of the subject
+    subject: Option<String>,
+    /// The role of the subject in the session
+    role: Option<String>,
+    /// Whether the subject participates remotely
+    remote: Option<bool>,
+}
+
+impl FromIterator<ParticipantRow> for SessionParticipants {
+    fn from_iter<T: IntoIterator<Item = ParticipantRow>>(iter: T) -> Self {
+        let mut session_participants = Self::default();
+        for participant_row in iter {
+            if let Some(subject) = participant_row.subject {
+                session_participants
+                    .entry(participant_row.session_id)
+                    .or_default()
+                    .insert(
+                        subject,
+                        Participant {
+                            role: participant_row.role,
+                            remote: participant_row.remote.unwrap_or_default(),
+                        },
+                    );
+            }
+        }
+        session_participants
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Participant, SessionParticipants};
+    use sqlx::MySqlPool;
+    use std::collections::BTreeMap;
+
+    #[sqlx::test(migrations = "tests/migrations")]
+    async fn fetch_empty(ispyb_pool: MySqlPool) {
+        let session_participants = SessionParticipants::fetch(&ispyb_pool).await.unwrap();
+        let expected = SessionParticipants(BTreeMap::new());
+        assert_eq!(expected, session_participants);
+    }
+
+    #[sqlx::test(
+        migrations = "tests/migrations",
+        fixtures(
+            path = "../../tests/fixtures",
+            scripts("persons", "session_membership")
+        )
+    )]
+    async fn fetch_some(ispyb_pool: MySqlPool) {
+        let session_participants = SessionParticipants::fetch(&ispyb_pool).await.unwrap();
+        let mut expected = BTreeMap::new();
+        expected.insert(
+            40,
+            BTreeMap::from([(
+                "foo".to_string(),
+                Participant {
+                    role: Some("Team Leader".to_string()),
+                    remote: false,
+                },
+            )]),
+        );
+        expected.insert(
+            41,
+            BTreeMap::from([(
+                "foo".to_string(),
+                Participant {
+                    role: Some("Co-Investigator".to_string()),
+                    remote: true,
+                },
+            )]),
+        );
+        expected.insert(
+            43,
+            BTreeMap::from([(
+                "bar".to_string(),
+                Participant {
+                    role: None,
+                    remote: false,
+                },
+            )]),
+        );
+        assert_eq!(expected, session_participants.0);
+    }
+}
@@ -1,12 +1,14 @@
 use derive_more::{Deref, DerefMut};
 use schemars::JsonSchema;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use sqlx::{query_as, MySqlPool};
 use std::collections::BTreeMap;
 use tracing::instrument;
 
 /// A mapping of sessions to their various attributes
-#[derive(Debug, Default, Deref, DerefMut, PartialEq, Eq, Hash, Serialize, JsonSchema)]
+#[derive(
+    Debug, Default, Clone, Deref, DerefMut, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema,
+)]
 pub struct Sessions(BTreeMap<u32, Session>);
 
 impl Sessions {
@@ -31,10 +33,40 @@ impl Sessions {
 
         Ok(session_rows.into_iter().collect())
     }
+
+    /// Fetches [`Sessions`] created in ISPyB at or after the given unix timestamp
+    #[instrument(name = "fetch_changed_sessions")]
+    pub async fn fetch_changed(ispyb_pool: &MySqlPool, since: i64) -> Result<Self, sqlx::Error> {
+        let session_rows = query_as!(
+            RawSessionRow,
+            "
+            SELECT
+                sessionId as session_id,
+                proposalNumber as proposal_number,
+                visit_number,
+                beamLineName as beamline
+            FROM
+                BLSession
+                JOIN Proposal USING (proposalId)
+            WHERE
+                BLSession.bltimeStamp >= FROM_UNIXTIME(?)
+            ",
+            since
+        )
+        .fetch_all(ispyb_pool)
+        .await?;
+
+        Ok(session_rows.into_iter().collect())
+    }
+
+    /// Updates the [`Sessions`] with newly fetched changes, replacing any existing entries
+    pub fn merge(&mut self, changes: Self) {
+        self.extend(changes.0);
+    }
 }
 
 /// The various attributes of a session
-#[derive(Debug, Default, PartialEq, Eq, Hash, Serialize, JsonSchema)]
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
 pub struct Session {
     /// The number of the proposal this session belongs to
     proposal_number: u32,
@@ -164,4 +196,81 @@ mod tests {
         );
         assert_eq!(expected, sessions.0);
     }
+
+    #[sqlx::test(
+        migrations = "tests/migrations",
+        fixtures(
+            path = "../../tests/fixtures",
+            scripts("beamline_sessions", "proposals")
+        )
+    )]
+    async fn fetch_changed_none(ispyb_pool: MySqlPool) {
+        let sessions = Sessions::fetch_changed(&ispyb_pool, i32::MAX.into())
+            .await
+            .unwrap();
+        let expected = Sessions(BTreeMap::new());
+        assert_eq!(expected, sessions);
+    }
+
+    #[sqlx::test(
+        migrations = "tests/migrations",
+        fixtures(
+            path = "../../tests/fixtures",
+            scripts("beamline_sessions", "proposals")
+        )
+    )]
+    async fn fetch_changed_all(ispyb_pool: MySqlPool) {
+        let sessions = Sessions::fetch_changed(&ispyb_pool, 0).await.unwrap();
+        let expected = Sessions::fetch(&ispyb_pool).await.unwrap();
+        assert_eq!(expected, sessions);
+    }
+
+    #[test]
+    fn merge_replaces_changed() {
+        let mut sessions = Sessions(BTreeMap::from([
+            (
+                40,
+                Session {
+                    proposal_number: 10030,
+                    visit_number: 10,
+                    beamline: "i12".to_string(),
+                },
+            ),
+            (
+                41,
+                Session {
+                    proposal_number: 10030,
+                    visit_number: 11,
+                    beamline: "i22".to_string(),
+                },
+            ),
+        ]));
+        sessions.merge(Sessions(BTreeMap::from([(
+            41,
+            Session {
+                proposal_number: 10030,
+                visit_number: 11,
+                beamline: "b13".to_string(),
+            },
+        )])));
+        let expected = BTreeMap::from([
+            (
+                40,
+                Session {
+                    proposal_number: 10030,
+                    visit_number: 10,
+                    beamline: "i12".to_string(),
+                },
+            ),
+            (
+                41,
+                Session {
+                    proposal_number: 10030,
+                    visit_number: 11,
+                    beamline: "b13".to_string(),
+                },
+            ),
+        ]);
+        assert_eq!(expected, sessions.0);
+    }
 }
@@ -8,20 +8,26 @@ mod sessions;
 use self::{
     permissions::SubjectPermissions, proposals::SubjectProposals, sessions::SubjectSessions,
 };
+use super::{with_timeout, FetchError};
 use derive_more::{Deref, DerefMut};
 use schemars::JsonSchema;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use sqlx::MySqlPool;
-use std::collections::{BTreeMap, HashSet};
+use std::{
+    collections::{BTreeMap, HashSet},
+    time::Duration,
+};
 use tokio::try_join;
 use tracing::instrument;
 
 /// A mapping of subjects to their various attributes
-#[derive(Debug, Default, Deref, DerefMut, PartialEq, Eq, Hash, Serialize, JsonSchema)]
+#[derive(
+    Debug, Default, Clone, Deref, DerefMut, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema,
+)]
 pub struct Subjects(BTreeMap<String, Subject>);
 
 /// The various attributes of a subject
-#[derive(Debug, Default, PartialEq, Eq, Hash, Serialize, JsonSchema)]
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
 pub struct Subject {
     /// The permissions given to a subject
     permissions: Vec<String>,
@@ -31,13 +37,36 @@ pub struct Subject {
     sessions: Vec<u32>,
 }
 
+impl Subject {
+    /// The sessions the subject is associated with
+    pub fn sessions(&self) -> &[u32] {
+        &self.sessions
+    }
+}
+
 impl Subjects {
+    /// Fetches [`Subjects`] from ISPyB, cancelling any query which exceeds the timeout
     #[instrument(name = "fetch_subjects")]
-    pub async fn fetch(ispyb_pool: &MySqlPool) -> Result<Self, sqlx::Error> {
+    pub async fn fetch(
+        ispyb_pool: &MySqlPool,
+        query_timeout: Duration,
+    ) -> Result<Self, FetchError> {
         let (mut permissions, mut proposals, mut sessions) = try_join!(
-            SubjectPermissions::fetch(ispyb_pool),
-            SubjectProposals::fetch(ispyb_pool),
-            SubjectSessions::fetch(ispyb_pool)
+            with_timeout(
+                "subject_permissions",
+                query_timeout,
+                SubjectPermissions::fetch(ispyb_pool)
+            ),
+            with_timeout(
+                "subject_proposals",
+                query_timeout,
+                SubjectProposals::fetch(ispyb_pool)
+            ),
+            with_timeout(
+                "subject_sessions",
+                query_timeout,
+                SubjectSessions::fetch(ispyb_pool)
+            ),
         )?;
 
         let mut subjects = Self::default();
@@ -51,9 +80,9 @@ impl Subjects {
             subjects.insert(
                 subject.to_owned(),
                 Subject {
-                    permissions: permissions.remove(&subject).clone().unwrap_or_default(),
-                    proposals: proposals.remove(&subject).unwrap_or_default(),
-                    sessions: sessions.remove(&subject).unwrap_or_default(),
+                    permissions: sorted(permissions.remove(&subject).unwrap_or_default()),
+                    proposals: sorted(proposals.remove(&subject).unwrap_or_default()),
+                    sessions: sorted(sessions.remove(&subject).unwrap_or_default()),
                 },
             );
         }
@@ -61,3 +90,9 @@ impl Subjects {
         Ok(subjects)
     }
 }
+
+/// Sorts the values, such that subjects are serialized identically regardless of the order in which rows were returned
+fn sorted<T: Ord>(mut values: Vec<T>) -> Vec<T> {
+    values.sort_unstable();
+    values
+}
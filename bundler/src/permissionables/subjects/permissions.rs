@@ -6,6 +6,10 @@ use std::collections::BTreeMap;
 use tracing::instrument;
 
 /// A mapping of subjects to their permissions via roles
+///
+/// ISPyB records no validity period against user groups, their members or their permissions, so permissions never expire and time-bounded access should rely on session membership
+///
+/// ISPyB user groups cannot contain other groups, so the direct memberships fetched here already include every permission a subject holds
 #[derive(Debug, Default, Deref, DerefMut, PartialEq, Eq, Hash, Serialize, JsonSchema)]
 pub struct SubjectPermissions(BTreeMap<String, Vec<String>>);
 
@@ -69,6 +73,41 @@ mod tests {
         assert_eq!(expected, permissions)
     }
 
+    #[sqlx::test(migrations = "tests/migrations")]
+    async fn permissions_undated(ispyb_pool: MySqlPool) {
+        let dated_columns = sqlx::query_scalar::<_, String>(
+            "
+            SELECT CONCAT(TABLE_NAME, '.', COLUMN_NAME)
+            FROM information_schema.COLUMNS
+            WHERE TABLE_SCHEMA = 'ispyb_build'
+                AND TABLE_NAME IN ('UserGroup', 'UserGroup_has_Person', 'UserGroup_has_Permission', 'Permission')
+                AND DATA_TYPE IN ('date', 'datetime', 'timestamp')
+            ",
+        )
+        .fetch_all(&ispyb_pool)
+        .await
+        .unwrap();
+        assert_eq!(Vec::<String>::new(), dated_columns)
+    }
+
+    #[sqlx::test(migrations = "tests/migrations")]
+    async fn user_groups_flat(ispyb_pool: MySqlPool) {
+        let nesting_tables = sqlx::query_scalar::<_, String>(
+            "
+            SELECT TABLE_NAME
+            FROM information_schema.KEY_COLUMN_USAGE
+            WHERE TABLE_SCHEMA = 'ispyb_build'
+                AND REFERENCED_TABLE_NAME = 'UserGroup'
+            GROUP BY TABLE_NAME
+            HAVING COUNT(*) > 1 OR TABLE_NAME = 'UserGroup'
+            ",
+        )
+        .fetch_all(&ispyb_pool)
+        .await
+        .unwrap();
+        assert_eq!(Vec::<String>::new(), nesting_tables)
+    }
+
     #[sqlx::test(
         migrations = "tests/migrations",
         fixtures(
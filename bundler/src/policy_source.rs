@@ -0,0 +1,333 @@
+use clap::Args;
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+use tokio::{
+    sync::Notify,
+    time::{interval, MissedTickBehavior},
+};
+
+/// The prefix under which policies are placed in the bundle, to which their packages must belong
+pub const POLICY_PREFIX: &str = "diamond/policy";
+
+/// Options for including Rego policies from a git repository in the bundle
+#[derive(Debug, Clone, Args)]
+pub struct PolicySourceArgs {
+    /// The URL of a git repository from which Rego policies are included in the bundle. Each policy must declare a package under 'diamond.policy'
+    #[arg(long, env = "BUNDLER_POLICY_GIT_URL")]
+    policy_git_url: Option<String>,
+    /// The branch, tag or commit of the git repository from which policies are read
+    #[arg(long, env = "BUNDLER_POLICY_GIT_REF", default_value = "main")]
+    policy_git_ref: String,
+    /// The directory of the git repository from which policies are read
+    #[arg(long, env = "BUNDLER_POLICY_GIT_PATH", default_value = ".")]
+    policy_git_path: PathBuf,
+    /// The interval at which the git repository is pulled
+    #[arg(long, env = "BUNDLER_POLICY_SYNC_INTERVAL", default_value_t = humantime::Duration::from(Duration::from_secs(300)))]
+    policy_sync_interval: humantime::Duration,
+    /// The directory into which the git repository is cloned, defaulting to a temporary directory
+    #[arg(long, env = "BUNDLER_POLICY_CHECKOUT_DIR")]
+    policy_checkout_dir: Option<PathBuf>,
+    /// The path of the git executable
+    #[arg(long, env = "BUNDLER_GIT_PATH", default_value = "git")]
+    git: PathBuf,
+}
+
+impl PolicySourceArgs {
+    /// The ref from which policies are read, if a repository is configured
+    pub fn git_ref(&self) -> Option<&str> {
+        self.policy_git_url
+            .is_some()
+            .then_some(self.policy_git_ref.as_str())
+    }
+}
+
+/// Rego policies read from a single commit of a git repository
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Policies {
+    /// The commit from which the policies were read
+    commit: String,
+    /// The source of each policy, keyed by its path relative to the policy directory
+    modules: BTreeMap<String, String>,
+}
+
+impl Policies {
+    /// Whether no policies are included
+    pub fn is_empty(&self) -> bool {
+        self.modules.is_empty()
+    }
+
+    /// The source of each policy, keyed by its path relative to the policy directory
+    pub fn modules(&self) -> &BTreeMap<String, String> {
+        &self.modules
+    }
+}
+
+/// The policies most recently synchronized from the git repository, shared between the synchronization task and the bundle updates
+#[derive(Debug, Clone, Default)]
+pub struct CurrentPolicies(Arc<RwLock<Policies>>);
+
+impl CurrentPolicies {
+    /// The policies most recently synchronized, which are empty until the repository has been synchronized
+    pub fn get(&self) -> Policies {
+        self.0.read().unwrap().clone()
+    }
+
+    /// Replaces the policies, returning whether they were read from a different commit
+    fn replace(&self, policies: Policies) -> bool {
+        let mut current = self.0.write().unwrap();
+        let changed = current.commit != policies.commit;
+        *current = policies;
+        changed
+    }
+}
+
+/// A git repository from which policies are synchronized
+#[derive(Debug, Clone)]
+pub struct PolicySource {
+    /// The URL of the repository
+    url: String,
+    /// The branch, tag or commit from which policies are read
+    git_ref: String,
+    /// The directory of the repository from which policies are read
+    path: PathBuf,
+    /// The interval at which the repository is pulled
+    sync_interval: Duration,
+    /// The directory into which the repository is cloned
+    checkout_dir: PathBuf,
+    /// The path of the git executable
+    git: PathBuf,
+    /// The policies most recently synchronized
+    current: CurrentPolicies,
+}
+
+impl PolicySource {
+    /// Creates a [`PolicySource`], if a repository is configured
+    pub fn from_args(args: PolicySourceArgs) -> Option<Self> {
+        Some(Self {
+            url: args.policy_git_url?,
+            git_ref: args.policy_git_ref,
+            path: args.policy_git_path,
+            sync_interval: args.policy_sync_interval.into(),
+            checkout_dir: args
+                .policy_checkout_dir
+                .unwrap_or_else(|| std::env::temp_dir().join("bundler-policies")),
+            git: args.git,
+            current: CurrentPolicies::default(),
+        })
+    }
+
+    /// The policies most recently synchronized
+    pub fn current(&self) -> CurrentPolicies {
+        self.current.clone()
+    }
+
+    /// Pulls the configured ref and reads the policies at it, returning whether they were read from a different commit
+    ///
+    /// Policies which fail validation are not used, such that the previously synchronized policies continue to be served
+    pub async fn sync(&self) -> Result<bool, anyhow::Error> {
+        let source = self.clone();
+        let policies = tokio::task::spawn_blocking(move || source.pull()).await??;
+        let commit = policies.commit.clone();
+        let changed = self.current.replace(policies);
+        if changed {
+            tracing::info!(
+                monotonic_counter.policy_syncs = 1,
+                "Using policies from commit {commit}"
+            );
+        }
+        Ok(changed)
+    }
+
+    /// Fetches the configured ref into the checkout directory and reads the policies from it
+    fn pull(&self) -> Result<Policies, anyhow::Error> {
+        std::fs::create_dir_all(&self.checkout_dir)?;
+        if !self.checkout_dir.join(".git").exists() {
+            self.git(&["init", "--quiet"])?;
+        }
+        self.git(&["fetch", "--quiet", "--depth", "1", &self.url, &self.git_ref])?;
+        self.git(&["checkout", "--quiet", "--force", "FETCH_HEAD"])?;
+        let commit = self.git(&["rev-parse", "HEAD"])?;
+        let modules = read_modules(&self.checkout_dir.join(&self.path))?;
+        Ok(Policies { commit, modules })
+    }
+
+    /// Runs git in the checkout directory, returning its trimmed standard output
+    fn git(&self, args: &[&str]) -> Result<String, anyhow::Error> {
+        let output = Command::new(&self.git)
+            .arg("-C")
+            .arg(&self.checkout_dir)
+            .args(args)
+            .output()
+            .map_err(|err| anyhow::anyhow!("Could not run {}: {err}", self.git.display()))?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "git {} failed: {}",
+                args.first().unwrap_or(&""),
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(String::from_utf8(output.stdout)?.trim().to_string())
+    }
+}
+
+/// Reads and validates each Rego policy beneath the directory, keyed by its path relative to the directory
+fn read_modules(directory: &Path) -> Result<BTreeMap<String, String>, anyhow::Error> {
+    let mut modules = BTreeMap::new();
+    let mut directories = vec![directory.to_path_buf()];
+    while let Some(current) = directories.pop() {
+        for entry in std::fs::read_dir(&current)? {
+            let path = entry?.path();
+            if path.file_name().is_some_and(|name| name == ".git") {
+                continue;
+            }
+            if path.is_dir() {
+                directories.push(path);
+            } else if path
+                .extension()
+                .is_some_and(|extension| extension == "rego")
+                && !path
+                    .file_stem()
+                    .is_some_and(|stem| stem.to_string_lossy().ends_with("_test"))
+            {
+                let relative = path
+                    .strip_prefix(directory)?
+                    .to_string_lossy()
+                    .replace('\\', "/");
+                let source = std::fs::read_to_string(&path)?;
+                validate_module(&relative, &source)?;
+                modules.insert(relative, source);
+            }
+        }
+    }
+    if modules.is_empty() {
+        anyhow::bail!("No policies found in {}", directory.display());
+    }
+    Ok(modules)
+}
+
+/// Checks that the policy declares a package beneath the policy prefix, such that Open Policy Agent accepts it within the roots of the bundle
+fn validate_module(path: &str, source: &str) -> Result<(), anyhow::Error> {
+    let package = source
+        .lines()
+        .map(str::trim)
+        .find_map(|line| line.strip_prefix("package "))
+        .map(str::trim)
+        .ok_or_else(|| anyhow::anyhow!("Policy {path} does not declare a package"))?;
+    let root = POLICY_PREFIX.replace('/', ".");
+    if package != root && !package.starts_with(&format!("{root}.")) {
+        anyhow::bail!("Policy {path} declares package {package}, which is not within {root}");
+    }
+    Ok(())
+}
+
+/// Periodically synchronizes policies from the git repository, requesting a refresh of the bundle whenever they change
+pub async fn sync_periodically(
+    policy_source: Option<PolicySource>,
+    refresh_requested: Arc<Notify>,
+) {
+    let Some(policy_source) = policy_source else {
+        return std::future::pending().await;
+    };
+    let mut syncs = interval(policy_source.sync_interval);
+    syncs.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    syncs.tick().await;
+    loop {
+        syncs.tick().await;
+        match policy_source.sync().await {
+            Ok(true) => refresh_requested.notify_one(),
+            Ok(false) => {}
+            Err(err) => tracing::warn!(
+                monotonic_counter.policy_sync_failures = 1,
+                "Could not synchronize policies, retrying at next interval: {err}"
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read_modules, validate_module, Policies, POLICY_PREFIX};
+    use crate::bundle::{Bundle, NoMetadata};
+    use std::{collections::BTreeMap, io::Read};
+
+    #[test]
+    fn packages_within_prefix() {
+        assert!(validate_module("a.rego", "package diamond.policy\n").is_ok());
+        assert!(validate_module("b.rego", "# comment\npackage diamond.policy.session\n").is_ok());
+        assert!(validate_module("c.rego", "package diamond.policyx\n").is_err());
+        assert!(validate_module("d.rego", "package other\n").is_err());
+        assert!(validate_module("e.rego", "allow := true\n").is_err());
+    }
+
+    #[test]
+    fn modules_read_recursively() {
+        let directory = std::env::temp_dir().join("bundler-policy-source-test");
+        let _ = std::fs::remove_dir_all(&directory);
+        std::fs::create_dir_all(directory.join("session")).unwrap();
+        std::fs::write(
+            directory.join("admin.rego"),
+            "package diamond.policy.admin\n",
+        )
+        .unwrap();
+        std::fs::write(
+            directory.join("session/access.rego"),
+            "package diamond.policy.session\n",
+        )
+        .unwrap();
+        std::fs::write(
+            directory.join("session/access_test.rego"),
+            "package diamond.policy.session_test\n",
+        )
+        .unwrap();
+        std::fs::write(directory.join("README.md"), "Policies").unwrap();
+        let modules = read_modules(&directory).unwrap();
+        assert_eq!(
+            vec!["admin.rego", "session/access.rego"],
+            modules.keys().collect::<Vec<_>>()
+        );
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn policies_included_in_bundle() {
+        let bundle = || Bundle::<NoMetadata>::default();
+        let policies = |commit: &str| Policies {
+            commit: commit.to_string(),
+            modules: BTreeMap::from([(
+                "access.rego".to_string(),
+                "package diamond.policy\n".to_string(),
+            )]),
+        };
+        let revision = bundle().revision().to_string();
+        assert_eq!(
+            revision,
+            bundle().with_policies(Policies::default()).revision()
+        );
+        let with_policies = bundle().with_policies(policies("a"));
+        assert_ne!(revision, with_policies.revision());
+        assert_ne!(
+            with_policies.revision(),
+            bundle().with_policies(policies("b")).revision()
+        );
+        let tar = with_policies.to_tar().unwrap();
+        let mut archive = tar::Archive::new(tar.as_slice());
+        let mut source = String::new();
+        archive
+            .entries()
+            .unwrap()
+            .map(Result::unwrap)
+            .find(|entry| {
+                entry.path().unwrap().to_string_lossy() == format!("{POLICY_PREFIX}/access.rego")
+            })
+            .unwrap()
+            .read_to_string(&mut source)
+            .unwrap();
+        assert_eq!("package diamond.policy\n", source);
+    }
+}
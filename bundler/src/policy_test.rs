@@ -0,0 +1,195 @@
+use clap::Parser;
+use serde::Deserialize;
+use serde_json::Value;
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+/// Arguments to evaluate policy test cases against a built bundle with Open Policy Agent
+#[derive(Debug, Parser)]
+pub struct TestArgs {
+    /// The path of the gzipped bundle against which cases are evaluated
+    #[arg(long)]
+    bundle: PathBuf,
+    /// The directory containing the Rego policies under test
+    #[arg(long)]
+    policy_dir: PathBuf,
+    /// The path of a YAML file listing the named cases to evaluate
+    #[arg(long)]
+    cases: PathBuf,
+    /// The Open Policy Agent binary with which cases are evaluated
+    #[arg(long, env = "BUNDLER_OPA_PATH", default_value = "opa")]
+    opa: PathBuf,
+}
+
+/// A named query, evaluated against an input, with its expected result
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TestCase {
+    /// The name by which the case is reported
+    name: String,
+    /// The Rego query to evaluate, such as `data.diamond.policy.session.access`
+    query: String,
+    /// The input document provided to the query
+    #[serde(default)]
+    input: Value,
+    /// The expected result of the query, or undefined if absent
+    #[serde(default)]
+    expect: Option<Value>,
+}
+
+/// The output of `opa eval` in JSON format
+#[derive(Debug, Deserialize)]
+struct Evaluation {
+    /// The result sets produced by the query, absent if it was undefined
+    #[serde(default)]
+    result: Vec<EvaluationResult>,
+}
+
+/// A result set produced by `opa eval`
+#[derive(Debug, Deserialize)]
+struct EvaluationResult {
+    /// The value of each expression in the query
+    expressions: Vec<EvaluationExpression>,
+}
+
+/// The value of an expression evaluated by `opa eval`
+#[derive(Debug, Deserialize)]
+struct EvaluationExpression {
+    /// The value the expression evaluated to
+    value: Value,
+}
+
+/// Evaluates each case against the bundle and policies, printing a report and returning whether all cases passed
+pub fn run(args: TestArgs) -> bool {
+    let cases = match read_cases(&args.cases) {
+        Ok(cases) => cases,
+        Err(err) => {
+            println!("[FAIL] cases: {err}");
+            return false;
+        }
+    };
+    let mut failures = 0;
+    for case in &cases {
+        match evaluate(&args, case) {
+            Ok(result) if result == case.expect => println!("[PASS] {}", case.name),
+            Ok(result) => {
+                failures += 1;
+                println!(
+                    "[FAIL] {}: expected {}, found {}",
+                    case.name,
+                    describe(case.expect.as_ref()),
+                    describe(result.as_ref())
+                );
+            }
+            Err(err) => {
+                failures += 1;
+                println!("[FAIL] {}: {err}", case.name);
+            }
+        }
+    }
+    println!("{} of {} cases failed", failures, cases.len());
+    failures == 0
+}
+
+/// Reads the [`TestCase`]s from a YAML file
+fn read_cases(path: &Path) -> Result<Vec<TestCase>, anyhow::Error> {
+    Ok(serde_yaml::from_slice(&std::fs::read(path)?)?)
+}
+
+/// Evaluates the query of the [`TestCase`] with `opa eval`, returning its result, or none if it was undefined
+fn evaluate(args: &TestArgs, case: &TestCase) -> Result<Option<Value>, anyhow::Error> {
+    let mut opa = Command::new(&args.opa)
+        .arg("eval")
+        .args(["--format", "json", "--stdin-input"])
+        .arg("--bundle")
+        .arg(&args.bundle)
+        .arg("--data")
+        .arg(&args.policy_dir)
+        .arg(&case.query)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| anyhow::anyhow!("Could not run {}: {err}", args.opa.display()))?;
+    if let Some(mut stdin) = opa.stdin.take() {
+        serde_json::to_writer(&mut stdin, &case.input)?;
+        stdin.flush()?;
+    }
+    let output = opa.wait_with_output()?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "opa eval failed with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    result_value(&output.stdout)
+}
+
+/// Extracts the value of the first expression of the first result set from the output of `opa eval`, or none if it was undefined
+fn result_value(output: &[u8]) -> Result<Option<Value>, anyhow::Error> {
+    let evaluation = serde_json::from_slice::<Evaluation>(output)?;
+    Ok(evaluation
+        .result
+        .into_iter()
+        .next()
+        .and_then(|result| result.expressions.into_iter().next())
+        .map(|expression| expression.value))
+}
+
+/// Describes a query result, which may be undefined, for reporting
+fn describe(result: Option<&Value>) -> String {
+    result.map_or_else(|| "undefined".to_string(), ToString::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{result_value, TestCase};
+    use serde_json::json;
+
+    #[test]
+    fn parse_cases() {
+        let cases = serde_yaml::from_str::<Vec<TestCase>>(
+            r#"
+            - name: member may read
+              query: data.diamond.policy.session.access
+              input: {subject: foo, proposal: 10030, visit: 10}
+              expect: true
+            - name: unknown undefined
+              query: data.diamond.policy.unknown
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            vec![
+                TestCase {
+                    name: "member may read".to_string(),
+                    query: "data.diamond.policy.session.access".to_string(),
+                    input: json!({"subject": "foo", "proposal": 10030, "visit": 10}),
+                    expect: Some(json!(true)),
+                },
+                TestCase {
+                    name: "unknown undefined".to_string(),
+                    query: "data.diamond.policy.unknown".to_string(),
+                    input: json!(null),
+                    expect: None,
+                },
+            ],
+            cases
+        );
+    }
+
+    #[test]
+    fn result_defined() {
+        let output = br#"{"result": [{"expressions": [{"value": true, "text": "data.x", "location": {"row": 1, "col": 1}}]}]}"#;
+        assert_eq!(Some(json!(true)), result_value(output).unwrap());
+    }
+
+    #[test]
+    fn result_undefined() {
+        assert_eq!(None, result_value(b"{}").unwrap());
+    }
+}
@@ -0,0 +1,294 @@
+use crate::bundle::DATASETS;
+use clap::ValueEnum;
+use serde::Serialize;
+use std::{collections::BTreeMap, str::FromStr, time::Duration};
+use tokio::time::{interval_at, Instant, Interval, MissedTickBehavior};
+
+/// The interval at which ISPyB is polled, backing off whilst fetches leave the bundle unchanged
+#[derive(Debug, Clone)]
+pub struct AdaptiveInterval {
+    /// The interval used after a change, or a requested refresh
+    base: Duration,
+    /// The longest interval reached by backing off
+    max: Duration,
+    /// The interval currently in use
+    current: Duration,
+}
+
+impl AdaptiveInterval {
+    /// Creates an [`AdaptiveInterval`] starting at the base interval, which may back off up to the maximum, if any
+    pub fn new(base: Duration, max: Option<Duration>) -> Self {
+        Self {
+            base,
+            max: max.unwrap_or(base).max(base),
+            current: base,
+        }
+    }
+
+    /// The interval currently in use
+    pub fn current(&self) -> Duration {
+        self.current
+    }
+
+    /// The longest interval reached by backing off
+    pub fn max(&self) -> Duration {
+        self.max
+    }
+
+    /// Doubles the interval, up to the maximum, after a fetch which left the bundle unchanged
+    pub fn back_off(&mut self) {
+        self.current = self.current.saturating_mul(2).min(self.max);
+    }
+
+    /// Returns to the base interval after a change, or a requested refresh, returning whether it had backed off
+    pub fn reset(&mut self) -> bool {
+        let backed_off = self.current != self.base;
+        self.current = self.base;
+        backed_off
+    }
+
+    /// Replaces the base interval, raising the maximum to it if necessary and tracking it if no back off was configured, and returns to it
+    #[cfg(feature = "k8s")]
+    pub fn rebase(&mut self, base: Duration) {
+        self.max = if self.max == self.base {
+            base
+        } else {
+            self.max.max(base)
+        };
+        self.base = base;
+        self.current = base;
+    }
+}
+
+/// How polls missed whilst the host was suspended, or whilst a fetch overran the polling interval, are made up
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum MissedPolls {
+    /// Missed polls are made up immediately, one after another, until the schedule has caught up
+    Burst,
+    /// A single poll is made immediately, with the schedule restarting from it
+    Delay,
+    /// A single poll is made immediately, with the schedule continuing as if no polls were missed
+    Skip,
+}
+
+impl From<MissedPolls> for MissedTickBehavior {
+    fn from(missed_polls: MissedPolls) -> Self {
+        match missed_polls {
+            MissedPolls::Burst => Self::Burst,
+            MissedPolls::Delay => Self::Delay,
+            MissedPolls::Skip => Self::Skip,
+        }
+    }
+}
+
+/// The schedule on which ISPyB is polled, driven by a [`tokio::time::Interval`] which is restarted whenever the polling interval changes
+#[derive(Debug)]
+pub struct PollSchedule {
+    /// The interval producing each poll
+    interval: Interval,
+    /// How missed polls are made up
+    missed_polls: MissedPolls,
+    /// The time at which the next poll is expected to be made
+    next: Instant,
+}
+
+/// The lateness beyond which Tokio considers a tick missed
+const MISSED_TICK_TOLERANCE: Duration = Duration::from_millis(5);
+
+impl PollSchedule {
+    /// Creates a [`PollSchedule`] making its first poll at the given time, then polling at the period
+    pub fn new(first: Instant, period: Duration, missed_polls: MissedPolls) -> Self {
+        let mut interval = interval_at(first, period);
+        interval.set_missed_tick_behavior(missed_polls.into());
+        Self {
+            interval,
+            missed_polls,
+            next: first,
+        }
+    }
+
+    /// Waits until the next poll is due, logging any polls missed since it was scheduled
+    pub async fn tick(&mut self) {
+        let scheduled = self.interval.tick().await;
+        let now = Instant::now();
+        let period = self.interval.period();
+        let late = now.saturating_duration_since(scheduled);
+        let missed = (late.as_nanos() / period.as_nanos().max(1)) as u64;
+        if missed > 0 {
+            tracing::warn!(
+                monotonic_counter.missed_polls = missed,
+                "Poll was {} late, missing {missed} polls, which are handled as {:?}",
+                humantime::format_duration(late),
+                self.missed_polls
+            );
+        }
+        self.next = match self.missed_polls {
+            MissedPolls::Delay if late > MISSED_TICK_TOLERANCE => now + period,
+            MissedPolls::Skip if late > MISSED_TICK_TOLERANCE => {
+                now + period
+                    - Duration::from_nanos((late.as_nanos() % period.as_nanos().max(1)) as u64)
+            }
+            _ => scheduled + period,
+        };
+    }
+
+    /// Polls at the period, rescheduling the next poll to the period after the previous, if it differs from the current period
+    pub fn set_period(&mut self, period: Duration) {
+        let previous_period = self.interval.period();
+        if period != previous_period {
+            let next = self
+                .next
+                .checked_sub(previous_period)
+                .map_or(self.next, |previous| previous + period);
+            self.restart(next, period);
+        }
+    }
+
+    /// Polls at the period, bringing the next poll forward to no later than the period from now
+    pub fn bring_forward(&mut self, period: Duration) {
+        let next = self.next.min(Instant::now() + period);
+        if next != self.next || period != self.interval.period() {
+            self.restart(next, period);
+        }
+    }
+
+    /// Replaces the interval with one making its first poll at the given time
+    fn restart(&mut self, next: Instant, period: Duration) {
+        *self = Self::new(next, period, self.missed_polls);
+    }
+}
+
+/// The interval at which a dataset is polled, in place of the base polling interval
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DatasetInterval {
+    /// The name of the dataset
+    dataset: String,
+    /// The interval at which the dataset is polled
+    interval: Duration,
+}
+
+impl FromStr for DatasetInterval {
+    type Err = anyhow::Error;
+
+    fn from_str(dataset_interval: &str) -> Result<Self, Self::Err> {
+        let (dataset, interval) = dataset_interval.split_once('=').ok_or_else(|| {
+            anyhow::anyhow!(
+                "Polling interval '{dataset_interval}' is not of the form '<dataset>=<interval>'"
+            )
+        })?;
+        if !DATASETS.contains(&dataset) {
+            anyhow::bail!(
+                "Polling interval '{dataset_interval}' names unknown dataset '{dataset}', expected one of {}",
+                DATASETS.join(", ")
+            );
+        }
+        Ok(Self {
+            dataset: dataset.to_string(),
+            interval: humantime::parse_duration(interval)?,
+        })
+    }
+}
+
+/// The intervals at which individual datasets are polled, with any other dataset fetched at every poll
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DatasetIntervals(BTreeMap<String, Duration>);
+
+impl From<Vec<DatasetInterval>> for DatasetIntervals {
+    fn from(dataset_intervals: Vec<DatasetInterval>) -> Self {
+        Self(
+            dataset_intervals
+                .into_iter()
+                .map(|dataset_interval| (dataset_interval.dataset, dataset_interval.interval))
+                .collect(),
+        )
+    }
+}
+
+impl DatasetIntervals {
+    /// Whether the named dataset should be refetched, having been fetched this long ago
+    pub fn is_due(&self, dataset: &str, elapsed: Duration) -> bool {
+        self.0
+            .get(dataset)
+            .is_none_or(|interval| elapsed >= *interval)
+    }
+
+    /// Describes the interval of each dataset, as reported in the effective configuration
+    pub fn describe(&self) -> BTreeMap<String, String> {
+        self.0
+            .iter()
+            .map(|(dataset, interval)| {
+                (
+                    dataset.clone(),
+                    humantime::format_duration(*interval).to_string(),
+                )
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AdaptiveInterval, DatasetInterval, DatasetIntervals, MissedPolls, PollSchedule};
+    use std::{str::FromStr, time::Duration};
+    use tokio::time::Instant;
+
+    #[test]
+    fn backs_off_to_max() {
+        let mut interval =
+            AdaptiveInterval::new(Duration::from_secs(60), Some(Duration::from_secs(300)));
+        let mut intervals = Vec::new();
+        for _ in 0..4 {
+            interval.back_off();
+            intervals.push(interval.current().as_secs());
+        }
+        assert_eq!(vec![120, 240, 300, 300], intervals);
+        assert!(interval.reset());
+        assert_eq!(Duration::from_secs(60), interval.current());
+        assert!(!interval.reset());
+    }
+
+    #[test]
+    fn fixed_without_max() {
+        let mut interval = AdaptiveInterval::new(Duration::from_secs(60), None);
+        interval.back_off();
+        assert_eq!(Duration::from_secs(60), interval.current());
+    }
+
+    #[tokio::test]
+    async fn poll_schedule_rescheduled() {
+        let start = Instant::now();
+        let mut schedule = PollSchedule::new(
+            start + Duration::from_secs(60),
+            Duration::from_secs(60),
+            MissedPolls::Delay,
+        );
+        schedule.set_period(Duration::from_secs(120));
+        assert_eq!(start + Duration::from_secs(120), schedule.next);
+        schedule.bring_forward(Duration::from_secs(60));
+        assert!(schedule.next <= Instant::now() + Duration::from_secs(60));
+        assert_eq!(Duration::from_secs(60), schedule.interval.period());
+        schedule.bring_forward(Duration::from_millis(10));
+        schedule.tick().await;
+        assert!(schedule.next > start);
+    }
+
+    #[test]
+    fn dataset_intervals_due() {
+        let intervals = DatasetIntervals::from(vec![
+            DatasetInterval::from_str("subjects=10m").unwrap(),
+            DatasetInterval::from_str("roles=1h").unwrap(),
+        ]);
+        assert!(!intervals.is_due("subjects", Duration::from_secs(599)));
+        assert!(intervals.is_due("subjects", Duration::from_secs(600)));
+        assert!(!intervals.is_due("roles", Duration::from_secs(600)));
+        assert!(intervals.is_due("sessions", Duration::ZERO));
+    }
+
+    #[test]
+    fn parse_dataset_intervals() {
+        assert!(DatasetInterval::from_str("subjects").is_err());
+        assert!(DatasetInterval::from_str("visits=10m").is_err());
+        assert!(DatasetInterval::from_str("subjects=often").is_err());
+    }
+}
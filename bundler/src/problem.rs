@@ -0,0 +1,226 @@
+use axum::{
+    body::{Body, HttpBody},
+    extract::Request,
+    http::{
+        header::{CONTENT_TYPE, WWW_AUTHENTICATE},
+        HeaderValue, StatusCode,
+    },
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+/// The media type of RFC 7807 problem details
+const PROBLEM_JSON: &str = "application/problem+json";
+
+/// An error returned by the HTTP API, which is described to the client by an RFC 7807 problem
+#[derive(Debug, thiserror::Error)]
+pub enum ApiError {
+    /// The request was malformed
+    #[error("{0}")]
+    BadRequest(String),
+    /// The request did not carry valid credentials
+    #[error("A valid bearer token was not provided")]
+    Unauthorized {
+        /// Whether the client should be challenged for HTTP Basic credentials
+        basic_auth: bool,
+    },
+    /// The requested resource does not exist
+    #[error("{0}")]
+    NotFound(String),
+    /// The requested revision is no longer retained
+    #[error("Revision {0} is no longer retained")]
+    Gone(String),
+    /// The request body exceeded the size limit
+    #[error("{0}")]
+    PayloadTooLarge(String),
+    /// The request could not be served due to a failure within the service
+    #[error("{0}")]
+    Internal(String),
+    /// The request cannot be served until a bundle has been fetched
+    #[error("No bundle has been fetched yet")]
+    Unavailable,
+    /// The request was shed as too many are being served concurrently
+    #[error("Too many requests are being served, retry later")]
+    Overloaded,
+}
+
+impl ApiError {
+    /// The status code with which the error is returned
+    fn status(&self) -> StatusCode {
+        match self {
+            Self::BadRequest(_) => StatusCode::BAD_REQUEST,
+            Self::Unauthorized { .. } => StatusCode::UNAUTHORIZED,
+            Self::NotFound(_) => StatusCode::NOT_FOUND,
+            Self::Gone(_) => StatusCode::GONE,
+            Self::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            Self::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::Unavailable | Self::Overloaded => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let status = self.status();
+        let mut response = Problem::new(status, Some(self.to_string())).into_response();
+        if let Self::Unauthorized { basic_auth: true } = self {
+            response.headers_mut().insert(
+                WWW_AUTHENTICATE,
+                HeaderValue::from_static("Basic realm=\"bundler\""),
+            );
+        }
+        response
+    }
+}
+
+/// The RFC 7807 description of an error returned by the HTTP API
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Problem {
+    /// A URI identifying the type of problem, which is 'about:blank' as problems are described by their status alone
+    #[serde(rename = "type")]
+    problem_type: String,
+    /// The reason phrase of the status code
+    title: String,
+    /// The status code of the response
+    status: u16,
+    /// An explanation specific to this occurrence of the problem, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    detail: Option<String>,
+    /// The 'X-Request-Id' of the request, with which it may be found in the logs and traces of the service
+    #[serde(skip_serializing_if = "Option::is_none")]
+    correlation_id: Option<String>,
+}
+
+impl Problem {
+    /// Creates a [`Problem`] describing the status code, with any detail
+    fn new(status: StatusCode, detail: Option<String>) -> Self {
+        Self {
+            problem_type: "about:blank".to_string(),
+            title: status.canonical_reason().unwrap_or_default().to_string(),
+            status: status.as_u16(),
+            detail,
+            correlation_id: None,
+        }
+    }
+}
+
+impl IntoResponse for Problem {
+    fn into_response(self) -> Response {
+        let status = StatusCode::from_u16(self.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        let mut response = (
+            status,
+            [(CONTENT_TYPE, HeaderValue::from_static(PROBLEM_JSON))],
+            serde_json::to_vec(&self).unwrap_or_default(),
+        )
+            .into_response();
+        response.extensions_mut().insert(self);
+        response
+    }
+}
+
+/// Describes each error response with an RFC 7807 problem carrying the 'X-Request-Id' of the request as its correlation ID, including those returned without a body
+pub async fn problem_details(request: Request, next: Next) -> Response {
+    let correlation_id = request
+        .headers()
+        .get("x-request-id")
+        .and_then(|request_id| request_id.to_str().ok())
+        .map(ToString::to_string);
+    let mut response = next.run(request).await;
+    let status = response.status();
+    if !status.is_client_error() && !status.is_server_error() {
+        return response;
+    }
+    let mut problem = match response.extensions_mut().remove::<Problem>() {
+        Some(problem) => problem,
+        None if response.body().size_hint().exact() == Some(0) => Problem::new(status, None),
+        None => return response,
+    };
+    problem.correlation_id = correlation_id;
+    let (mut parts, _) = response.into_parts();
+    parts
+        .headers
+        .insert(CONTENT_TYPE, HeaderValue::from_static(PROBLEM_JSON));
+    parts.headers.remove("content-length");
+    Response::from_parts(
+        parts,
+        Body::from(serde_json::to_vec(&problem).unwrap_or_default()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{problem_details, ApiError};
+    use axum::{
+        body::{to_bytes, Body},
+        extract::Request,
+        http::{header::CONTENT_TYPE, StatusCode},
+        middleware::from_fn,
+        routing::get,
+        Router,
+    };
+    use serde_json::{json, Value};
+    use tower::ServiceExt;
+
+    async fn problem(path: &str) -> (StatusCode, Option<String>, Value) {
+        let response = Router::new()
+            .route(
+                "/missing",
+                get(|| async { ApiError::NotFound("No such dataset".to_string()) }),
+            )
+            .route("/limited", get(|| async { StatusCode::TOO_MANY_REQUESTS }))
+            .layer(from_fn(problem_details))
+            .oneshot(
+                Request::builder()
+                    .uri(path)
+                    .header("x-request-id", "abc")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let status = response.status();
+        let content_type = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .map(|content_type| content_type.to_str().unwrap().to_string());
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, content_type, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn errors_described_with_correlation_id() {
+        assert_eq!(
+            (
+                StatusCode::NOT_FOUND,
+                Some("application/problem+json".to_string()),
+                json!({
+                    "type": "about:blank",
+                    "title": "Not Found",
+                    "status": 404,
+                    "detail": "No such dataset",
+                    "correlation_id": "abc"
+                })
+            ),
+            problem("/missing").await
+        );
+    }
+
+    #[tokio::test]
+    async fn bare_statuses_described() {
+        assert_eq!(
+            (
+                StatusCode::TOO_MANY_REQUESTS,
+                Some("application/problem+json".to_string()),
+                json!({
+                    "type": "about:blank",
+                    "title": "Too Many Requests",
+                    "status": 429,
+                    "correlation_id": "abc"
+                })
+            ),
+            problem("/limited").await
+        );
+    }
+}
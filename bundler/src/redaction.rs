@@ -0,0 +1,284 @@
+use crate::bundle::{ContentHasher, DATASETS};
+use clap::{Args, ValueEnum};
+use serde::Serialize;
+use serde_json::Value;
+use std::{collections::BTreeMap, fmt::Display, str::FromStr};
+
+/// Options for redacting personal data from datasets before they are serialized into the bundle
+#[derive(Debug, Clone, Args)]
+pub struct RedactionArgs {
+    /// Fields to redact from each entry of a dataset, as '<dataset>.<field>' optionally followed by '=drop' or '=hash'. Fields are dropped unless hashing is requested
+    #[arg(long = "redact", env = "BUNDLER_REDACT", value_delimiter = ',')]
+    redact: Vec<RedactionRule>,
+    /// If set, string values resembling email addresses are redacted from every dataset by this action
+    #[arg(long, env = "BUNDLER_REDACT_EMAILS", value_enum)]
+    redact_emails: Option<RedactionAction>,
+    /// If enabled, values which would be redacted are reported in the logs, but are left in the bundle
+    #[arg(long, env = "BUNDLER_REDACTION_DRY_RUN")]
+    redaction_dry_run: bool,
+}
+
+/// The means by which a value is redacted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum RedactionAction {
+    /// The value is removed from the bundle
+    Drop,
+    /// The value is replaced by the hex encoded SHA-256 digest of its JSON serialization, such that equal values remain comparable
+    Hash,
+}
+
+/// A field to be redacted from each entry of a dataset
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RedactionRule {
+    /// The dataset from which the field is redacted
+    dataset: String,
+    /// The name of the field redacted from each entry
+    field: String,
+    /// The means by which the field is redacted
+    action: RedactionAction,
+}
+
+impl FromStr for RedactionRule {
+    type Err = anyhow::Error;
+
+    fn from_str(rule: &str) -> Result<Self, Self::Err> {
+        let (path, action) = match rule.split_once('=') {
+            Some((path, action)) => (
+                path,
+                RedactionAction::from_str(action, true).map_err(|_| {
+                    anyhow::anyhow!("Redaction action must be 'drop' or 'hash', found '{action}'")
+                })?,
+            ),
+            None => (rule, RedactionAction::Drop),
+        };
+        let (dataset, field) = path
+            .split_once('.')
+            .filter(|(_, field)| !field.is_empty())
+            .ok_or_else(|| {
+                anyhow::anyhow!("Redaction '{rule}' is not of the form '<dataset>.<field>'")
+            })?;
+        if !DATASETS.contains(&dataset) {
+            anyhow::bail!(
+                "Redaction '{rule}' names unknown dataset '{dataset}', expected one of {}",
+                DATASETS.join(", ")
+            );
+        }
+        Ok(Self {
+            dataset: dataset.to_string(),
+            field: field.to_string(),
+            action,
+        })
+    }
+}
+
+impl Display for RedactionAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Drop => write!(f, "drop"),
+            Self::Hash => write!(f, "hash"),
+        }
+    }
+}
+
+impl Display for RedactionRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}={}", self.dataset, self.field, self.action)
+    }
+}
+
+/// The redactions applied to each dataset between fetching and serialization
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Redactions {
+    /// The fields redacted from each entry of a dataset
+    rules: Vec<RedactionRule>,
+    /// The action by which email-like strings are redacted from every dataset, if any
+    emails: Option<RedactionAction>,
+    /// Whether redactions are only reported, rather than applied
+    dry_run: bool,
+}
+
+impl From<RedactionArgs> for Redactions {
+    fn from(args: RedactionArgs) -> Self {
+        Self {
+            rules: args.redact,
+            emails: args.redact_emails,
+            dry_run: args.redaction_dry_run,
+        }
+    }
+}
+
+impl Redactions {
+    /// Whether no redactions are configured
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty() && self.emails.is_none()
+    }
+
+    /// Whether the redactions are only reported, rather than applied
+    pub fn is_dry_run(&self) -> bool {
+        self.dry_run
+    }
+
+    /// Whether any redaction may apply to the named dataset
+    pub fn applies_to(&self, dataset: &str) -> bool {
+        self.emails.is_some() || self.rules.iter().any(|rule| rule.dataset == dataset)
+    }
+
+    /// Describes each configured redaction, as reported in the effective configuration
+    pub fn describe(&self) -> Vec<String> {
+        self.rules
+            .iter()
+            .map(ToString::to_string)
+            .chain(self.emails.map(|action| format!("email={action}")))
+            .collect()
+    }
+
+    /// Redacts the serialized entries of the named dataset, returning the number of values redacted by each rule
+    pub fn apply(&self, dataset: &str, entries: &mut Value) -> BTreeMap<String, usize> {
+        let mut redacted = BTreeMap::new();
+        for rule in self.rules.iter().filter(|rule| rule.dataset == dataset) {
+            let Some(entries) = entries.as_object_mut() else {
+                continue;
+            };
+            let mut count = 0;
+            for entry in entries.values_mut().filter_map(Value::as_object_mut) {
+                match rule.action {
+                    RedactionAction::Drop => {
+                        count += usize::from(entry.remove(&rule.field).is_some())
+                    }
+                    RedactionAction::Hash => {
+                        if let Some(value) =
+                            entry.get_mut(&rule.field).filter(|value| !value.is_null())
+                        {
+                            *value = hash(value);
+                            count += 1;
+                        }
+                    }
+                }
+            }
+            redacted.insert(rule.to_string(), count);
+        }
+        if let Some(action) = self.emails {
+            let count = redact_emails(entries, action);
+            redacted.insert(format!("email={action}"), count);
+        }
+        redacted
+    }
+}
+
+/// Replaces a value with the hex encoded SHA-256 digest of its JSON serialization
+fn hash(value: &Value) -> Value {
+    let mut hasher = ContentHasher::default();
+    hasher.update(value);
+    Value::String(hasher.finish())
+}
+
+/// Whether a string resembles an email address, having a non-empty local part and a dotted domain
+fn is_email_like(value: &str) -> bool {
+    let Some((local, domain)) = value.split_once('@') else {
+        return false;
+    };
+    !local.is_empty()
+        && !value.chars().any(char::is_whitespace)
+        && domain
+            .split_once('.')
+            .is_some_and(|(host, suffix)| !host.is_empty() && !suffix.is_empty())
+}
+
+/// Redacts every email-like string within the value, at any depth, returning the number redacted
+fn redact_emails(value: &mut Value, action: RedactionAction) -> usize {
+    let mut count = 0;
+    match value {
+        Value::Object(object) => {
+            object.retain(|_, value| retain_redacted(value, action, &mut count))
+        }
+        Value::Array(array) => array.retain_mut(|value| retain_redacted(value, action, &mut count)),
+        _ => {}
+    }
+    count
+}
+
+/// Redacts the value if it is an email-like string, or any such strings within it, returning whether it should be retained
+fn retain_redacted(value: &mut Value, action: RedactionAction, count: &mut usize) -> bool {
+    match value {
+        Value::String(string) if is_email_like(string) => {
+            *count += 1;
+            match action {
+                RedactionAction::Drop => false,
+                RedactionAction::Hash => {
+                    *value = hash(value);
+                    true
+                }
+            }
+        }
+        value => {
+            *count += redact_emails(value, action);
+            true
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{hash, RedactionAction, RedactionRule, Redactions};
+    use serde_json::json;
+    use std::str::FromStr;
+
+    #[test]
+    fn parse_rules() {
+        assert_eq!(
+            RedactionRule {
+                dataset: "people".to_string(),
+                field: "email".to_string(),
+                action: RedactionAction::Drop,
+            },
+            RedactionRule::from_str("people.email").unwrap()
+        );
+        assert_eq!(
+            RedactionAction::Hash,
+            RedactionRule::from_str("people.name=hash").unwrap().action
+        );
+        assert!(RedactionRule::from_str("people").is_err());
+        assert!(RedactionRule::from_str("persons.email").is_err());
+        assert!(RedactionRule::from_str("people.email=mask").is_err());
+    }
+
+    #[test]
+    fn fields_redacted() {
+        let redactions = Redactions {
+            rules: vec![
+                RedactionRule::from_str("people.email").unwrap(),
+                RedactionRule::from_str("people.name=hash").unwrap(),
+            ],
+            ..Default::default()
+        };
+        let mut people = json!({
+            "foo": {"name": "Foo Fighter", "email": "foo@example.com"},
+            "bar": {"name": null, "email": null},
+        });
+        let redacted = redactions.apply("people", &mut people);
+        assert_eq!(
+            json!({
+                "foo": {"name": hash(&json!("Foo Fighter"))},
+                "bar": {"name": null},
+            }),
+            people
+        );
+        assert_eq!(Some(&2), redacted.get("people.email=drop"));
+        assert_eq!(Some(&1), redacted.get("people.name=hash"));
+    }
+
+    #[test]
+    fn emails_redacted_from_all_datasets() {
+        let redactions = Redactions {
+            emails: Some(RedactionAction::Drop),
+            ..Default::default()
+        };
+        let mut subjects = json!({
+            "foo": {"aliases": ["foo@example.com", "foo"], "contact": "foo@example.com"},
+        });
+        let redacted = redactions.apply("subjects", &mut subjects);
+        assert_eq!(json!({"foo": {"aliases": ["foo"]}}), subjects);
+        assert_eq!(Some(&2), redacted.get("email=drop"));
+    }
+}
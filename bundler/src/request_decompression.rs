@@ -0,0 +1,187 @@
+use crate::problem::ApiError;
+use axum::{
+    body::Body,
+    extract::{Request, State},
+    http::header::{CONTENT_ENCODING, CONTENT_LENGTH},
+    middleware::{from_fn_with_state, Next},
+    response::{IntoResponse, Response},
+    Router,
+};
+use clap::Args;
+use flate2::read::GzDecoder;
+use http_body_util::LengthLimitError;
+use std::io::Read;
+
+/// Options for decompressing gzipped request bodies, as uploaded by Open Policy Agent to the status and decision log receivers
+#[derive(Debug, Clone, Args)]
+pub struct RequestDecompressionArgs {
+    /// The largest request body accepted once decompressed, in bytes, guarding against decompression bombs
+    #[arg(
+        long,
+        env = "BUNDLER_MAX_DECOMPRESSED_REQUEST_BYTES",
+        default_value_t = 16 * 1024 * 1024
+    )]
+    max_decompressed_request_bytes: usize,
+}
+
+impl RequestDecompressionArgs {
+    /// The largest request body accepted, in bytes
+    pub fn body_limit(&self) -> usize {
+        self.max_decompressed_request_bytes
+    }
+
+    /// Transparently decompresses the request bodies of the routes of the router sent with 'Content-Encoding: gzip', refusing those exceeding the size limit
+    pub fn apply(&self, router: Router) -> Router {
+        router.layer(from_fn_with_state(
+            self.max_decompressed_request_bytes,
+            decompress_request,
+        ))
+    }
+}
+
+/// Replaces a gzipped request body with its decompression, reading no more than the size limit, such that handlers receive the body as is
+///
+/// Bodies exceeding the size limit are refused with 413 Payload Too Large, whilst malformed bodies are refused with 400 Bad Request
+async fn decompress_request(State(limit): State<usize>, request: Request, next: Next) -> Response {
+    let gzipped = request
+        .headers()
+        .get(CONTENT_ENCODING)
+        .is_some_and(|encoding| encoding.as_bytes().eq_ignore_ascii_case(b"gzip"));
+    if !gzipped {
+        return next.run(request).await;
+    }
+    let (mut parts, body) = request.into_parts();
+    let decompressed = match read_decompressed(body, limit).await {
+        Ok(decompressed) => decompressed,
+        Err(err) => {
+            tracing::warn!(
+                monotonic_counter.request_decompression_rejections = 1,
+                "Rejecting request to {}: {err}",
+                parts.uri.path()
+            );
+            return err.into_response();
+        }
+    };
+    parts.headers.remove(CONTENT_ENCODING);
+    parts.headers.remove(CONTENT_LENGTH);
+    next.run(Request::from_parts(parts, Body::from(decompressed)))
+        .await
+}
+
+/// Reads a gzipped body and decompresses it on the blocking thread pool, failing should it exceed the size limit either before or after decompression
+async fn read_decompressed(body: Body, limit: usize) -> Result<Vec<u8>, ApiError> {
+    let compressed = axum::body::to_bytes(body, limit).await.map_err(|err| {
+        if err.into_inner().is::<LengthLimitError>() {
+            ApiError::PayloadTooLarge(format!("Request body exceeds {limit} bytes"))
+        } else {
+            ApiError::BadRequest("Could not read request body".to_string())
+        }
+    })?;
+    tokio::task::spawn_blocking(move || decompress(&compressed, limit))
+        .await
+        .map_err(|err| ApiError::Internal(format!("Decompressing request body panicked: {err}")))?
+}
+
+/// Decompresses a gzipped body, failing should it exceed the size limit once decompressed
+fn decompress(compressed: &[u8], limit: usize) -> Result<Vec<u8>, ApiError> {
+    let mut decompressed = Vec::new();
+    GzDecoder::new(compressed)
+        .take(limit as u64 + 1)
+        .read_to_end(&mut decompressed)
+        .map_err(|err| ApiError::BadRequest(format!("Could not decompress request body: {err}")))?;
+    if decompressed.len() > limit {
+        return Err(ApiError::PayloadTooLarge(format!(
+            "Decompressed request body exceeds {limit} bytes"
+        )));
+    }
+    Ok(decompressed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RequestDecompressionArgs;
+    use axum::{
+        body::{Body, Bytes},
+        extract::Request,
+        http::{header::CONTENT_ENCODING, StatusCode},
+        routing::post,
+        Router,
+    };
+    use clap::Parser;
+    use flate2::{write::GzEncoder, Compression};
+    use std::io::Write;
+    use tower::ServiceExt;
+
+    #[derive(Parser)]
+    struct Args {
+        #[command(flatten)]
+        request_decompression: RequestDecompressionArgs,
+    }
+
+    async fn upload(args: &[&str], body: Vec<u8>, gzipped: bool) -> (StatusCode, Bytes) {
+        let router = Args::parse_from(args)
+            .request_decompression
+            .apply(Router::new().route("/status/opa", post(|body: Bytes| async { body })));
+        let mut request = Request::builder().method("POST").uri("/status/opa");
+        if gzipped {
+            request = request.header(CONTENT_ENCODING, "gzip");
+        }
+        let response = router
+            .oneshot(request.body(Body::from(body)).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, body)
+    }
+
+    fn gzip(body: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(body).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[tokio::test]
+    async fn gzipped_body_decompressed() {
+        let status = br#"{"labels": {"id": "a"}}"#;
+        assert_eq!(
+            (StatusCode::OK, Bytes::from_static(status)),
+            upload(&["bundler"], gzip(status), true).await
+        );
+        assert_eq!(
+            (StatusCode::OK, Bytes::from_static(status)),
+            upload(&["bundler"], status.to_vec(), false).await
+        );
+        assert_eq!(
+            StatusCode::BAD_REQUEST,
+            upload(&["bundler"], status.to_vec(), true).await.0
+        );
+    }
+
+    #[tokio::test]
+    async fn decompression_bomb_refused() {
+        let bomb = gzip(&vec![0; 1024 * 1024]);
+        assert_eq!(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            upload(
+                &["bundler", "--max-decompressed-request-bytes", "1024"],
+                bomb,
+                true
+            )
+            .await
+            .0
+        );
+        assert_eq!(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            upload(
+                &["bundler", "--max-decompressed-request-bytes", "1024"],
+                vec![0; 2048],
+                true
+            )
+            .await
+            .0
+        );
+    }
+}
@@ -0,0 +1,103 @@
+use crate::problem::ApiError;
+use axum::{error_handling::HandleErrorLayer, BoxError, Router};
+use clap::Args;
+use std::time::Duration;
+use tower::{limit::ConcurrencyLimitLayer, load_shed::error::Overloaded, ServiceBuilder};
+use tower_http::timeout::TimeoutLayer;
+
+/// Options for limiting the time taken by, and the number of, concurrent bundle requests, such that a slow client or a burst of downloads cannot starve the bundle updates
+#[derive(Debug, Clone, Args)]
+pub struct RequestLimitArgs {
+    /// The time after which a bundle request is abandoned with 408 Request Timeout, if any
+    #[arg(long, env = "BUNDLER_REQUEST_TIMEOUT")]
+    request_timeout: Option<humantime::Duration>,
+    /// The number of bundle requests served concurrently, beyond which further requests are shed with 503 Service Unavailable, if any
+    #[arg(long, env = "BUNDLER_MAX_CONCURRENT_REQUESTS", value_parser = clap::value_parser!(u32).range(1..))]
+    max_concurrent_requests: Option<u32>,
+}
+
+impl RequestLimitArgs {
+    /// Applies the configured timeout and concurrency limit to the routes of the router
+    pub fn apply(&self, router: Router) -> Router {
+        let router = match self.max_concurrent_requests {
+            Some(max_concurrent_requests) => router.layer(
+                ServiceBuilder::new()
+                    .layer(HandleErrorLayer::new(shed_request))
+                    .load_shed()
+                    .layer(ConcurrencyLimitLayer::new(max_concurrent_requests as usize)),
+            ),
+            None => router,
+        };
+        match self.request_timeout {
+            Some(request_timeout) => {
+                router.layer(TimeoutLayer::new(Duration::from(request_timeout)))
+            }
+            None => router,
+        }
+    }
+}
+
+/// Responds to a request refused by the concurrency limit with 503 Service Unavailable
+async fn shed_request(err: BoxError) -> ApiError {
+    if err.is::<Overloaded>() {
+        tracing::warn!(
+            monotonic_counter.shed_requests = 1,
+            "Shedding request, as the concurrency limit has been reached"
+        );
+        ApiError::Overloaded
+    } else {
+        tracing::error!("Could not serve request: {err}");
+        ApiError::Internal("Could not serve request".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RequestLimitArgs;
+    use axum::{body::Body, extract::Request, http::StatusCode, routing::get, Router};
+    use std::time::Duration;
+    use tokio::sync::Notify;
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn excess_requests_shed() {
+        let release = std::sync::Arc::new(Notify::new());
+        let router = RequestLimitArgs {
+            request_timeout: None,
+            max_concurrent_requests: Some(1),
+        }
+        .apply(Router::new().route(
+            "/",
+            get({
+                let release = release.clone();
+                || async move { release.notified().await }
+            }),
+        ));
+        let request = || Request::builder().uri("/").body(Body::empty()).unwrap();
+        let pending = tokio::spawn(router.clone().oneshot(request()));
+        tokio::task::yield_now().await;
+        assert_eq!(
+            StatusCode::SERVICE_UNAVAILABLE,
+            router.clone().oneshot(request()).await.unwrap().status()
+        );
+        release.notify_one();
+        assert_eq!(StatusCode::OK, pending.await.unwrap().unwrap().status());
+    }
+
+    #[tokio::test]
+    async fn slow_requests_time_out() {
+        let router = RequestLimitArgs {
+            request_timeout: Some(Duration::from_millis(10).into()),
+            max_concurrent_requests: None,
+        }
+        .apply(Router::new().route("/", get(std::future::pending::<()>)));
+        assert_eq!(
+            StatusCode::REQUEST_TIMEOUT,
+            router
+                .oneshot(Request::builder().uri("/").body(Body::empty()).unwrap())
+                .await
+                .unwrap()
+                .status()
+        );
+    }
+}
@@ -1,29 +1,153 @@
+#[cfg(feature = "introspection")]
+use crate::introspection::TokenIntrospector;
+use crate::{basic_auth::BasicAuthUsers, jwt::JwtValidator, problem::ApiError, scoped::Scope};
 use axum::{
     extract::Request,
-    http::StatusCode,
+    http::HeaderMap,
     response::{IntoResponse, Response},
 };
-use headers::{authorization::Bearer, Authorization, HeaderMapExt};
+use headers::{
+    authorization::{Basic, Bearer},
+    Authorization, HeaderMapExt,
+};
+use ring::constant_time::verify_slices_are_equal;
 use std::{
     future::Future,
     pin::Pin,
+    sync::Arc,
     task::{Context, Poll},
 };
 use tower::{Layer, Service};
 
+/// The outcome of checking the credentials of a request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Authentication {
+    /// The request did not carry valid credentials
+    Unauthenticated,
+    /// The request carried valid credentials, restricted to the [`Scope`] of a token if it has one
+    Authenticated(Option<Scope>),
+}
+
 /// A [`tower::Layer`] which checks for a correct Authorization Bearer token
 ///
-/// Requests which do not have a valid token are sent a 401 Unauthorized response
+/// Requests which do not have a valid token, or valid HTTP Basic credentials where users are configured, are sent a 401 Unauthorized response
 #[derive(Clone)]
 pub struct RequireBearerLayer {
     /// The required token value
     required_token: Option<String>,
+    /// The verifier of JSON Web Tokens accepted in place of the required token
+    jwt_validator: Option<Arc<JwtValidator>>,
+    /// The users accepted via HTTP Basic authentication
+    basic_auth_users: Arc<BasicAuthUsers>,
+    /// The introspector of bearer tokens not otherwise accepted
+    #[cfg(feature = "introspection")]
+    token_introspector: Option<Arc<TokenIntrospector>>,
 }
 
 impl RequireBearerLayer {
-    /// Creates the [`tower::Layer`] with a given required token
-    pub fn new(required_token: Option<String>) -> Self {
-        Self { required_token }
+    /// Creates the [`tower::Layer`] with a given required token, additionally accepting valid JSON Web Tokens if a verifier is provided
+    pub fn new(required_token: Option<String>, jwt_validator: Option<Arc<JwtValidator>>) -> Self {
+        Self {
+            required_token,
+            jwt_validator,
+            basic_auth_users: Arc::default(),
+            #[cfg(feature = "introspection")]
+            token_introspector: None,
+        }
+    }
+
+    /// Additionally accepts bearer tokens which the introspector verifies, if any
+    #[cfg(feature = "introspection")]
+    pub fn with_token_introspector(
+        self,
+        token_introspector: Option<Arc<TokenIntrospector>>,
+    ) -> Self {
+        Self {
+            token_introspector,
+            ..self
+        }
+    }
+
+    /// Additionally accepts requests carrying HTTP Basic credentials of any of the users
+    pub fn with_basic_auth_users(self, basic_auth_users: BasicAuthUsers) -> Self {
+        Self {
+            basic_auth_users: Arc::new(basic_auth_users),
+            ..self
+        }
+    }
+
+    /// Creates the [`tower::Layer`] guarding administrative routes, which accepts only the admin token if one is given, otherwise falling back to the credentials accepted by this layer
+    pub fn for_admin(&self, admin_token: Option<String>) -> Self {
+        match admin_token {
+            Some(admin_token) => Self::new(Some(admin_token), None),
+            None => self.clone(),
+        }
+    }
+
+    /// Whether clients should be challenged for HTTP Basic credentials, as users are configured
+    fn basic_auth(&self) -> bool {
+        !self.basic_auth_users.is_empty()
+    }
+
+    /// Checks the credentials carried in the headers of a request, accepting any request if no credentials are configured
+    ///
+    /// The required token is compared in constant time, whilst HTTP Basic credentials are verified on the blocking thread pool
+    pub async fn authenticate(&self, headers: &HeaderMap) -> Authentication {
+        #[cfg(feature = "introspection")]
+        let introspection = self.token_introspector.is_some();
+        #[cfg(not(feature = "introspection"))]
+        let introspection = false;
+        let bearer_token = headers.typed_get::<Authorization<Bearer>>();
+        match (
+            self.required_token.as_ref(),
+            self.jwt_validator.as_ref(),
+            bearer_token.as_ref(),
+        ) {
+            (None, None, _) if !introspection && !self.basic_auth() => {
+                return Authentication::Authenticated(None)
+            }
+            (Some(required_token), _, Some(bearer_token))
+                if verify_slices_are_equal(
+                    required_token.as_bytes(),
+                    bearer_token.token().as_bytes(),
+                )
+                .is_ok() =>
+            {
+                return Authentication::Authenticated(None)
+            }
+            (_, Some(jwt_validator), Some(bearer_token)) => {
+                if let Some(scope) = jwt_validator.validate(bearer_token.token()) {
+                    return Authentication::Authenticated(Some(scope));
+                }
+            }
+            _ => {}
+        }
+
+        if let (true, Some(basic)) = (
+            self.basic_auth(),
+            headers.typed_get::<Authorization<Basic>>(),
+        ) {
+            let basic_auth_users = self.basic_auth_users.clone();
+            let verified = tokio::task::spawn_blocking(move || {
+                basic_auth_users.verify(basic.username(), basic.password())
+            })
+            .await
+            .unwrap_or(false);
+            if verified {
+                return Authentication::Authenticated(None);
+            }
+        }
+
+        #[cfg(feature = "introspection")]
+        if let (Some(token_introspector), Some(bearer_token)) =
+            (self.token_introspector.as_ref(), bearer_token.as_ref())
+        {
+            if let Some(scope) = token_introspector.introspect(bearer_token.token()).await {
+                return Authentication::Authenticated(Some(scope));
+            }
+        }
+
+        Authentication::Unauthenticated
     }
 }
 
@@ -33,25 +157,25 @@ impl<S> Layer<S> for RequireBearerLayer {
     fn layer(&self, inner: S) -> Self::Service {
         RequireBearerMiddleware {
             inner,
-            required_token: self.required_token.clone(),
+            layer: self.clone(),
         }
     }
 }
 
 /// A [`tower::Service`] which checks for a correct Authorization Bearer token
 ///
-/// Requests which do not have a valid token are sent a 401 Unauthorized response
+/// Requests which do not have a valid token, or valid HTTP Basic credentials where users are configured, are sent a 401 Unauthorized response. The [`Scope`] of a valid JSON Web Token is inserted into the request extensions
 #[derive(Clone)]
 pub struct RequireBearerMiddleware<S> {
     /// The wrapped [`Service`]
     inner: S,
-    /// The required token value
-    required_token: Option<String>,
+    /// The credentials accepted by the middleware
+    layer: RequireBearerLayer,
 }
 
 impl<S> Service<Request> for RequireBearerMiddleware<S>
 where
-    S: Service<Request, Response = Response> + Send + 'static,
+    S: Service<Request, Response = Response> + Clone + Send + 'static,
     S::Future: Send + 'static,
 {
     type Response = S::Response;
@@ -63,24 +187,135 @@ where
         self.inner.poll_ready(cx)
     }
 
-    fn call(&mut self, request: Request) -> Self::Future {
-        let valid_token = match (
-            self.required_token.as_ref(),
-            request.headers().typed_get::<Authorization<Bearer>>(),
-        ) {
-            (Some(required_token), Some(bearer_token)) => required_token == bearer_token.token(),
-            (Some(_), None) => false,
-            (None, _) => true,
-        };
-
-        let future = self.inner.call(request);
-
+    fn call(&mut self, mut request: Request) -> Self::Future {
+        let layer = self.layer.clone();
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
         Box::pin(async move {
-            if valid_token {
-                Ok(future.await?)
-            } else {
-                Ok(StatusCode::UNAUTHORIZED.into_response())
+            match layer.authenticate(request.headers()).await {
+                Authentication::Authenticated(scope) => {
+                    if let Some(scope) = scope {
+                        request.extensions_mut().insert(scope);
+                    }
+                    inner.call(request).await
+                }
+                Authentication::Unauthenticated => Ok(unauthorized(layer.basic_auth())),
             }
         })
     }
 }
+
+/// A 401 Unauthorized response, challenging the client for HTTP Basic credentials if users are configured
+fn unauthorized(basic_auth: bool) -> Response {
+    ApiError::Unauthorized { basic_auth }.into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Authentication, RequireBearerLayer};
+    use crate::basic_auth::BasicAuthUsers;
+    use axum::{
+        body::Body,
+        extract::Request,
+        http::{header::AUTHORIZATION, HeaderMap, StatusCode},
+        response::{IntoResponse, Response},
+    };
+    use std::convert::Infallible;
+    use tower::{service_fn, Layer, ServiceExt};
+
+    async fn status(layer: &RequireBearerLayer, token: Option<&str>) -> StatusCode {
+        authorized_status(layer, token.map(|token| format!("Bearer {token}"))).await
+    }
+
+    async fn authorized_status(
+        layer: &RequireBearerLayer,
+        authorization: Option<String>,
+    ) -> StatusCode {
+        let mut request = Request::builder();
+        if let Some(authorization) = authorization {
+            request = request.header(AUTHORIZATION, authorization);
+        }
+        layer
+            .layer(service_fn(|_| async {
+                Ok::<Response, Infallible>(StatusCode::OK.into_response())
+            }))
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+            .status()
+    }
+
+    #[tokio::test]
+    async fn admin_token_separate() {
+        let layer = RequireBearerLayer::new(Some("read".to_string()), None);
+        let admin_layer = layer.for_admin(Some("admin".to_string()));
+        assert_eq!(StatusCode::OK, status(&layer, Some("read")).await);
+        assert_eq!(
+            StatusCode::UNAUTHORIZED,
+            status(&layer, Some("admin")).await
+        );
+        assert_eq!(StatusCode::OK, status(&admin_layer, Some("admin")).await);
+        assert_eq!(
+            StatusCode::UNAUTHORIZED,
+            status(&admin_layer, Some("read")).await
+        );
+        assert_eq!(StatusCode::UNAUTHORIZED, status(&admin_layer, None).await);
+    }
+
+    #[tokio::test]
+    async fn admin_falls_back_to_token() {
+        let admin_layer = RequireBearerLayer::new(Some("read".to_string()), None).for_admin(None);
+        assert_eq!(StatusCode::OK, status(&admin_layer, Some("read")).await);
+        assert_eq!(StatusCode::UNAUTHORIZED, status(&admin_layer, None).await);
+    }
+
+    #[tokio::test]
+    async fn basic_auth_accepted() {
+        let layer = RequireBearerLayer::new(Some("read".to_string()), None).with_basic_auth_users(
+            BasicAuthUsers::from(vec![
+                // The SHA-256 digest of "password"
+                "legacy:5e884898da28047151d0e56f8dc6292773603d0d6aabbdd62a11ef721d1542d8"
+                    .parse()
+                    .unwrap(),
+            ]),
+        );
+        // The base64 encoding of "legacy:password"
+        assert_eq!(
+            StatusCode::OK,
+            authorized_status(&layer, Some("Basic bGVnYWN5OnBhc3N3b3Jk".to_string())).await
+        );
+        // The base64 encoding of "legacy:wrong"
+        assert_eq!(
+            StatusCode::UNAUTHORIZED,
+            authorized_status(&layer, Some("Basic bGVnYWN5Ondyb25n".to_string())).await
+        );
+        assert_eq!(StatusCode::OK, status(&layer, Some("read")).await);
+        assert_eq!(StatusCode::UNAUTHORIZED, status(&layer, None).await);
+    }
+
+    #[tokio::test]
+    async fn authenticate_headers() {
+        let layer = RequireBearerLayer::new(Some("read".to_string()), None);
+        let mut headers = HeaderMap::new();
+        assert_eq!(
+            Authentication::Unauthenticated,
+            layer.authenticate(&headers).await
+        );
+        headers.insert(AUTHORIZATION, "Bearer reae".parse().unwrap());
+        assert_eq!(
+            Authentication::Unauthenticated,
+            layer.authenticate(&headers).await
+        );
+        headers.insert(AUTHORIZATION, "Bearer read".parse().unwrap());
+        assert_eq!(
+            Authentication::Authenticated(None),
+            layer.authenticate(&headers).await
+        );
+        assert_eq!(
+            Authentication::Authenticated(None),
+            RequireBearerLayer::new(None, None)
+                .authenticate(&HeaderMap::new())
+                .await
+        );
+    }
+}
@@ -0,0 +1,297 @@
+use crate::{
+    bundle::{ContentHasher, DATASETS, SCHEMA_PREFIX},
+    BundleFile, CurrentBundle,
+};
+use clap::Args;
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    str::FromStr,
+};
+
+/// Options for publishing an event to a message broker whenever a new bundle is activated
+#[derive(Debug, Clone, Args)]
+pub struct RevisionEventArgs {
+    /// The destination to which revision change events are published, as 'kafka://<brokers>/<topic>' to produce to a Kafka topic or 'nats://<servers>/<subject>' to publish to a NATS subject. Events are not published if unset
+    #[arg(long, env = "BUNDLER_REVISION_EVENT_SINK")]
+    revision_event_sink: Option<RevisionEventSink>,
+}
+
+/// A message broker to which revision change events are published
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RevisionEventSink {
+    /// Events are produced to the Kafka topic, keyed by revision
+    Kafka {
+        /// The comma separated addresses of the bootstrap brokers
+        brokers: String,
+        /// The topic to which events are produced
+        topic: String,
+    },
+    /// Events are published to the NATS subject
+    Nats {
+        /// The comma separated addresses of the servers
+        servers: String,
+        /// The subject to which events are published
+        subject: String,
+    },
+}
+
+impl FromStr for RevisionEventSink {
+    type Err = String;
+
+    fn from_str(sink: &str) -> Result<Self, Self::Err> {
+        let (scheme, address) = sink
+            .split_once("://")
+            .ok_or_else(|| format!("Revision event sink '{sink}' must include a scheme"))?;
+        let (servers, destination) = match address.split_once('/') {
+            Some((servers, destination)) if !servers.is_empty() && !destination.is_empty() => {
+                (servers.to_string(), destination.to_string())
+            }
+            _ => {
+                return Err(format!(
+                    "Revision event sink '{sink}' must be of the form '{scheme}://<servers>/<destination>'"
+                ))
+            }
+        };
+        match scheme {
+            "kafka" => Ok(Self::Kafka {
+                brokers: servers,
+                topic: destination,
+            }),
+            "nats" => Ok(Self::Nats {
+                servers,
+                subject: destination,
+            }),
+            _ => Err(format!(
+                "Revision event sink '{sink}' must begin with 'kafka://' or 'nats://'"
+            )),
+        }
+    }
+}
+
+/// The event published when a new bundle is activated
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct RevisionEvent {
+    /// The revision of the activated bundle
+    revision: String,
+    /// The revision of the previously activated bundle, if one was activated since startup
+    previous_revision: Option<String>,
+    /// The base64 encoded SHA-256 digest of the activated bundle
+    digest: String,
+    /// The datasets whose contents differ from those of the previously activated bundle, or all datasets if there was none
+    changed_datasets: Vec<String>,
+}
+
+/// A connection to the message broker, to which events are published
+enum Publisher {
+    /// A producer to the topic
+    #[cfg(feature = "kafka")]
+    Kafka(rdkafka::producer::FutureProducer, String),
+    /// A client publishing to the subject
+    #[cfg(feature = "nats")]
+    Nats(async_nats::Client, String),
+}
+
+impl Publisher {
+    /// Connects to the message broker
+    async fn connect(sink: RevisionEventSink) -> Result<Self, anyhow::Error> {
+        match sink {
+            #[cfg(feature = "kafka")]
+            RevisionEventSink::Kafka { brokers, topic } => Ok(Self::Kafka(
+                rdkafka::ClientConfig::new()
+                    .set("bootstrap.servers", brokers)
+                    .create()?,
+                topic,
+            )),
+            #[cfg(not(feature = "kafka"))]
+            RevisionEventSink::Kafka { brokers, topic } => anyhow::bail!(
+                "Publishing revision events to topic {topic} at {brokers} requires the kafka feature"
+            ),
+            #[cfg(feature = "nats")]
+            RevisionEventSink::Nats { servers, subject } => Ok(Self::Nats(
+                async_nats::connect(servers).await?,
+                subject,
+            )),
+            #[cfg(not(feature = "nats"))]
+            RevisionEventSink::Nats { servers, subject } => anyhow::bail!(
+                "Publishing revision events to subject {subject} at {servers} requires the nats feature"
+            ),
+        }
+    }
+
+    /// Publishes the event to the message broker
+    async fn publish(&self, event: &RevisionEvent) -> Result<(), anyhow::Error> {
+        let payload = serde_json::to_vec(event)?;
+        match self {
+            #[cfg(feature = "kafka")]
+            Self::Kafka(producer, topic) => {
+                producer
+                    .send(
+                        rdkafka::producer::FutureRecord::to(topic)
+                            .key(&event.revision)
+                            .payload(&payload),
+                        std::time::Duration::ZERO,
+                    )
+                    .await
+                    .map_err(|(err, _)| err)?;
+            }
+            #[cfg(feature = "nats")]
+            Self::Nats(client, subject) => {
+                client.publish(subject.clone(), payload.into()).await?;
+                client.flush().await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Computes a digest of the contents of each dataset in the bundle, from which changes between revisions are found
+fn dataset_digests(bundle_file: &BundleFile) -> Result<BTreeMap<String, String>, anyhow::Error> {
+    let mut hashers = BTreeMap::<&str, ContentHasher>::new();
+    let mut archive = tar::Archive::new(bundle_file.tar.as_ref());
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.to_string_lossy().into_owned();
+        if path.starts_with(SCHEMA_PREFIX) {
+            continue;
+        }
+        let Some(dataset) = DATASETS
+            .into_iter()
+            .find(|dataset| path.split('/').any(|segment| segment == *dataset))
+        else {
+            continue;
+        };
+        let hasher = hashers.entry(dataset).or_default();
+        hasher.update(&path);
+        std::io::copy(&mut entry, hasher)?;
+    }
+    Ok(hashers
+        .into_iter()
+        .map(|(dataset, hasher)| (dataset.to_string(), hasher.finish()))
+        .collect())
+}
+
+/// Lists the datasets which were added, removed or altered between the digests
+fn changed_datasets(
+    previous: &BTreeMap<String, String>,
+    current: &BTreeMap<String, String>,
+) -> Vec<String> {
+    previous
+        .keys()
+        .chain(current.keys())
+        .filter(|dataset| previous.get(*dataset) != current.get(*dataset))
+        .cloned()
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect()
+}
+
+/// Publishes an event describing each newly activated bundle to the message broker, if a sink is configured
+///
+/// Events which cannot be published are logged and dropped, such that a failing broker does not hold back the service
+pub async fn publish_revisions(args: RevisionEventArgs, current_bundle: CurrentBundle) {
+    let Some(sink) = args.revision_event_sink else {
+        return std::future::pending().await;
+    };
+    let publisher = match Publisher::connect(sink.clone()).await {
+        Ok(publisher) => publisher,
+        Err(err) => {
+            tracing::error!("Could not connect to revision event sink {sink:?}: {err}");
+            return std::future::pending().await;
+        }
+    };
+    let mut previous = None::<(String, BTreeMap<String, String>)>;
+    let mut revisions = current_bundle.revisions.subscribe();
+    loop {
+        revisions.borrow_and_update();
+        let bundle_file = current_bundle.as_ref().read().await.clone();
+        let is_new = !previous
+            .as_ref()
+            .is_some_and(|(revision, _)| *revision == bundle_file.revision);
+        if !bundle_file.is_placeholder() && is_new {
+            match dataset_digests(&bundle_file) {
+                Ok(digests) => {
+                    let event = RevisionEvent {
+                        revision: bundle_file.revision.clone(),
+                        previous_revision: previous.as_ref().map(|(revision, _)| revision.clone()),
+                        digest: bundle_file.digest.clone(),
+                        changed_datasets: changed_datasets(
+                            previous
+                                .as_ref()
+                                .map(|(_, digests)| digests)
+                                .unwrap_or(&BTreeMap::new()),
+                            &digests,
+                        ),
+                    };
+                    match publisher.publish(&event).await {
+                        Ok(()) => tracing::info!(
+                            monotonic_counter.revision_events_published = 1,
+                            "Published activation of bundle {} to {sink:?}",
+                            event.revision
+                        ),
+                        Err(err) => tracing::warn!(
+                            monotonic_counter.revision_event_failures = 1,
+                            "Could not publish activation of bundle {} to {sink:?}: {err}",
+                            event.revision
+                        ),
+                    }
+                    previous = Some((event.revision, digests));
+                }
+                Err(err) => tracing::warn!(
+                    monotonic_counter.revision_event_failures = 1,
+                    "Could not read datasets of bundle {}: {err}",
+                    bundle_file.revision
+                ),
+            }
+        }
+        if revisions.changed().await.is_err() {
+            return std::future::pending().await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{changed_datasets, RevisionEventSink};
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn parse_sinks() {
+        assert_eq!(
+            Ok(RevisionEventSink::Kafka {
+                brokers: "kafka-0:9092,kafka-1:9092".to_string(),
+                topic: "bundle-revisions".to_string()
+            }),
+            "kafka://kafka-0:9092,kafka-1:9092/bundle-revisions".parse()
+        );
+        assert_eq!(
+            Ok(RevisionEventSink::Nats {
+                servers: "nats-0:4222".to_string(),
+                subject: "bundler.revisions".to_string()
+            }),
+            "nats://nats-0:4222/bundler.revisions".parse()
+        );
+        assert!("nats://nats-0:4222".parse::<RevisionEventSink>().is_err());
+        assert!("amqp://rabbit/revisions"
+            .parse::<RevisionEventSink>()
+            .is_err());
+    }
+
+    #[test]
+    fn changes_between_digests() {
+        let previous = BTreeMap::from([
+            ("sessions".to_string(), "a".to_string()),
+            ("proposals".to_string(), "b".to_string()),
+            ("roles".to_string(), "c".to_string()),
+        ]);
+        let current = BTreeMap::from([
+            ("sessions".to_string(), "a".to_string()),
+            ("proposals".to_string(), "d".to_string()),
+            ("people".to_string(), "e".to_string()),
+        ]);
+        assert_eq!(
+            vec!["people", "proposals", "roles"],
+            changed_datasets(&previous, &current)
+        );
+    }
+}
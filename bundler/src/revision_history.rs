@@ -0,0 +1,240 @@
+use crate::{
+    problem::ApiError, revision_not_modified, scoped::Scope, scoped_bundle_response, ArchiveFormat,
+    BundleFile, CurrentBundle,
+};
+use axum::{
+    extract::{Path, State},
+    response::{IntoResponse, Response},
+    routing::get,
+    Extension, Json, Router,
+};
+use axum_extra::TypedHeader;
+use headers::IfNoneMatch;
+use serde::Serialize;
+use std::{collections::VecDeque, time::SystemTime};
+use utoipa::{OpenApi, ToSchema};
+
+/// A previously served [`BundleFile`] and the period over which it was served
+struct PreviousRevision {
+    /// The previously served bundle
+    bundle_file: BundleFile,
+    /// The time at which the bundle began being served
+    served_from: SystemTime,
+    /// The time at which the bundle was replaced
+    served_until: SystemTime,
+}
+
+/// A bounded history of previously served [`BundleFile`]s, from which the oldest are evicted first
+pub struct RevisionHistory {
+    /// The maximum number of previous revisions to retain
+    capacity: usize,
+    /// The time at which the current bundle began being served
+    current_since: SystemTime,
+    /// The previously served revisions, most recent last
+    previous: VecDeque<PreviousRevision>,
+}
+
+/// A summary of a served revision
+#[derive(Debug, PartialEq, Eq, Serialize, ToSchema)]
+struct RevisionSummary {
+    /// The revision of the bundle
+    revision: String,
+    /// The time at which the bundle began being served
+    #[serde(serialize_with = "crate::timestamp::serialize")]
+    #[schema(value_type = String, format = DateTime)]
+    served_from: SystemTime,
+    /// The time at which the bundle was replaced, if it has been
+    #[serde(serialize_with = "crate::timestamp::serialize_optional")]
+    #[schema(value_type = Option<String>, format = DateTime)]
+    served_until: Option<SystemTime>,
+}
+
+/// The paths served by the revision history endpoints
+#[derive(OpenApi)]
+#[openapi(
+    paths(revisions_endpoint, revision_endpoint),
+    components(schemas(RevisionSummary))
+)]
+pub struct RevisionHistoryApi;
+
+impl RevisionHistory {
+    /// Creates an empty [`RevisionHistory`], retaining up to capacity previous revisions
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            current_since: SystemTime::now(),
+            previous: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    /// Records the replacement of the current [`BundleFile`], evicting the oldest revision if at capacity
+    ///
+    /// Placeholders are not retained, as they were never served
+    pub fn push(&mut self, replaced: BundleFile, now: SystemTime) {
+        if self.capacity > 0 && !replaced.is_placeholder() {
+            if self.previous.len() == self.capacity {
+                self.previous.pop_front();
+            }
+            self.previous.push_back(PreviousRevision {
+                bundle_file: replaced,
+                served_from: self.current_since,
+                served_until: now,
+            });
+        }
+        self.current_since = now;
+    }
+
+    /// The previously served [`BundleFile`] with the given revision, if retained
+    pub fn get(&self, revision: &str) -> Option<&BundleFile> {
+        self.previous
+            .iter()
+            .rev()
+            .map(|previous| &previous.bundle_file)
+            .find(|bundle_file| bundle_file.revision == revision)
+    }
+
+    /// Summarises the current and retained previous revisions, most recent first
+    fn summaries(&self, current_revision: &str) -> Vec<RevisionSummary> {
+        std::iter::once(RevisionSummary {
+            revision: current_revision.to_string(),
+            served_from: self.current_since,
+            served_until: None,
+        })
+        .chain(self.previous.iter().rev().map(|previous| RevisionSummary {
+            revision: previous.bundle_file.revision.clone(),
+            served_from: previous.served_from,
+            served_until: Some(previous.served_until),
+        }))
+        .collect()
+    }
+}
+
+/// Creates a [`Router`] serving the current and retained previous revisions
+pub fn router(current_bundle: CurrentBundle) -> Router {
+    Router::new()
+        .route("/bundles/revisions", get(revisions_endpoint))
+        .route("/bundles/revisions/:file_name", get(revision_endpoint))
+        .with_state(current_bundle)
+}
+
+/// Lists the current and retained previous revisions, most recent first
+#[utoipa::path(
+    get,
+    path = "/bundles/revisions",
+    tag = "revisions",
+    responses((status = OK, description = "The served revisions, most recent first", body = [RevisionSummary])),
+)]
+async fn revisions_endpoint(State(current_bundle): State<CurrentBundle>) -> impl IntoResponse {
+    let bundle_file = current_bundle.as_ref().read().await;
+    Json(
+        current_bundle
+            .history
+            .read()
+            .await
+            .summaries(&bundle_file.revision),
+    )
+}
+
+/// Returns the bundle with the requested revision in gzipped tar format, if it is current or retained
+#[utoipa::path(
+    get,
+    path = "/bundles/revisions/{file_name}",
+    tag = "revisions",
+    params(
+        ("file_name" = String, Path, description = "The revision of the bundle, suffixed with '.tar.gz'"),
+        ("If-None-Match" = Option<String>, Header, description = "The ETags of previously fetched bundles, any of which is matched using weak comparison"),
+    ),
+    responses(
+        (status = OK, description = "The bundle in gzipped tar format", content_type = "application/gzip", body = [u8]),
+        (status = NOT_MODIFIED, description = "The revision is listed in the 'If-None-Match' header, whether or not it is retained"),
+        (status = NOT_FOUND, description = "The revision is neither current nor retained"),
+    ),
+)]
+async fn revision_endpoint(
+    State(current_bundle): State<CurrentBundle>,
+    Path(file_name): Path<String>,
+    scope: Option<Extension<Scope>>,
+    if_none_match: Option<TypedHeader<IfNoneMatch>>,
+) -> Response {
+    let Some(revision) = file_name.strip_suffix(".tar.gz") else {
+        return ApiError::NotFound(format!("No revision named {file_name}")).into_response();
+    };
+    let bundle_file = current_bundle.as_ref().read().await;
+    if bundle_file.revision == revision {
+        return scoped_bundle_response(
+            &current_bundle,
+            &bundle_file,
+            ArchiveFormat::TarGz,
+            scope,
+            if_none_match,
+        )
+        .await;
+    }
+    if let Some(not_modified) =
+        revision_not_modified(revision, scope.as_ref(), if_none_match.as_ref())
+    {
+        return not_modified;
+    }
+    let Some(bundle_file) = current_bundle.history.read().await.get(revision).cloned() else {
+        return ApiError::NotFound(format!(
+            "Revision {revision} is neither current nor retained"
+        ))
+        .into_response();
+    };
+    scoped_bundle_response(
+        &current_bundle,
+        &bundle_file,
+        ArchiveFormat::TarGz,
+        scope,
+        if_none_match,
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RevisionHistory;
+    use crate::BundleFile;
+    use std::time::{Duration, SystemTime};
+
+    fn bundle_file(revision: &str) -> BundleFile {
+        BundleFile::from_archives(
+            revision.to_string(),
+            Default::default(),
+            Default::default(),
+            false,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn push_evicts_oldest() {
+        let start = SystemTime::now();
+        let mut history = RevisionHistory::new(2);
+        for (idx, revision) in ["a", "b", "c"].into_iter().enumerate() {
+            history.push(
+                bundle_file(revision),
+                start + Duration::from_secs(idx as u64),
+            );
+        }
+        assert!(history.get("a").is_none());
+        assert_eq!("b", history.get("b").unwrap().revision);
+        assert_eq!("c", history.get("c").unwrap().revision);
+        assert_eq!(
+            vec!["d", "c", "b"],
+            history
+                .summaries("d")
+                .into_iter()
+                .map(|summary| summary.revision)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn push_without_capacity() {
+        let mut history = RevisionHistory::new(0);
+        history.push(bundle_file("a"), SystemTime::now());
+        assert!(history.get("a").is_none());
+        assert_eq!(1, history.summaries("b").len());
+    }
+}
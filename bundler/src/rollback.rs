@@ -0,0 +1,93 @@
+use crate::{problem::ApiError, CurrentBundle};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    routing::{delete, post},
+    Router,
+};
+use std::sync::Arc;
+use tokio::sync::Notify;
+use utoipa::OpenApi;
+
+/// The paths served by the rollback endpoints
+#[derive(OpenApi)]
+#[openapi(paths(rollback_endpoint, unpin_endpoint, refresh_endpoint))]
+pub struct RollbackApi;
+
+/// Shared state of the rollback endpoints
+#[derive(Clone)]
+struct RollbackState {
+    /// The bundle currently being served
+    current_bundle: CurrentBundle,
+    /// A notification which wakes the update loop
+    refresh_requested: Arc<Notify>,
+}
+
+/// Creates a [`Router`] serving endpoints which pin the served bundle to a previous revision, unpin it, or force a refresh
+pub fn router(current_bundle: CurrentBundle, refresh_requested: Arc<Notify>) -> Router {
+    Router::new()
+        .route("/rollback/:revision", post(rollback_endpoint))
+        .route("/rollback", delete(unpin_endpoint))
+        .route("/admin/refresh", post(refresh_endpoint))
+        .with_state(RollbackState {
+            current_bundle,
+            refresh_requested,
+        })
+}
+
+/// Pins the served bundle to the requested revision, if it is current or retained, such that updates are not served until unpinned
+#[utoipa::path(
+    post,
+    path = "/rollback/{revision}",
+    tag = "rollback",
+    params(("revision" = String, Path, description = "The revision to pin the served bundle to")),
+    responses(
+        (status = OK, description = "The served bundle was pinned"),
+        (status = NOT_FOUND, description = "The revision is neither current nor retained"),
+    ),
+)]
+async fn rollback_endpoint(
+    State(state): State<RollbackState>,
+    Path(revision): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    if state.current_bundle.pin(&revision).await {
+        tracing::warn!(
+            monotonic_counter.bundle_rollbacks = 1,
+            "Bundle pinned to revision {revision}"
+        );
+        Ok(StatusCode::OK)
+    } else {
+        Err(ApiError::NotFound(format!(
+            "Revision {revision} is neither current nor retained"
+        )))
+    }
+}
+
+/// Unpins the served bundle and requests a refresh, such that updates are served once more
+#[utoipa::path(
+    delete,
+    path = "/rollback",
+    tag = "rollback",
+    responses((status = OK, description = "The served bundle was unpinned")),
+)]
+async fn unpin_endpoint(State(state): State<RollbackState>) -> impl IntoResponse {
+    state.current_bundle.unpin();
+    tracing::info!("Bundle unpinned");
+    state.refresh_requested.notify_one();
+    StatusCode::OK
+}
+
+/// Unpins the served bundle, if pinned, and requests an immediate refresh
+#[utoipa::path(
+    post,
+    path = "/admin/refresh",
+    tag = "rollback",
+    responses((status = ACCEPTED, description = "A refresh was requested")),
+)]
+async fn refresh_endpoint(State(state): State<RollbackState>) -> impl IntoResponse {
+    state.current_bundle.unpin();
+    tracing::info!("Forced refresh requested");
+    state.refresh_requested.notify_one();
+    StatusCode::ACCEPTED
+}
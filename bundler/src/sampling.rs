@@ -0,0 +1,178 @@
+use clap::Args;
+use opentelemetry::{
+    trace::{Link, SamplingResult, SpanKind, TraceId},
+    Context, KeyValue,
+};
+use opentelemetry_sdk::trace::{Sampler, ShouldSample};
+use std::str::FromStr;
+
+/// Options for sampling the traces exported to the OpenTelemetry collector
+#[derive(Debug, Clone, Args)]
+pub struct TraceSamplingArgs {
+    /// The proportion of traces which are sampled, between 0 and 1, unless the caller propagated a sampling decision or the route has its own ratio
+    #[arg(long, env = "BUNDLER_TRACE_SAMPLE_RATIO", default_value_t = 1.0, value_parser = parse_ratio)]
+    trace_sample_ratio: f64,
+    /// The proportion of traces of requests to individual routes which are sampled, as '<path>=<ratio>', such as '/healthz=0' to never sample health checks
+    #[arg(
+        long = "trace-sample-route",
+        env = "BUNDLER_TRACE_SAMPLE_ROUTES",
+        value_delimiter = ','
+    )]
+    route_sample_ratios: Vec<RouteSampleRatio>,
+}
+
+impl TraceSamplingArgs {
+    /// The [`Sampler`] deciding whether each trace is exported, following any decision propagated by the caller
+    pub fn sampler(&self) -> Sampler {
+        Sampler::ParentBased(Box::new(RouteSampler {
+            default: Sampler::TraceIdRatioBased(self.trace_sample_ratio),
+            routes: self
+                .route_sample_ratios
+                .iter()
+                .map(|route| (route.path.clone(), Sampler::TraceIdRatioBased(route.ratio)))
+                .collect(),
+        }))
+    }
+}
+
+/// Parses a sampling ratio, which must lie between 0 and 1
+fn parse_ratio(ratio: &str) -> Result<f64, anyhow::Error> {
+    let ratio = f64::from_str(ratio)?;
+    if !(0.0..=1.0).contains(&ratio) {
+        anyhow::bail!("Sample ratio {ratio} is not between 0 and 1");
+    }
+    Ok(ratio)
+}
+
+/// The proportion of traces of requests to a route which are sampled, in place of the default ratio
+#[derive(Debug, Clone, PartialEq)]
+pub struct RouteSampleRatio {
+    /// The path of the route
+    path: String,
+    /// The proportion of traces which are sampled
+    ratio: f64,
+}
+
+impl FromStr for RouteSampleRatio {
+    type Err = anyhow::Error;
+
+    fn from_str(route_sample_ratio: &str) -> Result<Self, Self::Err> {
+        let (path, ratio) = route_sample_ratio.split_once('=').ok_or_else(|| {
+            anyhow::anyhow!(
+                "Route sample ratio '{route_sample_ratio}' is not of the form '<path>=<ratio>'"
+            )
+        })?;
+        if !path.starts_with('/') {
+            anyhow::bail!(
+                "Route sample ratio '{route_sample_ratio}' does not name an absolute path"
+            );
+        }
+        Ok(Self {
+            path: path.to_string(),
+            ratio: parse_ratio(ratio)?,
+        })
+    }
+}
+
+/// A sampler applying the ratio of the route requested, according to the 'uri' recorded on request spans, or the default ratio otherwise
+#[derive(Debug, Clone)]
+struct RouteSampler {
+    /// The sampler of spans which are not requests to a route with its own ratio
+    default: Sampler,
+    /// The sampler of requests to each route with its own ratio
+    routes: Vec<(String, Sampler)>,
+}
+
+impl RouteSampler {
+    /// The sampler of a span with the given attributes
+    fn sampler(&self, attributes: &[KeyValue]) -> &Sampler {
+        let Some(uri) = attributes
+            .iter()
+            .find(|attribute| attribute.key.as_str() == "uri")
+            .map(|attribute| attribute.value.as_str())
+        else {
+            return &self.default;
+        };
+        let path = uri.split('?').next().unwrap_or_default();
+        self.routes
+            .iter()
+            .find(|(route, _)| route == path)
+            .map_or(&self.default, |(_, sampler)| sampler)
+    }
+}
+
+impl ShouldSample for RouteSampler {
+    fn should_sample(
+        &self,
+        parent_context: Option<&Context>,
+        trace_id: TraceId,
+        name: &str,
+        span_kind: &SpanKind,
+        attributes: &[KeyValue],
+        links: &[Link],
+    ) -> SamplingResult {
+        self.sampler(attributes).should_sample(
+            parent_context,
+            trace_id,
+            name,
+            span_kind,
+            attributes,
+            links,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RouteSampleRatio, TraceSamplingArgs};
+    use clap::Parser;
+    use opentelemetry::{
+        trace::{SamplingDecision, SpanKind, TraceId},
+        KeyValue,
+    };
+    use opentelemetry_sdk::trace::ShouldSample;
+    use std::str::FromStr;
+
+    #[derive(Parser)]
+    struct Args {
+        #[command(flatten)]
+        trace_sampling: TraceSamplingArgs,
+    }
+
+    #[test]
+    fn routes_sampled_at_own_ratio() {
+        let sampler = Args::parse_from(["bundler", "--trace-sample-route", "/healthz=0"])
+            .trace_sampling
+            .sampler();
+        let decision = |uri: &str| {
+            sampler
+                .should_sample(
+                    None,
+                    TraceId::from_u128(1),
+                    "request",
+                    &SpanKind::Internal,
+                    &[KeyValue::new("uri", uri.to_string())],
+                    &[],
+                )
+                .decision
+        };
+        assert_eq!(SamplingDecision::Drop, decision("/healthz"));
+        assert_eq!(
+            SamplingDecision::RecordAndSample,
+            decision("/bundle.tar.gz")
+        );
+        assert_eq!(
+            SamplingDecision::RecordAndSample,
+            decision("/healthz/ready?verbose")
+        );
+    }
+
+    #[test]
+    fn parse_route_sample_ratios() {
+        assert!(RouteSampleRatio::from_str("/healthz").is_err());
+        assert!(RouteSampleRatio::from_str("healthz=0").is_err());
+        assert!(RouteSampleRatio::from_str("/healthz=2").is_err());
+        assert!(RouteSampleRatio::from_str("/healthz=0.5").is_ok());
+        assert!(Args::try_parse_from(["bundler", "--trace-sample-ratio", "1.5"]).is_err());
+    }
+}
@@ -0,0 +1,412 @@
+use crate::{
+    check::CheckReport,
+    database::{connect_limited, endpoint, DatabaseArgs},
+};
+use clap::Parser;
+use sqlx::{query_as, MySqlPool};
+use std::collections::BTreeMap;
+
+/// Arguments to check the schema of each ISPyB instance against the columns queried
+#[derive(Debug, Parser)]
+pub struct CheckDbArgs {
+    /// Options for connecting to ISPyB
+    #[command(flatten)]
+    database: DatabaseArgs,
+}
+
+/// The kinds of column from which permissionables can be decoded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnKind {
+    /// An unsigned integer, decoded as a [`u32`]
+    UnsignedInteger,
+    /// Text, or an enumeration compared as text
+    Text,
+    /// A point in time, compared against unix timestamps
+    Timestamp,
+    /// Opaque binary data, only compared against null
+    Binary,
+    /// A flag, decoded as a [`bool`]
+    Boolean,
+}
+
+impl ColumnKind {
+    /// Whether a column with the given 'DATA_TYPE' and 'COLUMN_TYPE' can be read as this kind
+    fn accepts(self, data_type: &str, column_type: &str) -> bool {
+        match self {
+            Self::UnsignedInteger => {
+                matches!(
+                    data_type,
+                    "tinyint" | "smallint" | "mediumint" | "int" | "bigint"
+                ) && column_type.contains("unsigned")
+            }
+            Self::Text => matches!(
+                data_type,
+                "char" | "varchar" | "tinytext" | "text" | "mediumtext" | "longtext" | "enum"
+            ),
+            Self::Timestamp => matches!(data_type, "timestamp" | "datetime"),
+            Self::Binary => matches!(data_type, "binary" | "varbinary"),
+            Self::Boolean => data_type == "tinyint" && column_type.starts_with("tinyint(1)"),
+        }
+    }
+}
+
+/// A column of ISPyB read by the permissionable queries
+struct QueriedColumn {
+    /// The table containing the column
+    table: &'static str,
+    /// The name of the column
+    column: &'static str,
+    /// The kind of column the queries expect
+    kind: ColumnKind,
+    /// The datasets whose queries read the column
+    datasets: &'static [&'static str],
+}
+
+/// The columns read by each permissionable query, grouped by table
+const QUERIED_COLUMNS: [QueriedColumn; 35] = [
+    column(
+        "BLSession",
+        "sessionId",
+        ColumnKind::UnsignedInteger,
+        &[
+            "sessions",
+            "proposals",
+            "beamlines",
+            "subjects",
+            "instrument_scientists",
+        ],
+    ),
+    column(
+        "BLSession",
+        "proposalId",
+        ColumnKind::UnsignedInteger,
+        &["sessions", "proposals"],
+    ),
+    column(
+        "BLSession",
+        "visit_number",
+        ColumnKind::UnsignedInteger,
+        &["sessions", "proposals"],
+    ),
+    column(
+        "BLSession",
+        "beamLineName",
+        ColumnKind::Text,
+        &["sessions", "beamlines", "instrument_scientists"],
+    ),
+    column(
+        "BLSession",
+        "bltimeStamp",
+        ColumnKind::Timestamp,
+        &["sessions", "proposals", "beamlines"],
+    ),
+    column(
+        "Proposal",
+        "proposalId",
+        ColumnKind::UnsignedInteger,
+        &["sessions", "proposals", "subjects", "lab_contacts"],
+    ),
+    column(
+        "Proposal",
+        "proposalNumber",
+        ColumnKind::Text,
+        &["sessions", "proposals", "subjects", "lab_contacts"],
+    ),
+    column("Proposal", "proposalCode", ColumnKind::Text, &["proposals"]),
+    column("Proposal", "externalId", ColumnKind::Binary, &["proposals"]),
+    column("Proposal", "state", ColumnKind::Text, &["proposals"]),
+    column(
+        "Person",
+        "personId",
+        ColumnKind::UnsignedInteger,
+        &[
+            "subjects",
+            "session_participants",
+            "lab_contacts",
+            "instrument_scientists",
+        ],
+    ),
+    column(
+        "Person",
+        "login",
+        ColumnKind::Text,
+        &[
+            "subjects",
+            "people",
+            "session_participants",
+            "lab_contacts",
+            "instrument_scientists",
+        ],
+    ),
+    column("Person", "title", ColumnKind::Text, &["people"]),
+    column("Person", "givenName", ColumnKind::Text, &["people"]),
+    column("Person", "familyName", ColumnKind::Text, &["people"]),
+    column("Person", "emailAddress", ColumnKind::Text, &["people"]),
+    column(
+        "Person",
+        "laboratoryId",
+        ColumnKind::UnsignedInteger,
+        &["people", "lab_contacts"],
+    ),
+    column(
+        "Laboratory",
+        "laboratoryId",
+        ColumnKind::UnsignedInteger,
+        &["people", "lab_contacts"],
+    ),
+    column(
+        "Laboratory",
+        "name",
+        ColumnKind::Text,
+        &["people", "lab_contacts"],
+    ),
+    column(
+        "LabContact",
+        "proposalId",
+        ColumnKind::UnsignedInteger,
+        &["lab_contacts"],
+    ),
+    column(
+        "LabContact",
+        "personId",
+        ColumnKind::UnsignedInteger,
+        &["lab_contacts"],
+    ),
+    column(
+        "ProposalHasPerson",
+        "proposalId",
+        ColumnKind::UnsignedInteger,
+        &["subjects"],
+    ),
+    column(
+        "ProposalHasPerson",
+        "personId",
+        ColumnKind::UnsignedInteger,
+        &["subjects"],
+    ),
+    column(
+        "Session_has_Person",
+        "sessionId",
+        ColumnKind::UnsignedInteger,
+        &["subjects", "session_participants", "instrument_scientists"],
+    ),
+    column(
+        "Session_has_Person",
+        "personId",
+        ColumnKind::UnsignedInteger,
+        &["subjects", "session_participants", "instrument_scientists"],
+    ),
+    column(
+        "Session_has_Person",
+        "role",
+        ColumnKind::Text,
+        &["session_participants", "instrument_scientists"],
+    ),
+    column(
+        "Session_has_Person",
+        "remote",
+        ColumnKind::Boolean,
+        &["session_participants"],
+    ),
+    column(
+        "UserGroup_has_Person",
+        "userGroupId",
+        ColumnKind::UnsignedInteger,
+        &["subjects"],
+    ),
+    column(
+        "UserGroup_has_Person",
+        "personId",
+        ColumnKind::UnsignedInteger,
+        &["subjects"],
+    ),
+    column(
+        "UserGroup",
+        "userGroupId",
+        ColumnKind::UnsignedInteger,
+        &["subjects", "roles"],
+    ),
+    column("UserGroup", "name", ColumnKind::Text, &["roles"]),
+    column(
+        "UserGroup_has_Permission",
+        "userGroupId",
+        ColumnKind::UnsignedInteger,
+        &["subjects", "roles"],
+    ),
+    column(
+        "UserGroup_has_Permission",
+        "permissionId",
+        ColumnKind::UnsignedInteger,
+        &["subjects", "roles"],
+    ),
+    column(
+        "Permission",
+        "permissionId",
+        ColumnKind::UnsignedInteger,
+        &["subjects", "roles"],
+    ),
+    column(
+        "Permission",
+        "type",
+        ColumnKind::Text,
+        &["subjects", "roles"],
+    ),
+];
+
+/// Creates a [`QueriedColumn`]
+const fn column(
+    table: &'static str,
+    column: &'static str,
+    kind: ColumnKind,
+    datasets: &'static [&'static str],
+) -> QueriedColumn {
+    QueriedColumn {
+        table,
+        column,
+        kind,
+        datasets,
+    }
+}
+
+/// The 'DATA_TYPE' and 'COLUMN_TYPE' of each column in the connected database, keyed by table and column name
+type Columns = BTreeMap<(String, String), (String, String)>;
+
+/// Checks the tables and columns read by each permissionable query against the schema of each ISPyB instance, printing a report and returning whether all were compatible
+pub async fn run(args: CheckDbArgs) -> bool {
+    let mut report = CheckReport::default();
+    for database_url in args.database.database_urls() {
+        let endpoint = endpoint(database_url);
+        let columns = match connect_limited(database_url, &args.database).await {
+            Ok(ispyb_pool) => {
+                let columns = fetch_columns(&ispyb_pool).await;
+                ispyb_pool.close().await;
+                columns
+            }
+            Err(err) => Err(err),
+        };
+        match columns {
+            Ok(columns) => {
+                for (table, incompatibilities) in incompatibilities(&columns) {
+                    report.record(
+                        &format!("{endpoint} {table}"),
+                        if incompatibilities.is_empty() {
+                            Ok("compatible".to_string())
+                        } else {
+                            Err(incompatibilities.join("; "))
+                        },
+                    );
+                }
+            }
+            Err(err) => report.record(&format!("{endpoint} schema"), Err::<&str, _>(err)),
+        }
+    }
+    println!("{} checks failed", report.failures);
+    report.failures == 0
+}
+
+/// Fetches the type of each column in the connected database from its information schema
+async fn fetch_columns(ispyb_pool: &MySqlPool) -> Result<Columns, sqlx::Error> {
+    let rows = query_as::<_, (String, String, String, String)>(
+        "
+        SELECT
+            CAST(TABLE_NAME AS CHAR),
+            CAST(COLUMN_NAME AS CHAR),
+            CAST(DATA_TYPE AS CHAR),
+            CAST(COLUMN_TYPE AS CHAR)
+        FROM information_schema.COLUMNS
+        WHERE TABLE_SCHEMA = DATABASE()
+        ",
+    )
+    .fetch_all(ispyb_pool)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(|(table, column, data_type, column_type)| {
+            (
+                (table, column),
+                (data_type.to_lowercase(), column_type.to_lowercase()),
+            )
+        })
+        .collect())
+}
+
+/// Describes each queried column which is missing from, or of an unexpected type in, the schema, grouped by table
+fn incompatibilities(columns: &Columns) -> BTreeMap<&'static str, Vec<String>> {
+    let mut incompatibilities = BTreeMap::<_, Vec<_>>::new();
+    for queried in &QUERIED_COLUMNS {
+        let table = incompatibilities.entry(queried.table).or_default();
+        match columns.get(&(queried.table.to_string(), queried.column.to_string())) {
+            None => table.push(format!(
+                "{} is missing, used by {}",
+                queried.column,
+                queried.datasets.join(", ")
+            )),
+            Some((data_type, column_type)) if !queried.kind.accepts(data_type, column_type) => {
+                table.push(format!(
+                    "{} has type {column_type}, expected {:?}, used by {}",
+                    queried.column,
+                    queried.kind,
+                    queried.datasets.join(", ")
+                ))
+            }
+            Some(_) => {}
+        }
+    }
+    incompatibilities
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fetch_columns, incompatibilities, ColumnKind, Columns, QUERIED_COLUMNS};
+    use sqlx::MySqlPool;
+
+    fn compatible_columns() -> Columns {
+        QUERIED_COLUMNS
+            .iter()
+            .map(|queried| {
+                let (data_type, column_type) = match queried.kind {
+                    ColumnKind::UnsignedInteger => ("int", "int unsigned"),
+                    ColumnKind::Text => ("varchar", "varchar(45)"),
+                    ColumnKind::Timestamp => ("timestamp", "timestamp"),
+                    ColumnKind::Binary => ("binary", "binary(16)"),
+                    ColumnKind::Boolean => ("tinyint", "tinyint(1)"),
+                };
+                (
+                    (queried.table.to_string(), queried.column.to_string()),
+                    (data_type.to_string(), column_type.to_string()),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn missing_and_changed_columns_reported() {
+        let mut columns = compatible_columns();
+        columns.remove(&("Permission".to_string(), "type".to_string()));
+        columns.insert(
+            ("BLSession".to_string(), "visit_number".to_string()),
+            ("int".to_string(), "int".to_string()),
+        );
+        let incompatibilities = incompatibilities(&columns);
+        assert_eq!(
+            vec!["type is missing, used by subjects, roles".to_string()],
+            incompatibilities["Permission"]
+        );
+        assert_eq!(
+            vec![
+                "visit_number has type int, expected UnsignedInteger, used by sessions, proposals"
+                    .to_string()
+            ],
+            incompatibilities["BLSession"]
+        );
+        assert!(incompatibilities["Person"].is_empty());
+    }
+
+    #[sqlx::test(migrations = "tests/migrations")]
+    async fn migrated_schema_compatible(ispyb_pool: MySqlPool) {
+        let columns = fetch_columns(&ispyb_pool).await.unwrap();
+        assert!(incompatibilities(&columns)
+            .values()
+            .all(|incompatibilities| incompatibilities.is_empty()));
+    }
+}
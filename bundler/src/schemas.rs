@@ -0,0 +1,65 @@
+use crate::{
+    bundle::{Bundle, NoMetadata},
+    problem::ApiError,
+};
+use axum::{
+    extract::Path,
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use utoipa::OpenApi;
+
+/// The paths served by the schemas endpoint
+#[derive(OpenApi)]
+#[openapi(paths(schema_endpoint))]
+pub struct SchemasApi;
+
+/// Creates a [`Router`] serving the JSON Schema of each dataset
+pub fn router() -> Router {
+    Router::new().route("/schemas/:file_name", get(schema_endpoint))
+}
+
+/// Returns the JSON Schema of a dataset, describing the fields of each of its entries
+#[utoipa::path(
+    get,
+    path = "/schemas/{file_name}",
+    tag = "meta",
+    security(()),
+    params(
+        ("file_name" = String, Path, description = "The name of the dataset, suffixed with '.json'"),
+    ),
+    responses(
+        (status = OK, description = "The JSON Schema of the dataset", content_type = "application/json"),
+        (status = NOT_FOUND, description = "No dataset of this name is included in bundles"),
+    ),
+)]
+async fn schema_endpoint(Path(file_name): Path<String>) -> Response {
+    match file_name
+        .strip_suffix(".json")
+        .and_then(|dataset| Bundle::<NoMetadata>::dataset_schemas().remove(dataset))
+    {
+        Some(schema) => Json(schema).into_response(),
+        None => ApiError::NotFound(format!("No schema named {file_name}")).into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::schema_endpoint;
+    use axum::{extract::Path, http::StatusCode};
+
+    #[tokio::test]
+    async fn schema_served() {
+        let response = schema_endpoint(Path("sessions.json".to_string())).await;
+        assert_eq!(StatusCode::OK, response.status());
+    }
+
+    #[tokio::test]
+    async fn unknown_dataset_not_found() {
+        for file_name in ["visits.json", "sessions"] {
+            let response = schema_endpoint(Path(file_name.to_string())).await;
+            assert_eq!(StatusCode::NOT_FOUND, response.status(), "{file_name}");
+        }
+    }
+}
@@ -0,0 +1,688 @@
+use crate::{
+    bundle::{gzip, AppendJson, ContentHasher, DATASETS, SCHEMA_PREFIX},
+    layout::{to_keyed, to_records},
+    BundleFile, CurrentBundle,
+};
+use clap::Args;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    collections::{BTreeSet, HashMap, HashSet, VecDeque},
+    io::Read,
+};
+use tokio::sync::Mutex;
+
+/// The claims of a bearer token which restrict the data served to its holder
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Scope {
+    /// The beamlines to which served sessions are restricted, or all if absent
+    #[serde(default)]
+    beamlines: Option<BTreeSet<String>>,
+}
+
+impl Scope {
+    /// The beamlines to which served sessions are restricted, if any
+    pub fn beamlines(&self) -> Option<&BTreeSet<String>> {
+        self.beamlines.as_ref()
+    }
+}
+
+/// Options for caching the variants of the bundle restricted to the scopes of requesting tokens
+#[derive(Debug, Clone, Args)]
+pub struct ScopedVariantArgs {
+    /// The number of recently seen scopes for which restricted variants of the bundle are cached, and rebuilt in the background after each refresh. The least recently seen scopes are evicted first
+    #[arg(long, env = "BUNDLER_SCOPED_VARIANT_CAPACITY", default_value_t = DEFAULT_CAPACITY)]
+    scoped_variant_capacity: usize,
+}
+
+/// The number of recently seen scopes for which variants are cached, unless configured otherwise
+const DEFAULT_CAPACITY: usize = 16;
+
+/// A cache of [`BundleFile`] variants restricted to each recently seen [`Scope`], built from a single revision
+pub struct ScopedVariants {
+    /// The maximum number of scopes for which variants are cached
+    capacity: usize,
+    /// The revision from which the cached variants were built
+    revision: String,
+    /// The variant built for each scope
+    variants: HashMap<Scope, BundleFile>,
+    /// The scopes seen most recently, least recently seen first, for which variants are rebuilt after each refresh
+    recent: VecDeque<Scope>,
+}
+
+impl Default for ScopedVariants {
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPACITY)
+    }
+}
+
+impl From<ScopedVariantArgs> for ScopedVariants {
+    fn from(args: ScopedVariantArgs) -> Self {
+        Self::new(args.scoped_variant_capacity)
+    }
+}
+
+impl ScopedVariants {
+    /// Creates an empty [`ScopedVariants`], caching the variants of up to capacity scopes
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            revision: String::new(),
+            variants: HashMap::new(),
+            recent: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    /// The cached variant of the revision restricted to the [`Scope`], marking the scope as the most recently seen
+    ///
+    /// Variants of any other revision are evicted, such that only those of the most recently requested revision are retained, though their scopes are remembered such that they may be rebuilt
+    fn cached(&mut self, revision: &str, scope: &Scope) -> Option<BundleFile> {
+        self.advance(revision);
+        self.touch(scope);
+        let cached = self.variants.get(scope).cloned();
+        match &cached {
+            Some(variant) => tracing::info!(
+                monotonic_counter.scoped_bundle_cache_hits = 1,
+                "Serving cached scoped bundle {}",
+                variant.revision
+            ),
+            None => tracing::info!(
+                monotonic_counter.scoped_bundle_cache_misses = 1,
+                "No cached scoped bundle of {revision} for {scope:?}"
+            ),
+        }
+        cached
+    }
+
+    /// Evicts the cached variants if they were not built from the revision
+    fn advance(&mut self, revision: &str) {
+        if self.revision != revision {
+            self.revision = revision.to_string();
+            self.variants.clear();
+        }
+    }
+
+    /// Marks the [`Scope`] as the most recently seen, evicting the least recently seen scope and its variant if over capacity
+    fn touch(&mut self, scope: &Scope) {
+        if let Some(position) = self.recent.iter().position(|recent| recent == scope) {
+            self.recent.remove(position);
+        }
+        self.recent.push_back(scope.clone());
+        while self.recent.len() > self.capacity {
+            if let Some(evicted) = self.recent.pop_front() {
+                self.variants.remove(&evicted);
+            }
+        }
+    }
+
+    /// Caches the variant of the current revision restricted to the [`Scope`], if the scope was recently seen
+    fn insert(&mut self, scope: &Scope, variant: BundleFile) {
+        if self.recent.contains(scope) {
+            self.variants.insert(scope.clone(), variant);
+        }
+    }
+
+    /// The recently seen scopes lacking a variant of the revision, most recently seen first
+    fn unbuilt_scopes(&mut self, revision: &str) -> Vec<Scope> {
+        self.advance(revision);
+        self.recent
+            .iter()
+            .rev()
+            .filter(|scope| !self.variants.contains_key(*scope))
+            .cloned()
+            .collect()
+    }
+}
+
+/// The variant of the [`BundleFile`] restricted to the [`Scope`], built and cached if not already
+///
+/// Variants are built on the blocking thread pool without holding the lock, such that neither other requests nor the runtime wait upon the build
+pub async fn get_or_build(
+    scoped_variants: &Mutex<ScopedVariants>,
+    bundle_file: &BundleFile,
+    scope: &Scope,
+) -> Result<BundleFile, anyhow::Error> {
+    if let Some(variant) = scoped_variants
+        .lock()
+        .await
+        .cached(&bundle_file.revision, scope)
+    {
+        return Ok(variant);
+    }
+    let variant = {
+        let bundle_file = bundle_file.clone();
+        let scope = scope.clone();
+        tokio::task::spawn_blocking(move || build_variant(&bundle_file, &scope)).await??
+    };
+    tracing::info!(
+        monotonic_counter.scoped_bundle_builds = 1,
+        "Built scoped bundle {} from {}",
+        variant.revision,
+        bundle_file.revision
+    );
+    let mut scoped_variants = scoped_variants.lock().await;
+    if scoped_variants.revision == bundle_file.revision {
+        scoped_variants.insert(scope, variant.clone());
+    }
+    Ok(variant)
+}
+
+/// Builds the variants of each newly served bundle for the recently seen scopes in the background, such that requests holding those scopes are served from the cache
+///
+/// Variants are built one at a time on the blocking thread pool, with those of a revision no longer being served abandoned
+pub async fn warm_variants(current_bundle: CurrentBundle) {
+    let mut revisions = current_bundle.revisions.subscribe();
+    loop {
+        revisions.borrow_and_update();
+        let bundle_file = current_bundle.as_ref().read().await.clone();
+        if !bundle_file.is_placeholder() {
+            let scopes = current_bundle
+                .scoped_variants
+                .lock()
+                .await
+                .unbuilt_scopes(&bundle_file.revision);
+            for scope in scopes {
+                if revisions.has_changed().unwrap_or(true) {
+                    break;
+                }
+                let build = {
+                    let bundle_file = bundle_file.clone();
+                    let scope = scope.clone();
+                    tokio::task::spawn_blocking(move || build_variant(&bundle_file, &scope))
+                };
+                match build.await {
+                    Ok(Ok(variant)) => {
+                        tracing::info!(
+                            monotonic_counter.scoped_bundle_warm_builds = 1,
+                            "Warmed scoped bundle {} from {}",
+                            variant.revision,
+                            bundle_file.revision
+                        );
+                        let mut scoped_variants = current_bundle.scoped_variants.lock().await;
+                        if scoped_variants.revision == bundle_file.revision {
+                            scoped_variants.insert(&scope, variant);
+                        }
+                    }
+                    Ok(Err(err)) => tracing::warn!(
+                        "Could not warm scoped bundle of {} for {scope:?}: {err}",
+                        bundle_file.revision
+                    ),
+                    Err(err) => tracing::warn!(
+                        "Warming scoped bundle of {} for {scope:?} panicked: {err}",
+                        bundle_file.revision
+                    ),
+                }
+            }
+        }
+        if revisions.changed().await.is_err() {
+            return std::future::pending().await;
+        }
+    }
+}
+
+/// The revision of the variant of a bundle restricted to the [`Scope`], distinguishing the scope from others
+pub fn variant_revision(revision: &str, scope: &Scope) -> String {
+    let mut hasher = ContentHasher::default();
+    hasher.update(scope);
+    format!("{revision}+{:.16}", hasher.finish())
+}
+
+/// Rebuilds the [`BundleFile`] with its data restricted to the [`Scope`], under a revision distinguishing the scope
+fn build_variant(bundle_file: &BundleFile, scope: &Scope) -> Result<BundleFile, anyhow::Error> {
+    let revision = variant_revision(&bundle_file.revision, scope);
+
+    let mut entries = Vec::new();
+    let mut archive = tar::Archive::new(bundle_file.tar.as_ref());
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.to_string_lossy().into_owned();
+        let mut contents = Vec::new();
+        entry.read_to_end(&mut contents)?;
+        entries.push((path, serde_json::from_slice::<Value>(&contents)?));
+    }
+    let mut datasets = entries
+        .iter_mut()
+        .map(|(path, value)| (path.as_str(), value))
+        .collect::<HashMap<_, _>>();
+    if let Some(manifest) = datasets.get_mut(".manifest") {
+        manifest["revision"] = Value::String(revision.clone());
+    }
+    if let Some(beamlines) = scope.beamlines() {
+        let records = DATASETS
+            .into_iter()
+            .filter(|dataset| {
+                matches!(dataset_entry(&mut datasets, dataset), Some(Value::Array(_)))
+            })
+            .collect::<Vec<_>>();
+        for dataset in &records {
+            if let Some(entries) = dataset_entry(&mut datasets, dataset) {
+                *entries = to_keyed(dataset, entries.take());
+            }
+        }
+        restrict_to_beamlines(&mut datasets, beamlines);
+        for dataset in &records {
+            if let Some(entries) = dataset_entry(&mut datasets, dataset) {
+                *entries = to_records(dataset, entries.take());
+            }
+        }
+    }
+
+    let mut bundle_builder = tar::Builder::new(Vec::new());
+    for (path, value) in &entries {
+        bundle_builder.append_json(path, value)?;
+    }
+    let tar = bundle_builder.into_inner()?;
+    let file = gzip(&tar)?;
+    BundleFile::from_archives(revision, tar.into(), file.into(), bundle_file.stale)
+}
+
+/// Restricts the datasets to the sessions which took place on the beamlines, and the proposals containing them
+///
+/// Subjects are retained along with their permissions, though their sessions and proposals are similarly restricted
+fn restrict_to_beamlines(datasets: &mut HashMap<&str, &mut Value>, beamlines: &BTreeSet<String>) {
+    let mut sessions = HashSet::new();
+    if let Some(Value::Object(dataset)) = dataset_entry(datasets, "sessions") {
+        dataset.retain(|_, session| {
+            session["beamline"]
+                .as_str()
+                .is_some_and(|beamline| beamlines.contains(beamline))
+        });
+        sessions.extend(dataset.keys().filter_map(|id| id.parse::<u64>().ok()));
+    }
+
+    for dataset in ["session_members", "session_participants"] {
+        if let Some(Value::Object(dataset)) = dataset_entry(datasets, dataset) {
+            dataset.retain(|id, _| id.parse::<u64>().is_ok_and(|id| sessions.contains(&id)));
+        }
+    }
+
+    for dataset in ["beamlines", "instrument_scientists"] {
+        if let Some(Value::Object(dataset)) = dataset_entry(datasets, dataset) {
+            dataset.retain(|beamline, _| beamlines.contains(beamline));
+        }
+    }
+
+    let mut proposals = HashSet::new();
+    if let Some(Value::Object(dataset)) = dataset_entry(datasets, "proposals") {
+        for proposal in dataset.values_mut() {
+            if let Some(Value::Object(visits)) = proposal.get_mut("sessions") {
+                visits
+                    .retain(|_, session| session.as_u64().is_some_and(|id| sessions.contains(&id)));
+            }
+        }
+        let visited = dataset
+            .iter()
+            .filter(|(_, proposal)| {
+                proposal["sessions"]
+                    .as_object()
+                    .is_some_and(|visits| !visits.is_empty())
+            })
+            .filter_map(|(number, _)| number.parse::<u64>().ok())
+            .collect::<HashSet<_>>();
+        // Proposals known only by an alias hold no sessions of their own, so are retained alongside those they are linked to
+        dataset.retain(|number, proposal| {
+            let linked = proposal["canonical_number"].as_u64().into_iter().chain(
+                proposal["aliases"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(Value::as_u64),
+            );
+            number
+                .parse::<u64>()
+                .ok()
+                .into_iter()
+                .chain(linked)
+                .any(|number| visited.contains(&number))
+        });
+        proposals.extend(
+            dataset
+                .keys()
+                .filter_map(|number| number.parse::<u64>().ok()),
+        );
+    }
+
+    if let Some(Value::Object(dataset)) = dataset_entry(datasets, "lab_contacts") {
+        dataset.retain(|number, _| {
+            number
+                .parse::<u64>()
+                .is_ok_and(|number| proposals.contains(&number))
+        });
+    }
+
+    if let Some(Value::Object(dataset)) = dataset_entry(datasets, "subjects") {
+        for subject in dataset.values_mut() {
+            retain_ids(&mut subject["sessions"], &sessions);
+            retain_ids(&mut subject["proposals"], &proposals);
+        }
+    }
+}
+
+/// The data of the named dataset, under whichever root it is placed
+fn dataset_entry<'a>(
+    datasets: &'a mut HashMap<&str, &mut Value>,
+    dataset: &str,
+) -> Option<&'a mut Value> {
+    let suffix = format!("/{dataset}/data.json");
+    datasets
+        .iter_mut()
+        .find(|(path, _)| path.ends_with(&suffix) && !path.starts_with(SCHEMA_PREFIX))
+        .map(|(_, value)| &mut **value)
+}
+
+/// Retains the identifiers within a JSON array which are members of the set
+fn retain_ids(ids: &mut Value, retained: &HashSet<u64>) {
+    if let Value::Array(ids) = ids {
+        ids.retain(|id| id.as_u64().is_some_and(|id| retained.contains(&id)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{build_variant, get_or_build, Scope, ScopedVariants};
+    use crate::{
+        bundle::{AppendJson, Bundle, NoMetadata},
+        BundleFile,
+    };
+    use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+    use serde_json::{json, Value};
+    use std::{collections::BTreeMap, io::Read};
+    use tokio::sync::Mutex;
+
+    fn archive<const N: usize>(entries: [(&str, Value); N]) -> BundleFile {
+        let mut bundle_builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::fast()));
+        for (path, value) in entries {
+            bundle_builder.append_json(path, &value).unwrap();
+        }
+        BundleFile::new(
+            "a".to_string(),
+            bundle_builder
+                .into_inner()
+                .unwrap()
+                .finish()
+                .unwrap()
+                .into(),
+            false,
+        )
+        .unwrap()
+    }
+
+    fn bundle_file() -> BundleFile {
+        archive([
+            (
+                ".manifest",
+                json!({"revision": "a", "roots": ["diamond/data"]}),
+            ),
+            (
+                "diamond/data/subjects/data.json",
+                json!({"alice": {"permissions": ["super_admin"], "proposals": [1, 2], "sessions": [10, 20]}}),
+            ),
+            (
+                "diamond/data/sessions/data.json",
+                json!({
+                    "10": {"proposal_number": 1, "visit_number": 1, "beamline": "i03"},
+                    "20": {"proposal_number": 2, "visit_number": 1, "beamline": "i04"}
+                }),
+            ),
+            (
+                "diamond/data/proposals/data.json",
+                json!({"1": {"sessions": {"1": 10}}, "2": {"sessions": {"1": 20}}}),
+            ),
+            (
+                "diamond/data/beamlines/data.json",
+                json!({"i03": {"sessions": [10]}, "i04": {"sessions": [20]}}),
+            ),
+            (
+                "diamond/data/session_members/data.json",
+                json!({"10": ["alice"], "20": ["alice"]}),
+            ),
+        ])
+    }
+
+    fn read_entries(bundle_file: &BundleFile) -> BTreeMap<String, Value> {
+        let mut archive = tar::Archive::new(GzDecoder::new(bundle_file.file.as_ref()));
+        archive
+            .entries()
+            .unwrap()
+            .map(|entry| {
+                let mut entry = entry.unwrap();
+                let mut contents = Vec::new();
+                entry.read_to_end(&mut contents).unwrap();
+                (
+                    entry.path().unwrap().to_string_lossy().into_owned(),
+                    serde_json::from_slice(&contents).unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    fn scope(claims: Value) -> Scope {
+        serde_json::from_value(claims).unwrap()
+    }
+
+    #[test]
+    fn variant_restricted_to_beamlines() {
+        let variant = build_variant(&bundle_file(), &scope(json!({"beamlines": ["i03"]}))).unwrap();
+        assert_eq!(
+            variant.revision,
+            Bundle::<NoMetadata>::read_revision(&variant.file).unwrap()
+        );
+        assert!(variant.revision.starts_with("a+"));
+        let entries = read_entries(&variant);
+        assert_eq!(
+            json!({"alice": {"permissions": ["super_admin"], "proposals": [1], "sessions": [10]}}),
+            entries["diamond/data/subjects/data.json"]
+        );
+        assert_eq!(
+            json!({"10": {"proposal_number": 1, "visit_number": 1, "beamline": "i03"}}),
+            entries["diamond/data/sessions/data.json"]
+        );
+        assert_eq!(
+            json!({"1": {"sessions": {"1": 10}}}),
+            entries["diamond/data/proposals/data.json"]
+        );
+        assert_eq!(
+            json!({"i03": {"sessions": [10]}}),
+            entries["diamond/data/beamlines/data.json"]
+        );
+        assert_eq!(
+            json!({"10": ["alice"]}),
+            entries["diamond/data/session_members/data.json"]
+        );
+    }
+
+    #[test]
+    fn session_participants_restricted_to_beamlines() {
+        let bundle_file = archive([
+            (
+                ".manifest",
+                json!({"revision": "a", "roots": ["diamond/data"]}),
+            ),
+            (
+                "diamond/data/sessions/data.json",
+                json!({
+                    "10": {"proposal_number": 1, "visit_number": 1, "beamline": "i03"},
+                    "20": {"proposal_number": 2, "visit_number": 1, "beamline": "i04"}
+                }),
+            ),
+            (
+                "diamond/data/session_participants/data.json",
+                json!({
+                    "10": {"alice": {"role": "Team Leader", "remote": false}},
+                    "20": {"bob": {"role": null, "remote": true}}
+                }),
+            ),
+        ]);
+        let variant = build_variant(&bundle_file, &scope(json!({"beamlines": ["i03"]}))).unwrap();
+        assert_eq!(
+            json!({"10": {"alice": {"role": "Team Leader", "remote": false}}}),
+            read_entries(&variant)["diamond/data/session_participants/data.json"]
+        );
+    }
+
+    #[test]
+    fn lab_contacts_restricted_to_proposals() {
+        let bundle_file = archive([
+            (
+                ".manifest",
+                json!({"revision": "a", "roots": ["diamond/data"]}),
+            ),
+            (
+                "diamond/data/sessions/data.json",
+                json!({
+                    "10": {"proposal_number": 1, "visit_number": 1, "beamline": "i03"},
+                    "20": {"proposal_number": 2, "visit_number": 1, "beamline": "i04"}
+                }),
+            ),
+            (
+                "diamond/data/proposals/data.json",
+                json!({"1": {"sessions": {"1": 10}}, "2": {"sessions": {"1": 20}}}),
+            ),
+            (
+                "diamond/data/lab_contacts/data.json",
+                json!({
+                    "1": {"alice": {"institution": "Diamond"}},
+                    "2": {"bob": {"institution": null}}
+                }),
+            ),
+        ]);
+        let variant = build_variant(&bundle_file, &scope(json!({"beamlines": ["i03"]}))).unwrap();
+        assert_eq!(
+            json!({"1": {"alice": {"institution": "Diamond"}}}),
+            read_entries(&variant)["diamond/data/lab_contacts/data.json"]
+        );
+    }
+
+    #[test]
+    fn instrument_scientists_restricted_to_beamlines() {
+        let bundle_file = archive([
+            (
+                ".manifest",
+                json!({"revision": "a", "roots": ["diamond/data", "users"]}),
+            ),
+            (
+                "users/instrument_scientists/data.json",
+                json!({"i03": {"scientists": ["alice"]}, "i04": {"scientists": ["bob"]}}),
+            ),
+        ]);
+        let variant = build_variant(&bundle_file, &scope(json!({"beamlines": ["i03"]}))).unwrap();
+        assert_eq!(
+            json!({"i03": {"scientists": ["alice"]}}),
+            read_entries(&variant)["users/instrument_scientists/data.json"]
+        );
+    }
+
+    #[test]
+    fn aliased_proposals_retained() {
+        let bundle_file = archive([
+            (
+                ".manifest",
+                json!({"revision": "a", "roots": ["diamond/data"]}),
+            ),
+            (
+                "diamond/data/sessions/data.json",
+                json!({
+                    "10": {"proposal_number": 1, "visit_number": 1, "beamline": "i03"},
+                    "20": {"proposal_number": 2, "visit_number": 1, "beamline": "i04"}
+                }),
+            ),
+            (
+                "diamond/data/proposals/data.json",
+                json!({
+                    "1": {"sessions": {"1": 10}, "canonical_number": 3},
+                    "2": {"sessions": {"1": 20}, "canonical_number": 4},
+                    "3": {"sessions": {}, "aliases": [1]},
+                    "4": {"sessions": {}, "aliases": [2]}
+                }),
+            ),
+        ]);
+        let variant = build_variant(&bundle_file, &scope(json!({"beamlines": ["i03"]}))).unwrap();
+        assert_eq!(
+            json!({
+                "1": {"sessions": {"1": 10}, "canonical_number": 3},
+                "3": {"sessions": {}, "aliases": [1]}
+            }),
+            read_entries(&variant)["diamond/data/proposals/data.json"]
+        );
+    }
+
+    #[test]
+    fn records_restricted_to_beamlines() {
+        let bundle_file = archive([
+            (
+                ".manifest",
+                json!({"revision": "a", "roots": ["diamond/data"]}),
+            ),
+            (
+                "diamond/data/sessions/data.json",
+                json!([
+                    {"session": 10, "beamline": "i03"},
+                    {"session": 20, "beamline": "i04"}
+                ]),
+            ),
+        ]);
+        let variant = build_variant(&bundle_file, &scope(json!({"beamlines": ["i03"]}))).unwrap();
+        assert_eq!(
+            json!([{"session": 10, "beamline": "i03"}]),
+            read_entries(&variant)["diamond/data/sessions/data.json"]
+        );
+    }
+
+    #[tokio::test]
+    async fn variants_cached_per_scope() {
+        let bundle_file = bundle_file();
+        let variants = Mutex::new(ScopedVariants::default());
+        let i03 = get_or_build(
+            &variants,
+            &bundle_file,
+            &scope(json!({"beamlines": ["i03"]})),
+        )
+        .await
+        .unwrap();
+        let i04 = get_or_build(
+            &variants,
+            &bundle_file,
+            &scope(json!({"beamlines": ["i04"]})),
+        )
+        .await
+        .unwrap();
+        assert_ne!(i03.revision, i04.revision);
+        assert_eq!(
+            i03.revision,
+            get_or_build(
+                &variants,
+                &bundle_file,
+                &scope(json!({"beamlines": ["i03"]}))
+            )
+            .await
+            .unwrap()
+            .revision
+        );
+        assert_eq!(2, variants.lock().await.variants.len());
+    }
+
+    #[tokio::test]
+    async fn least_recently_seen_scopes_evicted() {
+        let bundle_file = bundle_file();
+        let variants = Mutex::new(ScopedVariants::new(2));
+        let [i03, i04, both] = [
+            json!({"beamlines": ["i03"]}),
+            json!({"beamlines": ["i04"]}),
+            json!({"beamlines": ["i03", "i04"]}),
+        ]
+        .map(scope);
+        for scope in [&i03, &i04, &i03, &both] {
+            get_or_build(&variants, &bundle_file, scope).await.unwrap();
+        }
+        let mut variants = variants.lock().await;
+        assert!(variants.variants.contains_key(&i03));
+        assert!(variants.variants.contains_key(&both));
+        assert!(!variants.variants.contains_key(&i04));
+        assert_eq!(
+            vec![both, i03],
+            variants.unbuilt_scopes("b"),
+            "Scopes are rebuilt for a new revision, most recently seen first"
+        );
+        assert!(variants.variants.is_empty());
+    }
+}
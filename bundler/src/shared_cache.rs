@@ -0,0 +1,173 @@
+use crate::{verification, BundleFile, CurrentBundle};
+use clap::Args;
+use redis::{aio::ConnectionManager, RedisError, Script};
+use std::time::Duration;
+use tracing::instrument;
+use url::Url;
+
+/// Acquires the lease if it is free, or renews it if already held by this replica, returning 1 if held
+const ACQUIRE_LEASE: &str = r#"
+if redis.call('GET', KEYS[1]) == ARGV[1] then
+    return redis.call('PEXPIRE', KEYS[1], ARGV[2])
+end
+if redis.call('SET', KEYS[1], ARGV[1], 'NX', 'PX', ARGV[2]) then
+    return 1
+end
+return 0
+"#;
+
+/// Options for sharing bundles between replicas via Redis
+#[derive(Debug, Clone, Args)]
+pub struct SharedCacheArgs {
+    /// The URL of a Redis instance via which replicas elect a leader to build the bundle and share the result
+    #[arg(long, env = "BUNDLER_REDIS_URL")]
+    redis_url: Option<Url>,
+    /// The prefix applied to all keys written to Redis
+    #[arg(long, env = "BUNDLER_REDIS_KEY_PREFIX", default_value = "bundler")]
+    redis_key_prefix: String,
+    /// The time for which leadership is held without renewal, which should exceed the polling interval
+    #[arg(long, env = "BUNDLER_REDIS_LEASE_DURATION", default_value_t = humantime::Duration::from(Duration::from_secs(180)))]
+    redis_lease_duration: humantime::Duration,
+}
+
+/// A bundle cache shared between replicas, of which only the leader builds and publishes bundles
+pub struct SharedCache {
+    /// The connection to Redis, which is re-established on failure
+    connection: ConnectionManager,
+    /// The key under which the leadership lease is held
+    lease_key: String,
+    /// The key of the hash containing the published bundle revision and file
+    bundle_key: String,
+    /// The time for which leadership is held without renewal
+    lease_duration: Duration,
+    /// A value identifying this replica as the holder of the lease
+    replica_id: String,
+    /// Whether this replica held the lease when last checked
+    leading: bool,
+}
+
+impl SharedCache {
+    /// Connects to Redis if configured
+    pub async fn connect(args: SharedCacheArgs) -> Result<Option<Self>, RedisError> {
+        let Some(redis_url) = args.redis_url else {
+            return Ok(None);
+        };
+        let connection = ConnectionManager::new(redis::Client::open(redis_url.as_str())?).await?;
+        Ok(Some(Self {
+            connection,
+            lease_key: format!("{}:leader", args.redis_key_prefix),
+            bundle_key: format!("{}:bundle", args.redis_key_prefix),
+            lease_duration: args.redis_lease_duration.into(),
+            replica_id: replica_id(),
+            leading: false,
+        }))
+    }
+
+    /// Acquires or renews the leadership lease, returning whether this replica leads
+    #[instrument(skip(self), fields(replica_id = self.replica_id))]
+    async fn try_lead(&mut self) -> Result<bool, RedisError> {
+        let held: i64 = Script::new(ACQUIRE_LEASE)
+            .key(&self.lease_key)
+            .arg(&self.replica_id)
+            .arg(self.lease_duration.as_millis() as u64)
+            .invoke_async(&mut self.connection)
+            .await?;
+        let leading = held == 1;
+        if leading != self.leading {
+            tracing::info!(counter.shared_cache_leader = if leading { 1 } else { -1 });
+            tracing::info!("Leadership {}", if leading { "acquired" } else { "lost" });
+        }
+        self.leading = leading;
+        Ok(leading)
+    }
+
+    /// Publishes the [`BundleFile`] for other replicas to serve
+    #[instrument(skip_all, fields(revision = bundle_file.revision))]
+    pub async fn publish(&mut self, bundle_file: &BundleFile) {
+        if let Err(err) = redis::cmd("HSET")
+            .arg(&self.bundle_key)
+            .arg("revision")
+            .arg(&bundle_file.revision)
+            .arg("file")
+            .arg(bundle_file.file.as_ref())
+            .query_async::<_, ()>(&mut self.connection)
+            .await
+        {
+            tracing::warn!(
+                monotonic_counter.shared_cache_failures = 1,
+                "Could not publish bundle: {err}"
+            );
+        }
+    }
+
+    /// Fetches the published [`BundleFile`] if its revision differs from the current revision
+    async fn fetch(&mut self, current_revision: &str) -> Result<Option<BundleFile>, anyhow::Error> {
+        let published_revision = redis::cmd("HGET")
+            .arg(&self.bundle_key)
+            .arg("revision")
+            .query_async::<_, Option<String>>(&mut self.connection)
+            .await?;
+        if published_revision.is_none() || published_revision.as_deref() == Some(current_revision) {
+            return Ok(None);
+        }
+        let (revision, file) = redis::cmd("HMGET")
+            .arg(&self.bundle_key)
+            .arg("revision")
+            .arg("file")
+            .query_async::<_, (Option<String>, Option<Vec<u8>>)>(&mut self.connection)
+            .await?;
+        let Some(bundle_file) = revision
+            .zip(file)
+            .map(|(revision, file)| BundleFile::new(revision, file.into(), false))
+            .transpose()?
+        else {
+            return Ok(None);
+        };
+        verification::verify(&bundle_file).map_err(|err| {
+            anyhow::anyhow!("Bundle published by leader failed verification: {err}")
+        })?;
+        Ok(Some(bundle_file))
+    }
+
+    /// Determines whether this replica should build the bundle, otherwise replacing the current bundle with that published by the leader
+    ///
+    /// Should Redis be unavailable the replica builds the bundle itself, such that it continues to poll ISPyB independently
+    pub async fn lead_or_follow(&mut self, current_bundle: &CurrentBundle) -> bool {
+        let result = match self.try_lead().await {
+            Ok(true) => return true,
+            Ok(false) => {
+                let current_revision = current_bundle.as_ref().read().await.revision.clone();
+                self.fetch(&current_revision).await
+            }
+            Err(err) => Err(err.into()),
+        };
+        match result {
+            Ok(Some(bundle_file)) => {
+                tracing::info!(
+                    "Updated bundle from {} to {} published by leader",
+                    current_bundle.as_ref().read().await.revision,
+                    bundle_file.revision
+                );
+                current_bundle.replace(bundle_file).await;
+                false
+            }
+            Ok(None) => false,
+            Err(err) => {
+                tracing::warn!(
+                    monotonic_counter.shared_cache_failures = 1,
+                    "Could not follow shared cache, building bundle independently: {err}"
+                );
+                true
+            }
+        }
+    }
+}
+
+/// An identifier for this replica, unique amongst replicas sharing the cache
+fn replica_id() -> String {
+    format!(
+        "{}-{}",
+        std::env::var("HOSTNAME").unwrap_or_else(|_| "bundler".to_string()),
+        std::process::id()
+    )
+}
@@ -0,0 +1,194 @@
+use crate::{problem::ApiError, scoped::Scope, scoped_bundle_file, BundleFile, CurrentBundle};
+use axum::{
+    extract::State,
+    http::{header::CONTENT_TYPE, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Extension, Router,
+};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use clap::Args;
+use headers::HeaderMapExt;
+use ring::signature::{Ed25519KeyPair, KeyPair};
+use std::{collections::HashMap, path::PathBuf, sync::Arc};
+use tokio::sync::Mutex;
+use utoipa::OpenApi;
+
+/// The number of signatures retained, covering the served bundle and its scoped variants
+const SIGNATURE_CACHE_CAPACITY: usize = 64;
+
+/// Options for serving detached signatures of the bundle
+#[derive(Debug, Clone, Args)]
+pub struct SigningArgs {
+    /// The path of a PEM encoded PKCS#8 Ed25519 private key with which detached signatures of the bundle are produced, served alongside it at '/bundle.tar.gz.sig'. Signatures can be verified with `cosign verify-blob`
+    #[arg(long, env = "BUNDLER_BUNDLE_SIGNING_KEY")]
+    bundle_signing_key: Option<PathBuf>,
+}
+
+impl SigningArgs {
+    /// Whether a signing key is configured, such that signatures are served
+    pub fn is_configured(&self) -> bool {
+        self.bundle_signing_key.is_some()
+    }
+}
+
+/// A producer of detached Ed25519 signatures over served bundles, caching the signature of each
+pub struct BundleSigner {
+    /// The key with which bundles are signed
+    key_pair: Ed25519KeyPair,
+    /// The base64 encoded signature of each recently signed bundle, keyed by its digest
+    signatures: Mutex<HashMap<String, String>>,
+}
+
+impl BundleSigner {
+    /// Reads the signing key, if configured
+    pub fn from_args(args: SigningArgs) -> Result<Option<Self>, anyhow::Error> {
+        let Some(signing_key_path) = args.bundle_signing_key else {
+            return Ok(None);
+        };
+        let pem = pem::parse(std::fs::read(signing_key_path)?)?;
+        if pem.tag() != "PRIVATE KEY" {
+            anyhow::bail!(
+                "Signing key is a {}, expected a PKCS#8 PRIVATE KEY",
+                pem.tag()
+            );
+        }
+        let key_pair = Ed25519KeyPair::from_pkcs8_maybe_unchecked(pem.contents())
+            .map_err(|err| anyhow::anyhow!("Signing key is not an Ed25519 key: {err}"))?;
+        Ok(Some(Self::new(key_pair)))
+    }
+
+    /// Creates a [`BundleSigner`] from a key pair
+    fn new(key_pair: Ed25519KeyPair) -> Self {
+        Self {
+            key_pair,
+            signatures: Mutex::default(),
+        }
+    }
+
+    /// The base64 encoded public key with which signatures are verified
+    pub fn public_key(&self) -> String {
+        BASE64.encode(self.key_pair.public_key())
+    }
+
+    /// The base64 encoded signature over the gzipped bundle, signed and cached if not already
+    async fn sign(&self, bundle_file: &BundleFile) -> String {
+        let mut signatures = self.signatures.lock().await;
+        if let Some(signature) = signatures.get(&bundle_file.digest) {
+            return signature.clone();
+        }
+        let signature = BASE64.encode(self.key_pair.sign(&bundle_file.file));
+        if signatures.len() >= SIGNATURE_CACHE_CAPACITY {
+            signatures.clear();
+        }
+        signatures.insert(bundle_file.digest.clone(), signature.clone());
+        signature
+    }
+}
+
+/// The paths served by the signature endpoint
+#[derive(OpenApi)]
+#[openapi(paths(signature_endpoint))]
+pub struct SignatureApi;
+
+/// Shared state of the signature endpoint
+#[derive(Clone)]
+struct SignatureState {
+    /// The bundle currently being served
+    current_bundle: CurrentBundle,
+    /// The producer of signatures
+    bundle_signer: Arc<BundleSigner>,
+}
+
+/// Creates a [`Router`] serving detached signatures of the bundle, if a signing key is configured
+pub fn router(current_bundle: CurrentBundle, bundle_signer: Option<BundleSigner>) -> Router {
+    let Some(bundle_signer) = bundle_signer else {
+        return Router::new();
+    };
+    tracing::info!(
+        "Signing bundles with Ed25519 public key {}",
+        bundle_signer.public_key()
+    );
+    Router::new()
+        .route("/bundle.tar.gz.sig", get(signature_endpoint))
+        .with_state(SignatureState {
+            current_bundle,
+            bundle_signer: Arc::new(bundle_signer),
+        })
+}
+
+/// Returns the base64 encoded Ed25519 signature over the gzipped bundle currently served to the requester
+///
+/// The signature shares the ETag of the bundle it covers, such that clients can confirm it matches the bundle they fetched
+#[utoipa::path(
+    get,
+    path = "/bundle.tar.gz.sig",
+    tag = "bundle",
+    responses(
+        (status = OK, description = "The detached signature of the bundle", content_type = "text/plain", body = String),
+        (status = SERVICE_UNAVAILABLE, description = "No bundle has been fetched yet"),
+        (status = UNAUTHORIZED, description = "A valid bearer token was not provided"),
+    ),
+)]
+async fn signature_endpoint(
+    State(state): State<SignatureState>,
+    scope: Option<Extension<Scope>>,
+) -> Response {
+    let bundle_file = state.current_bundle.as_ref().read().await;
+    if bundle_file.is_placeholder() {
+        return ApiError::Unavailable.into_response();
+    }
+    let bundle_file = match scoped_bundle_file(&state.current_bundle, &bundle_file, scope).await {
+        Ok(bundle_file) => bundle_file,
+        Err(err) => {
+            tracing::error!("Could not build scoped bundle: {err}");
+            return ApiError::Internal("Could not build scoped bundle".to_string()).into_response();
+        }
+    };
+    let mut headers = HeaderMap::new();
+    headers.typed_insert(bundle_file.etag.clone());
+    headers.insert(CONTENT_TYPE, HeaderValue::from_static("text/plain"));
+    (
+        StatusCode::OK,
+        headers,
+        state.bundle_signer.sign(&bundle_file).await,
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BundleSigner;
+    use crate::{
+        bundle::{gzip, Bundle, NoMetadata},
+        BundleFile,
+    };
+    use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+    use ring::{
+        rand::SystemRandom,
+        signature::{Ed25519KeyPair, KeyPair, UnparsedPublicKey, ED25519},
+    };
+
+    #[tokio::test]
+    async fn signature_verifies() {
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap();
+        let key_pair = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap();
+        let public_key = UnparsedPublicKey::new(&ED25519, key_pair.public_key().as_ref().to_vec());
+        let bundle = Bundle::<NoMetadata>::default();
+        let bundle_file = BundleFile::new(
+            bundle.revision().to_string(),
+            gzip(&bundle.to_tar().unwrap()).unwrap().into(),
+            false,
+        )
+        .unwrap();
+        let bundle_signer = BundleSigner::new(key_pair);
+        let signature = bundle_signer.sign(&bundle_file).await;
+        assert_eq!(signature, bundle_signer.sign(&bundle_file).await);
+        assert!(public_key
+            .verify(&bundle_file.file, &BASE64.decode(&signature).unwrap())
+            .is_ok());
+        assert!(public_key
+            .verify(b"tampered", &BASE64.decode(&signature).unwrap())
+            .is_err());
+    }
+}
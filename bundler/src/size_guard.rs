@@ -0,0 +1,39 @@
+/// A limit on the size of the bundle, refusing updates which would exceed it such that agents are not overwhelmed by a surge in upstream data
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SizeGuard {
+    /// The size, in bytes, of the uncompressed bundle beyond which an update is refused, if any
+    max_bytes: Option<u64>,
+}
+
+impl SizeGuard {
+    /// Creates a [`SizeGuard`] which refuses bundles larger than the number of bytes, if any
+    pub fn new(max_bytes: Option<u64>) -> Self {
+        Self { max_bytes }
+    }
+
+    /// Checks the size of an uncompressed bundle, returning the reason it should be refused, if it should be
+    pub fn check(&self, bytes: usize) -> Result<(), String> {
+        match self.max_bytes {
+            Some(max_bytes) if bytes as u64 > max_bytes => Err(format!(
+                "Bundle of {bytes} bytes exceeds the limit of {max_bytes} bytes"
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SizeGuard;
+
+    #[test]
+    fn oversized_bundle_refused() {
+        let size_guard = SizeGuard::new(Some(1024));
+        assert!(size_guard.check(1024).is_ok());
+        assert_eq!(
+            Err("Bundle of 1025 bytes exceeds the limit of 1024 bytes".to_string()),
+            size_guard.check(1025)
+        );
+        assert!(SizeGuard::default().check(usize::MAX).is_ok());
+    }
+}
@@ -0,0 +1,136 @@
+use crate::{
+    bundle::{gzip, Bundle, DATASETS},
+    bundle_endpoint, uncompressed_bundle_endpoint, BundleFile, CurrentBundle,
+};
+use axum::{routing::get, Router};
+use clap::{builder::PossibleValuesParser, Args};
+use serde::Serialize;
+use std::{collections::BTreeMap, fmt::Debug};
+
+/// Options for publishing bundles containing individual datasets
+#[derive(Debug, Clone, Args)]
+pub struct SplitBundleArgs {
+    /// Datasets to additionally publish as bundles of their own, at '/bundles/<dataset>.tar.gz', for agents which only require some of the data
+    #[arg(
+        long = "split-dataset",
+        env = "BUNDLER_SPLIT_DATASETS",
+        value_delimiter = ',',
+        value_parser = PossibleValuesParser::new(DATASETS),
+    )]
+    split_datasets: Vec<String>,
+}
+
+/// The bundles containing individual datasets, each rooted at its dataset and with a revision of its own, built from the same fetch as the complete bundle
+///
+/// Split bundles are only built by the replica which fetches from ISPyB, so are unavailable from followers of a shared cache or leader election
+#[derive(Clone, Default)]
+pub struct SplitBundles(BTreeMap<String, CurrentBundle>);
+
+impl SplitBundles {
+    /// Creates [`SplitBundles`] for each of the configured datasets, served as unavailable until the first bundle is published
+    pub fn new(args: SplitBundleArgs) -> Self {
+        Self(
+            args.split_datasets
+                .into_iter()
+                .map(|dataset| (dataset, CurrentBundle::new(BundleFile::placeholder(), 0)))
+                .collect(),
+        )
+    }
+
+    /// Builds the bundle of each dataset from the [`Bundle`], replacing those served where the dataset has changed
+    pub async fn publish<Metadata>(&self, bundle: &Bundle<Metadata>)
+    where
+        Metadata: Debug + Serialize,
+    {
+        for (dataset, current_bundle) in &self.0 {
+            let bundle_file = match split_bundle_file(bundle, dataset) {
+                Ok(Some(bundle_file)) => bundle_file,
+                Ok(None) => {
+                    tracing::warn!("Bundle does not include {dataset}, not publishing its bundle");
+                    continue;
+                }
+                Err(err) => {
+                    tracing::error!("Could not build bundle of {dataset}: {err}");
+                    continue;
+                }
+            };
+            let old_revision = current_bundle.as_ref().read().await.revision.clone();
+            if bundle_file.revision != old_revision {
+                let new_revision = bundle_file.revision.clone();
+                current_bundle.replace(bundle_file).await;
+                tracing::info!(
+                    "Updated bundle of {dataset} from {} to {}",
+                    old_revision,
+                    new_revision
+                );
+            }
+        }
+    }
+
+    /// Creates a [`Router`] serving the bundle of each dataset, in both gzipped and uncompressed tar formats
+    pub fn router(&self) -> Router {
+        self.0
+            .iter()
+            .fold(Router::new(), |router, (dataset, current_bundle)| {
+                router
+                    .route(
+                        &format!("/bundles/{dataset}.tar.gz"),
+                        get(bundle_endpoint).with_state(current_bundle.clone()),
+                    )
+                    .route(
+                        &format!("/bundles/{dataset}.tar"),
+                        get(uncompressed_bundle_endpoint).with_state(current_bundle.clone()),
+                    )
+            })
+    }
+}
+
+/// Serializes the named dataset of the [`Bundle`] as a [`BundleFile`] of its own, if the dataset is included
+fn split_bundle_file<Metadata>(
+    bundle: &Bundle<Metadata>,
+    dataset: &str,
+) -> Result<Option<BundleFile>, anyhow::Error>
+where
+    Metadata: Debug + Serialize,
+{
+    let Some((revision, tar)) = bundle.dataset_to_tar(dataset)? else {
+        return Ok(None);
+    };
+    let file = gzip(&tar)?;
+    BundleFile::from_archives(revision, tar.into(), file.into(), false).map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SplitBundleArgs, SplitBundles};
+    use crate::bundle::{Bundle, NoMetadata};
+    use clap::Parser;
+
+    #[derive(Parser)]
+    struct Args {
+        #[command(flatten)]
+        split_bundles: SplitBundleArgs,
+    }
+
+    #[tokio::test]
+    async fn datasets_published_separately() {
+        let split_bundles = SplitBundles::new(
+            Args::parse_from(["bundler", "--split-dataset", "sessions,people"]).split_bundles,
+        );
+        let bundle = Bundle::<NoMetadata>::default();
+        split_bundles.publish(&bundle).await;
+        let sessions = split_bundles.0["sessions"].as_ref().read().await.clone();
+        assert!(!sessions.is_placeholder());
+        assert_ne!(bundle.revision(), sessions.revision);
+        assert!(split_bundles.0["people"]
+            .as_ref()
+            .read()
+            .await
+            .is_placeholder());
+    }
+
+    #[test]
+    fn unknown_dataset_rejected() {
+        assert!(Args::try_parse_from(["bundler", "--split-dataset", "visits"]).is_err());
+    }
+}
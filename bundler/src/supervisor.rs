@@ -0,0 +1,159 @@
+use crate::polling::AdaptiveInterval;
+use serde::Serialize;
+use std::{
+    any::Any,
+    collections::BTreeMap,
+    future::Future,
+    sync::{Arc, Mutex},
+};
+use tokio::time::Instant;
+use utoipa::ToSchema;
+
+/// The state of a supervised task
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, ToSchema)]
+pub struct TaskState {
+    /// Whether the task is running, rather than awaiting restart
+    running: bool,
+    /// The number of times the task has been restarted
+    restarts: u32,
+    /// The number of times the task has been restarted without running stably in between
+    consecutive_restarts: u32,
+    /// The reason the task last stopped, if it has
+    last_failure: Option<String>,
+}
+
+/// A thread safe record of the state of each supervised task
+#[derive(Debug, Clone, Default)]
+pub struct TaskHealth(Arc<Mutex<BTreeMap<&'static str, TaskState>>>);
+
+impl TaskHealth {
+    /// The state of each supervised task, by name
+    pub fn report(&self) -> BTreeMap<&'static str, TaskState> {
+        self.0.lock().unwrap().clone()
+    }
+
+    /// The greatest number of times any task has been restarted without running stably in between
+    pub fn max_consecutive_restarts(&self) -> u32 {
+        self.0
+            .lock()
+            .unwrap()
+            .values()
+            .map(|state| state.consecutive_restarts)
+            .max()
+            .unwrap_or_default()
+    }
+
+    /// Records that the task is running
+    fn record_running(&self, name: &'static str) {
+        self.0.lock().unwrap().entry(name).or_default().running = true;
+    }
+
+    /// Records that the task stopped, and whether it had run stably before doing so
+    fn record_failure(&self, name: &'static str, reason: String, stable: bool) {
+        let mut tasks = self.0.lock().unwrap();
+        let state = tasks.entry(name).or_default();
+        state.running = false;
+        state.restarts += 1;
+        state.consecutive_restarts = if stable {
+            1
+        } else {
+            state.consecutive_restarts + 1
+        };
+        state.last_failure = Some(reason);
+    }
+}
+
+/// Runs the task, restarting it whenever it panics or exits, after a delay which doubles with each restart unless the task ran for longer than the maximum delay
+pub async fn supervise<F, Fut>(
+    name: &'static str,
+    task_health: TaskHealth,
+    mut restart_delay: AdaptiveInterval,
+    mut task: F,
+) where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    loop {
+        task_health.record_running(name);
+        let started = Instant::now();
+        let reason = match tokio::spawn(task()).await {
+            Ok(()) => "exited".to_string(),
+            Err(err) if err.is_panic() => {
+                format!("panicked: {}", panic_message(err.into_panic()))
+            }
+            Err(err) => format!("failed: {err}"),
+        };
+        let stable = started.elapsed() > restart_delay.max();
+        if stable {
+            restart_delay.reset();
+        }
+        tracing::error!(
+            monotonic_counter.task_restarts = 1,
+            task = name,
+            "Task {name} {reason}, restarting in {}",
+            humantime::format_duration(restart_delay.current())
+        );
+        task_health.record_failure(name, reason, stable);
+        tokio::time::sleep(restart_delay.current()).await;
+        restart_delay.back_off();
+    }
+}
+
+/// The message with which a task panicked, if it was a string
+fn panic_message(panic: Box<dyn Any + Send>) -> String {
+    match panic.downcast::<String>() {
+        Ok(message) => *message,
+        Err(panic) => panic
+            .downcast_ref::<&str>()
+            .map(ToString::to_string)
+            .unwrap_or_else(|| "unknown cause".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{supervise, TaskHealth};
+    use crate::polling::AdaptiveInterval;
+    use std::{
+        sync::{
+            atomic::{AtomicU32, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    #[tokio::test]
+    async fn restarted_after_panic() {
+        let task_health = TaskHealth::default();
+        let runs = Arc::new(AtomicU32::new(0));
+        let supervisor = tokio::spawn(supervise(
+            "flaky",
+            task_health.clone(),
+            AdaptiveInterval::new(Duration::from_millis(1), Some(Duration::from_millis(4))),
+            {
+                let runs = runs.clone();
+                move || {
+                    let runs = runs.clone();
+                    async move {
+                        if runs.fetch_add(1, Ordering::SeqCst) < 2 {
+                            panic!("flaky failure");
+                        }
+                        std::future::pending().await
+                    }
+                }
+            },
+        ));
+        while runs.load(Ordering::SeqCst) < 3 {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+        let state = &task_health.report()["flaky"];
+        assert!(state.running);
+        assert_eq!(2, state.restarts);
+        assert_eq!(2, task_health.max_consecutive_restarts());
+        assert_eq!(
+            Some("panicked: flaky failure".to_string()),
+            state.last_failure
+        );
+        supervisor.abort();
+    }
+}
@@ -0,0 +1,120 @@
+use std::{io, os::unix::net::UnixDatagram, path::Path, time::Duration};
+
+/// The socket on which systemd receives notifications, and the interval at which it expects watchdog pings
+pub struct SystemdNotifier {
+    /// The socket connected to the systemd notification socket, if the service was started with one
+    socket: Option<UnixDatagram>,
+    /// The interval at which watchdog pings are sent, being half the configured watchdog timeout
+    watchdog_interval: Option<Duration>,
+    /// Whether readiness has been signalled
+    ready: bool,
+}
+
+impl SystemdNotifier {
+    /// Connects to the notification socket named by 'NOTIFY_SOCKET', if set, enabling watchdog pings if 'WATCHDOG_USEC' is set for this process
+    pub fn from_env() -> Self {
+        let socket = std::env::var_os("NOTIFY_SOCKET").and_then(|notify_socket| {
+            connect(Path::new(&notify_socket))
+                .inspect_err(|err| {
+                    tracing::warn!("Could not connect to systemd notification socket: {err}")
+                })
+                .ok()
+        });
+        let watchdog_pid = std::env::var("WATCHDOG_PID")
+            .ok()
+            .and_then(|pid| pid.parse::<u32>().ok());
+        let watchdog_interval = std::env::var("WATCHDOG_USEC")
+            .ok()
+            .and_then(|usec| usec.parse::<u64>().ok())
+            .filter(|_| watchdog_pid.map_or(true, |pid| pid == std::process::id()))
+            .map(|usec| Duration::from_micros(usec) / 2);
+        Self::new(socket, watchdog_interval)
+    }
+
+    /// Creates a [`SystemdNotifier`] sending notifications on the socket, if any, and pinging the watchdog at the interval, if any
+    fn new(socket: Option<UnixDatagram>, watchdog_interval: Option<Duration>) -> Self {
+        Self {
+            watchdog_interval: socket.as_ref().and(watchdog_interval),
+            socket,
+            ready: false,
+        }
+    }
+
+    /// The interval at which watchdog pings should be sent, if systemd expects them
+    pub fn watchdog_interval(&self) -> Option<Duration> {
+        self.watchdog_interval
+    }
+
+    /// Signals that the service is ready, once a bundle is being served, along with the revision of the bundle
+    pub fn ready(&mut self, revision: &str) {
+        if !self.ready {
+            self.ready = true;
+            self.notify(&format!("READY=1\nSTATUS=Serving bundle {revision}"));
+        }
+    }
+
+    /// Signals that the service is still alive, such that systemd does not restart it
+    pub fn ping_watchdog(&self) {
+        self.notify("WATCHDOG=1");
+    }
+
+    /// Sends the newline separated assignments to systemd, logging any failure
+    fn notify(&self, state: &str) {
+        if let Some(socket) = &self.socket {
+            if let Err(err) = socket.send(state.as_bytes()) {
+                tracing::warn!("Could not notify systemd: {err}");
+            }
+        }
+    }
+}
+
+/// Connects a datagram socket to the notification socket at the path, or in the abstract namespace if prefixed with '@'
+fn connect(notify_socket: &Path) -> Result<UnixDatagram, io::Error> {
+    let socket = UnixDatagram::unbound()?;
+    match notify_socket
+        .to_str()
+        .and_then(|path| path.strip_prefix('@'))
+    {
+        #[cfg(target_os = "linux")]
+        Some(name) => {
+            use std::os::{linux::net::SocketAddrExt, unix::net::SocketAddr};
+            socket.connect_addr(&SocketAddr::from_abstract_name(name)?)?
+        }
+        _ => socket.connect(notify_socket)?,
+    }
+    Ok(socket)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{connect, SystemdNotifier};
+    use std::{os::unix::net::UnixDatagram, time::Duration};
+
+    #[test]
+    fn ready_signalled_once() {
+        let socket_path = std::env::temp_dir().join("bundler-systemd-test.sock");
+        let _ = std::fs::remove_file(&socket_path);
+        let systemd = UnixDatagram::bind(&socket_path).unwrap();
+        systemd.set_nonblocking(true).unwrap();
+        let mut notifier = SystemdNotifier::new(
+            Some(connect(&socket_path).unwrap()),
+            Some(Duration::from_secs(5)),
+        );
+        notifier.ready("a");
+        notifier.ready("b");
+        notifier.ping_watchdog();
+        let mut buffer = [0; 64];
+        let received = systemd.recv(&mut buffer).unwrap();
+        assert_eq!(b"READY=1\nSTATUS=Serving bundle a", &buffer[..received]);
+        let received = systemd.recv(&mut buffer).unwrap();
+        assert_eq!(b"WATCHDOG=1", &buffer[..received]);
+        assert!(systemd.recv(&mut buffer).is_err());
+        std::fs::remove_file(&socket_path).unwrap();
+    }
+
+    #[test]
+    fn watchdog_disabled_without_socket() {
+        let notifier = SystemdNotifier::new(None, Some(Duration::from_secs(5)));
+        assert_eq!(None, notifier.watchdog_interval());
+    }
+}
@@ -0,0 +1,18 @@
+use serde::Serializer;
+use std::time::SystemTime;
+
+/// Serializes a [`SystemTime`] as an RFC 3339 timestamp
+pub fn serialize<S: Serializer>(time: &SystemTime, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(&humantime::format_rfc3339_seconds(*time))
+}
+
+/// Serializes an optional [`SystemTime`] as an RFC 3339 timestamp
+pub fn serialize_optional<S: Serializer>(
+    time: &Option<SystemTime>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match time {
+        Some(time) => serialize(time, serializer),
+        None => serializer.serialize_none(),
+    }
+}
@@ -0,0 +1,95 @@
+use axum::Router;
+use axum_server::tls_rustls::RustlsConfig;
+use clap::Args;
+use rustls::{Certificate, PrivateKey, ServerConfig, SupportedProtocolVersion};
+use rustls_pemfile::Item;
+use std::{fs::File, io::BufReader, net::SocketAddr, path::PathBuf, sync::Arc};
+
+/// The protocols negotiated by the TCP listener, in order of preference
+const TCP_ALPN: [&[u8]; 2] = [b"h2", b"http/1.1"];
+
+/// Options for serving the HTTP API over TLS
+#[derive(Debug, Clone, Args)]
+pub struct TlsArgs {
+    /// The path to a PEM encoded certificate chain, with which the HTTP API is served over TLS
+    #[arg(long, env = "BUNDLER_TLS_CERTIFICATE", requires = "tls_private_key")]
+    tls_certificate: Option<PathBuf>,
+    /// The path to the PEM encoded private key of the certificate
+    #[arg(long, env = "BUNDLER_TLS_PRIVATE_KEY", requires = "tls_certificate")]
+    tls_private_key: Option<PathBuf>,
+}
+
+/// The certificate chain and private key with which listeners are served over TLS
+#[derive(Clone)]
+pub struct Tls {
+    /// The certificate chain, leaf first
+    certificates: Vec<Certificate>,
+    /// The private key of the leaf certificate
+    private_key: PrivateKey,
+}
+
+impl Tls {
+    /// Reads the certificate chain and private key, if TLS is configured
+    pub fn from_args(args: TlsArgs) -> Result<Option<Self>, anyhow::Error> {
+        let (Some(certificate_path), Some(private_key_path)) =
+            (args.tls_certificate, args.tls_private_key)
+        else {
+            return Ok(None);
+        };
+        let certificates =
+            rustls_pemfile::certs(&mut BufReader::new(File::open(&certificate_path)?))?
+                .into_iter()
+                .map(Certificate)
+                .collect::<Vec<_>>();
+        if certificates.is_empty() {
+            anyhow::bail!("No certificates found in {}", certificate_path.display());
+        }
+        let private_key =
+            rustls_pemfile::read_all(&mut BufReader::new(File::open(&private_key_path)?))?
+                .into_iter()
+                .find_map(|item| match item {
+                    Item::PKCS8Key(key) | Item::RSAKey(key) | Item::ECKey(key) => {
+                        Some(PrivateKey(key))
+                    }
+                    _ => None,
+                })
+                .ok_or_else(|| {
+                    anyhow::anyhow!("No private key found in {}", private_key_path.display())
+                })?;
+        Ok(Some(Self {
+            certificates,
+            private_key,
+        }))
+    }
+
+    /// Creates a server configuration presenting the certificate, restricted to the protocol versions and negotiating the application protocols
+    pub fn server_config(
+        &self,
+        versions: &[&'static SupportedProtocolVersion],
+        alpn: &[&[u8]],
+    ) -> Result<ServerConfig, rustls::Error> {
+        let mut server_config = ServerConfig::builder()
+            .with_safe_default_cipher_suites()
+            .with_safe_default_kx_groups()
+            .with_protocol_versions(versions)?
+            .with_no_client_auth()
+            .with_single_cert(self.certificates.clone(), self.private_key.clone())?;
+        server_config.alpn_protocols = alpn.iter().map(|protocol| protocol.to_vec()).collect();
+        Ok(server_config)
+    }
+}
+
+/// Serves the application endpoints over TLS at the socket address
+pub async fn serve(socket_addr: SocketAddr, app: Router, tls: &Tls) {
+    let server_config = tls
+        .server_config(rustls::DEFAULT_VERSIONS, &TCP_ALPN)
+        .unwrap();
+    tracing::info!("Serving HTTPS API on {}", socket_addr);
+    axum_server::bind_rustls(
+        socket_addr,
+        RustlsConfig::from_config(Arc::new(server_config)),
+    )
+    .serve(app.into_make_service_with_connect_info::<SocketAddr>())
+    .await
+    .unwrap()
+}
@@ -0,0 +1,61 @@
+use axum::http::HeaderMap;
+use opentelemetry::{propagation::Extractor, Context};
+
+/// An [`Extractor`] reading propagated trace context from the headers of a request
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|key| key.as_str()).collect()
+    }
+}
+
+/// Installs the W3C Trace Context propagator, such that the 'traceparent' and 'tracestate' headers of incoming requests are honoured
+pub fn install_propagator() {
+    opentelemetry::global::set_text_map_propagator(
+        opentelemetry_sdk::propagation::TraceContextPropagator::new(),
+    );
+}
+
+/// The trace context propagated by the caller in the headers of a request, which is empty if none was propagated
+pub fn remote_context(headers: &HeaderMap) -> Context {
+    opentelemetry::global::get_text_map_propagator(|propagator| {
+        propagator.extract(&HeaderExtractor(headers))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::HeaderExtractor;
+    use axum::http::{HeaderMap, HeaderValue};
+    use opentelemetry::{
+        propagation::TextMapPropagator,
+        trace::{TraceContextExt, TraceId},
+    };
+    use opentelemetry_sdk::propagation::TraceContextPropagator;
+
+    #[test]
+    fn traceparent_extracted() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "traceparent",
+            HeaderValue::from_static("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"),
+        );
+        let context = TraceContextPropagator::new().extract(&HeaderExtractor(&headers));
+        assert_eq!(
+            TraceId::from_hex("4bf92f3577b34da6a3ce929d0e0e4736").unwrap(),
+            context.span().span_context().trace_id()
+        );
+        assert!(context.span().span_context().is_remote());
+    }
+
+    #[test]
+    fn missing_traceparent_ignored() {
+        let context = TraceContextPropagator::new().extract(&HeaderExtractor(&HeaderMap::new()));
+        assert!(!context.span().span_context().is_valid());
+    }
+}
@@ -0,0 +1,157 @@
+use crate::bundle::DATASETS;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{collections::BTreeMap, path::Path};
+
+/// An operation reshaping each entry of a dataset
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum Transformation {
+    /// Renames a field, replacing any existing field of the new name
+    Rename {
+        /// The current name of the field
+        from: String,
+        /// The new name of the field
+        to: String,
+    },
+    /// Removes a field
+    Remove {
+        /// The name of the field
+        field: String,
+    },
+    /// Removes every field not listed
+    Keep {
+        /// The names of the fields to retain
+        fields: Vec<String>,
+    },
+    /// Sets a field to a constant value, replacing any existing value
+    Set {
+        /// The name of the field
+        field: String,
+        /// The value the field is set to
+        value: Value,
+    },
+}
+
+impl Transformation {
+    /// Applies the transformation to an entry of a dataset
+    fn apply(&self, entry: &mut serde_json::Map<String, Value>) {
+        match self {
+            Self::Rename { from, to } => {
+                if let Some(value) = entry.remove(from) {
+                    entry.insert(to.clone(), value);
+                }
+            }
+            Self::Remove { field } => {
+                entry.remove(field);
+            }
+            Self::Keep { fields } => entry.retain(|field, _| fields.contains(field)),
+            Self::Set { field, value } => {
+                entry.insert(field.clone(), value.clone());
+            }
+        }
+    }
+}
+
+/// The transformations applied, in order, to each entry of each dataset before it is written into the bundle
+///
+/// Transformations are read from a JSON file mapping dataset names to lists of operations, for example
+/// `{"subjects": [{"rename": {"from": "permissions", "to": "roles"}}, {"remove": {"field": "sessions"}}]}`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Transformations(BTreeMap<String, Vec<Transformation>>);
+
+impl Transformations {
+    /// Reads the [`Transformations`] from a JSON file, if configured, failing if any names an unknown dataset
+    pub fn load(path: Option<&Path>) -> Result<Self, anyhow::Error> {
+        let Some(path) = path else {
+            return Ok(Self::default());
+        };
+        let transformations =
+            serde_json::from_slice::<Self>(&std::fs::read(path)?).map_err(|err| {
+                anyhow::anyhow!("Could not parse transformations {}: {err}", path.display())
+            })?;
+        if let Some(dataset) = transformations
+            .0
+            .keys()
+            .find(|dataset| !DATASETS.contains(&dataset.as_str()))
+        {
+            anyhow::bail!(
+                "Transformations name unknown dataset '{dataset}', expected one of {}",
+                DATASETS.join(", ")
+            );
+        }
+        Ok(transformations)
+    }
+
+    /// Whether no transformations are configured
+    pub fn is_empty(&self) -> bool {
+        self.0.values().all(Vec::is_empty)
+    }
+
+    /// Whether any transformation applies to the named dataset
+    pub fn applies_to(&self, dataset: &str) -> bool {
+        self.0
+            .get(dataset)
+            .is_some_and(|transformations| !transformations.is_empty())
+    }
+
+    /// Applies the transformations of the named dataset to each of its serialized entries
+    pub fn apply(&self, dataset: &str, entries: &mut Value) {
+        let (Some(transformations), Some(entries)) = (self.0.get(dataset), entries.as_object_mut())
+        else {
+            return;
+        };
+        for entry in entries.values_mut().filter_map(Value::as_object_mut) {
+            for transformation in transformations {
+                transformation.apply(entry);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Transformations;
+    use serde_json::json;
+    use std::io::Write;
+
+    #[test]
+    fn entries_transformed_in_order() {
+        let transformations = serde_json::from_value::<Transformations>(json!({
+            "subjects": [
+                {"rename": {"from": "permissions", "to": "roles"}},
+                {"keep": {"fields": ["roles", "proposals"]}},
+                {"remove": {"field": "proposals"}},
+                {"set": {"field": "facility", "value": "diamond"}}
+            ]
+        }))
+        .unwrap();
+        let mut subjects = json!({
+            "foo": {"permissions": ["read_data"], "proposals": [10030], "sessions": [40]}
+        });
+        transformations.apply("subjects", &mut subjects);
+        assert_eq!(
+            json!({"foo": {"roles": ["read_data"], "facility": "diamond"}}),
+            subjects
+        );
+
+        let mut sessions = json!({"40": {"beamline": "i22"}});
+        transformations.apply("sessions", &mut sessions);
+        assert_eq!(json!({"40": {"beamline": "i22"}}), sessions);
+    }
+
+    #[test]
+    fn load_rejects_unknown_dataset() {
+        let transformations_path = std::env::temp_dir().join("bundler-transformations.json");
+        std::fs::File::create(&transformations_path)
+            .unwrap()
+            .write_all(br#"{"visits": [{"remove": {"field": "beamline"}}]}"#)
+            .unwrap();
+        assert!(Transformations::load(Some(&transformations_path)).is_err());
+    }
+
+    #[test]
+    fn load_unconfigured() {
+        assert!(Transformations::load(None).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,130 @@
+use crate::{
+    bundle::{Bundle, NoMetadata},
+    cache_bundle, verification, BundleFile, CurrentBundle,
+};
+use clap::Args;
+use reqwest::{header::IF_NONE_MATCH, StatusCode};
+use std::path::Path;
+use tracing::instrument;
+use url::Url;
+
+/// Options for mirroring the bundle from an upstream bundler
+#[derive(Debug, Clone, Args)]
+pub struct UpstreamArgs {
+    /// The URL of an upstream bundler from which the bundle is mirrored at the polling interval, in place of fetching it from ISPyB, such that no database connection is required. The mirrored bundle is served with the authentication of this service
+    #[arg(long, env = "BUNDLER_UPSTREAM_URL")]
+    upstream_url: Option<Url>,
+    /// The bearer token presented to the upstream bundler, if it requires one
+    #[arg(long, env = "BUNDLER_UPSTREAM_TOKEN", requires = "upstream_url")]
+    upstream_token: Option<String>,
+}
+
+/// An upstream bundler from which the bundle is mirrored
+pub struct Upstream {
+    /// The client with which bundles are fetched
+    http_client: reqwest::Client,
+    /// The URL of the bundle served by the upstream bundler
+    bundle_url: Url,
+    /// The bearer token presented to the upstream bundler, if one is required
+    upstream_token: Option<String>,
+}
+
+impl Upstream {
+    /// Creates an [`Upstream`], if one is configured
+    pub fn from_args(args: UpstreamArgs) -> Result<Option<Self>, url::ParseError> {
+        let Some(upstream_url) = args.upstream_url else {
+            return Ok(None);
+        };
+        Ok(Some(Self {
+            http_client: reqwest::Client::new(),
+            bundle_url: upstream_url.join("bundle.tar.gz")?,
+            upstream_token: args.upstream_token,
+        }))
+    }
+
+    /// Fetches the bundle from the upstream bundler, unless its revision matches the current revision
+    #[instrument(skip(self))]
+    pub async fn fetch(
+        &self,
+        current_revision: Option<&str>,
+    ) -> Result<Option<BundleFile>, anyhow::Error> {
+        let mut request = self.http_client.get(self.bundle_url.clone());
+        if let Some(current_revision) = current_revision {
+            request = request.header(IF_NONE_MATCH, format!(r#""{current_revision}""#));
+        }
+        if let Some(upstream_token) = &self.upstream_token {
+            request = request.bearer_auth(upstream_token);
+        }
+        let response = request.send().await?.error_for_status()?;
+        if response.status() == StatusCode::NOT_MODIFIED {
+            tracing::info!(monotonic_counter.upstream_fetches = 1, modified = false);
+            return Ok(None);
+        }
+        let file = response.bytes().await?;
+        tracing::info!(monotonic_counter.upstream_fetches = 1, modified = true);
+        let bundle_file =
+            BundleFile::new(Bundle::<NoMetadata>::read_revision(&file)?, file, false)?;
+        verification::verify(&bundle_file).map_err(|err| {
+            anyhow::anyhow!("Bundle served by upstream failed verification: {err}")
+        })?;
+        Ok(Some(bundle_file))
+    }
+
+    /// Replaces the current bundle with that served by the upstream bundler, caching it if a cache is configured, returning whether it changed
+    pub async fn mirror(
+        &self,
+        current_bundle: &CurrentBundle,
+        bundle_cache_path: Option<&Path>,
+    ) -> Result<bool, anyhow::Error> {
+        let (current_revision, stale) = {
+            let bundle_file = current_bundle.as_ref().read().await;
+            (bundle_file.revision.clone(), bundle_file.stale)
+        };
+        let Some(bundle_file) = self
+            .fetch((!stale).then_some(current_revision.as_str()))
+            .await?
+        else {
+            return Ok(false);
+        };
+        if bundle_file.revision == current_revision && !stale {
+            return Ok(false);
+        }
+        if let Some(bundle_cache_path) = bundle_cache_path {
+            cache_bundle(bundle_cache_path, &bundle_file).await;
+        }
+        let new_revision = bundle_file.revision.clone();
+        if current_bundle.replace(bundle_file).await {
+            tracing::info!(
+                "Updated bundle from {} to {} served by upstream",
+                current_revision,
+                new_revision
+            );
+        } else {
+            tracing::warn!(
+                "Bundle pinned to {}, not updating to {}",
+                current_revision,
+                new_revision
+            );
+        }
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Upstream, UpstreamArgs};
+
+    #[test]
+    fn bundle_url_relative_to_upstream() {
+        let upstream = Upstream::from_args(UpstreamArgs {
+            upstream_url: Some("https://bundler.example.com/central/".parse().unwrap()),
+            upstream_token: None,
+        })
+        .unwrap()
+        .unwrap();
+        assert_eq!(
+            "https://bundler.example.com/central/bundle.tar.gz",
+            upstream.bundle_url.as_str()
+        );
+    }
+}
@@ -0,0 +1,320 @@
+use schemars::schema::{InstanceType, RootSchema, Schema, SchemaObject, SingleOrVec};
+use serde_json::Value;
+use std::fmt::Display;
+
+/// A location at which a value does not conform to its schema
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    /// The JSON pointer to the non-conforming value
+    pointer: String,
+    /// A description of the constraint which was violated
+    message: String,
+}
+
+impl Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.pointer.is_empty() {
+            write!(f, "/: {}", self.message)
+        } else {
+            write!(f, "{}: {}", self.pointer, self.message)
+        }
+    }
+}
+
+/// Validates a value against a JSON Schema, returning every location at which it does not conform
+///
+/// Only the keywords produced by [`schemars`] are asserted, with the exception of `pattern`. Formats are treated as annotations
+pub fn validate(schema: &RootSchema, value: &Value) -> Vec<Violation> {
+    let mut validator = Validator {
+        root: schema,
+        violations: Vec::new(),
+    };
+    validator.schema_object(&schema.schema, value, "");
+    validator.violations
+}
+
+/// The state of a validation against a [`RootSchema`]
+struct Validator<'a> {
+    /// The schema against which values are validated, holding any referenced definitions
+    root: &'a RootSchema,
+    /// The violations found so far
+    violations: Vec<Violation>,
+}
+
+impl<'a> Validator<'a> {
+    /// Records a violation at the location
+    fn violation(&mut self, pointer: &str, message: impl Into<String>) {
+        self.violations.push(Violation {
+            pointer: pointer.to_string(),
+            message: message.into(),
+        });
+    }
+
+    /// Whether the value conforms to the schema, without recording any violations
+    fn conforms(&self, schema: &Schema, value: &Value) -> bool {
+        let mut validator = Validator {
+            root: self.root,
+            violations: Vec::new(),
+        };
+        validator.schema(schema, value, "");
+        validator.violations.is_empty()
+    }
+
+    /// Validates the value at the location against a schema
+    fn schema(&mut self, schema: &Schema, value: &Value, pointer: &str) {
+        match schema {
+            Schema::Bool(true) => {}
+            Schema::Bool(false) => self.violation(pointer, "no value is permitted"),
+            Schema::Object(schema) => self.schema_object(schema, value, pointer),
+        }
+    }
+
+    /// Validates the value at the location against each keyword of a schema object
+    fn schema_object(&mut self, schema: &SchemaObject, value: &Value, pointer: &str) {
+        if let Some(reference) = &schema.reference {
+            match reference
+                .strip_prefix("#/definitions/")
+                .and_then(|name| self.root.definitions.get(name))
+            {
+                Some(definition) => self.schema(definition, value, pointer),
+                None => self.violation(pointer, format!("unresolved reference {reference}")),
+            }
+        }
+        if let Some(instance_type) = &schema.instance_type {
+            let instance_types = match instance_type {
+                SingleOrVec::Single(instance_type) => std::slice::from_ref(instance_type.as_ref()),
+                SingleOrVec::Vec(instance_types) => instance_types.as_slice(),
+            };
+            if !instance_types
+                .iter()
+                .any(|instance_type| is_instance(value, instance_type))
+            {
+                let expected = instance_types
+                    .iter()
+                    .map(|instance_type| format!("{instance_type:?}").to_lowercase())
+                    .collect::<Vec<_>>()
+                    .join(" or ");
+                self.violation(
+                    pointer,
+                    format!("expected {expected}, found {}", type_name(value)),
+                );
+                return;
+            }
+        }
+        if let Some(enum_values) = &schema.enum_values {
+            if !enum_values.contains(value) {
+                self.violation(pointer, format!("{value} is not an enumerated value"));
+            }
+        }
+        if let Some(const_value) = &schema.const_value {
+            if const_value != value {
+                self.violation(pointer, format!("expected {const_value}, found {value}"));
+            }
+        }
+        if let Some(subschemas) = &schema.subschemas {
+            for subschema in subschemas.all_of.iter().flatten() {
+                self.schema(subschema, value, pointer);
+            }
+            if let Some(any_of) = &subschemas.any_of {
+                if !any_of
+                    .iter()
+                    .any(|subschema| self.conforms(subschema, value))
+                {
+                    self.violation(pointer, "does not conform to any permitted schema");
+                }
+            }
+            if let Some(one_of) = &subschemas.one_of {
+                let conforming = one_of
+                    .iter()
+                    .filter(|subschema| self.conforms(subschema, value))
+                    .count();
+                if conforming != 1 {
+                    self.violation(
+                        pointer,
+                        format!("conforms to {conforming} schemas, expected exactly one"),
+                    );
+                }
+            }
+            if let Some(not) = &subschemas.not {
+                if self.conforms(not, value) {
+                    self.violation(pointer, format!("{value} is not permitted"));
+                }
+            }
+        }
+        if let (Some(number), Some(actual)) = (&schema.number, value.as_f64()) {
+            if number.minimum.is_some_and(|minimum| actual < minimum) {
+                self.violation(pointer, format!("{value} is below the minimum"));
+            }
+            if number.maximum.is_some_and(|maximum| actual > maximum) {
+                self.violation(pointer, format!("{value} is above the maximum"));
+            }
+        }
+        if let (Some(string), Some(actual)) = (&schema.string, value.as_str()) {
+            let length = actual.chars().count();
+            if string
+                .min_length
+                .is_some_and(|min_length| length < min_length as usize)
+            {
+                self.violation(pointer, format!("{value} is shorter than permitted"));
+            }
+            if string
+                .max_length
+                .is_some_and(|max_length| length > max_length as usize)
+            {
+                self.violation(pointer, format!("{value} is longer than permitted"));
+            }
+        }
+        if let (Some(array), Some(items)) = (&schema.array, value.as_array()) {
+            match &array.items {
+                Some(SingleOrVec::Single(item_schema)) => {
+                    for (idx, item) in items.iter().enumerate() {
+                        self.schema(item_schema, item, &format!("{pointer}/{idx}"));
+                    }
+                }
+                Some(SingleOrVec::Vec(item_schemas)) => {
+                    for (idx, (item_schema, item)) in item_schemas.iter().zip(items).enumerate() {
+                        self.schema(item_schema, item, &format!("{pointer}/{idx}"));
+                    }
+                }
+                None => {}
+            }
+            if array
+                .min_items
+                .is_some_and(|min_items| items.len() < min_items as usize)
+            {
+                self.violation(pointer, "has fewer items than permitted");
+            }
+            if array
+                .max_items
+                .is_some_and(|max_items| items.len() > max_items as usize)
+            {
+                self.violation(pointer, "has more items than permitted");
+            }
+        }
+        if let (Some(object), Some(entries)) = (&schema.object, value.as_object()) {
+            for required in object
+                .required
+                .iter()
+                .filter(|required| !entries.contains_key(*required))
+            {
+                self.violation(pointer, format!("missing required property '{required}'"));
+            }
+            for (key, entry) in entries {
+                let entry_pointer = format!("{pointer}/{}", escape(key));
+                if let Some(property_names) = &object.property_names {
+                    if !self.conforms(property_names, &Value::String(key.clone())) {
+                        self.violation(
+                            &entry_pointer,
+                            format!("property name '{key}' is not permitted"),
+                        );
+                    }
+                }
+                match (
+                    object.properties.get(key),
+                    object.additional_properties.as_deref(),
+                ) {
+                    (Some(property), _) | (None, Some(property)) => {
+                        self.schema(property, entry, &entry_pointer)
+                    }
+                    (None, None) => {}
+                }
+            }
+        }
+    }
+}
+
+/// Whether the value is an instance of the JSON Schema type
+fn is_instance(value: &Value, instance_type: &InstanceType) -> bool {
+    match instance_type {
+        InstanceType::Null => value.is_null(),
+        InstanceType::Boolean => value.is_boolean(),
+        InstanceType::Object => value.is_object(),
+        InstanceType::Array => value.is_array(),
+        InstanceType::Number => value.is_number(),
+        InstanceType::String => value.is_string(),
+        InstanceType::Integer => {
+            value.is_i64() || value.is_u64() || value.as_f64().is_some_and(|n| n.fract() == 0.0)
+        }
+    }
+}
+
+/// The JSON type of the value, for reporting
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+/// Escapes a property name for inclusion in a JSON pointer
+fn escape(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::validate;
+    use schemars::{schema_for, JsonSchema};
+    use serde_json::json;
+    use std::collections::BTreeMap;
+
+    /// An entry of a dataset used to exercise validation
+    #[derive(JsonSchema)]
+    #[allow(dead_code)]
+    struct Entry {
+        /// A required list of unsigned integers
+        sessions: Vec<u32>,
+        /// An optional string
+        email: Option<String>,
+    }
+
+    #[test]
+    fn conforming() {
+        let schema = schema_for!(BTreeMap<String, Entry>);
+        let value = json!({
+            "foo": {"sessions": [1, 2], "email": null},
+            "bar": {"sessions": [], "email": "bar@example.com"},
+        });
+        assert_eq!(Vec::<String>::new(), violations(&schema, &value));
+    }
+
+    #[test]
+    fn violations_located() {
+        let schema = schema_for!(BTreeMap<String, Entry>);
+        let value = json!({
+            "foo": {"sessions": [1, -2, null]},
+            "a/b": {"email": 3},
+            "baz": null,
+        });
+        assert_eq!(
+            vec![
+                "/a~1b: missing required property 'sessions'",
+                "/a~1b/email: expected string or null, found number",
+                "/baz: expected object, found null",
+                "/foo/sessions/1: -2 is below the minimum",
+                "/foo/sessions/2: expected integer, found null",
+            ],
+            violations(&schema, &value)
+        );
+    }
+
+    #[test]
+    fn root_type_mismatch() {
+        let schema = schema_for!(BTreeMap<String, Entry>);
+        assert_eq!(
+            vec!["/: expected object, found array"],
+            violations(&schema, &json!([]))
+        );
+    }
+
+    fn violations(schema: &schemars::schema::RootSchema, value: &serde_json::Value) -> Vec<String> {
+        validate(schema, value)
+            .iter()
+            .map(ToString::to_string)
+            .collect()
+    }
+}
@@ -0,0 +1,249 @@
+use clap::Args;
+use serde::Deserialize;
+use std::{
+    fmt::{self, Debug, Formatter},
+    time::Duration,
+};
+use tokio::time::Instant;
+use url::Url;
+
+/// Options for fetching ISPyB credentials from HashiCorp Vault
+#[derive(Clone, Args)]
+pub struct VaultArgs {
+    /// The address of a HashiCorp Vault server which issues the credentials with which ISPyB is connected to, taking precedence over any in the database URLs
+    #[arg(
+        long,
+        env = "BUNDLER_VAULT_ADDR",
+        requires_all = ["vault_token", "vault_credentials_path"]
+    )]
+    vault_addr: Option<Url>,
+    /// The token with which requests to Vault are authenticated
+    #[arg(long, env = "BUNDLER_VAULT_TOKEN")]
+    vault_token: Option<String>,
+    /// The path of the Vault secret which issues ISPyB credentials, such as 'database/creds/bundler'
+    #[arg(long, env = "BUNDLER_VAULT_CREDENTIALS_PATH")]
+    vault_credentials_path: Option<String>,
+}
+
+impl Debug for VaultArgs {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VaultArgs")
+            .field("vault_addr", &self.vault_addr.as_ref().map(Url::as_str))
+            .field("vault_credentials_path", &self.vault_credentials_path)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A username and password with which ISPyB is connected to
+#[derive(Clone, PartialEq, Eq, Deserialize)]
+pub struct Credentials {
+    /// The username of the database user
+    pub username: String,
+    /// The password of the database user
+    pub password: String,
+}
+
+impl Debug for Credentials {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Credentials")
+            .field("username", &self.username)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The response of Vault to the issue of a secret or the renewal of its lease
+#[derive(Debug, Deserialize)]
+struct SecretResponse {
+    /// The identifier of the lease on the secret
+    lease_id: String,
+    /// The time, in seconds, for which the lease is valid
+    lease_duration: u64,
+    /// Whether the lease may be renewed
+    renewable: bool,
+    /// The credentials issued, absent from renewals
+    #[serde(default)]
+    data: Option<Credentials>,
+}
+
+/// A lease on credentials issued by Vault
+#[derive(Debug)]
+struct Lease {
+    /// The identifier of the lease
+    id: String,
+    /// Whether the lease may be renewed
+    renewable: bool,
+    /// The time for which the lease was originally issued
+    duration: Duration,
+    /// The time after which the lease is renewed
+    renew_at: Instant,
+    /// The time at which the lease expires
+    expires_at: Instant,
+    /// The credentials issued under the lease
+    credentials: Credentials,
+}
+
+/// A source of ISPyB credentials issued by Vault, which are renewed as their lease nears expiry and replaced once it can no longer be renewed
+pub struct VaultCredentials {
+    /// The client with which Vault is called
+    http_client: reqwest::Client,
+    /// The address of the Vault server
+    addr: Url,
+    /// The token with which requests are authenticated
+    token: String,
+    /// The path of the secret which issues credentials
+    path: String,
+    /// The lease on the current credentials, if any have been issued
+    lease: Option<Lease>,
+}
+
+impl Debug for VaultCredentials {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VaultCredentials")
+            .field("addr", &self.addr.as_str())
+            .field("path", &self.path)
+            .field("lease", &self.lease)
+            .finish_non_exhaustive()
+    }
+}
+
+impl VaultCredentials {
+    /// Creates [`VaultCredentials`], if a Vault server is configured
+    pub fn from_args(args: VaultArgs) -> Option<Self> {
+        Some(Self {
+            http_client: reqwest::Client::new(),
+            addr: args.vault_addr?,
+            token: args.vault_token?,
+            path: args.vault_credentials_path?,
+            lease: None,
+        })
+    }
+
+    /// The credentials issued under the current lease, if any
+    pub fn current(&self) -> Option<&Credentials> {
+        self.lease.as_ref().map(|lease| &lease.credentials)
+    }
+
+    /// Renews the current lease once it is due, or issues new credentials if there is no lease or it can no longer be renewed for its full duration, returning whether the credentials changed
+    pub async fn refresh(&mut self) -> Result<bool, anyhow::Error> {
+        let now = Instant::now();
+        if let Some(lease) = &self.lease {
+            if now < lease.renew_at {
+                return Ok(false);
+            }
+            if lease.renewable && now < lease.expires_at {
+                match self.renew(&lease.id, lease.duration).await {
+                    Ok(renewal) if Duration::from_secs(renewal.lease_duration) >= lease.duration => {
+                        let (renew_at, expires_at) = lease_timings(now, lease.duration);
+                        if let Some(lease) = self.lease.as_mut() {
+                            lease.renew_at = renew_at;
+                            lease.expires_at = expires_at;
+                        }
+                        tracing::debug!("Renewed lease on ISPyB credentials");
+                        return Ok(false);
+                    }
+                    Ok(_) => tracing::info!(
+                        "Lease on ISPyB credentials is nearing its maximum TTL, issuing new credentials"
+                    ),
+                    Err(err) => tracing::warn!(
+                        "Could not renew lease on ISPyB credentials, issuing new credentials: {err}"
+                    ),
+                }
+            }
+        }
+        let secret = self.issue().await?;
+        let credentials = secret
+            .data
+            .ok_or_else(|| anyhow::anyhow!("Vault secret {} carried no credentials", self.path))?;
+        let duration = Duration::from_secs(secret.lease_duration);
+        let (renew_at, expires_at) = lease_timings(now, duration);
+        self.lease = Some(Lease {
+            id: secret.lease_id,
+            renewable: secret.renewable,
+            duration,
+            renew_at,
+            expires_at,
+            credentials,
+        });
+        tracing::info!(
+            monotonic_counter.ispyb_credential_rotations = 1,
+            "Issued ISPyB credentials by Vault, valid for {}",
+            humantime::format_duration(duration)
+        );
+        Ok(true)
+    }
+
+    /// Requests new credentials from the secret
+    async fn issue(&self) -> Result<SecretResponse, anyhow::Error> {
+        let body = self
+            .http_client
+            .get(self.addr.join(&format!("v1/{}", self.path))?)
+            .header("X-Vault-Token", &self.token)
+            .send()
+            .await?
+            .error_for_status()?
+            .bytes()
+            .await?;
+        Ok(serde_json::from_slice(&body)?)
+    }
+
+    /// Requests the renewal of the lease for its original duration
+    async fn renew(
+        &self,
+        lease_id: &str,
+        duration: Duration,
+    ) -> Result<SecretResponse, anyhow::Error> {
+        let body = self
+            .http_client
+            .put(self.addr.join("v1/sys/leases/renew")?)
+            .header("X-Vault-Token", &self.token)
+            .body(serde_json::to_vec(
+                &serde_json::json!({ "lease_id": lease_id, "increment": duration.as_secs() }),
+            )?)
+            .send()
+            .await?
+            .error_for_status()?
+            .bytes()
+            .await?;
+        Ok(serde_json::from_slice(&body)?)
+    }
+}
+
+/// The times at which a lease starting now should be renewed, once two thirds of its duration have elapsed, and at which it expires
+fn lease_timings(now: Instant, duration: Duration) -> (Instant, Instant) {
+    (now + duration * 2 / 3, now + duration)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{lease_timings, SecretResponse};
+    use serde_json::json;
+    use std::time::Duration;
+    use tokio::time::Instant;
+
+    #[test]
+    fn renewed_before_expiry() {
+        let now = Instant::now();
+        let (renew_at, expires_at) = lease_timings(now, Duration::from_secs(3600));
+        assert_eq!(now + Duration::from_secs(2400), renew_at);
+        assert_eq!(now + Duration::from_secs(3600), expires_at);
+    }
+
+    #[test]
+    fn parse_secret_response() {
+        let secret = serde_json::from_value::<SecretResponse>(json!({
+            "lease_id": "database/creds/bundler/abc",
+            "lease_duration": 3600,
+            "renewable": true,
+            "data": {"username": "v-bundler", "password": "secret"}
+        }))
+        .unwrap();
+        assert_eq!("v-bundler", secret.data.unwrap().username);
+        let renewal = serde_json::from_value::<SecretResponse>(json!({
+            "lease_id": "database/creds/bundler/abc",
+            "lease_duration": 1800,
+            "renewable": true
+        }))
+        .unwrap();
+        assert!(renewal.data.is_none());
+    }
+}
@@ -0,0 +1,141 @@
+use crate::{bundle::gunzip, BundleFile};
+use anyhow::{anyhow, ensure};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use serde::{de::IgnoredAny, Deserialize};
+use sha2::{Digest, Sha256};
+
+/// The fields of a bundle manifest which Open Policy Agent checks upon activation
+#[derive(Debug, Deserialize)]
+struct VerifiedManifest {
+    /// The revision of the bundle
+    revision: String,
+    /// The directory prefixes of the data contained within the bundle
+    roots: Vec<String>,
+}
+
+/// Verifies a freshly built [`BundleFile`] by re-reading its archives, such that a bundle which Open Policy Agent would refuse to activate is never served
+///
+/// The digests must match both archives, which must hold the same contents, and the manifest must carry the revision of the [`BundleFile`] and roots which neither overlap nor omit any entry, each JSON entry of which must parse
+pub fn verify(bundle_file: &BundleFile) -> Result<(), anyhow::Error> {
+    ensure!(
+        BASE64.encode(Sha256::digest(&bundle_file.file)) == bundle_file.digest,
+        "Digest does not match the gzipped archive"
+    );
+    ensure!(
+        BASE64.encode(Sha256::digest(&bundle_file.tar)) == bundle_file.tar_digest,
+        "Digest does not match the uncompressed archive"
+    );
+    ensure!(
+        gunzip(&bundle_file.file)? == bundle_file.tar,
+        "Gzipped archive does not match the uncompressed archive"
+    );
+    let mut manifest = None;
+    let mut paths = Vec::new();
+    let mut archive = tar::Archive::new(bundle_file.tar.as_ref());
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.to_string_lossy().into_owned();
+        if path == ".manifest" {
+            manifest = Some(
+                serde_json::from_reader::<_, VerifiedManifest>(&mut entry)
+                    .map_err(|err| anyhow!("Could not parse manifest: {err}"))?,
+            );
+            continue;
+        }
+        if path.ends_with(".json") {
+            serde_json::from_reader::<_, IgnoredAny>(&mut entry)
+                .map_err(|err| anyhow!("Could not parse {path}: {err}"))?;
+        }
+        paths.push(path);
+    }
+    let manifest = manifest.ok_or_else(|| anyhow!("Bundle contains no manifest"))?;
+    ensure!(
+        manifest.revision == bundle_file.revision,
+        "Manifest revision {} does not match {}",
+        manifest.revision,
+        bundle_file.revision
+    );
+    for (index, root) in manifest.roots.iter().enumerate() {
+        if let Some(other) = manifest.roots[index + 1..]
+            .iter()
+            .find(|other| is_within(root, other) || is_within(other, root))
+        {
+            return Err(anyhow!("Manifest roots {root} and {other} overlap"));
+        }
+    }
+    if let Some(path) = paths
+        .iter()
+        .find(|path| !manifest.roots.iter().any(|root| is_within(path, root)))
+    {
+        return Err(anyhow!("{path} lies outside of the manifest roots"));
+    }
+    Ok(())
+}
+
+/// Whether the path lies within the root, as a whole path segment
+fn is_within(path: &str, root: &str) -> bool {
+    root.is_empty()
+        || path
+            .strip_prefix(root)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::verify;
+    use crate::{
+        bundle::{gzip, AppendJson},
+        BundleFile,
+    };
+    use serde_json::json;
+
+    fn bundle_file(revision: &str, roots: &[&str], paths: &[&str]) -> BundleFile {
+        let mut builder = tar::Builder::new(Vec::new());
+        builder
+            .append_json(".manifest", &json!({"revision": "a", "roots": roots}))
+            .unwrap();
+        for path in paths {
+            builder.append_json(path, &json!({})).unwrap();
+        }
+        let tar = builder.into_inner().unwrap();
+        BundleFile::new(revision.to_string(), gzip(&tar).unwrap().into(), false).unwrap()
+    }
+
+    #[test]
+    fn built_bundle_verified() {
+        let bundle_file = bundle_file(
+            "a",
+            &["diamond/data", "users"],
+            &[
+                "diamond/data/sessions/data.json",
+                "users/instrument_scientists/data.json",
+            ],
+        );
+        assert!(verify(&bundle_file).is_ok());
+    }
+
+    #[test]
+    fn mismatched_revision_refused() {
+        let bundle_file = bundle_file("b", &["diamond/data"], &[]);
+        assert!(verify(&bundle_file).is_err());
+    }
+
+    #[test]
+    fn entries_outside_roots_refused() {
+        let bundle_file = bundle_file("a", &["diamond/data"], &["diamond/database/data.json"]);
+        assert!(verify(&bundle_file).is_err());
+    }
+
+    #[test]
+    fn overlapping_roots_refused() {
+        let bundle_file = bundle_file("a", &["diamond", "diamond/data"], &[]);
+        assert!(verify(&bundle_file).is_err());
+    }
+
+    #[test]
+    fn mismatched_digest_refused() {
+        let mut bundle_file = bundle_file("a", &["diamond/data"], &[]);
+        bundle_file.digest = String::new();
+        assert!(verify(&bundle_file).is_err());
+    }
+}
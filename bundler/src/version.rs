@@ -0,0 +1,82 @@
+use crate::{built_info, effective_config::enabled_features};
+use axum::{extract::State, response::IntoResponse, routing::get, Json, Router};
+use serde::Serialize;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use utoipa::{OpenApi, ToSchema};
+
+/// The build of the service, such that fleet tooling can verify which build each replica runs
+#[derive(Debug, Clone, Serialize, ToSchema)]
+struct VersionInfo {
+    /// The version of the crate
+    version: String,
+    /// The git commit from which the service was built, if known
+    git_commit: Option<String>,
+    /// The time at which the service was built
+    #[serde(serialize_with = "crate::timestamp::serialize_optional")]
+    #[schema(value_type = Option<String>, format = DateTime)]
+    build_timestamp: Option<SystemTime>,
+    /// The version of the Rust compiler with which the service was built
+    rustc_version: String,
+    /// The target triple for which the service was built
+    target: String,
+    /// The profile with which the service was built
+    profile: String,
+    /// The optional features the service was built with
+    features: Vec<String>,
+}
+
+impl VersionInfo {
+    /// The [`VersionInfo`] of the running service
+    fn current() -> Self {
+        Self {
+            version: built_info::PKG_VERSION.to_string(),
+            git_commit: built_info::GIT_COMMIT.map(ToString::to_string),
+            build_timestamp: built_info::BUILD_TIMESTAMP
+                .parse()
+                .ok()
+                .map(|secs| UNIX_EPOCH + Duration::from_secs(secs)),
+            rustc_version: built_info::RUSTC_VERSION.to_string(),
+            target: built_info::TARGET.to_string(),
+            profile: built_info::PROFILE.to_string(),
+            features: enabled_features(),
+        }
+    }
+}
+
+/// The paths served by the version endpoint
+#[derive(OpenApi)]
+#[openapi(paths(version_endpoint), components(schemas(VersionInfo)))]
+pub struct VersionApi;
+
+/// Creates a [`Router`] serving the build of the service
+pub fn router() -> Router {
+    Router::new()
+        .route("/version", get(version_endpoint))
+        .with_state(VersionInfo::current())
+}
+
+/// Returns the version, git commit and build time of the service, along with the compiler and features it was built with
+#[utoipa::path(
+    get,
+    path = "/version",
+    tag = "meta",
+    security(()),
+    responses((status = OK, description = "The build of the service", body = VersionInfo)),
+)]
+async fn version_endpoint(State(version): State<VersionInfo>) -> impl IntoResponse {
+    Json(version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::VersionInfo;
+    use crate::built_info;
+
+    #[test]
+    fn build_reported() {
+        let version = VersionInfo::current();
+        assert_eq!(built_info::PKG_VERSION, version.version);
+        assert!(version.build_timestamp.is_some());
+        assert!(version.rustc_version.starts_with("rustc "));
+    }
+}
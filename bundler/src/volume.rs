@@ -0,0 +1,119 @@
+use std::collections::BTreeMap;
+
+/// The number of entries in a dataset and the size of its serialization
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DatasetVolume {
+    /// The number of entries in the dataset
+    pub rows: usize,
+    /// The size of the serialized dataset, in bytes
+    pub bytes: u64,
+}
+
+/// A record of the volume of each dataset, exported as gauges, which warns of sudden changes between polls
+#[derive(Debug, Clone)]
+pub struct VolumeMonitor {
+    /// The percentage by which the volume of a dataset may change between polls before a warning is logged
+    threshold: f64,
+    /// The volume of each dataset in the previously observed bundle
+    previous: BTreeMap<&'static str, DatasetVolume>,
+}
+
+impl VolumeMonitor {
+    /// Creates a [`VolumeMonitor`] which warns of changes exceeding the threshold percentage
+    pub fn new(threshold: f64) -> Self {
+        Self {
+            threshold,
+            previous: BTreeMap::new(),
+        }
+    }
+
+    /// The volume of each dataset in the previously observed bundle
+    pub fn previous(&self) -> &BTreeMap<&'static str, DatasetVolume> {
+        &self.previous
+    }
+
+    /// Records the volume of each dataset, returning those whose row count or serialized size changed by more than the threshold since the previous observation
+    ///
+    /// Gauges are exported as up-down counters, adjusted by the change in volume of each dataset
+    pub fn observe(&mut self, volumes: BTreeMap<&'static str, DatasetVolume>) -> Vec<&'static str> {
+        let mut anomalous = Vec::new();
+        for (dataset, volume) in &volumes {
+            let previous = self.previous.remove(dataset);
+            let DatasetVolume { rows, bytes } = previous.unwrap_or_default();
+            if previous != Some(*volume) {
+                tracing::info!(
+                    counter.dataset_rows = volume.rows as i64 - rows as i64,
+                    counter.dataset_bytes = volume.bytes as i64 - bytes as i64,
+                    dataset,
+                    "Dataset {dataset} contains {} rows, serialized as {} bytes",
+                    volume.rows,
+                    volume.bytes
+                );
+            }
+            if previous.is_some()
+                && (exceeds(rows as f64, volume.rows as f64, self.threshold)
+                    || exceeds(bytes as f64, volume.bytes as f64, self.threshold))
+            {
+                tracing::warn!(
+                    monotonic_counter.dataset_volume_anomalies = 1,
+                    dataset,
+                    "Dataset {dataset} changed from {rows} rows ({bytes} bytes) to {} rows ({} bytes), exceeding the threshold of {}%",
+                    volume.rows,
+                    volume.bytes,
+                    self.threshold
+                );
+                anomalous.push(*dataset);
+            }
+        }
+        for (dataset, volume) in std::mem::replace(&mut self.previous, volumes) {
+            tracing::info!(
+                counter.dataset_rows = -(volume.rows as i64),
+                counter.dataset_bytes = -(volume.bytes as i64),
+                dataset,
+                "Dataset {dataset} is no longer included in the bundle"
+            );
+        }
+        anomalous
+    }
+}
+
+/// Whether the change from the previous to the current value exceeds the threshold percentage of the previous value
+fn exceeds(previous: f64, current: f64, threshold: f64) -> bool {
+    if previous == 0.0 {
+        return current != 0.0;
+    }
+    (current - previous).abs() / previous * 100.0 > threshold
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DatasetVolume, VolumeMonitor};
+    use std::collections::BTreeMap;
+
+    fn volumes(sessions: usize) -> BTreeMap<&'static str, DatasetVolume> {
+        BTreeMap::from([(
+            "sessions",
+            DatasetVolume {
+                rows: sessions,
+                bytes: sessions as u64 * 64,
+            },
+        )])
+    }
+
+    #[test]
+    fn sudden_drop_detected() {
+        let mut monitor = VolumeMonitor::new(50.0);
+        assert!(monitor.observe(volumes(40_000)).is_empty());
+        assert!(monitor.observe(volumes(39_000)).is_empty());
+        assert_eq!(vec!["sessions"], monitor.observe(volumes(300)));
+        assert_eq!(vec!["sessions"], monitor.observe(volumes(40_000)));
+    }
+
+    #[test]
+    fn growth_from_empty_detected() {
+        let mut monitor = VolumeMonitor::new(50.0);
+        assert!(monitor.observe(volumes(0)).is_empty());
+        assert_eq!(vec!["sessions"], monitor.observe(volumes(10)));
+        assert!(monitor.observe(volumes(10)).is_empty());
+    }
+}
@@ -0,0 +1,194 @@
+//! Integration tests asserting the data emitted in bundles built from a containerised ISPyB instance
+
+mod ispyb;
+
+use flate2::read::GzDecoder;
+use ispyb::Ispyb;
+use serde_json::{json, Value};
+use std::{collections::BTreeMap, io::Read};
+
+/// Every script in `tests/fixtures`
+const ALL_FIXTURES: &[&str] = &[
+    "beamline_sessions",
+    "group_permissions",
+    "lab_contacts",
+    "laboratories",
+    "permissions",
+    "persons",
+    "proposal_membership",
+    "proposals",
+    "session_membership",
+    "user_group_membership",
+    "user_groups",
+];
+
+/// Fetches the bundle served by the bundler, returning the JSON contents of each entry keyed by path
+async fn fetch_entries(url: &str) -> BTreeMap<String, Value> {
+    let archive = reqwest::get(format!("{url}/bundle.tar.gz"))
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .bytes()
+        .await
+        .unwrap();
+    tar::Archive::new(GzDecoder::new(archive.as_ref()))
+        .entries()
+        .unwrap()
+        .map(|entry| {
+            let mut entry = entry.unwrap();
+            let mut contents = Vec::new();
+            entry.read_to_end(&mut contents).unwrap();
+            (
+                entry.path().unwrap().to_string_lossy().into_owned(),
+                serde_json::from_slice(&contents).unwrap(),
+            )
+        })
+        .collect()
+}
+
+/// Sorts each array within the entries of a dataset, which are emitted in the order returned by ISPyB
+fn sort_ids(dataset: &mut Value) {
+    for entry in dataset.as_object_mut().unwrap().values_mut() {
+        for ids in entry.as_object_mut().unwrap().values_mut() {
+            ids.as_array_mut()
+                .unwrap()
+                .sort_by_key(|id| (id.as_u64(), id.as_str().map(ToString::to_string)));
+        }
+    }
+}
+
+#[tokio::test]
+async fn bundle_contains_fixtures() {
+    let ispyb = Ispyb::start(ALL_FIXTURES).await;
+    let bundler = ispyb.serve(&[]).await;
+    let mut entries = fetch_entries(bundler.url()).await;
+
+    assert_eq!(
+        json!(["diamond/data", "users", "diamond/schemas"]),
+        entries[".manifest"]["roots"]
+    );
+    assert!(!entries.contains_key("users/people/data.json"));
+    assert!(!entries.contains_key("diamond/schemas/people/data.json"));
+    assert_eq!(
+        "object",
+        entries["diamond/schemas/subjects/data.json"]["type"]
+    );
+    for dataset in ["subjects", "beamlines"] {
+        sort_ids(
+            entries
+                .get_mut(&format!("diamond/data/{dataset}/data.json"))
+                .unwrap(),
+        );
+    }
+    assert_eq!(
+        json!({
+            "bar": {
+                "permissions": ["read_data", "write_data"],
+                "proposals": [10030],
+                "sessions": [43]
+            },
+            "foo": {
+                "permissions": ["read_data", "read_proc", "write_data"],
+                "proposals": [10030, 10031, 10032],
+                "sessions": [40, 41]
+            }
+        }),
+        entries["diamond/data/subjects/data.json"]
+    );
+    assert_eq!(
+        json!({
+            "40": {"proposal_number": 10030, "visit_number": 10, "beamline": "i12"},
+            "41": {"proposal_number": 10030, "visit_number": 11, "beamline": "i22"},
+            "42": {"proposal_number": 10030, "visit_number": 12, "beamline": "b13"},
+            "43": {"proposal_number": 10031, "visit_number": 10, "beamline": "p99"},
+            "44": {"proposal_number": 10031, "visit_number": 11, "beamline": "i22"}
+        }),
+        entries["diamond/data/sessions/data.json"]
+    );
+    assert_eq!(
+        json!({
+            "10030": {"sessions": {"10": 40, "11": 41, "12": 42}},
+            "10031": {"sessions": {"10": 43, "11": 44}}
+        }),
+        entries["diamond/data/proposals/data.json"]
+    );
+    assert_eq!(
+        json!({
+            "b13": {"sessions": [42]},
+            "i12": {"sessions": [40]},
+            "i22": {"sessions": [41, 44]},
+            "p99": {"sessions": [43]}
+        }),
+        entries["diamond/data/beamlines/data.json"]
+    );
+    assert_eq!(
+        json!({
+            "data": {"permissions": ["read_data", "write_data"]},
+            "proc": {"permissions": ["read_proc"]}
+        }),
+        entries["diamond/data/roles/data.json"]
+    );
+    assert_eq!(
+        json!({
+            "40": {"foo": {"role": "Team Leader", "remote": false}},
+            "41": {"foo": {"role": "Co-Investigator", "remote": true}},
+            "43": {"bar": {"role": null, "remote": false}}
+        }),
+        entries["diamond/data/session_participants/data.json"]
+    );
+    assert_eq!(
+        json!({
+            "10030": {
+                "bar": {"institution": null},
+                "foo": {"institution": "Diamond Light Source"}
+            },
+            "10032": {"foo": {"institution": "Diamond Light Source"}}
+        }),
+        entries["diamond/data/lab_contacts/data.json"]
+    );
+}
+
+#[tokio::test]
+async fn bundle_contains_people_if_requested() {
+    let ispyb = Ispyb::start(ALL_FIXTURES).await;
+    let bundler = ispyb.serve(&["--include-personal-data"]).await;
+    let entries = fetch_entries(bundler.url()).await;
+
+    assert_eq!(
+        json!({
+            "bar": {"title": null, "name": "Bar", "email": null, "institution": null},
+            "foo": {
+                "title": "Dr",
+                "name": "Foo Fighter",
+                "email": "foo@example.com",
+                "institution": "Diamond Light Source"
+            }
+        }),
+        entries["users/people/data.json"]
+    );
+}
+
+#[tokio::test]
+async fn bundle_empty_without_fixtures() {
+    let ispyb = Ispyb::start(&[]).await;
+    let bundler = ispyb.serve(&[]).await;
+    let entries = fetch_entries(bundler.url()).await;
+
+    for dataset in [
+        "subjects",
+        "sessions",
+        "proposals",
+        "beamlines",
+        "roles",
+        "session_participants",
+        "lab_contacts",
+    ] {
+        assert_eq!(
+            json!({}),
+            entries[&format!("diamond/data/{dataset}/data.json")],
+            "{dataset} not empty"
+        );
+    }
+    assert_eq!(json!({}), entries["users/instrument_scientists/data.json"]);
+}
@@ -0,0 +1,3 @@
+INSERT INTO
+    `Session_has_Person` (`sessionId`, `personId`, `role`, `remote`)
+VALUES (41, 21, "Local Contact", 0), (42, 21, "Local Contact", 0), (44, 20, "Local Contact 2", 0);
\ No newline at end of file
@@ -0,0 +1,3 @@
+INSERT INTO
+    `LabContact` (`labContactId`, `personId`, `cardName`, `proposalId`)
+VALUES (80, 20, "Foo", 30), (81, 21, "Bar", 30), (82, 20, "Foo", 32);
\ No newline at end of file
@@ -0,0 +1,3 @@
+INSERT INTO
+    `Laboratory` (`laboratoryId`, `name`)
+VALUES (70, "Diamond Light Source");
\ No newline at end of file
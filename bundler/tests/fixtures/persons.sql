@@ -1,3 +1,11 @@
 INSERT INTO
-    `Person` (`personId`, `login`)
-VALUES (20, "foo"), (21, "bar");
\ No newline at end of file
+    `Person` (
+        `personId`,
+        `login`,
+        `laboratoryId`,
+        `title`,
+        `givenName`,
+        `familyName`,
+        `emailAddress`
+    )
+VALUES (20, "foo", 70, "Dr", "Foo", "Fighter", "foo@example.com"), (21, "bar", NULL, NULL, "Bar", NULL, NULL);
\ No newline at end of file
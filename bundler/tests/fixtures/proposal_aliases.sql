@@ -0,0 +1,9 @@
+INSERT INTO
+    `Proposal` (
+        `proposalId`,
+        `proposalNumber`,
+        `externalId`,
+        `proposalCode`,
+        `state`
+    )
+VALUES (33, "10033", '272E', "cm", "Open")
\ No newline at end of file
@@ -2,6 +2,8 @@ INSERT INTO
     `Proposal` (
         `proposalId`,
         `proposalNumber`,
-        `externalId`
+        `externalId`,
+        `proposalCode`,
+        `state`
     )
-VALUES (30, "10030", '272E'), (31, "10031", '272F'), (32, "10032", '2730')
\ No newline at end of file
+VALUES (30, "10030", '272E', "mx", "Open"), (31, "10031", '272F', "in", "Cancelled"), (32, "10032", '2730', "cm", "Open")
\ No newline at end of file
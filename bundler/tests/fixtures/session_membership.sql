@@ -1,3 +1,3 @@
 INSERT INTO
-    `Session_has_Person` (`sessionId`, `personId`)
-VALUES (40, 20), (41, 20), (43, 21);
\ No newline at end of file
+    `Session_has_Person` (`sessionId`, `personId`, `role`, `remote`)
+VALUES (40, 20, "Team Leader", 0), (41, 20, "Co-Investigator", 1), (43, 21, NULL, 0);
\ No newline at end of file
@@ -0,0 +1 @@
+CREATE TABLE Laboratory LIKE ispyb_build.Laboratory;
\ No newline at end of file
@@ -0,0 +1 @@
+CREATE TABLE LabContact LIKE ispyb_build.LabContact;
\ No newline at end of file